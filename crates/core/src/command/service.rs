@@ -251,7 +251,14 @@ impl Service {
                             caller: request.caller, instance
                         };
 
-                        let result = clone.execute_handler(&request.command, &ctx);
+                        // Handlers are synchronous and might take a while, such as those of plugins.
+                        let command = request.command;
+                        let result = tokio::task::spawn_blocking(move || clone.execute_handler(&command, &ctx)).await;
+                        let result = result.unwrap_or_else(|err| {
+                            tracing::error!("Command handler panicked: {err}");
+                            Err(HandlerOutput::new().message("An internal error occurred while executing the command"))
+                        });
+
                         // Error can be ignored because it only occurs if the receiver does not exist anymore.
                         let _: Result<(), HandlerResult> = request.sender.send(result);
                     });
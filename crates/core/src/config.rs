@@ -1,17 +1,24 @@
 //! Server configuration
 
 use std::{
-    net::{SocketAddrV4, SocketAddrV6},
+    net::{IpAddr, SocketAddr, SocketAddrV4, SocketAddrV6},
+    path::{Path, PathBuf},
     sync::{
         atomic::{AtomicUsize, Ordering},
         Arc,
     },
+    time::Duration,
 };
 
+use anyhow::Context;
+use level::provider::CreateOptions;
+use level::settings::GeneratorType;
 use proto::bedrock::{CompressionAlgorithm, ThrottleSettings};
-use util::CowString;
+use serde::Deserialize;
+use util::{CowString, Vector};
 
 use crate::instance::{Instance, IPV4_LOCAL_ADDR};
+use crate::net::Cidr;
 
 /// Compression related settings.
 pub struct Compression {
@@ -19,12 +26,171 @@ pub struct Compression {
     pub algorithm: CompressionAlgorithm,
     /// Packets above this size threshold will be compressed.
     pub threshold: u16,
+    /// Compression level used by the Flate algorithm, ranging from 0 (fastest) to 9 (smallest).
+    pub flate_level: u32,
 }
 
 /// Configuration of the level
 pub struct LevelConfig {
     /// The path to the level.
     pub path: String,
+    /// Maximum amount of memory used by the chunk cache in megabytes.
+    ///
+    /// Chunks that are in view of a player are always kept in memory, even if this is exceeded.
+    pub cache_size: usize,
+    /// Options used to create the level if it does not exist yet.
+    pub create: CreateOptions,
+    /// Layers generated in superflat levels, such as `minecraft:bedrock,2*minecraft:dirt,minecraft:grass`.
+    ///
+    /// The layers stored in `level.dat` are used if this is `None`.
+    /// Missing terrain in infinite and limited levels is only generated as superflat if this is set.
+    /// See [`FlatPreset`](crate::level::generator::FlatPreset) for the format.
+    pub flat_layers: Option<String>,
+}
+
+/// Configuration of the resource packs.
+pub struct ResourcePackConfig {
+    /// Directory containing the `.mcpack` and `.zip` archives of the packs.
+    ///
+    /// No packs are loaded if this is `None`.
+    pub path: Option<String>,
+    /// Whether clients must accept the packs to be able to join.
+    pub required: bool,
+}
+
+/// Configuration of the server network that players can be transferred within.
+#[derive(Default)]
+pub struct NetworkConfig {
+    /// Servers that players can be transferred to with a handoff token and that handoff tokens are accepted from.
+    pub trusted_peers: Vec<SocketAddr>,
+    /// Secret shared by all servers in the network, used to sign handoff tokens.
+    ///
+    /// Handoff tokens are neither sent nor accepted if this is `None`.
+    pub secret: Option<String>,
+}
+
+impl NetworkConfig {
+    /// Whether the given server is a trusted peer.
+    pub fn is_trusted(&self, addr: &SocketAddr) -> bool {
+        self.trusted_peers.contains(addr)
+    }
+
+    /// Whether datagrams from the given IP address can come from a trusted peer.
+    ///
+    /// Peers may send from a different port than the one they listen on, so only the IP address is compared.
+    pub fn is_trusted_ip(&self, ip: &IpAddr) -> bool {
+        self.trusted_peers.iter().any(|peer| peer.ip() == *ip)
+    }
+}
+
+/// Configuration of the Prometheus metrics exporter.
+#[derive(Default)]
+pub struct MetricsConfig {
+    /// Address that the exporter listens on.
+    ///
+    /// The exporter is disabled if this is `None`.
+    pub addr: Option<SocketAddr>,
+}
+
+/// Configuration of the firewall that filters packets from clients without a session.
+#[derive(Debug, Clone)]
+pub struct FirewallConfig {
+    /// Unconnected packets that a single address can send per second.
+    pub ip_rate: f64,
+    /// Unconnected packets that a single address can send in a burst.
+    pub ip_burst: f64,
+    /// Unconnected packets that a subnet (/24 for IPv4, /64 for IPv6) can send per second.
+    pub subnet_rate: f64,
+    /// Unconnected packets that a subnet can send in a burst.
+    pub subnet_burst: f64,
+    /// Amount of times an address can misbehave within ten minutes before it is banned temporarily.
+    pub strike_limit: u32,
+    /// How long automatic bans last.
+    pub ban_duration: Duration,
+    /// Ranges of addresses that are banned permanently.
+    pub bans: Vec<Cidr>,
+}
+
+impl Default for FirewallConfig {
+    fn default() -> FirewallConfig {
+        FirewallConfig {
+            ip_rate: 10.0,
+            ip_burst: 20.0,
+            subnet_rate: 50.0,
+            subnet_burst: 100.0,
+            strike_limit: 3,
+            ban_duration: Duration::from_secs(15 * 60),
+            bans: Vec::new(),
+        }
+    }
+}
+
+/// Game mode shown in the server list.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GameModeName {
+    /// Survival mode.
+    #[default]
+    Survival,
+    /// Creative mode.
+    Creative,
+    /// Adventure mode.
+    Adventure,
+    /// Spectator mode.
+    Spectator,
+}
+
+impl GameModeName {
+    /// Name of the game mode as it appears in the message of the day.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            GameModeName::Survival => "Survival",
+            GameModeName::Creative => "Creative",
+            GameModeName::Adventure => "Adventure",
+            GameModeName::Spectator => "Spectator",
+        }
+    }
+}
+
+/// Configuration of how the server presents itself in the server list and on the local network.
+#[derive(Default)]
+pub struct DiscoveryConfig {
+    /// Whether the server periodically broadcasts its message of the day on the local network,
+    /// so that it shows up in the Friends tab of clients on the same network.
+    pub lan_broadcast: bool,
+    /// Game mode shown in the server list.
+    pub game_mode: GameModeName,
+    /// Unique ID of the server, which is also used as its RakNet GUID.
+    ///
+    /// A random ID is generated on startup if this is `None`.
+    pub server_id: Option<u64>,
+    /// Second line of the message of the day, shown as the world name on the local network.
+    ///
+    /// The name of the server is used if this is `None`.
+    pub sub_motd: Option<String>,
+}
+
+/// Configuration of the WebAssembly plugin host.
+#[derive(Debug, Clone)]
+pub struct PluginConfig {
+    /// Directory containing a subdirectory for every plugin.
+    ///
+    /// No plugins are loaded if the directory does not exist.
+    pub path: String,
+    /// Amount of fuel that a plugin can consume in a single call before it is aborted.
+    ///
+    /// Every WebAssembly instruction consumes roughly one unit of fuel.
+    pub fuel: u64,
+    /// Maximum size of the memory of a plugin in megabytes.
+    ///
+    /// Attempts of a plugin to grow its memory beyond this size fail.
+    pub memory: usize,
+}
+
+impl Default for PluginConfig {
+    fn default() -> PluginConfig {
+        PluginConfig { path: String::from("plugins"), fuel: 10_000_000, memory: 64 }
+    }
 }
 
 /// A callback for the message of the day.
@@ -46,6 +212,10 @@ pub struct Config {
     ///
     /// See [`ThrottleSettings`] for more info,
     pub(super) throttling: ThrottleSettings,
+    /// Whether clients must be signed into Xbox Live to join.
+    ///
+    /// In offline mode, clients can join with any username.
+    pub(super) online_mode: bool,
     /// Maximum amount of players the server allows concurrently.
     pub(super) max_connections: AtomicUsize,
     /// The maximum render distance that clients are allowed to use.
@@ -54,8 +224,24 @@ pub struct Config {
     pub(super) max_render_distance: AtomicUsize,
     /// Level configuration
     pub(super) level: LevelConfig,
+    /// Resource pack configuration.
+    pub(super) resource_packs: ResourcePackConfig,
+    /// Server network configuration.
+    pub(super) network: NetworkConfig,
+    /// Metrics exporter configuration.
+    pub(super) metrics: MetricsConfig,
+    /// Firewall configuration.
+    pub(super) firewall: FirewallConfig,
+    /// Server list and LAN discovery configuration.
+    pub(super) discovery: DiscoveryConfig,
+    /// Plugin host configuration.
+    pub(super) plugins: PluginConfig,
     /// Callback that generates a new message of the day.
     pub(super) motd_callback: MotdCallback,
+    /// File that the configuration was loaded from, used to reload it.
+    pub(super) source: Option<PathBuf>,
+    /// Options that take precedence over the configuration file, such as command line flags.
+    pub(super) overrides: ConfigFile,
 }
 
 impl Config {
@@ -67,16 +253,171 @@ impl Config {
             compression: Compression {
                 algorithm: CompressionAlgorithm::Flate,
                 threshold: 1,
+                flate_level: 9,
             },
             throttling: ThrottleSettings {
                 enabled: false,
                 scalar: 0.0,
                 threshold: 0,
             },
-            level: LevelConfig { path: String::from("resources\\level") },
+            level: LevelConfig {
+                path: String::from("resources/level"),
+                cache_size: 256,
+                create: CreateOptions::default(),
+                flat_layers: None,
+            },
+            resource_packs: ResourcePackConfig { path: None, required: false },
+            network: NetworkConfig::default(),
+            metrics: MetricsConfig::default(),
+            firewall: FirewallConfig::default(),
+            discovery: DiscoveryConfig::default(),
+            plugins: PluginConfig::default(),
+            online_mode: true,
             max_connections: AtomicUsize::new(10),
             max_render_distance: AtomicUsize::new(12),
             motd_callback: Box::new(|_| "Powered by Mirai".into()),
+            source: None,
+            overrides: ConfigFile::default(),
+        }
+    }
+
+    /// Applies every option that is present in a configuration file.
+    pub(super) fn apply(&mut self, file: &ConfigFile) {
+        if let Some(addr) = file.ipv4_addr {
+            self.ipv4_addr = addr;
+        }
+        if let Some(addr) = file.ipv6_addr {
+            self.ipv6_addr = Some(addr);
+        }
+        if let Some(name) = &file.name {
+            self.name = CowString::from(name.clone());
+        }
+        if let Some(motd) = &file.motd {
+            let motd = motd.clone();
+            self.motd_callback = Box::new(move |_| CowString::from(motd.clone()));
+        }
+        if let Some(online_mode) = file.online_mode {
+            self.online_mode = online_mode;
+        }
+
+        let compression = &file.compression;
+        if let Some(algorithm) = compression.algorithm {
+            self.compression.algorithm = algorithm.into();
+        }
+        if let Some(threshold) = compression.threshold {
+            self.compression.threshold = threshold;
+        }
+        if let Some(level) = compression.flate_level {
+            self.compression.flate_level = level.min(9);
+        }
+
+        let throttling = &file.throttling;
+        if let Some(enabled) = throttling.enabled {
+            self.throttling.enabled = enabled;
+        }
+        if let Some(threshold) = throttling.threshold {
+            self.throttling.threshold = threshold;
+        }
+        if let Some(scalar) = throttling.scalar {
+            self.throttling.scalar = scalar;
+        }
+
+        if let Some(path) = &file.level.path {
+            self.level.path.clone_from(path);
+        }
+        if let Some(size) = file.level.cache_size {
+            self.level.cache_size = size;
+        }
+        if let Some(name) = &file.level.name {
+            self.level.create.name.clone_from(name);
+        }
+        if let Some(seed) = file.level.seed {
+            self.level.create.seed = seed;
+        }
+        if let Some(spawn) = file.level.spawn {
+            self.level.create.spawn = Some(Vector::from(spawn));
+        }
+        if let Some(generator) = file.level.generator {
+            self.level.create.generator = generator;
+        }
+        if let Some(layers) = &file.level.flat_layers {
+            self.level.flat_layers = Some(layers.clone());
+        }
+        if let Some(path) = &file.resource_packs.path {
+            self.resource_packs.path = Some(path.clone());
+        }
+        if let Some(required) = file.resource_packs.required {
+            self.resource_packs.required = required;
+        }
+
+        if let Some(peers) = &file.network.trusted_peers {
+            self.network.trusted_peers.clone_from(peers);
+        }
+        if let Some(secret) = &file.network.secret {
+            self.network.secret = Some(secret.clone());
+        }
+        if let Some(addr) = file.metrics.addr {
+            self.metrics.addr = Some(addr);
+        }
+
+        let firewall = &file.firewall;
+        if let Some(rate) = firewall.ip_rate {
+            self.firewall.ip_rate = rate;
+        }
+        if let Some(burst) = firewall.ip_burst {
+            self.firewall.ip_burst = burst;
+        }
+        if let Some(rate) = firewall.subnet_rate {
+            self.firewall.subnet_rate = rate;
+        }
+        if let Some(burst) = firewall.subnet_burst {
+            self.firewall.subnet_burst = burst;
+        }
+        if let Some(limit) = firewall.strike_limit {
+            self.firewall.strike_limit = limit;
+        }
+        if let Some(secs) = firewall.ban_duration {
+            self.firewall.ban_duration = Duration::from_secs(secs);
+        }
+        if let Some(bans) = &firewall.bans {
+            self.firewall.bans.clone_from(bans);
+        }
+
+        let discovery = &file.discovery;
+        if let Some(enabled) = discovery.lan_broadcast {
+            self.discovery.lan_broadcast = enabled;
+        }
+        if let Some(game_mode) = discovery.game_mode {
+            self.discovery.game_mode = game_mode;
+        }
+        if let Some(id) = discovery.server_id {
+            self.discovery.server_id = Some(id);
+        }
+        if let Some(sub_motd) = &discovery.sub_motd {
+            self.discovery.sub_motd = Some(sub_motd.clone());
+        }
+
+        let plugins = &file.plugins;
+        if let Some(path) = &plugins.path {
+            self.plugins.path.clone_from(path);
+        }
+        if let Some(fuel) = plugins.fuel {
+            self.plugins.fuel = fuel;
+        }
+        if let Some(memory) = plugins.memory {
+            self.plugins.memory = memory;
+        }
+
+        self.reload(file);
+    }
+
+    /// Applies the options of a configuration file that can be changed while the server is running.
+    pub(super) fn reload(&self, file: &ConfigFile) {
+        if let Some(max) = file.max_players {
+            self.set_max_connections(max);
+        }
+        if let Some(max) = file.max_render_distance {
+            self.set_max_render_distance(max);
         }
     }
 
@@ -110,6 +451,12 @@ impl Config {
         &self.throttling
     }
 
+    /// Whether clients must be signed into Xbox Live to join.
+    #[inline]
+    pub const fn online_mode(&self) -> bool {
+        self.online_mode
+    }
+
     /// Returns the current maximum player count.
     #[inline]
     pub fn max_connections(&self) -> usize {
@@ -146,4 +493,296 @@ impl Config {
     pub const fn level(&self) -> &LevelConfig {
         &self.level
     }
+
+    /// Returns the resource pack configuration.
+    #[inline]
+    pub const fn resource_packs(&self) -> &ResourcePackConfig {
+        &self.resource_packs
+    }
+
+    /// Returns the server network configuration.
+    #[inline]
+    pub const fn network(&self) -> &NetworkConfig {
+        &self.network
+    }
+
+    /// Returns the metrics exporter configuration.
+    #[inline]
+    pub const fn metrics(&self) -> &MetricsConfig {
+        &self.metrics
+    }
+
+    /// Returns the firewall configuration.
+    #[inline]
+    pub const fn firewall(&self) -> &FirewallConfig {
+        &self.firewall
+    }
+
+    /// Returns the server list and LAN discovery configuration.
+    #[inline]
+    pub const fn discovery(&self) -> &DiscoveryConfig {
+        &self.discovery
+    }
+
+    /// Returns the plugin host configuration.
+    #[inline]
+    pub const fn plugins(&self) -> &PluginConfig {
+        &self.plugins
+    }
+}
+
+/// Compression algorithm names used in the configuration file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompressionName {
+    /// See [`CompressionAlgorithm::Flate`].
+    Flate,
+    /// See [`CompressionAlgorithm::Snappy`].
+    Snappy,
+}
+
+impl From<CompressionName> for CompressionAlgorithm {
+    fn from(name: CompressionName) -> CompressionAlgorithm {
+        match name {
+            CompressionName::Flate => CompressionAlgorithm::Flate,
+            CompressionName::Snappy => CompressionAlgorithm::Snappy,
+        }
+    }
+}
+
+/// The `[compression]` section of the configuration file.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CompressionSection {
+    /// See [`Compression::algorithm`].
+    pub algorithm: Option<CompressionName>,
+    /// See [`Compression::threshold`].
+    pub threshold: Option<u16>,
+    /// See [`Compression::flate_level`].
+    pub flate_level: Option<u32>,
+}
+
+/// The `[throttling]` section of the configuration file.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThrottlingSection {
+    /// See [`ThrottleSettings::enabled`].
+    pub enabled: Option<bool>,
+    /// See [`ThrottleSettings::threshold`].
+    pub threshold: Option<u8>,
+    /// See [`ThrottleSettings::scalar`].
+    pub scalar: Option<f32>,
+}
+
+/// The `[level]` section of the configuration file.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LevelSection {
+    /// See [`LevelConfig::path`].
+    pub path: Option<String>,
+    /// See [`LevelConfig::cache_size`].
+    pub cache_size: Option<usize>,
+    /// Name of the level when it is created.
+    pub name: Option<String>,
+    /// Seed of the level when it is created.
+    pub seed: Option<i64>,
+    /// Spawn point of the level when it is created.
+    ///
+    /// The spawn point is placed on the surface above the origin if this is not set.
+    pub spawn: Option<[i32; 3]>,
+    /// Generator of the level when it is created.
+    pub generator: Option<GeneratorType>,
+    /// See [`LevelConfig::flat_layers`].
+    pub flat_layers: Option<String>,
+}
+
+/// The `[resource_packs]` section of the configuration file.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResourcePackSection {
+    /// See [`ResourcePackConfig::path`].
+    pub path: Option<String>,
+    /// See [`ResourcePackConfig::required`].
+    pub required: Option<bool>,
+}
+
+/// The `[network]` section of the configuration file.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkSection {
+    /// See [`NetworkConfig::trusted_peers`].
+    pub trusted_peers: Option<Vec<SocketAddr>>,
+    /// See [`NetworkConfig::secret`].
+    pub secret: Option<String>,
+}
+
+/// The `[metrics]` section of the configuration file.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsSection {
+    /// See [`MetricsConfig::addr`].
+    pub addr: Option<SocketAddr>,
+}
+
+/// The `[firewall]` section of the configuration file.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FirewallSection {
+    /// See [`FirewallConfig::ip_rate`].
+    pub ip_rate: Option<f64>,
+    /// See [`FirewallConfig::ip_burst`].
+    pub ip_burst: Option<f64>,
+    /// See [`FirewallConfig::subnet_rate`].
+    pub subnet_rate: Option<f64>,
+    /// See [`FirewallConfig::subnet_burst`].
+    pub subnet_burst: Option<f64>,
+    /// See [`FirewallConfig::strike_limit`].
+    pub strike_limit: Option<u32>,
+    /// See [`FirewallConfig::ban_duration`], in seconds.
+    pub ban_duration: Option<u64>,
+    /// See [`FirewallConfig::bans`].
+    pub bans: Option<Vec<Cidr>>,
+}
+
+/// The `[discovery]` section of the configuration file.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiscoverySection {
+    /// See [`DiscoveryConfig::lan_broadcast`].
+    pub lan_broadcast: Option<bool>,
+    /// See [`DiscoveryConfig::game_mode`].
+    pub game_mode: Option<GameModeName>,
+    /// See [`DiscoveryConfig::server_id`].
+    pub server_id: Option<u64>,
+    /// See [`DiscoveryConfig::sub_motd`].
+    pub sub_motd: Option<String>,
+}
+
+/// The `[plugins]` section of the configuration file.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PluginSection {
+    /// See [`PluginConfig::path`].
+    pub path: Option<String>,
+    /// See [`PluginConfig::fuel`].
+    pub fuel: Option<u64>,
+    /// See [`PluginConfig::memory`].
+    pub memory: Option<usize>,
+}
+
+/// Contents of a `mirai.toml` configuration file.
+///
+/// Every option is optional. Options that are not present keep their default value.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    /// Address of the IPv4 socket.
+    pub ipv4_addr: Option<SocketAddrV4>,
+    /// Address of the optional IPv6 socket.
+    pub ipv6_addr: Option<SocketAddrV6>,
+    /// Name of the server.
+    pub name: Option<String>,
+    /// Message of the day shown in the server list.
+    pub motd: Option<String>,
+    /// Maximum amount of players. This can be reloaded while the server is running.
+    pub max_players: Option<usize>,
+    /// Maximum render distance. This can be reloaded while the server is running.
+    pub max_render_distance: Option<usize>,
+    /// Whether clients must be signed into Xbox Live.
+    pub online_mode: Option<bool>,
+    /// Compression settings.
+    pub compression: CompressionSection,
+    /// Client throttling settings.
+    pub throttling: ThrottlingSection,
+    /// Level settings.
+    pub level: LevelSection,
+    /// Resource pack settings.
+    pub resource_packs: ResourcePackSection,
+    /// Server network settings.
+    pub network: NetworkSection,
+    /// Metrics exporter settings.
+    pub metrics: MetricsSection,
+    /// Firewall settings.
+    pub firewall: FirewallSection,
+    /// Server list and LAN discovery settings.
+    pub discovery: DiscoverySection,
+    /// Plugin host settings.
+    pub plugins: PluginSection,
+}
+
+impl ConfigFile {
+    /// Reads a configuration file.
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<ConfigFile> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).with_context(|| format!("Unable to read configuration file {}", path.display()))?;
+
+        Self::parse(&contents).with_context(|| format!("Invalid configuration file {}", path.display()))
+    }
+
+    /// Parses the contents of a configuration file.
+    pub fn parse(contents: &str) -> anyhow::Result<ConfigFile> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    /// Combines two configurations, preferring the options in `overrides`.
+    #[must_use]
+    pub fn merge(self, overrides: ConfigFile) -> ConfigFile {
+        ConfigFile {
+            ipv4_addr: overrides.ipv4_addr.or(self.ipv4_addr),
+            ipv6_addr: overrides.ipv6_addr.or(self.ipv6_addr),
+            name: overrides.name.or(self.name),
+            motd: overrides.motd.or(self.motd),
+            max_players: overrides.max_players.or(self.max_players),
+            max_render_distance: overrides.max_render_distance.or(self.max_render_distance),
+            online_mode: overrides.online_mode.or(self.online_mode),
+            compression: CompressionSection {
+                algorithm: overrides.compression.algorithm.or(self.compression.algorithm),
+                threshold: overrides.compression.threshold.or(self.compression.threshold),
+                flate_level: overrides.compression.flate_level.or(self.compression.flate_level),
+            },
+            throttling: ThrottlingSection {
+                enabled: overrides.throttling.enabled.or(self.throttling.enabled),
+                threshold: overrides.throttling.threshold.or(self.throttling.threshold),
+                scalar: overrides.throttling.scalar.or(self.throttling.scalar),
+            },
+            level: LevelSection {
+                path: overrides.level.path.or(self.level.path),
+                cache_size: overrides.level.cache_size.or(self.level.cache_size),
+                name: overrides.level.name.or(self.level.name),
+                seed: overrides.level.seed.or(self.level.seed),
+                spawn: overrides.level.spawn.or(self.level.spawn),
+                generator: overrides.level.generator.or(self.level.generator),
+                flat_layers: overrides.level.flat_layers.or(self.level.flat_layers),
+            },
+            resource_packs: ResourcePackSection {
+                path: overrides.resource_packs.path.or(self.resource_packs.path),
+                required: overrides.resource_packs.required.or(self.resource_packs.required),
+            },
+            network: NetworkSection {
+                trusted_peers: overrides.network.trusted_peers.or(self.network.trusted_peers),
+                secret: overrides.network.secret.or(self.network.secret),
+            },
+            metrics: MetricsSection { addr: overrides.metrics.addr.or(self.metrics.addr) },
+            firewall: FirewallSection {
+                ip_rate: overrides.firewall.ip_rate.or(self.firewall.ip_rate),
+                ip_burst: overrides.firewall.ip_burst.or(self.firewall.ip_burst),
+                subnet_rate: overrides.firewall.subnet_rate.or(self.firewall.subnet_rate),
+                subnet_burst: overrides.firewall.subnet_burst.or(self.firewall.subnet_burst),
+                strike_limit: overrides.firewall.strike_limit.or(self.firewall.strike_limit),
+                ban_duration: overrides.firewall.ban_duration.or(self.firewall.ban_duration),
+                bans: overrides.firewall.bans.or(self.firewall.bans),
+            },
+            discovery: DiscoverySection {
+                lan_broadcast: overrides.discovery.lan_broadcast.or(self.discovery.lan_broadcast),
+                game_mode: overrides.discovery.game_mode.or(self.discovery.game_mode),
+                server_id: overrides.discovery.server_id.or(self.discovery.server_id),
+                sub_motd: overrides.discovery.sub_motd.or(self.discovery.sub_motd),
+            },
+            plugins: PluginSection {
+                path: overrides.plugins.path.or(self.plugins.path),
+                fuel: overrides.plugins.fuel.or(self.plugins.fuel),
+                memory: overrides.plugins.memory.or(self.plugins.memory),
+            },
+        }
+    }
 }
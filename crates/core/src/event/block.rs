@@ -0,0 +1,45 @@
+use std::sync::Arc;
+
+use util::Vector;
+
+use crate::net::BedrockClient;
+
+use super::Event;
+
+/// Dispatched when a player breaks a block.
+///
+/// Cancelling this event restores the block for the player.
+pub struct BlockBreak {
+    /// The player that broke the block.
+    pub client: Arc<BedrockClient>,
+    /// Position of the block.
+    pub position: Vector<i32, 3>,
+    /// Whether the block is kept.
+    pub cancelled: bool,
+}
+
+impl Event for BlockBreak {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+/// Dispatched when a player places a block.
+///
+/// Cancelling this event removes the block for the player and keeps the item in their inventory.
+pub struct BlockPlace {
+    /// The player that placed the block.
+    pub client: Arc<BedrockClient>,
+    /// Position of the block.
+    pub position: Vector<i32, 3>,
+    /// Runtime ID of the placed block.
+    pub block: u32,
+    /// Whether the block is not placed.
+    pub cancelled: bool,
+}
+
+impl Event for BlockPlace {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
@@ -0,0 +1,164 @@
+use std::any::{Any, TypeId};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use dashmap::DashMap;
+
+/// An event that can be dispatched through the [`EventBus`].
+pub trait Event: Send + Sync + 'static {
+    /// Whether a listener has cancelled the event.
+    ///
+    /// Events that cannot be cancelled always return `false`.
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+/// Determines the order in which listeners are called.
+///
+/// Listeners with a lower priority are called first, so listeners with a higher priority have the final say
+/// over the outcome of the event. Listeners with the same priority are called in order of registration.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum EventPriority {
+    /// Called first.
+    Lowest,
+    /// Called before normal listeners.
+    Low,
+    /// The default priority.
+    #[default]
+    Normal,
+    /// Called after normal listeners.
+    High,
+    /// Called last of the listeners that modify the event.
+    Highest,
+    /// Called after all other listeners to observe the final outcome.
+    ///
+    /// Monitoring listeners should not modify the event.
+    Monitor,
+}
+
+/// Identifies a registered listener so that it can be removed again.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ListenerId(u64);
+
+/// Future returned by asynchronous listeners.
+pub type EventFuture<E> = Pin<Box<dyn Future<Output = E> + Send>>;
+
+enum Callback<E> {
+    Sync(Box<dyn Fn(&mut E) + Send + Sync>),
+    Async(Box<dyn Fn(E) -> EventFuture<E> + Send + Sync>),
+}
+
+struct Listener<E> {
+    id: ListenerId,
+    priority: EventPriority,
+    callback: Callback<E>,
+}
+
+/// Listeners of a single event type, sorted by priority.
+///
+/// The list is replaced rather than modified so that dispatching does not hold a lock while awaiting listeners.
+type Listeners<E> = Arc<Vec<Arc<Listener<E>>>>;
+
+/// Dispatches events to the listeners registered for them.
+#[derive(Default)]
+pub struct EventBus {
+    listeners: DashMap<TypeId, Box<dyn Any + Send + Sync>>,
+    next_id: AtomicU64,
+}
+
+impl EventBus {
+    /// Creates an event bus without any listeners.
+    pub fn new() -> EventBus {
+        EventBus::default()
+    }
+
+    /// Registers a listener that is called synchronously.
+    pub fn listen<E, F>(&self, priority: EventPriority, listener: F) -> ListenerId
+    where
+        E: Event,
+        F: Fn(&mut E) + Send + Sync + 'static,
+    {
+        self.register(priority, Callback::Sync(Box::new(listener)))
+    }
+
+    /// Registers a listener that is awaited.
+    ///
+    /// The listener takes ownership of the event and has to return it, possibly modified, when it is done.
+    pub fn listen_async<E, F, Fut>(&self, priority: EventPriority, listener: F) -> ListenerId
+    where
+        E: Event,
+        F: Fn(E) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = E> + Send + 'static,
+    {
+        self.register(priority, Callback::Async(Box::new(move |event| Box::pin(listener(event)))))
+    }
+
+    /// Removes a listener of the event, returning whether it was registered.
+    pub fn unlisten<E: Event>(&self, id: ListenerId) -> bool {
+        let Some(mut entry) = self.listeners.get_mut(&TypeId::of::<E>()) else {
+            return false;
+        };
+
+        let Some(listeners) = entry.downcast_mut::<Listeners<E>>() else {
+            return false;
+        };
+
+        let len = listeners.len();
+        *listeners = Arc::new(listeners.iter().filter(|listener| listener.id != id).cloned().collect());
+
+        listeners.len() != len
+    }
+
+    /// Whether any listeners have been registered for the event.
+    pub fn has_listeners<E: Event>(&self) -> bool {
+        self.listeners::<E>().is_some_and(|listeners| !listeners.is_empty())
+    }
+
+    /// Calls all listeners of the event and returns the event as they left it.
+    ///
+    /// Cancelled events are still passed to the remaining listeners, which can check
+    /// [`is_cancelled`](Event::is_cancelled) and can also undo the cancellation.
+    pub async fn dispatch<E: Event>(&self, mut event: E) -> E {
+        let Some(listeners) = self.listeners::<E>() else {
+            return event;
+        };
+
+        for listener in listeners.iter() {
+            match &listener.callback {
+                Callback::Sync(callback) => callback(&mut event),
+                Callback::Async(callback) => event = callback(event).await,
+            }
+        }
+
+        event
+    }
+
+    fn register<E: Event>(&self, priority: EventPriority, callback: Callback<E>) -> ListenerId {
+        let id = ListenerId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let listener = Arc::new(Listener { id, priority, callback });
+
+        let mut entry = self
+            .listeners
+            .entry(TypeId::of::<E>())
+            .or_insert_with(|| Box::<Listeners<E>>::default());
+
+        if let Some(listeners) = entry.downcast_mut::<Listeners<E>>() {
+            let mut updated = Vec::clone(listeners);
+            // The sort is stable, so listeners with the same priority stay in order of registration.
+            updated.push(listener);
+            updated.sort_by_key(|listener| listener.priority);
+
+            *listeners = Arc::new(updated);
+        }
+        drop(entry);
+
+        id
+    }
+
+    fn listeners<E: Event>(&self) -> Option<Listeners<E>> {
+        self.listeners.get(&TypeId::of::<E>()).and_then(|entry| entry.downcast_ref::<Listeners<E>>().cloned())
+    }
+}
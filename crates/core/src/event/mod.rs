@@ -0,0 +1,15 @@
+//! Typed events that allow gameplay to be hooked without modifying the packet handlers.
+//!
+//! Listeners are registered on the [`EventBus`] of the [`Instance`](crate::instance::Instance) and are called
+//! in order of their [`EventPriority`]. Listeners receive the event mutably, so they can modify it or,
+//! if the event supports it, cancel it by setting its `cancelled` field.
+//!
+//! Events are dispatched from within packet handlers, which have to finish within a short time limit.
+//! Listeners that need to do more work should spawn a separate task.
+
+use ::util::glob_export;
+
+glob_export!(bus);
+glob_export!(player);
+glob_export!(block);
+glob_export!(packet);
@@ -0,0 +1,28 @@
+use std::sync::Arc;
+
+use util::RVec;
+
+use crate::net::BedrockClient;
+
+use super::Event;
+
+/// Dispatched for every game packet received from a player, before it is handled.
+///
+/// Cancelling this event drops the packet. This should be done with care, dropping packets that the server
+/// expects during login leaves the player stuck.
+pub struct PacketReceive {
+    /// The player that sent the packet.
+    pub client: Arc<BedrockClient>,
+    /// ID of the packet.
+    pub id: u32,
+    /// Body of the packet, without its header.
+    pub packet: RVec,
+    /// Whether the packet is dropped.
+    pub cancelled: bool,
+}
+
+impl Event for PacketReceive {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
@@ -0,0 +1,180 @@
+use std::sync::Arc;
+
+use util::Vector;
+
+use crate::net::BedrockClient;
+
+use super::Event;
+
+/// Dispatched when a player has been authenticated, before they are sent any game data.
+///
+/// Cancelling this event disconnects the player with [`kick_message`](Self::kick_message).
+pub struct PlayerLogin {
+    /// The player that is logging in.
+    pub client: Arc<BedrockClient>,
+    /// Name of the player.
+    pub name: String,
+    /// XUID of the player, 0 if they are not signed into Xbox Live.
+    pub xuid: u64,
+    /// Message shown to the player when the login is cancelled.
+    pub kick_message: String,
+    /// Whether the player is refused.
+    pub cancelled: bool,
+}
+
+impl Event for PlayerLogin {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+/// Dispatched when a player has spawned in the world.
+pub struct PlayerJoin {
+    /// The player that joined.
+    pub client: Arc<BedrockClient>,
+    /// Whether the other players are told that this player joined.
+    pub announce: bool,
+}
+
+impl Event for PlayerJoin {}
+
+/// Dispatched when a player that had spawned leaves the server.
+pub struct PlayerQuit {
+    /// The player that left.
+    pub client: Arc<BedrockClient>,
+    /// Whether the other players are told that this player left.
+    pub announce: bool,
+}
+
+impl Event for PlayerQuit {}
+
+/// Dispatched when a player sends a chat message.
+///
+/// Cancelling this event prevents the message from being broadcast.
+pub struct ChatMessage {
+    /// The player that sent the message.
+    pub client: Arc<BedrockClient>,
+    /// The message that is broadcast.
+    pub message: String,
+    /// Whether the message is dropped.
+    pub cancelled: bool,
+}
+
+impl Event for ChatMessage {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+/// Dispatched when a player executes a command.
+///
+/// Cancelling this event prevents the command from being executed.
+pub struct CommandExecute {
+    /// The player that executed the command.
+    pub client: Arc<BedrockClient>,
+    /// The full command line, which is executed instead of the original if it is modified.
+    pub command: String,
+    /// Whether the command is not executed.
+    pub cancelled: bool,
+}
+
+impl Event for CommandExecute {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+/// Dispatched when a player moves or rotates, after the movement has been validated.
+///
+/// Cancelling this event moves the player back to [`from`](Self::from).
+pub struct PlayerMove {
+    /// The player that moved.
+    pub client: Arc<BedrockClient>,
+    /// Position of the player before the movement.
+    pub from: Vector<f32, 3>,
+    /// Position of the player after the movement.
+    pub to: Vector<f32, 3>,
+    /// Pitch, yaw and head yaw of the player after the movement.
+    pub rotation: Vector<f32, 3>,
+    /// Whether the movement is refused.
+    pub cancelled: bool,
+}
+
+impl Event for PlayerMove {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+/// Dispatched when a player starts or stops flying.
+///
+/// Cancelling this event reverts the change: a player that started flying lands again
+/// and a player that stopped flying is put back into flight.
+pub struct PlayerToggleFlight {
+    /// The player that toggled flight.
+    pub client: Arc<BedrockClient>,
+    /// Whether the player started flying.
+    pub flying: bool,
+    /// Whether the change is refused.
+    pub cancelled: bool,
+}
+
+impl Event for PlayerToggleFlight {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+/// Dispatched when a player changes their skin.
+///
+/// Cancelling this event prevents the new skin from being shown to other players.
+pub struct PlayerSkinChange {
+    /// The player that changed their skin.
+    pub client: Arc<BedrockClient>,
+    /// Whether the skin change is hidden.
+    pub cancelled: bool,
+}
+
+impl Event for PlayerSkinChange {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+/// Dispatched when a player requests a render distance.
+pub struct ChunkRadiusChange {
+    /// The player that requested the render distance.
+    pub client: Arc<BedrockClient>,
+    /// The render distance requested by the player.
+    pub requested: i32,
+    /// The render distance that is used.
+    ///
+    /// This is capped to the maximum render distance of the server, even if a listener raises it.
+    pub radius: i32,
+}
+
+impl Event for ChunkRadiusChange {}
+
+/// Dispatched when a player opens their inventory.
+///
+/// Cancelling this event keeps the inventory closed.
+pub struct InventoryOpen {
+    /// The player that opened their inventory.
+    pub client: Arc<BedrockClient>,
+    /// Whether the inventory stays closed.
+    pub cancelled: bool,
+}
+
+impl Event for InventoryOpen {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+/// Dispatched when a player closes their inventory.
+pub struct InventoryClose {
+    /// The player that closed their inventory.
+    pub client: Arc<BedrockClient>,
+}
+
+impl Event for InventoryClose {}
@@ -0,0 +1,322 @@
+//! Transfers of players between trusted servers in a network.
+//!
+//! When a player is transferred to a trusted peer, the server sends a signed handoff token to the peer
+//! right before telling the client to connect to it. The peer uses the token to recognise the player
+//! when they log in, which allows it to skip the authentication checks and restore data selected by the
+//! previous server.
+//!
+//! Tokens are delivered over TCP on the same address that the server is listening on for game traffic.
+//! The client is only told to connect once the peer has confirmed that it accepted the token.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use hmac::{Hmac, Mac};
+use parking_lot::Mutex;
+use sha2::Sha256;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::sync::CancellationToken;
+use util::{BinaryRead, BinaryWrite};
+
+use crate::instance::Instance;
+
+/// Byte that every handoff token starts with.
+const HANDOFF_MAGIC: u8 = 0x70;
+
+/// Version of the handoff token format.
+const HANDOFF_VERSION: u8 = 2;
+
+/// Maximum size of a token that a peer is allowed to send.
+const MAX_TOKEN_SIZE: usize = 16 * 1024;
+
+/// Maximum amount of handoffs that can wait for their player to log in.
+/// The oldest handoff is discarded when another one is received.
+const MAX_PENDING_HANDOFFS: usize = 1024;
+
+/// How long delivering a token to a peer is allowed to take.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Response sent by a peer that accepted a token.
+const ACCEPTED: u8 = 1;
+/// Response sent by a peer that rejected a token.
+const REJECTED: u8 = 0;
+
+/// Size of the signature appended to a token.
+const SIGNATURE_SIZE: usize = 32;
+
+/// How long a handoff token stays valid after it has been issued.
+pub const HANDOFF_LIFETIME: Duration = Duration::from_secs(30);
+
+/// Information about a player that is handed from one server to another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Handoff {
+    /// XUID of the player.
+    pub xuid: u64,
+    /// Username of the player.
+    pub name: String,
+    /// Identity public key of the player.
+    ///
+    /// The client proves that it owns this key during the encryption handshake, which makes sure that
+    /// the token can only be used by the player that it was issued for.
+    pub public_key: String,
+    /// When the token was issued, in seconds since the Unix epoch.
+    pub issued_at: u64,
+    /// Random value that identifies the token, used to make sure that every token is only accepted once.
+    pub nonce: u64,
+    /// Additional data selected by the server that transferred the player.
+    pub data: Vec<(String, String)>,
+}
+
+impl Handoff {
+    /// Creates a handoff that was issued now.
+    pub fn new(xuid: u64, name: String, public_key: String, data: Vec<(String, String)>) -> Handoff {
+        Handoff { xuid, name, public_key, issued_at: unix_time(), nonce: rand::random(), data }
+    }
+
+    /// Returns the value of an entry in the additional data.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.data.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    /// Whether the token has expired.
+    pub fn is_expired(&self) -> bool {
+        unix_time().saturating_sub(self.issued_at) > HANDOFF_LIFETIME.as_secs()
+    }
+
+    /// Serialises the handoff into a token signed with the shared secret of the network.
+    pub fn sign(&self, secret: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let mut token = Vec::new();
+        token.write_u8(HANDOFF_MAGIC)?;
+        token.write_u8(HANDOFF_VERSION)?;
+        token.write_u64_le(self.xuid)?;
+        token.write_str(&self.name)?;
+        token.write_str(&self.public_key)?;
+        token.write_u64_le(self.issued_at)?;
+        token.write_u64_le(self.nonce)?;
+        token.write_var_u32(self.data.len() as u32)?;
+        for (key, value) in &self.data {
+            token.write_str(key)?;
+            token.write_str(value)?;
+        }
+
+        let signature = mac(secret)?.chain_update(&token).finalize().into_bytes();
+        token.extend_from_slice(&signature);
+
+        Ok(token)
+    }
+
+    /// Reads a token created by [`sign`](Self::sign), checking its signature and expiry.
+    pub fn verify(token: &[u8], secret: &[u8]) -> anyhow::Result<Handoff> {
+        let Some(split) = token.len().checked_sub(SIGNATURE_SIZE) else {
+            anyhow::bail!("Handoff token is too short");
+        };
+
+        let (content, signature) = token.split_at(split);
+        mac(secret)?
+            .chain_update(content)
+            .verify_slice(signature)
+            .map_err(|_| anyhow::anyhow!("Handoff token has an invalid signature"))?;
+
+        let mut reader = content;
+        if reader.read_u8()? != HANDOFF_MAGIC {
+            anyhow::bail!("Data is not a handoff token");
+        }
+
+        let version = reader.read_u8()?;
+        if version != HANDOFF_VERSION {
+            anyhow::bail!("Unsupported handoff token version {version}");
+        }
+
+        let xuid = reader.read_u64_le()?;
+        let name = reader.read_str()?.to_owned();
+        let public_key = reader.read_str()?.to_owned();
+        let issued_at = reader.read_u64_le()?;
+        let nonce = reader.read_u64_le()?;
+
+        let count = reader.read_var_u32()?;
+        let mut data = Vec::with_capacity(count.min(64) as usize);
+        for _ in 0..count {
+            data.push((reader.read_str()?.to_owned(), reader.read_str()?.to_owned()));
+        }
+
+        let handoff = Handoff { xuid, name, public_key, issued_at, nonce, data };
+        if handoff.is_expired() {
+            anyhow::bail!("Handoff token of {} has expired", handoff.name);
+        }
+
+        Ok(handoff)
+    }
+}
+
+/// Handoffs received from trusted peers that have not been claimed by a joining player yet.
+#[derive(Default)]
+pub struct Handoffs {
+    state: Mutex<HandoffState>,
+}
+
+#[derive(Default)]
+struct HandoffState {
+    /// Handoffs waiting for their player, oldest first.
+    pending: VecDeque<Handoff>,
+    /// Nonces of the tokens that have been accepted and have not expired yet, with their issue time.
+    used: HashMap<u64, u64>,
+}
+
+impl HandoffState {
+    /// Discards expired handoffs and nonces.
+    ///
+    /// Expired tokens are rejected by [`Handoff::verify`] anyway, so their nonces do not have to be kept.
+    fn prune(&mut self) {
+        let now = unix_time();
+        self.pending.retain(|handoff| !handoff.is_expired());
+        self.used.retain(|_, issued_at| now.saturating_sub(*issued_at) <= HANDOFF_LIFETIME.as_secs());
+    }
+}
+
+impl Handoffs {
+    /// Stores a handoff until the player logs in, replacing any previous handoff of the same player.
+    ///
+    /// Every token is only accepted once. This function returns an error if a token with the same nonce
+    /// has already been received.
+    pub fn insert(&self, handoff: Handoff) -> anyhow::Result<()> {
+        let mut state = self.state.lock();
+        state.prune();
+
+        if state.used.insert(handoff.nonce, handoff.issued_at).is_some() {
+            anyhow::bail!("Handoff token of {} has already been used", handoff.name);
+        }
+
+        state.pending.retain(|other| other.name != handoff.name);
+        if state.pending.len() >= MAX_PENDING_HANDOFFS {
+            state.pending.pop_front();
+        }
+        state.pending.push_back(handoff);
+        drop(state);
+
+        Ok(())
+    }
+
+    /// Whether a handoff is waiting for the given player.
+    ///
+    /// Both the username and the identity public key must match.
+    pub fn contains(&self, name: &str, public_key: &str) -> bool {
+        let mut state = self.state.lock();
+        state.prune();
+        state.pending.iter().any(|handoff| handoff.name == name && handoff.public_key == public_key)
+    }
+
+    /// Removes and returns the handoff of a player that is logging in.
+    ///
+    /// Both the username and the identity public key must match.
+    pub fn take(&self, name: &str, public_key: &str) -> Option<Handoff> {
+        let mut state = self.state.lock();
+        state.prune();
+
+        let index = state.pending.iter().position(|handoff| handoff.name == name && handoff.public_key == public_key)?;
+        state.pending.remove(index)
+    }
+
+    /// Amount of handoffs waiting for their player.
+    pub fn len(&self) -> usize {
+        self.state.lock().pending.len()
+    }
+
+    /// Whether no handoffs are waiting.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Sends a signed token to a peer and waits until the peer has accepted it.
+pub async fn deliver(peer: SocketAddr, token: &[u8]) -> anyhow::Result<()> {
+    // Imported here because these conflict with the binary readers used for the token itself.
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let delivery = async {
+        let mut stream = TcpStream::connect(peer).await?;
+        stream.write_u32_le(token.len() as u32).await?;
+        stream.write_all(token).await?;
+
+        if stream.read_u8().await? != ACCEPTED {
+            anyhow::bail!("{peer} rejected the handoff token");
+        }
+
+        Ok(())
+    };
+
+    tokio::time::timeout(DELIVERY_TIMEOUT, delivery)
+        .await
+        .with_context(|| format!("{peer} did not accept the handoff token in time"))?
+}
+
+/// Accepts handoff tokens from trusted peers until the token is cancelled.
+pub async fn serve(addr: SocketAddr, instance: Arc<Instance>, token: CancellationToken) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    tracing::info!("Accepting handoffs on {addr}");
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, peer) = match accepted {
+                    Ok(accepted) => accepted,
+                    Err(err) => {
+                        tracing::warn!("Failed to accept handoff connection: {err}");
+                        continue
+                    }
+                };
+
+                if !instance.config().network().is_trusted_ip(&peer.ip()) {
+                    tracing::warn!("Ignoring handoff connection from untrusted address {peer}");
+                    continue
+                }
+
+                let instance = Arc::clone(&instance);
+                tokio::spawn(async move {
+                    if let Err(err) = receive(stream, peer, &instance).await {
+                        tracing::warn!("Rejected handoff token from {peer}: {err:#}");
+                    }
+                });
+            },
+            () = token.cancelled() => break
+        }
+    }
+
+    Ok(())
+}
+
+/// Receives a single token and tells the peer whether it was accepted.
+async fn receive(mut stream: TcpStream, peer: SocketAddr, instance: &Instance) -> anyhow::Result<()> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let read = async {
+        let len = stream.read_u32_le().await? as usize;
+        if len > MAX_TOKEN_SIZE {
+            anyhow::bail!("Handoff token is too large");
+        }
+
+        let mut token = vec![0; len];
+        stream.read_exact(&mut token).await?;
+        Ok(token)
+    };
+
+    let token = tokio::time::timeout(DELIVERY_TIMEOUT, read).await.context("Peer did not send a token in time")??;
+    let result = instance.receive_handoff(peer, &token);
+
+    stream.write_u8(if result.is_ok() { ACCEPTED } else { REJECTED }).await?;
+    stream.shutdown().await?;
+
+    result
+}
+
+/// Creates the message authentication code used to sign tokens.
+fn mac(secret: &[u8]) -> anyhow::Result<Hmac<Sha256>> {
+    Hmac::<Sha256>::new_from_slice(secret).map_err(|_| anyhow::anyhow!("Invalid handoff secret"))
+}
+
+/// Current time in seconds since the Unix epoch.
+fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs())
+}
@@ -6,25 +6,33 @@ use parking_lot::RwLock;
 use raknet::RakNetCreateDescription;
 use tokio::task::JoinHandle;
 
-use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use tokio::net::UdpSocket;
 
 use tokio_util::sync::CancellationToken;
 
-use util::{CowString, Deserialize, Joinable, RVec, ReserveTo, Serialize};
+use util::{BlockPosition, CowString, Deserialize, Joinable, RVec, ReserveTo, Serialize, Vector};
 
 use crate::command::{self, HandlerOutput, HandlerResult, ParsedCommand};
-use crate::config::Config;
-use crate::net::{Clients, ForwardablePacket};
+use crate::config::{Config, ConfigFile, GameModeName};
+use crate::event::EventBus;
+use crate::net::{Ban, Cidr, Clients, Firewall, ForwardablePacket};
+use crate::handoff::{self, Handoff, Handoffs};
+use crate::metrics;
+use crate::pack::ResourcePacks;
+use crate::plugin::Plugins;
+use level::provider::CreateOptions;
 use level::{BlockStates, CreativeItems, ItemNetworkIds};
 use proto::bedrock::{
-    Command, CommandDataType, CommandEnum, CommandOverload, CommandParameter, CommandPermissionLevel, CreditsStatus, CreditsUpdate, MovePlayer,
-    MovementMode, TeleportCause, CLIENT_VERSION_STRING, PROTOCOL_VERSION,
+    Command, CommandDataType, CommandEnum, CommandOverload, CommandParameter, CommandPermissionLevel, CompressionAlgorithm, CreditsStatus, CreditsUpdate, DisconnectReason, MovePlayer,
+    MovementMode, TeleportCause, UpdateBlock, UpdateBlockFlags, CLIENT_VERSION_STRING, PROTOCOL_VERSION,
 };
+use proto::types::Dimension;
 use proto::raknet::{
     IncompatibleProtocol, OpenConnectionReply1, OpenConnectionReply2, OpenConnectionRequest1, OpenConnectionRequest2, UnconnectedPing,
     UnconnectedPong, RAKNET_VERSION,
@@ -36,9 +44,13 @@ pub const IPV4_LOCAL_ADDR: Ipv4Addr = Ipv4Addr::UNSPECIFIED;
 pub const IPV6_LOCAL_ADDR: Ipv6Addr = Ipv6Addr::UNSPECIFIED;
 /// Size of the UDP receive buffer.
 const RECV_BUF_SIZE: usize = 2048;
+/// Interval at which expired bans and unused rate limits are removed from the firewall.
+const FIREWALL_PRUNE_INTERVAL: Duration = Duration::from_secs(30);
 /// Refresh rate of the server's metadata.
 /// This data is displayed in the server menu.
 const METADATA_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+/// Port that clients listen on for servers broadcasting themselves on the local network.
+const LAN_DISCOVERY_PORT: u16 = 19132;
 
 /// Configures and instance and constructs it.
 pub struct InstanceBuilder(Config);
@@ -55,6 +67,147 @@ impl InstanceBuilder {
         self
     }
 
+    /// Sets the options used to create the level if it does not exist at the level path.
+    pub fn level_create_options(mut self, options: CreateOptions) -> InstanceBuilder {
+        self.0.level.create = options;
+        self
+    }
+
+    /// Sets the layers generated in superflat levels.
+    ///
+    /// See [`LevelConfig::flat_layers`](crate::config::LevelConfig::flat_layers).
+    pub fn level_flat_layers<S: Into<String>>(mut self, layers: S) -> InstanceBuilder {
+        self.0.level.flat_layers = Some(layers.into());
+        self
+    }
+
+    /// Sets the maximum amount of memory used by the chunk cache in megabytes.
+    pub const fn level_cache_size(mut self, size: usize) -> InstanceBuilder {
+        self.0.level.cache_size = size;
+        self
+    }
+
+    /// Loads options from a TOML configuration file such as `mirai.toml`.
+    ///
+    /// Options in `overrides`, usually taken from command line flags, take precedence over the file.
+    /// If the file does not exist, only the overrides are applied.
+    ///
+    /// The file is read again when [`reload_config`](Instance::reload_config) is called.
+    pub fn config_file<P: Into<PathBuf>>(mut self, path: P, overrides: ConfigFile) -> anyhow::Result<InstanceBuilder> {
+        let path = path.into();
+        let file = if path.exists() {
+            ConfigFile::load(&path)?
+        } else {
+            tracing::warn!("Configuration file {} does not exist, using default options", path.display());
+            ConfigFile::default()
+        };
+
+        self.0.apply(&file.merge(overrides.clone()));
+        self.0.source = Some(path);
+        self.0.overrides = overrides;
+
+        Ok(self)
+    }
+
+    /// Sets whether clients must be signed into Xbox Live to join.
+    ///
+    /// Online mode is enabled by default. Disabling it allows anyone to join with any username.
+    pub const fn online_mode(mut self, enabled: bool) -> InstanceBuilder {
+        self.0.online_mode = enabled;
+        self
+    }
+
+    /// Sets the compression algorithm and the size above which packets are compressed.
+    ///
+    /// Snappy uses less CPU time than Flate but produces larger packets.
+    pub const fn compression(mut self, algorithm: CompressionAlgorithm, threshold: u16) -> InstanceBuilder {
+        self.0.compression.algorithm = algorithm;
+        self.0.compression.threshold = threshold;
+        self
+    }
+
+    /// Sets the compression level of the Flate algorithm, ranging from 0 (fastest) to 9 (smallest).
+    ///
+    /// Levels above 9 are clamped.
+    pub fn flate_level(mut self, level: u32) -> InstanceBuilder {
+        self.0.compression.flate_level = level.min(9);
+        self
+    }
+
+    /// Sets the directory that resource packs are loaded from.
+    pub fn resource_pack_path<P: Into<String>>(mut self, path: P) -> InstanceBuilder {
+        self.0.resource_packs.path = Some(path.into());
+        self
+    }
+
+    /// Sets whether clients must accept the resource packs to be able to join.
+    pub const fn require_resource_packs(mut self, required: bool) -> InstanceBuilder {
+        self.0.resource_packs.required = required;
+        self
+    }
+
+    /// Adds a server that players can be transferred to with a handoff token.
+    ///
+    /// Handoff tokens sent by this server are also accepted.
+    pub fn trusted_peer<A: Into<SocketAddr>>(mut self, addr: A) -> InstanceBuilder {
+        self.0.network.trusted_peers.push(addr.into());
+        self
+    }
+
+    /// Sets the secret used to sign handoff tokens. This must be the same for all servers in the network.
+    pub fn handoff_secret<S: Into<String>>(mut self, secret: S) -> InstanceBuilder {
+        self.0.network.secret = Some(secret.into());
+        self
+    }
+
+    /// Bans a range of addresses permanently.
+    pub fn ban<C: Into<Cidr>>(mut self, range: C) -> InstanceBuilder {
+        self.0.firewall.bans.push(range.into());
+        self
+    }
+
+    /// Sets whether the server broadcasts itself on the local network.
+    pub const fn lan_broadcast(mut self, enabled: bool) -> InstanceBuilder {
+        self.0.discovery.lan_broadcast = enabled;
+        self
+    }
+
+    /// Sets the game mode shown in the server list.
+    pub const fn motd_game_mode(mut self, game_mode: GameModeName) -> InstanceBuilder {
+        self.0.discovery.game_mode = game_mode;
+        self
+    }
+
+    /// Sets the unique ID of the server, instead of generating a random one on startup.
+    pub const fn server_id(mut self, id: u64) -> InstanceBuilder {
+        self.0.discovery.server_id = Some(id);
+        self
+    }
+
+    /// Sets the second line of the message of the day.
+    pub fn sub_motd<S: Into<String>>(mut self, sub_motd: S) -> InstanceBuilder {
+        self.0.discovery.sub_motd = Some(sub_motd.into());
+        self
+    }
+
+    /// Sets the directory that plugins are loaded from.
+    pub fn plugin_path<P: Into<String>>(mut self, path: P) -> InstanceBuilder {
+        self.0.plugins.path = path.into();
+        self
+    }
+
+    /// Sets the amount of fuel that a plugin can consume in a single call.
+    pub const fn plugin_fuel(mut self, fuel: u64) -> InstanceBuilder {
+        self.0.plugins.fuel = fuel;
+        self
+    }
+
+    /// Enables the Prometheus metrics exporter on the given address.
+    pub fn metrics_addr<A: Into<SocketAddr>>(mut self, addr: A) -> InstanceBuilder {
+        self.0.metrics.addr = Some(addr.into());
+        self
+    }
+
     /// Sets the IPv4 address of the instance.
     pub fn ipv4_addr<A: Into<SocketAddrV4>>(mut self, addr: A) -> InstanceBuilder {
         self.0.ipv4_addr = addr.into();
@@ -78,6 +231,10 @@ impl InstanceBuilder {
         let item_network_ids = ItemNetworkIds::new()?;
         let block_states = BlockStates::new()?;
         let creative_items = CreativeItems::new(&item_network_ids, &block_states)?;
+        let resource_packs = match &self.0.resource_packs.path {
+            Some(path) => ResourcePacks::load(path)?,
+            None => ResourcePacks::default(),
+        };
 
         let ipv4_socket = UdpSocket::bind(self.0.ipv4_addr).await.context("Unable to create IPv4 UDP socket")?;
         let ipv6_socket = match self.0.ipv6_addr {
@@ -94,19 +251,26 @@ impl InstanceBuilder {
         let level_service = crate::level::service::Service::new(crate::level::service::ServiceOptions {
             instance_token: running_token.clone(),
             level_path: self.0.level.path.clone(),
+            cache_capacity: self.0.level.cache_size.saturating_mul(1024 * 1024),
+            create_options: self.0.level.create.clone(),
+            flat_layers: self.0.level.flat_layers.clone(),
         })?;
 
         let user_map = Arc::new(Clients::new(Arc::clone(&command_service), Arc::clone(&level_service)));
-        let user_map = Arc::new(Clients::new(Arc::clone(&command_service), Arc::clone(&level_service)));
+        let firewall = Firewall::new(&self.0.firewall);
+        let raknet_guid = self.0.discovery.server_id.unwrap_or_else(rand::random);
         let instance = Instance {
             ipv4_socket,
             ipv6_socket,
             clients: user_map,
+            firewall,
+            events: EventBus::new(),
+            plugins: Plugins::new(),
             command_service,
             level_service,
             config: self.0,
 
-            raknet_guid: rand::random(),
+            raknet_guid,
             current_motd: RwLock::new(String::new()),
             running_token,
             shutdown_token: CancellationToken::new(),
@@ -116,6 +280,8 @@ impl InstanceBuilder {
             creative_items,
             block_states,
             item_network_ids,
+            resource_packs,
+            handoffs: Handoffs::default(),
         };
 
         let instance = Arc::new(instance);
@@ -148,6 +314,12 @@ pub struct Instance {
     command_service: Arc<crate::command::Service>,
     /// Keeps track of the level state.
     level_service: Arc<crate::level::service::Service>,
+    /// Filters packets from clients that do not have a session yet.
+    firewall: Firewall,
+    /// Dispatches gameplay events to listeners.
+    events: EventBus,
+    /// WebAssembly plugins loaded from the plugin directory.
+    plugins: Plugins,
     /// Keeps track of the current configuration of the server.
     config: Config,
     /// Cancelled when the server has started up successfully.
@@ -164,6 +336,10 @@ pub struct Instance {
     pub creative_items: CreativeItems,
     pub block_states: BlockStates,
     pub item_network_ids: ItemNetworkIds,
+    /// Resource packs that are sent to clients when they join.
+    pub resource_packs: ResourcePacks,
+    /// Players that are being transferred to this server by a trusted peer.
+    pub handoffs: Handoffs,
 }
 
 impl Instance {
@@ -205,18 +381,155 @@ impl Instance {
         &self.clients
     }
 
+    /// Gets the firewall of this instance.
+    #[inline]
+    pub const fn firewall(&self) -> &Firewall {
+        &self.firewall
+    }
+
+    /// Gets the event bus of this instance, which is used to listen for gameplay events.
+    #[inline]
+    pub const fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// Gets the plugins that have been loaded by this instance.
+    #[inline]
+    pub const fn plugins(&self) -> &Plugins {
+        &self.plugins
+    }
+
+    /// Bans a range of addresses and disconnects all clients within it.
+    ///
+    /// The ban is permanent if `duration` is `None`.
+    pub fn ban<C: Into<Cidr>>(&self, range: C, duration: Option<Duration>, reason: &str) {
+        let range = range.into();
+        self.firewall.ban(range, duration, reason);
+
+        for client in self.clients.all() {
+            if !client.is_subclient() && range.contains(&client.raknet.address.ip()) {
+                if let Err(err) = client.kick_with_reason(reason, DisconnectReason::Kicked) {
+                    tracing::error!("Failed to kick banned client: {err:#}");
+                    client.raknet.active.cancel();
+                }
+            }
+        }
+
+        tracing::info!("Banned {range}: {reason}");
+    }
+
+    /// Lifts the ban of a range of addresses, returning whether it was banned.
+    pub fn unban<C: Into<Cidr>>(&self, range: C) -> bool {
+        let range = range.into();
+        let unbanned = self.firewall.unban(&range);
+        if unbanned {
+            tracing::info!("Unbanned {range}");
+        }
+
+        unbanned
+    }
+
+    /// Returns all active bans.
+    pub fn bans(&self) -> Vec<Ban> {
+        self.firewall.bans()
+    }
+
+    /// Returns the runtime ID of the block at the given position.
+    pub fn block(&self, position: Vector<i32, 3>, dimension: Dimension) -> anyhow::Result<u32> {
+        let block = self.level_service.block(position, dimension)?;
+        Ok(self.block_states.state(&block).unwrap_or_else(|| self.block_states.air()))
+    }
+
+    /// Changes a block in the level and sends the change to every client that can see it.
+    ///
+    /// This function returns an error if the runtime ID does not belong to a block state.
+    pub fn set_block(&self, position: Vector<i32, 3>, dimension: Dimension, runtime_id: u32) -> anyhow::Result<()> {
+        let Some(block) = self.block_states.entry(runtime_id).cloned() else {
+            anyhow::bail!("Unknown block runtime ID {runtime_id}");
+        };
+
+        self.level_service.set_block(position.clone(), dimension, block)?;
+
+        let packet = UpdateBlock {
+            position: BlockPosition::new(position.x, position.y as u32, position.z),
+            block_runtime_id: runtime_id,
+            flags: UpdateBlockFlags::UpdateNetwork as u32,
+            layer: 0,
+        };
+
+        let column = Vector::from([position.x >> 4, position.z >> 4]);
+        for client in self.clients.viewing(&column, dimension) {
+            if let Err(err) = client.send_block_update(packet.clone()) {
+                tracing::error!("Failed to send block update: {err:#}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads the configuration file again and applies the options that can be changed while the server is running.
+    ///
+    /// This is also triggered by sending SIGHUP to the server process.
+    pub fn reload_config(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.config.source else {
+            anyhow::bail!("Configuration was not loaded from a file");
+        };
+
+        let file = ConfigFile::load(path)?.merge(self.config.overrides.clone());
+        self.config.reload(&file);
+
+        tracing::info!("Reloaded configuration from {}", path.display());
+        Ok(())
+    }
+
+    /// Sends a signed handoff token to a trusted peer and waits until the peer has accepted it.
+    ///
+    /// The token is delivered over TCP to the address that the peer is listening on.
+    pub async fn send_handoff(&self, peer: SocketAddr, handoff: &Handoff) -> anyhow::Result<()> {
+        let token = {
+            let network = self.config.network();
+            if !network.is_trusted(&peer) {
+                anyhow::bail!("{peer} is not a trusted peer");
+            }
+
+            let Some(secret) = &network.secret else {
+                anyhow::bail!("No handoff secret has been configured");
+            };
+
+            handoff.sign(secret.as_bytes())?
+        };
+
+        handoff::deliver(peer, &token).await.context("Unable to send handoff token")
+    }
+
+    /// Stores a handoff token sent by a trusted peer until the player joins.
+    pub(crate) fn receive_handoff(&self, peer: SocketAddr, token: &[u8]) -> anyhow::Result<()> {
+        let network = self.config.network();
+        let Some(secret) = network.secret.as_ref().filter(|_| network.is_trusted_ip(&peer.ip())) else {
+            anyhow::bail!("{peer} is not a trusted peer");
+        };
+
+        let handoff = Handoff::verify(token, secret.as_bytes())?;
+        tracing::debug!("Received handoff of {} from {peer}", handoff.name);
+
+        self.handoffs.insert(handoff)
+    }
+
     /// Refreshes the message of the day by calling the generating function again.
     pub fn refresh_motd(self: &Arc<Instance>) {
         let motd: CowString<'_> = (self.config.motd_callback)(self);
+        let discovery = self.config.discovery();
         let metadata = format!(
-            "MCPE;{};{};{};{};{};{};{};Survival;1;{};{};",
+            // The field after the game mode is 0 for clients that are restricted to Nintendo Switch servers.
+            "MCPE;{};{};{};{};{};{};{};{};1;{};{};",
             motd.as_str(),
             PROTOCOL_VERSION,
             CLIENT_VERSION_STRING,
             self.clients.total_connected(),
             self.config.max_connections(),
             self.raknet_guid,
-            self.config.name.as_str(),
+            discovery.sub_motd.as_deref().unwrap_or_else(|| self.config.name.as_str()),
+            discovery.game_mode.as_str(),
             self.config.ipv4_addr.port(),
             self.config.ipv6_addr.map(|addr| addr.port()).unwrap_or(0)
         );
@@ -224,6 +537,25 @@ impl Instance {
         *self.current_motd.write() = metadata;
     }
 
+    /// Broadcasts the message of the day on the local network.
+    ///
+    /// Clients list servers that broadcast an [`UnconnectedPong`] in their Friends tab.
+    async fn broadcast_lan(&self) -> anyhow::Result<()> {
+        let metadata = self.current_motd.read().clone();
+        let time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+        let pong = UnconnectedPong { time, server_guid: self.raknet_guid, metadata: &metadata };
+
+        let mut buf = RVec::alloc_with_capacity(pong.size_hint());
+        pong.serialize_into(&mut buf)?;
+
+        self.ipv4_socket
+            .send_to(buf.as_ref(), (Ipv4Addr::BROADCAST, LAN_DISCOVERY_PORT))
+            .await
+            .context("Unable to broadcast message of the day")?;
+
+        Ok(())
+    }
+
     /// Signals the server to start shutting down.
     ///
     /// This function returns `None` if the server is already shutting down.
@@ -429,6 +761,8 @@ impl Instance {
             create_fn,
         )?;
 
+        self.plugins.load_all(self)?;
+
         {
             let socket = Arc::clone(&self.ipv4_socket);
             let this = Arc::clone(self);
@@ -445,6 +779,71 @@ impl Instance {
             tracing::info!("IPv6 listener ready");
         }
 
+        {
+            let this = Arc::clone(self);
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(FIREWALL_PRUNE_INTERVAL);
+                loop {
+                    tokio::select! {
+                        _ = interval.tick() => this.firewall.prune(),
+                        () = this.running_token.cancelled() => break
+                    }
+                }
+            });
+        }
+
+        {
+            let lan_broadcast = self.config.discovery().lan_broadcast;
+            if lan_broadcast {
+                self.ipv4_socket.set_broadcast(true).context("Unable to enable LAN broadcasting")?;
+                tracing::info!("Broadcasting on the local network");
+            }
+
+            let this = Arc::clone(self);
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(METADATA_REFRESH_INTERVAL);
+                loop {
+                    tokio::select! {
+                        _ = interval.tick() => {
+                            this.refresh_motd();
+                            if lan_broadcast {
+                                if let Err(err) = this.broadcast_lan().await {
+                                    tracing::warn!("{err:#}");
+                                }
+                            }
+                        },
+                        () = this.running_token.cancelled() => break
+                    }
+                }
+            });
+        }
+
+        if let Some(addr) = self.config.metrics().addr {
+            let clients = Arc::clone(&self.clients);
+            let token = self.running_token.clone();
+
+            tokio::spawn(async move {
+                if let Err(err) = metrics::serve(addr, clients, token).await {
+                    tracing::error!("Metrics exporter stopped: {err:#}");
+                }
+            });
+        }
+
+        if self.config.network().secret.is_some() {
+            let sockets = std::iter::once(&self.ipv4_socket).chain(self.ipv6_socket.as_ref());
+            for addr in sockets.map(|socket| socket.local_addr()) {
+                let addr = addr.context("Unable to determine handoff address")?;
+                let this = Arc::clone(self);
+                let token = self.running_token.clone();
+
+                tokio::spawn(async move {
+                    if let Err(err) = handoff::serve(addr, this, token).await {
+                        tracing::error!("Handoff listener stopped: {err:#}");
+                    }
+                });
+            }
+        }
+
         {
             let this = Arc::clone(self);
             tokio::spawn(async move {
@@ -456,6 +855,25 @@ impl Instance {
             });
         }
 
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            let this = Arc::clone(self);
+            match signal(SignalKind::hangup()) {
+                Ok(mut hangup) => {
+                    tokio::spawn(async move {
+                        while hangup.recv().await.is_some() {
+                            if let Err(err) = this.reload_config() {
+                                tracing::error!("Failed to reload configuration: {err:#}");
+                            }
+                        }
+                    });
+                }
+                Err(err) => tracing::error!("Failed to create SIGHUP signal handler: {err:#}"),
+            }
+        }
+
         self.startup_token.cancel();
 
         Ok(())
@@ -587,6 +1005,11 @@ impl Instance {
             };
 
             if packet.is_unconnected() {
+                if !self.firewall.allow_unconnected(&address.ip()) {
+                    metrics::metrics().record_firewall_drop();
+                    continue;
+                }
+
                 let udp_socket = Arc::clone(&udp_socket);
                 let session_manager = Arc::clone(&self.clients);
                 let metadata = self.current_motd.read().clone();
@@ -598,6 +1021,11 @@ impl Instance {
                         return;
                     };
 
+                    // Servers on the local network, including this one, broadcast pongs to all clients.
+                    if id == UnconnectedPong::ID {
+                        return;
+                    }
+
                     let pk_result = match id {
                         UnconnectedPing::ID => Instance::process_unconnected_ping(packet, this.raknet_guid, &metadata),
                         OpenConnectionRequest1::ID => Instance::process_open_connection_request1(packet, this.raknet_guid),
@@ -622,6 +1050,8 @@ impl Instance {
                         }
                     }
                 });
+            } else if self.firewall.is_banned(&address.ip()) {
+                metrics::metrics().record_firewall_drop();
             } else if let Err(e) = self.clients.forward(packet).await {
                 tracing::error!("{e:#}");
             }
@@ -0,0 +1,225 @@
+use proto::bedrock::{ItemInstance, WindowId};
+
+/// Amount of slots in the hotbar.
+pub const HOTBAR_SIZE: usize = 9;
+/// Amount of slots in the main inventory, excluding the hotbar.
+pub const MAIN_SIZE: usize = 27;
+/// Amount of armour slots.
+pub const ARMOR_SIZE: usize = 4;
+
+/// A fixed-size collection of item slots.
+#[derive(Debug, Clone)]
+pub struct Container {
+    slots: Box<[ItemInstance<'static>]>,
+}
+
+impl Container {
+    /// Creates a container filled with air.
+    pub fn new(size: usize) -> Self {
+        Self { slots: vec![ItemInstance::air(); size].into_boxed_slice() }
+    }
+
+    /// The amount of slots in this container.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Whether every slot in this container is empty.
+    pub fn is_empty(&self) -> bool {
+        self.slots.iter().all(|item| item.network_id == 0)
+    }
+
+    /// Returns the item in the given slot.
+    #[inline]
+    pub fn get(&self, slot: usize) -> Option<&ItemInstance<'static>> {
+        self.slots.get(slot)
+    }
+
+    /// Returns a mutable reference to the item in the given slot.
+    #[inline]
+    pub fn get_mut(&mut self, slot: usize) -> Option<&mut ItemInstance<'static>> {
+        self.slots.get_mut(slot)
+    }
+
+    /// Replaces the item in the given slot, returning the old item.
+    ///
+    /// This returns `None` if the slot does not exist.
+    pub fn set(&mut self, slot: usize, item: ItemInstance<'static>) -> Option<ItemInstance<'static>> {
+        self.slots.get_mut(slot).map(|old| std::mem::replace(old, item))
+    }
+
+    /// Returns all items in this container, indexed by slot.
+    #[inline]
+    pub fn items(&self) -> &[ItemInstance<'static>] {
+        &self.slots
+    }
+
+    /// Returns the index of the first empty slot.
+    pub fn first_empty(&self) -> Option<usize> {
+        self.slots.iter().position(|item| item.network_id == 0)
+    }
+
+    /// Replaces every item in this container with air.
+    pub fn clear(&mut self) {
+        self.slots.fill(ItemInstance::air());
+    }
+}
+
+/// The inventory of a player.
+#[derive(Debug, Clone)]
+pub struct Inventory {
+    /// The hotbar. These are slots 0 to 8 in the inventory window.
+    pub hotbar: Container,
+    /// The main inventory. These are slots 9 to 35 in the inventory window.
+    pub main: Container,
+    /// Helmet, chestplate, leggings and boots.
+    pub armor: Container,
+    /// Item held in the player's off hand.
+    pub offhand: Container,
+    /// Item that the player is currently moving around in the inventory window.
+    pub cursor: Container,
+    /// Hotbar slot that the player has selected.
+    selected: usize,
+    /// Stack network ID that will be given to the next new item.
+    next_stack_id: i32,
+}
+
+impl Inventory {
+    /// Creates an empty inventory.
+    pub fn new() -> Self {
+        Self {
+            hotbar: Container::new(HOTBAR_SIZE),
+            main: Container::new(MAIN_SIZE),
+            armor: Container::new(ARMOR_SIZE),
+            offhand: Container::new(1),
+            cursor: Container::new(1),
+            selected: 0,
+            next_stack_id: 1,
+        }
+    }
+
+    /// Returns the item in the given slot of a window.
+    ///
+    /// This returns `None` if the window is not part of the player's inventory or the slot does not exist.
+    pub fn slot(&self, window: WindowId, slot: u32) -> Option<&ItemInstance<'static>> {
+        let (container, slot) = self.resolve(window, slot)?;
+        container.get(slot)
+    }
+
+    /// Returns a mutable reference to the item in the given slot of a window.
+    ///
+    /// This returns `None` if the window is not part of the player's inventory or the slot does not exist.
+    pub fn slot_mut(&mut self, window: WindowId, slot: u32) -> Option<&mut ItemInstance<'static>> {
+        let slot = slot as usize;
+        match window {
+            WindowId::Inventory if slot < HOTBAR_SIZE => self.hotbar.get_mut(slot),
+            WindowId::Inventory => self.main.get_mut(slot - HOTBAR_SIZE),
+            WindowId::Hotbar => self.hotbar.get_mut(slot),
+            WindowId::Armor => self.armor.get_mut(slot),
+            WindowId::OffHand => self.offhand.get_mut(slot),
+            WindowId::Ui => self.cursor.get_mut(slot),
+            _ => None,
+        }
+    }
+
+    /// Adds an item to the first empty slot of the hotbar or main inventory.
+    /// The item is given a new stack network ID.
+    ///
+    /// Returns the window and slot the item was put in, or `None` if the inventory is full.
+    pub fn add(&mut self, mut item: ItemInstance<'static>) -> Option<(WindowId, u32)> {
+        let slot = self
+            .hotbar
+            .first_empty()
+            .or_else(|| self.main.first_empty().map(|slot| slot + HOTBAR_SIZE));
+
+        let slot = slot? as u32;
+        item.stack_id = None;
+        self.assign_stack_id(&mut item);
+        if let Some(old) = self.slot_mut(WindowId::Inventory, slot) {
+            *old = item;
+        }
+
+        Some((WindowId::Inventory, slot))
+    }
+
+    /// Gives the item a new stack network ID if it does not have one yet.
+    ///
+    /// Clients refer to items by these IDs in item stack requests. Air never has an ID.
+    pub fn assign_stack_id(&mut self, item: &mut ItemInstance<'static>) {
+        if item.network_id == 0 {
+            item.stack_id = None;
+        } else if item.stack_id.is_none() {
+            item.stack_id = Some(self.next_stack_id);
+            self.next_stack_id = self.next_stack_id.wrapping_add(1).max(1);
+        }
+    }
+
+    /// The hotbar slot that the player has selected.
+    #[inline]
+    pub const fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Changes the selected hotbar slot.
+    ///
+    /// This returns `false` if the slot is not in the hotbar.
+    pub fn set_selected(&mut self, slot: usize) -> bool {
+        if slot >= HOTBAR_SIZE {
+            return false;
+        }
+
+        self.selected = slot;
+        true
+    }
+
+    /// The item that the player is holding in their main hand.
+    pub fn held(&self) -> &ItemInstance<'static> {
+        &self.hotbar.items()[self.selected]
+    }
+
+    /// Returns the hotbar and main inventory as they are laid out in the inventory window.
+    pub fn window_items(&self) -> Vec<ItemInstance<'static>> {
+        self.hotbar.items().iter().chain(self.main.items()).cloned().collect()
+    }
+
+    /// Replaces every item in the inventory with air.
+    pub fn clear(&mut self) {
+        self.hotbar.clear();
+        self.main.clear();
+        self.armor.clear();
+        self.offhand.clear();
+        self.cursor.clear();
+    }
+
+    /// Maps a window slot to a container and the slot within that container.
+    const fn resolve(&self, window: WindowId, slot: u32) -> Option<(&Container, usize)> {
+        let slot = slot as usize;
+        Some(match window {
+            WindowId::Inventory if slot < HOTBAR_SIZE => (&self.hotbar, slot),
+            WindowId::Inventory => (&self.main, slot - HOTBAR_SIZE),
+            WindowId::Hotbar => (&self.hotbar, slot),
+            WindowId::Armor => (&self.armor, slot),
+            WindowId::OffHand => (&self.offhand, slot),
+            WindowId::Ui => (&self.cursor, slot),
+            _ => return None,
+        })
+    }
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether two items are of the same type and amount.
+///
+/// Item data such as NBT is not compared because the client does not always send it back unchanged.
+pub const fn same_stack(a: &ItemInstance, b: &ItemInstance) -> bool {
+    if a.network_id == 0 || b.network_id == 0 {
+        return a.network_id == b.network_id;
+    }
+
+    a.network_id == b.network_id && a.metadata == b.metadata && a.count == b.count
+}
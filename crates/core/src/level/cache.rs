@@ -0,0 +1,606 @@
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap},
+    mem::size_of,
+    sync::Arc,
+    time::Duration,
+};
+
+use futures::SinkExt;
+use level::{Biomes, PaletteEntry, SubChunk, SubStorage};
+use parking_lot::Mutex;
+use proto::types::Dimension;
+use tokio::sync::mpsc;
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+use util::Vector;
+
+use super::io::sink::{Collector, FlushProgress, RegionSink};
+use super::io::stream::{IndexedColumn, IndexedSubChunk, RegionIndex};
+use super::subchunk_range;
+
+/// Identifies a cached subchunk.
+type CacheKey = (Dimension, RegionIndex);
+
+/// Identifies a chunk column.
+type ColumnKey = (Dimension, Vector<i32, 2>);
+
+/// Rough estimate of the memory used by a single block state property.
+const STATE_SIZE: usize = 64;
+
+/// Whether a cached subchunk still has to be written to disk.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum EntryState {
+    /// The subchunk is identical to the one on disk.
+    Clean,
+    /// The subchunk has been modified since it was last submitted to the collector.
+    Dirty,
+    /// The subchunk has been submitted to the collector but might not have been written yet.
+    ///
+    /// Contains the collector round that was running when the subchunk was submitted,
+    /// or `None` if it is still on its way to the collector.
+    Saving(Option<u64>),
+}
+
+/// A subchunk stored in the cache.
+struct CacheEntry {
+    subchunk: SubChunk,
+    /// Estimated amount of bytes used by the subchunk.
+    size: usize,
+    /// Time of the last access, used to order entries by recency.
+    tick: u64,
+    /// Incremented every time the subchunk is modified.
+    version: u64,
+    state: EntryState,
+}
+
+impl CacheEntry {
+    /// Whether the entry can be removed without losing data.
+    const fn is_saved(&self, saved: u64) -> bool {
+        match self.state {
+            EntryState::Clean => true,
+            EntryState::Saving(Some(round)) => round < saved,
+            EntryState::Dirty | EntryState::Saving(None) => false,
+        }
+    }
+}
+
+/// Biomes of a generated column, which are kept until the column has been written to disk.
+struct GeneratedBiomes {
+    biomes: Biomes,
+    /// Collector round that was running when the column was submitted,
+    /// or `None` if it is still on its way to the collector.
+    round: Option<u64>,
+}
+
+/// Data that is on its way to the collector.
+enum Funneled {
+    /// A modified subchunk.
+    SubChunk { key: CacheKey, version: u64, subchunk: SubChunk },
+    /// A column created by a generator, containing only the subchunks that are not empty.
+    Column { key: ColumnKey, subchunks: Vec<SubChunk>, biomes: Biomes },
+}
+
+/// Data that has been fed to the collector, used to record the round it was submitted in.
+enum Submission {
+    SubChunk { key: CacheKey, version: u64 },
+    Column { key: ColumnKey },
+}
+
+#[derive(Default)]
+struct CacheInner {
+    entries: HashMap<CacheKey, CacheEntry>,
+    /// Entries that can be evicted, ordered from least to most recently used.
+    ///
+    /// Entries located in pinned columns are not part of this list.
+    order: BTreeMap<u64, CacheKey>,
+    /// Amount of viewers referencing each column.
+    pins: HashMap<ColumnKey, usize>,
+    /// Biomes of generated columns that might not have been written yet.
+    biomes: HashMap<ColumnKey, GeneratedBiomes>,
+    /// Total estimated size of all entries in bytes.
+    size: usize,
+    /// Counter used to assign ticks.
+    clock: u64,
+}
+
+impl CacheInner {
+    /// Returns the next tick.
+    #[inline]
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    /// Whether the column that the subchunk is located in is referenced by a viewer.
+    #[inline]
+    fn is_pinned(&self, key: &CacheKey) -> bool {
+        self.pins.contains_key(&column_of(key))
+    }
+
+    /// Records that a subchunk has been received by the collector.
+    ///
+    /// The entry is marked as modified again if submitting it failed.
+    fn submitted(&mut self, key: &CacheKey, version: u64, round: Option<u64>) {
+        let Some(entry) = self.entries.get_mut(key) else { return };
+
+        // The entry might have been modified again in the meantime.
+        if entry.version != version || entry.state != EntryState::Saving(None) {
+            return;
+        }
+
+        entry.state = round.map_or(EntryState::Dirty, |round| EntryState::Saving(Some(round)));
+    }
+
+    /// Marks the entry as most recently used.
+    fn touch(&mut self, key: &CacheKey) {
+        let tick = self.tick();
+        let pinned = self.is_pinned(key);
+
+        let Some(entry) = self.entries.get_mut(key) else { return };
+        if !pinned {
+            self.order.remove(&entry.tick);
+            self.order.insert(tick, *key);
+        }
+        entry.tick = tick;
+    }
+}
+
+/// Returns the column that a subchunk is located in.
+#[inline]
+fn column_of(key: &CacheKey) -> ColumnKey {
+    let coordinates = Vector::<i32, 3>::from(key.1);
+    (key.0, Vector::from([coordinates.x, coordinates.z]))
+}
+
+/// Estimates the amount of memory used by a subchunk.
+fn estimate_size(subchunk: &SubChunk) -> usize {
+    let layers = subchunk
+        .layers
+        .iter()
+        .map(|layer| {
+            let palette = layer
+                .palette
+                .iter()
+                .map(|entry| size_of::<PaletteEntry>() + entry.name.len() + entry.states.len() * STATE_SIZE)
+                .sum::<usize>();
+
+            size_of::<SubStorage>() + size_of::<[u16; 4096]>() + palette
+        })
+        .sum::<usize>();
+
+    size_of::<CacheEntry>() + layers
+}
+
+/// Keeps recently used subchunks in memory so that they do not have to be read and decoded again.
+///
+/// Columns that are referenced by a [`Viewer`](super::Viewer) stay resident. All other subchunks are evicted
+/// in least recently used order once the cache exceeds its memory capacity.
+///
+/// Modified subchunks are only kept in the cache and are submitted to the [`Collector`] when they are evicted,
+/// periodically and when the server shuts down. They are not removed until the collector has written them to disk,
+/// so the cache can temporarily exceed its capacity.
+pub struct ChunkCache {
+    inner: Mutex<CacheInner>,
+    /// Maximum amount of bytes used by evictable entries.
+    capacity: usize,
+    /// Progress of the collector that modified subchunks are submitted to.
+    progress: FlushProgress,
+    funnel: mpsc::UnboundedSender<Funneled>,
+    /// Subchunks that are currently being loaded by [`modify`](Self::modify).
+    loading: Mutex<HashMap<CacheKey, Arc<Mutex<()>>>>,
+}
+
+impl ChunkCache {
+    /// Creates a new cache that submits modified subchunks to the given collector.
+    ///
+    /// When `instance_token` is cancelled, all modified subchunks are submitted and `collector_token` is cancelled
+    /// to let the collector perform its final flush.
+    pub(crate) fn new(
+        capacity: usize,
+        collector: &Collector,
+        interval: Duration,
+        instance_token: CancellationToken,
+        collector_token: CancellationToken,
+    ) -> Arc<ChunkCache> {
+        let (funnel, receiver) = mpsc::unbounded_channel();
+        let cache = Arc::new(ChunkCache {
+            inner: Mutex::new(CacheInner::default()),
+            capacity,
+            progress: collector.progress(),
+            funnel,
+            loading: Mutex::new(HashMap::new()),
+        });
+
+        tokio::spawn(Arc::clone(&cache).submission(collector.create_sink(), receiver, interval, instance_token, collector_token));
+
+        cache
+    }
+
+    /// Returns the subchunk with the given index if it is cached.
+    pub fn get(&self, dimension: Dimension, index: RegionIndex) -> Option<SubChunk> {
+        let key = (dimension, index);
+
+        let mut inner = self.inner.lock();
+        inner.touch(&key);
+        inner.entries.get(&key).map(|entry| entry.subchunk.clone())
+    }
+
+    /// Returns the subchunk with the given index, loading and caching it if it is not cached yet.
+    ///
+    /// `load` is called without holding a lock, so loading the same subchunk concurrently is possible.
+    /// In that case the first subchunk that is inserted is returned.
+    pub fn get_or_load<F>(&self, dimension: Dimension, index: RegionIndex, load: F) -> anyhow::Result<SubChunk>
+    where
+        F: FnOnce() -> anyhow::Result<SubChunk>,
+    {
+        if let Some(subchunk) = self.get(dimension, index) {
+            return Ok(subchunk);
+        }
+
+        let subchunk = load()?;
+
+        let mut inner = self.inner.lock();
+        if let Some(entry) = inner.entries.get(&(dimension, index)) {
+            return Ok(entry.subchunk.clone());
+        }
+
+        Self::insert(&mut inner, (dimension, index), subchunk.clone());
+        self.evict(&mut inner);
+        drop(inner);
+
+        Ok(subchunk)
+    }
+
+    /// Modifies the subchunk with the given index, loading it if it is not cached yet.
+    ///
+    /// Concurrent modifications of a subchunk that is not cached only load it once.
+    /// The subchunk is marked as modified if `modify` succeeds.
+    pub fn modify<L, M, R>(&self, dimension: Dimension, index: RegionIndex, load: L, modify: M) -> anyhow::Result<R>
+    where
+        L: Fn() -> anyhow::Result<SubChunk>,
+        M: FnOnce(&mut SubChunk) -> anyhow::Result<R>,
+    {
+        let key = (dimension, index);
+        loop {
+            let mut guard = self.inner.lock();
+            let inner = &mut *guard;
+            let tick = inner.tick();
+            let pinned = inner.is_pinned(&key);
+
+            // The subchunk might have been evicted after it was loaded, in which case it is loaded again.
+            let Entry::Occupied(entry) = inner.entries.entry(key) else {
+                drop(guard);
+                self.load_once(key, &load)?;
+                continue;
+            };
+
+            let entry = entry.into_mut();
+            if !pinned {
+                inner.order.remove(&entry.tick);
+                inner.order.insert(tick, key);
+            }
+            entry.tick = tick;
+
+            let output = modify(&mut entry.subchunk)?;
+            entry.version += 1;
+            entry.state = EntryState::Dirty;
+
+            let old_size = entry.size;
+            entry.size = estimate_size(&entry.subchunk);
+            inner.size = inner.size - old_size + entry.size;
+
+            self.evict(inner);
+            drop(guard);
+
+            return Ok(output);
+        }
+    }
+
+    /// Loads a subchunk that is not cached and inserts it into the cache.
+    ///
+    /// Callers loading the same subchunk wait for the first one to finish instead of loading it again.
+    /// `load` is called without holding the cache lock, since it might generate and insert the column.
+    fn load_once<L>(&self, key: CacheKey, load: L) -> anyhow::Result<()>
+    where
+        L: FnOnce() -> anyhow::Result<SubChunk>,
+    {
+        let slot = Arc::clone(self.loading.lock().entry(key).or_default());
+        let guard = slot.lock();
+
+        let result = if self.inner.lock().entries.contains_key(&key) {
+            Ok(())
+        } else {
+            load().map(|subchunk| {
+                let mut inner = self.inner.lock();
+                if !inner.entries.contains_key(&key) {
+                    Self::insert(&mut inner, key, subchunk);
+                }
+            })
+        };
+        drop(guard);
+
+        // Remove the slot if nobody else is waiting for it.
+        let mut loading = self.loading.lock();
+        if Arc::strong_count(&slot) == 2 {
+            loading.remove(&key);
+        }
+        drop(loading);
+
+        result
+    }
+
+    /// Inserts a column created by a [`Generator`](super::generator::Generator) and submits it to the collector.
+    ///
+    /// `subchunks` must contain every subchunk in the column. If one of them is already cached, the column has been
+    /// generated by another thread in the meantime and `false` is returned without changing anything.
+    pub fn insert_generated(&self, dimension: Dimension, column: Vector<i32, 2>, subchunks: Vec<SubChunk>, biomes: Biomes) -> bool {
+        let column_key = (dimension, column.clone());
+        let keys = subchunks
+            .iter()
+            .map(|subchunk| (dimension, RegionIndex::from(Vector::from([column.x, i32::from(subchunk.index), column.y]))))
+            .collect::<Vec<_>>();
+
+        let mut inner = self.inner.lock();
+        if inner.biomes.contains_key(&column_key) || keys.iter().any(|key| inner.entries.contains_key(key)) {
+            return false;
+        }
+
+        let saved = subchunks.iter().filter(|subchunk| !subchunk.is_empty()).cloned().collect();
+        for (key, subchunk) in keys.iter().zip(subchunks) {
+            Self::insert(&mut inner, *key, subchunk);
+            if let Some(entry) = inner.entries.get_mut(key) {
+                entry.state = EntryState::Saving(None);
+            }
+        }
+        inner.biomes.insert(column_key.clone(), GeneratedBiomes { biomes: biomes.clone(), round: None });
+
+        let funneled = Funneled::Column { key: column_key, subchunks: saved, biomes };
+        if self.funnel.send(funneled).is_err() {
+            // The submission task has stopped, keep the subchunks so they are not lost.
+            for key in &keys {
+                inner.submitted(key, 0, None);
+            }
+        }
+
+        self.evict(&mut inner);
+        drop(inner);
+
+        true
+    }
+
+    /// Returns the biomes of a generated column that might not have been written to disk yet.
+    pub fn biomes(&self, dimension: Dimension, column: Vector<i32, 2>) -> Option<Biomes> {
+        self.inner.lock().biomes.get(&(dimension, column)).map(|generated| generated.biomes.clone())
+    }
+
+    /// Keeps all subchunks in the given column resident until [`unpin`](Self::unpin) is called.
+    ///
+    /// Columns can be pinned multiple times, they become evictable once every pin has been removed.
+    pub fn pin(&self, dimension: Dimension, column: Vector<i32, 2>) {
+        let mut inner = self.inner.lock();
+
+        let count = inner.pins.entry((dimension, column.clone())).or_insert(0);
+        *count += 1;
+        if *count > 1 {
+            return;
+        }
+
+        for y in subchunk_range(dimension) {
+            let key = (dimension, RegionIndex::from(Vector::from([column.x, y, column.y])));
+            if let Some(tick) = inner.entries.get(&key).map(|entry| entry.tick) {
+                inner.order.remove(&tick);
+            }
+        }
+    }
+
+    /// Removes a pin created by [`pin`](Self::pin).
+    pub fn unpin(&self, dimension: Dimension, column: Vector<i32, 2>) {
+        let mut inner = self.inner.lock();
+
+        let Entry::Occupied(mut count) = inner.pins.entry((dimension, column.clone())) else {
+            tracing::warn!("Attempted to unpin column {column:?} which is not pinned");
+            return;
+        };
+
+        *count.get_mut() -= 1;
+        if *count.get() > 0 {
+            return;
+        }
+        count.remove();
+
+        for y in subchunk_range(dimension) {
+            let key = (dimension, RegionIndex::from(Vector::from([column.x, y, column.y])));
+            if let Some(tick) = inner.entries.get(&key).map(|entry| entry.tick) {
+                inner.order.insert(tick, key);
+            }
+        }
+
+        self.evict(&mut inner);
+        drop(inner);
+    }
+
+    /// Estimated amount of memory used by the cache in bytes.
+    pub fn size(&self) -> usize {
+        self.inner.lock().size
+    }
+
+    /// Amount of subchunks in the cache.
+    pub fn len(&self) -> usize {
+        self.inner.lock().entries.len()
+    }
+
+    /// Whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.inner.lock().entries.is_empty()
+    }
+
+    /// Inserts a subchunk that is identical to the one on disk.
+    fn insert(inner: &mut CacheInner, key: CacheKey, subchunk: SubChunk) {
+        let tick = inner.tick();
+        let size = estimate_size(&subchunk);
+
+        if !inner.is_pinned(&key) {
+            inner.order.insert(tick, key);
+        }
+        inner.size += size;
+        inner.entries.insert(key, CacheEntry { subchunk, size, tick, version: 0, state: EntryState::Clean });
+    }
+
+    /// Evicts the least recently used entries until the cache fits within its capacity.
+    ///
+    /// Modified entries are submitted to the collector instead and are evicted once they have been written.
+    fn evict(&self, inner: &mut CacheInner) {
+        let saved = self.progress.saved();
+        inner.biomes.retain(|_, generated| generated.round.map_or(true, |round| round >= saved));
+
+        let mut cursor = 0;
+        while inner.size > self.capacity {
+            let Some((&tick, &key)) = inner.order.range(cursor..).next() else { break };
+            cursor = tick + 1;
+
+            let Some(entry) = inner.entries.get_mut(&key) else {
+                inner.order.remove(&tick);
+                continue;
+            };
+
+            if entry.state == EntryState::Dirty {
+                self.submit(key, entry);
+                continue;
+            }
+            if !entry.is_saved(saved) {
+                continue;
+            }
+
+            inner.order.remove(&tick);
+            if let Some(entry) = inner.entries.remove(&key) {
+                inner.size -= entry.size;
+            }
+        }
+    }
+
+    /// Submits a modified entry to the collector.
+    fn submit(&self, key: CacheKey, entry: &mut CacheEntry) {
+        entry.state = EntryState::Saving(None);
+
+        let funneled = Funneled::SubChunk { key, version: entry.version, subchunk: entry.subchunk.clone() };
+        if self.funnel.send(funneled).is_err() {
+            // The submission task has stopped, keep the entry so the change is not lost.
+            entry.state = EntryState::Dirty;
+        }
+    }
+
+    /// Submits all modified entries to the collector.
+    fn submit_modified(&self) {
+        let mut inner = self.inner.lock();
+        for (key, entry) in &mut inner.entries {
+            if entry.state == EntryState::Dirty {
+                self.submit(*key, entry);
+            }
+        }
+    }
+
+    /// Removes entries and generated biomes that have been written to disk since they were submitted.
+    ///
+    /// Eviction otherwise only happens when the cache changes, so entries that were submitted while the server
+    /// was idle would be kept until the next change.
+    fn prune(&self) {
+        let mut inner = self.inner.lock();
+        self.evict(&mut inner);
+    }
+
+    /// Forwards modified subchunks to the collector.
+    ///
+    /// A single task forwards all subchunks so that older versions of a subchunk can never overtake newer ones
+    /// on their way to the collector.
+    async fn submission(
+        self: Arc<Self>,
+        mut sink: RegionSink,
+        mut receiver: mpsc::UnboundedReceiver<Funneled>,
+        interval: Duration,
+        instance_token: CancellationToken,
+        collector_token: CancellationToken,
+    ) {
+        let mut interval = tokio::time::interval(interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                Some(funneled) = receiver.recv() => {
+                    let mut batch = vec![funneled];
+                    while let Ok(funneled) = receiver.try_recv() {
+                        batch.push(funneled);
+                    }
+                    self.forward(&mut sink, batch).await;
+                }
+                _ = interval.tick() => {
+                    self.prune();
+                    self.submit_modified();
+                }
+                _ = instance_token.cancelled() => break
+            }
+        }
+
+        // Submit all remaining changes before the collector performs its final flush.
+        self.submit_modified();
+        let mut batch = Vec::new();
+        while let Ok(funneled) = receiver.try_recv() {
+            batch.push(funneled);
+        }
+        self.forward(&mut sink, batch).await;
+
+        collector_token.cancel();
+    }
+
+    /// Sends a batch of subchunks and generated columns to the collector.
+    ///
+    /// The sink is only flushed once per batch, so all changes in the batch can be written by the same collector round.
+    async fn forward(&self, sink: &mut RegionSink, batch: Vec<Funneled>) {
+        let mut fed = Vec::with_capacity(batch.len());
+        for funneled in batch {
+            match funneled {
+                Funneled::SubChunk { key, version, subchunk } => {
+                    let indexed = IndexedSubChunk { index: key.1, dimension: key.0, data: subchunk };
+                    let result = sink.feed(indexed).await;
+                    if let Err(err) = &result {
+                        tracing::error!("Failed to submit modified subchunk to collector: {err:#}");
+                    }
+
+                    fed.push((Submission::SubChunk { key, version }, result.is_ok()));
+                }
+                Funneled::Column { key: (dimension, column), subchunks, biomes } => {
+                    let indexed = IndexedColumn { coordinates: column.clone(), dimension, subchunks, biomes };
+                    let result = sink.feed(indexed).await;
+                    if let Err(err) = &result {
+                        tracing::error!("Failed to submit generated column to collector: {err:#}");
+                    }
+
+                    fed.push((Submission::Column { key: (dimension, column) }, result.is_ok()));
+                }
+            }
+        }
+
+        if let Err(err) = SinkExt::<IndexedSubChunk>::flush(sink).await {
+            tracing::error!("Failed to flush collector sink: {err:#}");
+        }
+
+        // The flush waits until the collector has taken the batch, so it is written by the current round or a later one.
+        let round = self.progress.started();
+
+        let mut inner = self.inner.lock();
+        for (submission, ok) in fed {
+            let round = ok.then_some(round);
+            match submission {
+                Submission::SubChunk { key, version } => inner.submitted(&key, version, round),
+                Submission::Column { key: (dimension, column) } => {
+                    for y in subchunk_range(dimension) {
+                        inner.submitted(&(dimension, RegionIndex::from(Vector::from([column.x, y, column.y]))), 0, round);
+                    }
+                    if let Some(generated) = inner.biomes.get_mut(&(dimension, column)) {
+                        generated.round = round;
+                    }
+                }
+            }
+        }
+        drop(inner);
+    }
+}
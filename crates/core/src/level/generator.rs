@@ -0,0 +1,282 @@
+use std::str::FromStr;
+
+use anyhow::Context;
+use level::{provider::UNSET_SPAWN_Y, BiomeEncoding, Biomes, BlockStates, PaletteEntry, SubChunk, SubStorage};
+use proto::types::Dimension;
+use serde::Deserialize;
+use util::Vector;
+
+use super::subchunk_range;
+
+/// Biome used when none has been configured.
+const PLAINS: u32 = 1;
+
+/// Distance from the spawn point to the edge of the platform created by the [`VoidGenerator`].
+const PLATFORM_RADIUS: i32 = 2;
+/// Spawn height used by the [`VoidGenerator`] if the level has no spawn point yet.
+const VOID_SPAWN_Y: i32 = 64;
+
+/// A chunk column created by a [`Generator`].
+pub struct GeneratedColumn {
+    /// Subchunks in the column.
+    ///
+    /// Subchunks that are not included are filled with air.
+    pub subchunks: Vec<SubChunk>,
+    /// Biomes of the column.
+    pub biomes: Biomes,
+}
+
+/// Creates the terrain of chunk columns that do not exist yet.
+///
+/// The level [`Service`](super::Service) calls the generator whenever a column is requested that has never been
+/// generated before. Generated columns are saved through the [`Collector`](super::io::sink::Collector),
+/// so every column is only generated once.
+///
+/// A custom generator can be installed using [`Service::set_generator`](super::Service::set_generator).
+pub trait Generator: Send + Sync {
+    /// Generates the column at the given chunk coordinates.
+    fn generate(&self, coordinates: Vector<i32, 2>, dimension: Dimension) -> anyhow::Result<GeneratedColumn>;
+
+    /// Returns the Y coordinate of the first block above the terrain at the given position.
+    ///
+    /// This is used to place the spawn point of new levels on the surface.
+    /// The default implementation generates the column and reads its heightmap.
+    fn surface_height(&self, x: i32, z: i32, dimension: Dimension) -> anyhow::Result<i32> {
+        let column = self.generate(Vector::from([x >> 4, z >> 4]), dimension)?;
+        let height = column.biomes.heightmap[(z & 0xf) as usize][(x & 0xf) as usize];
+
+        Ok(min_y(dimension) + i32::from(height))
+    }
+}
+
+/// A single layer of the `FlatWorldLayers` setting.
+#[derive(Deserialize)]
+struct BlockLayer {
+    block_name: String,
+    count: u32,
+}
+
+/// The `FlatWorldLayers` setting stored in `level.dat`.
+#[derive(Deserialize)]
+struct FlatWorldLayers {
+    biome_id: u32,
+    block_layers: Vec<BlockLayer>,
+}
+
+/// Layers that make up a superflat level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlatPreset {
+    /// Block names and the amount of layers they fill, from the bottom of the level upwards.
+    pub layers: Vec<(String, u32)>,
+    /// Biome ID of the level.
+    pub biome: u32,
+}
+
+impl FlatPreset {
+    /// Reads the preset from the JSON stored in the `FlatWorldLayers` setting of `level.dat`.
+    pub fn from_json(json: &str) -> anyhow::Result<FlatPreset> {
+        let settings: FlatWorldLayers = serde_json::from_str(json)?;
+        Ok(FlatPreset {
+            layers: settings.block_layers.into_iter().map(|layer| (layer.block_name, layer.count)).collect(),
+            biome: settings.biome_id,
+        })
+    }
+}
+
+impl Default for FlatPreset {
+    fn default() -> FlatPreset {
+        FlatPreset {
+            layers: vec![
+                ("minecraft:bedrock".to_owned(), 1),
+                ("minecraft:dirt".to_owned(), 2),
+                ("minecraft:grass".to_owned(), 1),
+            ],
+            biome: PLAINS,
+        }
+    }
+}
+
+/// Parses a preset string such as `minecraft:bedrock,2*minecraft:dirt,minecraft:grass;1`.
+///
+/// Layers are listed from the bottom up and separated by commas. Every layer can be prefixed by the amount of layers
+/// followed by a `*`. The biome ID optionally follows after a semicolon.
+impl FromStr for FlatPreset {
+    type Err = anyhow::Error;
+
+    fn from_str(preset: &str) -> anyhow::Result<FlatPreset> {
+        let (layers, biome) = match preset.split_once(';') {
+            Some((layers, biome)) => (layers, biome.trim().parse().with_context(|| format!("Invalid biome ID {biome:?}"))?),
+            None => (preset, PLAINS),
+        };
+
+        let layers = layers
+            .split(',')
+            .map(str::trim)
+            .filter(|layer| !layer.is_empty())
+            .map(|layer| {
+                let (count, name) = match layer.split_once('*') {
+                    Some((count, name)) => (count.trim().parse().with_context(|| format!("Invalid layer count in {layer:?}"))?, name.trim()),
+                    None => (1, layer),
+                };
+
+                let name = if name.contains(':') { name.to_owned() } else { format!("minecraft:{name}") };
+                Ok((name, count))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(FlatPreset { layers, biome })
+    }
+}
+
+/// Looks up the block state used for a block name.
+fn block_state(states: &BlockStates, name: &str) -> anyhow::Result<PaletteEntry> {
+    states.default_state(name).cloned().with_context(|| format!("Unknown block {name}"))
+}
+
+/// Returns the lowest block Y coordinate in the dimension.
+const fn min_y(dimension: Dimension) -> i32 {
+    subchunk_range(dimension).start * 16
+}
+
+/// Creates a subchunk layer from a function that returns the block at every position.
+///
+/// Positions for which the function returns `None` are filled with air.
+fn fill<F>(air: &PaletteEntry, mut block: F) -> SubStorage
+where
+    F: FnMut(Vector<u8, 3>) -> Option<PaletteEntry>,
+{
+    let mut storage = SubStorage::empty();
+    storage.palette.push(air.clone());
+
+    for offset in 0..4096 {
+        let position = level::from_offset(offset);
+        let Some(block) = block(position) else { continue };
+
+        let index = storage.palette.iter().position(|entry| *entry == block).unwrap_or_else(|| {
+            storage.palette.push(block);
+            storage.palette.len() - 1
+        });
+        storage.indices[offset] = index as u16;
+    }
+
+    storage
+}
+
+/// Creates biomes that consist of a single biome and a flat heightmap.
+fn single_biome(dimension: Dimension, biome: u32, height: u16) -> Biomes {
+    Biomes {
+        heightmap: Box::new([[height; 16]; 16]),
+        fragments: subchunk_range(dimension).map(|_| BiomeEncoding::Single(biome)).collect(),
+    }
+}
+
+/// Generates an infinite superflat level.
+///
+/// The layers start at the bottom of every dimension.
+pub struct FlatGenerator {
+    /// Block at every height, starting at the bottom of the dimension.
+    blocks: Vec<PaletteEntry>,
+    air: PaletteEntry,
+    biome: u32,
+}
+
+impl FlatGenerator {
+    /// Creates a generator that produces the layers in the given preset.
+    pub fn new(preset: &FlatPreset, states: &BlockStates) -> anyhow::Result<FlatGenerator> {
+        let mut blocks = Vec::new();
+        for (name, count) in &preset.layers {
+            let block = block_state(states, name)?;
+            blocks.extend(std::iter::repeat(block).take(*count as usize));
+        }
+
+        Ok(FlatGenerator { blocks, air: block_state(states, "minecraft:air")?, biome: preset.biome })
+    }
+}
+
+impl Generator for FlatGenerator {
+    fn generate(&self, _coordinates: Vector<i32, 2>, dimension: Dimension) -> anyhow::Result<GeneratedColumn> {
+        let range = subchunk_range(dimension);
+        let height = self.blocks.len().min(range.len() * 16);
+
+        let subchunks = range
+            .enumerate()
+            .take(height.div_ceil(16))
+            .map(|(i, y)| {
+                let mut subchunk = SubChunk::empty(y as i8);
+                subchunk.layers[0] = fill(&self.air, |position| self.blocks.get(i * 16 + position.y as usize).cloned());
+                subchunk
+            })
+            .collect();
+
+        Ok(GeneratedColumn { subchunks, biomes: single_biome(dimension, self.biome, height as u16) })
+    }
+}
+
+/// Generates an empty level with a small platform below the spawn point.
+pub struct VoidGenerator {
+    /// Position of the spawn point in the overworld.
+    spawn: Vector<i32, 3>,
+    platform: PaletteEntry,
+    air: PaletteEntry,
+}
+
+impl VoidGenerator {
+    /// Creates a generator that places a platform of stone below the given spawn point.
+    ///
+    /// Void levels have no surface to place an unset spawn point on, so it is placed at a fixed height instead.
+    pub fn new(mut spawn: Vector<i32, 3>, states: &BlockStates) -> anyhow::Result<VoidGenerator> {
+        if spawn.y == UNSET_SPAWN_Y {
+            spawn.y = VOID_SPAWN_Y;
+        }
+
+        Ok(VoidGenerator {
+            spawn,
+            platform: block_state(states, "minecraft:stone")?,
+            air: block_state(states, "minecraft:air")?,
+        })
+    }
+}
+
+impl Generator for VoidGenerator {
+    fn generate(&self, coordinates: Vector<i32, 2>, dimension: Dimension) -> anyhow::Result<GeneratedColumn> {
+        let empty = GeneratedColumn { subchunks: Vec::new(), biomes: single_biome(dimension, PLAINS, 0) };
+
+        let platform_y = self.spawn.y - 1;
+        let index = platform_y >> 4;
+        if dimension != Dimension::Overworld || !subchunk_range(dimension).contains(&index) {
+            return Ok(empty);
+        }
+
+        // Part of the platform that is located in this column, in local coordinates.
+        let min_x = (self.spawn.x - PLATFORM_RADIUS - coordinates.x * 16).max(0);
+        let max_x = (self.spawn.x + PLATFORM_RADIUS - coordinates.x * 16).min(15);
+        let min_z = (self.spawn.z - PLATFORM_RADIUS - coordinates.y * 16).max(0);
+        let max_z = (self.spawn.z + PLATFORM_RADIUS - coordinates.y * 16).min(15);
+        if min_x > max_x || min_z > max_z {
+            return Ok(empty);
+        }
+
+        let local_y = (platform_y & 0xf) as u8;
+        let inside = |position: &Vector<u8, 3>| {
+            let (x, z) = (i32::from(position.x), i32::from(position.z));
+            position.y == local_y && (min_x..=max_x).contains(&x) && (min_z..=max_z).contains(&z)
+        };
+
+        let mut subchunk = SubChunk::empty(index as i8);
+        subchunk.layers[0] = fill(&self.air, |position| inside(&position).then(|| self.platform.clone()));
+
+        let mut biomes = empty.biomes;
+        let height = (platform_y - min_y(dimension) + 1) as u16;
+        for x in min_x..=max_x {
+            for z in min_z..=max_z {
+                biomes.heightmap[z as usize][x as usize] = height;
+            }
+        }
+
+        Ok(GeneratedColumn { subchunks: vec![subchunk], biomes })
+    }
+
+    fn surface_height(&self, _x: i32, _z: i32, _dimension: Dimension) -> anyhow::Result<i32> {
+        Ok(self.spawn.y)
+    }
+}
@@ -3,28 +3,58 @@ use rayon::iter::IntoParallelIterator;
 use util::Vector;
 
 use std::ops::Range;
+use std::sync::Arc;
 
 use super::region::{Region, RegionIter};
 
 /// A region representing all chunks in a radius around a center.
+///
+/// The chunk columns are ordered by their distance to the center, nearest first.
+/// Every column is iterated over from bottom to top.
 #[derive(Clone)]
 pub struct RadialRegion {
     center: Vector<i32, 2>,
     radius: usize,
     vertical: Range<i32>,
     dimension: Dimension,
+    /// Offsets of all columns in this region relative to the center, sorted by distance.
+    offsets: Arc<[Vector<i32, 2>]>,
 }
 
 impl RadialRegion {
     /// Creates a radial region around a central point.
     pub fn from_center<C: Into<Vector<i32, 2>>>(center: C, radius: usize, vertical: Range<i32>, dimension: Dimension) -> Self {
+        let r = radius as i32;
+        let mut offsets = (-r..=r)
+            .flat_map(|z| (-r..=r).map(move |x| Vector::from([x, z])))
+            .filter(|offset| offset.x * offset.x + offset.y * offset.y <= r * r)
+            .collect::<Vec<_>>();
+
+        offsets.sort_by_key(|offset| offset.x * offset.x + offset.y * offset.y);
+
         Self {
             center: center.into(),
             radius,
             vertical,
             dimension,
+            offsets: offsets.into(),
         }
     }
+
+    /// The center of this region.
+    pub const fn center(&self) -> &Vector<i32, 2> {
+        &self.center
+    }
+
+    /// The radius of this region.
+    pub const fn radius(&self) -> usize {
+        self.radius
+    }
+
+    /// Returns the coordinates of all chunk columns in this region, nearest first.
+    pub fn columns(&self) -> impl Iterator<Item = Vector<i32, 2>> + '_ {
+        self.offsets.iter().map(|offset| Vector::from([self.center.x + offset.x, self.center.y + offset.y]))
+    }
 }
 
 impl IntoIterator for RadialRegion {
@@ -55,32 +85,28 @@ impl IntoParallelIterator for RadialRegion {
 
 impl Region for RadialRegion {
     fn as_coord(&self, index: usize) -> Option<Vector<i32, 3>> {
-        let y = (index % (self.len() / self.vertical.len())) as i32 + self.vertical.start;
-
-        let row_size = |row: usize| -> usize { 2 * (((self.radius.pow(2) - row.pow(2)) as f32).sqrt()).floor() as usize + 1 };
-
-        let mut count = 0;
-        let mut last = 0;
+        let height = self.vertical.len();
+        if height == 0 {
+            return None;
+        }
 
-        for row in 0..self.radius * 2 + 1 {
-            count += row_size((self.radius as i32 - row as i32).unsigned_abs() as usize);
-            if index < count {
-                let mut coord = Vector::from([(index - last) as i32, y, row as i32]);
+        let offset = self.offsets.get(index / height)?;
+        let y = (index % height) as i32 + self.vertical.start;
 
-                // By default the coordinates are centered around (0, 0), move it to the given center point.
-                coord.x += self.center.x;
-                coord.z += self.center.y;
+        Some(Vector::from([self.center.x + offset.x, y, self.center.y + offset.y]))
+    }
 
-                return Some(coord);
-            }
-            last = count;
+    fn as_index(&self, coord: &Vector<i32, 3>) -> Option<usize> {
+        if !self.vertical.contains(&coord.y) {
+            return None;
         }
 
-        None
-    }
+        let column = self
+            .offsets
+            .iter()
+            .position(|offset| self.center.x + offset.x == coord.x && self.center.y + offset.y == coord.z)?;
 
-    fn as_index(&self, _coord: &Vector<i32, 3>) -> Option<usize> {
-        todo!()
+        Some(column * self.vertical.len() + (coord.y - self.vertical.start) as usize)
     }
 
     fn dimension(&self) -> Dimension {
@@ -88,11 +114,6 @@ impl Region for RadialRegion {
     }
 
     fn len(&self) -> usize {
-        // Using the sum from Gauss's circle problem.
-
-        1 + 4 * self.radius
-            + 4 * (1..self.radius)
-                .map(|i: usize| (((self.radius.pow(2) - i.pow(2)) as f32).sqrt()).floor() as usize)
-                .sum::<usize>()
+        self.offsets.len() * self.vertical.len()
     }
 }
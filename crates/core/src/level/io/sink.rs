@@ -1,22 +1,25 @@
 use std::{
     future::Future,
+    panic::AssertUnwindSafe,
     pin::Pin,
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         Arc,
     },
     task::{Context, Poll, Waker},
+    time::{Duration, Instant},
 };
 
 use futures::Sink;
-use level::provider::Provider;
+use level::{provider::Provider, ChangeSet};
 use parking_lot::Mutex;
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::MissedTickBehavior;
 use tokio_util::sync::CancellationToken;
-use util::Joinable;
+use util::{Joinable, Vector};
 
-use super::stream::IndexedSubChunk;
+use super::stream::{IndexedColumn, IndexedSubChunk};
+use crate::metrics;
 
 /// Future that resolves when [`FlushState`] transitions into a busy state.
 pub struct Flushing<'state> {
@@ -135,26 +138,77 @@ impl Future for FlushState {
     }
 }
 
+/// Keeps track of which flushes of a [`Collector`] have completed.
+///
+/// Every flush is assigned a round number. A subchunk that was submitted while round `n` had started is guaranteed
+/// to be on disk once [`saved`](Self::saved) is greater than `n`.
+#[derive(Clone, Default)]
+pub struct FlushProgress {
+    /// Number of the most recently started round.
+    started: Arc<AtomicU64>,
+    /// Number of the most recent round that was written successfully.
+    saved: Arc<AtomicU64>,
+}
+
+impl FlushProgress {
+    /// Returns the number of the most recently started round.
+    #[inline]
+    pub fn started(&self) -> u64 {
+        self.started.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of the most recent round that was written successfully.
+    ///
+    /// Failed rounds are retried as part of the next one, so everything submitted before this round is saved as well.
+    #[inline]
+    pub fn saved(&self) -> u64 {
+        self.saved.load(Ordering::SeqCst)
+    }
+
+    /// Starts a new round, returning its number.
+    fn start(&self) -> u64 {
+        self.started.fetch_add(1, Ordering::SeqCst) + 1
+    }
+}
+
+/// An item that is sent to the collector.
+#[derive(Debug)]
+enum Submitted {
+    SubChunk(IndexedSubChunk),
+    Column(IndexedColumn),
+}
+
 /// Collects all subchunk updates and writes them to disk periodically.
+///
+/// Updates are written in batches that are committed atomically, so a crash never leaves
+/// a partially written batch behind. Batches that fail to write are retried during the next flush.
 pub struct Collector {
-    producer: mpsc::Sender<IndexedSubChunk>,
+    producer: mpsc::Sender<Submitted>,
     provider: Arc<Provider>,
     state: FlushState,
     shutdown_token: CancellationToken,
+    /// Error of the last flush, if it failed.
+    error: Arc<Mutex<Option<anyhow::Error>>>,
+    progress: FlushProgress,
 }
 
 impl Collector {
-    pub(crate) fn new(provider: Arc<Provider>, instance_token: CancellationToken, collector_size: usize) -> Self {
+    pub(crate) fn new(provider: Arc<Provider>, instance_token: CancellationToken, collector_size: usize, interval: Duration) -> Self {
         let (producer, consumer) = mpsc::channel(collector_size);
         let state = FlushState::new();
         let shutdown_token = CancellationToken::new();
+        let error = Arc::new(Mutex::new(None));
+        let progress = FlushProgress::default();
 
         tokio::spawn(Collector::collection(
-            instance_token.clone(),
+            Arc::clone(&provider),
+            instance_token,
             shutdown_token.clone(),
             consumer,
             state.clone(),
-            collector_size,
+            interval,
+            Arc::clone(&error),
+            progress.clone(),
         ));
 
         Self {
@@ -162,9 +216,16 @@ impl Collector {
             provider,
             state,
             shutdown_token,
+            error,
+            progress,
         }
     }
 
+    /// Returns the progress of the flushes performed by this collector.
+    pub fn progress(&self) -> FlushProgress {
+        self.progress.clone()
+    }
+
     /// Creates a new sink that can be used to write into this collector.
     pub fn create_sink(&self) -> RegionSink {
         RegionSink {
@@ -173,59 +234,131 @@ impl Collector {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn collection(
+        provider: Arc<Provider>,
         instance_token: CancellationToken,
         shutdown_token: CancellationToken,
-        mut receiver: mpsc::Receiver<IndexedSubChunk>,
+        mut receiver: mpsc::Receiver<Submitted>,
         state: FlushState,
-        collector_size: usize,
+        interval: Duration,
+        error: Arc<Mutex<Option<anyhow::Error>>>,
+        progress: FlushProgress,
     ) {
+        let mut pending = ChangeSet::default();
+        let mut interval = tokio::time::interval(interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
         loop {
             tokio::select! {
-                _ = state.flushing() => {
-                    // Empty channel and collect all changes.
-                    let collected = Collector::collect(&mut receiver, collector_size);
-
-                    // Resume normal sink operations.
-                    state.finish();
-
-                    Collector::flush(collected);
-                },
-                _ = instance_token.cancelled() => {
-                    shutdown_token.cancel();
-                    break
-                }
+                _ = state.flushing() => {},
+                _ = interval.tick() => {},
+                _ = instance_token.cancelled() => break
+            }
+
+            // Empty channel and collect all changes.
+            let round = progress.start();
+            Collector::collect(&mut receiver, &mut pending);
+
+            // Resume normal sink operations.
+            state.finish();
+
+            // Flushes are awaited so that an older version of a subchunk can never overwrite a newer one.
+            pending = Collector::flush(&provider, pending, &error).await;
+            if pending.is_empty() {
+                progress.saved.store(round, Ordering::SeqCst);
             }
         }
 
         // Final flush before closing to prevent data loss
-        let collected = Collector::collect(&mut receiver, collector_size);
-        Collector::flush(collected);
+        receiver.close();
+        let round = progress.start();
+        Collector::collect(&mut receiver, &mut pending);
+        state.finish();
+
+        let pending = Collector::flush(&provider, pending, &error).await;
+        if pending.is_empty() {
+            progress.saved.store(round, Ordering::SeqCst);
+        } else {
+            tracing::error!("{} subchunks and {} columns could not be saved", pending.subchunk_count(), pending.column_count());
+        }
 
+        shutdown_token.cancel();
         tracing::info!("Level sink closed");
     }
 
+    /// Moves all received subchunks into the pending set, replacing older versions of the same subchunk.
     #[inline]
-    fn collect(receiver: &mut mpsc::Receiver<IndexedSubChunk>, collector_size: usize) -> Vec<IndexedSubChunk> {
-        let mut buffered = Vec::with_capacity(collector_size);
+    fn collect(receiver: &mut mpsc::Receiver<Submitted>, pending: &mut ChangeSet) {
         while let Ok(recv) = receiver.try_recv() {
-            buffered.push(recv);
+            match recv {
+                Submitted::SubChunk(recv) => {
+                    pending.insert_subchunk(recv.dimension, Vector::from(recv.index), recv.data);
+                }
+                Submitted::Column(recv) => {
+                    for subchunk in recv.subchunks {
+                        let coordinates = Vector::from([recv.coordinates.x, i32::from(subchunk.index), recv.coordinates.y]);
+                        pending.insert_subchunk(recv.dimension, coordinates, subchunk);
+                    }
+                    pending.insert_column(recv.dimension, recv.coordinates, recv.biomes);
+                }
+            }
         }
-
-        buffered
     }
 
-    fn flush(data: Vec<IndexedSubChunk>) {
-        rayon::spawn(|| {
-            data.into_par_iter().for_each(|chunk| {});
+    /// Writes the pending changes to disk, returning the changes that could not be written.
+    ///
+    /// If the write fails or is aborted, all changes are kept so that they are retried during the next flush
+    /// and the error is stored so that it is reported when the collector is joined.
+    async fn flush(provider: &Arc<Provider>, pending: ChangeSet, error: &Mutex<Option<anyhow::Error>>) -> ChangeSet {
+        if pending.is_empty() {
+            return pending;
+        }
+
+        // The changes are shared with the writer so that they are not lost if the write is aborted.
+        let pending = Arc::new(pending);
+        let (sender, receiver) = oneshot::channel();
+        let provider = Arc::clone(provider);
+        let changes = Arc::clone(&pending);
+        rayon::spawn(move || {
+            let start = Instant::now();
+            let result = std::panic::catch_unwind(AssertUnwindSafe(|| provider.commit(&changes)))
+                .unwrap_or_else(|_| Err(anyhow::anyhow!("Collector flush panicked")));
+            metrics::metrics().observe_collector_flush(start.elapsed());
+
+            // Release the changes before reporting back so that the collection task can reclaim them.
+            drop(changes);
+            let _: Result<(), _> = sender.send(result);
         });
+
+        let result = receiver.await.unwrap_or_else(|_| Err(anyhow::anyhow!("Collector flush was aborted")));
+        let pending = Arc::try_unwrap(pending).unwrap_or_else(|shared| ChangeSet::clone(&shared));
+        match result {
+            Ok(()) => {
+                tracing::debug!("Saved {} subchunks and {} columns", pending.subchunk_count(), pending.column_count());
+                *error.lock() = None;
+                ChangeSet::default()
+            }
+            Err(err) => {
+                tracing::error!(
+                    "Failed to save {} subchunks and {} columns, retrying during the next flush: {err:#}",
+                    pending.subchunk_count(),
+                    pending.column_count()
+                );
+                *error.lock() = Some(err);
+                pending
+            }
+        }
     }
 }
 
 impl Joinable for Collector {
+    /// Waits for the final flush and returns an error if the most recent flush failed.
     async fn join(&self) -> anyhow::Result<()> {
         self.shutdown_token.cancelled().await;
-        Ok(())
+
+        let error = self.error.lock().take();
+        error.map_or(Ok(()), |err| Err(err.context("Failed to save the level")))
     }
 }
 
@@ -233,14 +366,12 @@ impl Joinable for Collector {
 /// and will automatically be written to disk at a fixed interval or
 /// when the sink is filled up.
 pub struct RegionSink {
-    producer: mpsc::Sender<IndexedSubChunk>,
+    producer: mpsc::Sender<Submitted>,
     state: FlushState,
 }
 
-impl Sink<IndexedSubChunk> for RegionSink {
-    type Error = anyhow::Error;
-
-    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<anyhow::Result<()>> {
+impl RegionSink {
+    fn poll_ready_inner(&mut self, cx: &mut Context) -> Poll<anyhow::Result<()>> {
         // Check whether collector has space.
         // If not, notify it to flush.
         if self.producer.capacity() == 0 {
@@ -254,13 +385,12 @@ impl Sink<IndexedSubChunk> for RegionSink {
         Poll::Ready(Ok(()))
     }
 
-    fn start_send(self: Pin<&mut Self>, item: IndexedSubChunk) -> anyhow::Result<()> {
-        println!("Send");
+    fn start_send_inner(&self, item: Submitted) -> anyhow::Result<()> {
         self.producer.try_send(item)?;
         Ok(())
     }
 
-    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<anyhow::Result<()>> {
+    fn poll_flush_inner(&mut self, cx: &mut Context) -> Poll<anyhow::Result<()>> {
         if self.producer.capacity() == self.producer.max_capacity() {
             // Don't flush when the collector is empty.
             return Poll::Ready(Ok(()));
@@ -272,8 +402,45 @@ impl Sink<IndexedSubChunk> for RegionSink {
         let pin = unsafe { Pin::new_unchecked(&mut self.state) };
         pin.poll(cx).map(|_| Ok(()))
     }
+}
+
+impl Sink<IndexedSubChunk> for RegionSink {
+    type Error = anyhow::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<anyhow::Result<()>> {
+        self.poll_ready_inner(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: IndexedSubChunk) -> anyhow::Result<()> {
+        self.start_send_inner(Submitted::SubChunk(item))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<anyhow::Result<()>> {
+        self.poll_flush_inner(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<anyhow::Result<()>> {
+        self.poll_flush_inner(cx)
+    }
+}
+
+/// Generated columns are written in the same batch as their subchunks.
+impl Sink<IndexedColumn> for RegionSink {
+    type Error = anyhow::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<anyhow::Result<()>> {
+        self.poll_ready_inner(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: IndexedColumn) -> anyhow::Result<()> {
+        self.start_send_inner(Submitted::Column(item))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<anyhow::Result<()>> {
+        self.poll_flush_inner(cx)
+    }
 
-    fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<anyhow::Result<()>> {
-        self.poll_flush(cx)
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<anyhow::Result<()>> {
+        self.poll_flush_inner(cx)
     }
 }
@@ -1,32 +1,53 @@
 use std::{
     pin::Pin,
     task::{ready, Context, Poll},
+    time::Instant,
 };
 
 use futures::Stream;
-use level::SubChunk;
+use level::{Biomes, SubChunk};
+use proto::types::Dimension;
 use tokio::sync::mpsc;
 use util::Vector;
 
+use crate::metrics;
+
 /// A unique identifier for a specific subchunk.
 ///
 /// First 6 bits are the vertical index,
 /// then 29 bits for the x-coordinate
 /// and 29 bits for the z-coordinate.
+///
+/// All components are stored in two's complement so negative coordinates are supported.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct RegionIndex(u64);
 
+impl RegionIndex {
+    const Y_BITS: u32 = 6;
+    const XZ_BITS: u32 = 29;
+    const Y_MASK: u64 = (1 << Self::Y_BITS) - 1;
+    const XZ_MASK: u64 = (1 << Self::XZ_BITS) - 1;
+
+    /// Sign extends the lowest `bits` bits of `value`.
+    #[inline]
+    const fn sign_extend(value: u64, bits: u32) -> i32 {
+        let shift = 64 - bits;
+        (((value << shift) as i64) >> shift) as i32
+    }
+}
+
 impl From<Vector<i32, 3>> for RegionIndex {
     fn from(value: Vector<i32, 3>) -> Self {
-        const XZ_MASK: u64 = 2u64.pow(29) - 1;
+        const Y_LIMIT: i32 = 1 << (RegionIndex::Y_BITS - 1);
+        const XZ_LIMIT: i32 = 1 << (RegionIndex::XZ_BITS - 1);
 
-        assert!((value.y as u64) < 63, "Region Y-coordinate too large");
-        assert!((value.x as u64) < XZ_MASK, "Region X-coordinate too large");
-        assert!((value.z as u64) < XZ_MASK, "Region Z-coordinate too large");
+        assert!((-Y_LIMIT..Y_LIMIT).contains(&value.y), "Region Y-coordinate out of range");
+        assert!((-XZ_LIMIT..XZ_LIMIT).contains(&value.x), "Region X-coordinate out of range");
+        assert!((-XZ_LIMIT..XZ_LIMIT).contains(&value.z), "Region Z-coordinate out of range");
 
-        let mut index = (value.y as u64) << 58;
-        index |= ((value.x as u64) & XZ_MASK) << 29;
-        index |= (value.z as u64) & XZ_MASK;
+        let mut index = (value.y as u64 & Self::Y_MASK) << (2 * Self::XZ_BITS);
+        index |= (value.x as u64 & Self::XZ_MASK) << Self::XZ_BITS;
+        index |= value.z as u64 & Self::XZ_MASK;
 
         RegionIndex(index)
     }
@@ -34,12 +55,10 @@ impl From<Vector<i32, 3>> for RegionIndex {
 
 impl From<RegionIndex> for Vector<i32, 3> {
     fn from(value: RegionIndex) -> Self {
-        const XZ_MASK: u64 = 2u64.pow(29) - 1;
-
         let index = value.0;
-        let y = (index >> 58) as i32;
-        let x = ((index >> 29) & XZ_MASK) as i32;
-        let z = (index & XZ_MASK) as i32;
+        let y = RegionIndex::sign_extend(index >> (2 * RegionIndex::XZ_BITS), RegionIndex::Y_BITS);
+        let x = RegionIndex::sign_extend((index >> RegionIndex::XZ_BITS) & RegionIndex::XZ_MASK, RegionIndex::XZ_BITS);
+        let z = RegionIndex::sign_extend(index & RegionIndex::XZ_MASK, RegionIndex::XZ_BITS);
 
         Vector::from([x, y, z])
     }
@@ -50,22 +69,42 @@ impl From<RegionIndex> for Vector<i32, 3> {
 pub struct IndexedSubChunk {
     /// The region index.
     pub index: RegionIndex,
+    /// Dimension the subchunk is located in.
+    pub dimension: Dimension,
     /// The subchunk data.
     pub data: SubChunk,
 }
 
+/// A chunk column created by a [`Generator`](crate::level::generator::Generator).
+///
+/// The subchunks and biomes of a generated column are written together, after which the column is no longer
+/// considered missing.
+#[derive(Debug)]
+pub struct IndexedColumn {
+    /// Chunk coordinates of the column.
+    pub coordinates: Vector<i32, 2>,
+    /// Dimension the column is located in.
+    pub dimension: Dimension,
+    /// Subchunks in the column that are not empty.
+    pub subchunks: Vec<SubChunk>,
+    /// Biomes of the column.
+    pub biomes: Biomes,
+}
+
 /// Streams subchunk data as it is produced by an iterator.
 pub struct RegionStream {
     /// Chunk receiver
     pub(super) inner: mpsc::Receiver<IndexedSubChunk>,
     /// Remaining items in the receiver.
     pub(super) len: usize,
+    /// When loading of the region started, used to measure the load latency.
+    pub(super) started: Instant,
 }
 
 impl RegionStream {
     #[inline]
-    pub const fn from_receiver(inner: mpsc::Receiver<IndexedSubChunk>, len: usize) -> RegionStream {
-        RegionStream { inner, len }
+    pub fn from_receiver(inner: mpsc::Receiver<IndexedSubChunk>, len: usize) -> RegionStream {
+        RegionStream { inner, len, started: Instant::now() }
     }
 
     /// Remaining length of this stream.
@@ -88,6 +127,9 @@ impl Stream for RegionStream {
 
         if ready.is_some() {
             self.len -= 1;
+            if self.len == 0 {
+                metrics::metrics().observe_region_load(self.started.elapsed());
+            }
         }
 
         Poll::Ready(ready)
@@ -1,5 +1,7 @@
 //! Implements basic Minecraft level functionality.
 
+pub mod cache;
+pub mod generator;
 pub mod io;
 pub mod net;
 pub mod rule;
@@ -1,4 +1,6 @@
 mod ser;
 
+pub use ser::*;
+
 pub mod column;
 pub mod heightmap;
@@ -1,4 +1,4 @@
-use level::{BlockStates, SubChunk, SubChunkVersion, SubStorage};
+use level::{BiomeEncoding, Biomes, BlockStates, SubChunk, SubChunkVersion, SubStorage};
 use util::{BinaryWrite, RVec};
 
 pub trait NetworkChunkExt {
@@ -19,17 +19,21 @@ impl NetworkChunkExt for SubStorage {
     where
         W: BinaryWrite,
     {
-        level::serialize_packed_array(&mut writer, &self.indices, self.palette.len(), true)?;
+        if self.palette.len() <= 1 {
+            // Storages consisting of a single block do not need any indices.
+            // Empty storages are sent as air.
+            let runtime_id = self.palette.first().and_then(|entry| states.state(entry)).unwrap_or(states.air());
 
-        if !self.palette.is_empty() {
-            writer.write_var_i32(self.palette.len() as i32)?;
+            writer.write_u8(1)?; // Zero bits per index, network format.
+            return writer.write_var_i32(runtime_id as i32);
         }
 
+        level::serialize_packed_array(&mut writer, &self.indices, self.palette.len(), true)?;
+        writer.write_var_i32(self.palette.len() as i32)?;
+
         for entry in &self.palette {
             // Obtain block runtime ID of palette entry.
             let runtime_id = states.state(entry).unwrap_or(states.air());
-            tracing::debug!("{}: {runtime_id}", entry.name);
-
             writer.write_var_i32(runtime_id as i32)?;
 
             // https://github.com/df-mc/dragonfly/blob/master/server/world/chunk/paletted_storage.go#L35
@@ -60,3 +64,41 @@ impl NetworkChunkExt for SubChunk {
         Ok(())
     }
 }
+
+/// Biome ID used for subchunks that do not have any biome data.
+const DEFAULT_BIOME: u32 = 1; // Plains
+
+/// Serialises the biomes of a chunk column in network format.
+///
+/// Exactly `count` biome sections are written, one for every subchunk in the dimension.
+/// Sections that are missing from `biomes` inherit the biome of the section below them.
+pub fn serialize_biomes_network<W>(biomes: Option<&Biomes>, count: usize, mut writer: W) -> anyhow::Result<()>
+where
+    W: BinaryWrite,
+{
+    let fragments = biomes.map(Biomes::fragments).unwrap_or_default();
+    for i in 0..count {
+        match fragments.get(i) {
+            Some(BiomeEncoding::Single(id)) => {
+                writer.write_u8(1)?; // Zero bits per index, network format.
+                writer.write_var_i32(*id as i32)?;
+            }
+            Some(BiomeEncoding::Paletted(storage)) => {
+                level::serialize_packed_array(&mut writer, storage.indices(), storage.palette().len(), true)?;
+
+                writer.write_var_i32(storage.palette().len() as i32)?;
+                for id in storage.palette() {
+                    writer.write_var_i32(*id as i32)?;
+                }
+            }
+            // The first section has nothing to inherit from.
+            _ if i == 0 => {
+                writer.write_u8(1)?;
+                writer.write_var_i32(DEFAULT_BIOME as i32)?;
+            }
+            _ => writer.write_u8(0x7f << 1 | 1)?,
+        }
+    }
+
+    Ok(())
+}
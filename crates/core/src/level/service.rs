@@ -2,11 +2,20 @@ use super::io::sink::RegionSink;
 use super::io::stream::{IndexedSubChunk, RegionIndex};
 use std::{
     any::TypeId,
+    collections::HashMap,
+    mem,
+    path::Path,
     sync::{Arc, OnceLock, Weak},
+    time::Duration,
 };
 
+use anyhow::Context;
 use dashmap::DashMap;
-use level::{provider::Provider, SubChunk};
+use level::provider::{CreateOptions, Provider, UNSET_SPAWN_Y};
+use level::settings::{GeneratorType, LevelSettings};
+use level::{Biomes, BlockStates, PaletteEntry, SubChunk, SubStorage};
+use parking_lot::{Mutex, RwLock};
+use proto::bedrock::Difficulty;
 use proto::types::Dimension;
 use rayon::iter::ParallelIterator;
 use tokio::sync::mpsc::{self, error::SendError};
@@ -16,13 +25,22 @@ use util::{Joinable, Vector};
 use crate::instance::Instance;
 
 use super::{
+    cache::ChunkCache,
+    generator::{FlatGenerator, FlatPreset, Generator, VoidGenerator},
     io::{region::Region, sink::Collector, stream::RegionStream},
     rule::{Rule, RuleValue},
+    subchunk_range,
 };
 
 pub struct ServiceOptions {
     pub instance_token: CancellationToken,
     pub level_path: String,
+    /// Maximum amount of memory used by the chunk cache in bytes.
+    pub cache_capacity: usize,
+    /// Options used to create the level if it does not exist yet.
+    pub create_options: CreateOptions,
+    /// Preset string of the superflat layers, overriding the layers stored in `level.dat`.
+    pub flat_layers: Option<String>,
 }
 
 /// Threshold for the service to switch from singular to batching mode.
@@ -30,6 +48,13 @@ pub struct ServiceOptions {
 /// with a parallel iterator and threadpool.
 const REGION_PARALLEL_THRESHOLD: usize = 100;
 
+/// Amount of subchunk updates that the collector buffers before it is forced to flush.
+const COLLECTOR_CAPACITY: usize = 100;
+/// Interval at which the collector writes buffered subchunk updates to disk.
+const COLLECTOR_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+/// Spawn height used if the level has no spawn point and there is no terrain to place it on.
+const FALLBACK_SPAWN_Y: i32 = 64;
+
 /// Manages the world of the server.
 pub struct Service {
     /// Cancelled when the whole server is shutting down. This will then signal to this
@@ -43,22 +68,80 @@ pub struct Service {
     pub(super) provider: Arc<level::provider::Provider>,
     /// Collects subchunk changes using sinks and writes them to disk periodically.
     collector: Collector,
-    /// Current gamerule values.
+    /// Subchunks that have recently been used, including modifications that have not been written to disk yet.
+    pub(super) cache: Arc<ChunkCache>,
+    /// Settings loaded from `level.dat`, which are written back when the service shuts down.
+    settings: Mutex<LevelSettings>,
+    /// Gamerule values stored in `level.dat`, indexed by name.
+    ///
+    /// These are used for gamerules that have not been changed since the level was loaded.
+    saved_gamerules: HashMap<String, RuleValue>,
+    /// Current gamerule values and their names.
     /// The gamerules are stored by TypeId to allow for user-defined gamerules.
-    gamerules: DashMap<TypeId, RuleValue>,
+    gamerules: DashMap<TypeId, (&'static str, RuleValue)>,
+    /// Superflat layers that were configured, overriding the layers stored in `level.dat`.
+    flat_preset: Option<FlatPreset>,
+    /// Creates columns that have not been generated yet.
+    ///
+    /// This is set once the instance is available, because generators need the block states.
+    /// It stays `None` for levels that cannot be generated, in which case missing terrain is empty.
+    generator: RwLock<Option<Arc<dyn Generator>>>,
+    /// Held while a column is being generated, to prevent columns from being generated twice.
+    generation: Mutex<()>,
 }
 
 impl Service {
     pub(crate) fn new(options: ServiceOptions) -> anyhow::Result<Arc<Service>> {
-        let provider = Arc::new(level::provider::Provider::open(&options.level_path)?);
+        let path = Path::new(&options.level_path);
+        let provider = if path.join("level.dat").exists() {
+            Provider::open(path)?
+        } else {
+            tracing::info!("No level found at {}, creating a new one", path.display());
+            Provider::create(path, options.create_options).context("Failed to create level")?
+        };
+        let provider = Arc::new(provider);
+        let settings = provider.settings().context("Failed to read level.dat")?;
+        let flat_preset = options
+            .flat_layers
+            .map(|layers| layers.parse::<FlatPreset>())
+            .transpose()
+            .context("Invalid superflat layers")?;
+
+        // Gamerules are stored as bytes and ints alongside the other settings.
+        let saved_gamerules = settings
+            .to_compound()?
+            .into_iter()
+            .filter_map(|(name, value)| match value {
+                nbt::Value::Byte(value) => Some((name, RuleValue::Bool(value != 0))),
+                nbt::Value::Int(value) => Some((name, RuleValue::I32(value))),
+                _ => None,
+            })
+            .collect();
+
+        // The collector shuts down after the cache has submitted its remaining modifications.
+        let collector_token = CancellationToken::new();
+        let collector = Collector::new(Arc::clone(&provider), collector_token.clone(), COLLECTOR_CAPACITY, COLLECTOR_FLUSH_INTERVAL);
+        let cache = ChunkCache::new(
+            options.cache_capacity,
+            &collector,
+            COLLECTOR_FLUSH_INTERVAL,
+            options.instance_token.clone(),
+            collector_token,
+        );
 
         let service = Arc::new(Service {
-            collector: Collector::new(Arc::clone(&provider), options.instance_token.clone(), 100),
+            collector,
+            cache,
             instance_token: options.instance_token,
             shutdown_token: CancellationToken::new(),
             instance: OnceLock::new(),
             provider,
+            settings: Mutex::new(settings),
+            saved_gamerules,
             gamerules: DashMap::new(),
+            flat_preset,
+            generator: RwLock::new(None),
+            generation: Mutex::new(()),
         });
         Ok(service)
     }
@@ -67,7 +150,85 @@ impl Service {
     pub(crate) fn set_instance(&self, instance: &Arc<Instance>) -> anyhow::Result<()> {
         self.instance
             .set(Arc::downgrade(instance))
-            .map_err(|_| anyhow::anyhow!("Level service instance was already set"))
+            .map_err(|_| anyhow::anyhow!("Level service instance was already set"))?;
+
+        let generator = self.default_generator(&instance.block_states).context("Failed to create level generator")?;
+        self.place_spawn(generator.as_deref()).context("Failed to place spawn point")?;
+        *self.generator.write() = generator;
+
+        Ok(())
+    }
+
+    /// Places a spawn point that has not been set yet on the surface.
+    ///
+    /// Columns that already exist use their stored heightmap, others use the surface height of the generator.
+    /// Without either, the spawn point is placed at a fixed height.
+    fn place_spawn(&self, generator: Option<&dyn Generator>) -> anyhow::Result<()> {
+        let spawn = self.spawn();
+        if spawn.y != UNSET_SPAWN_Y {
+            return Ok(());
+        }
+
+        let column = Vector::from([spawn.x >> 4, spawn.z >> 4]);
+        let y = match self.provider.biomes(column, Dimension::Overworld)? {
+            Some(biomes) => {
+                let height = biomes.heightmap[(spawn.z & 0xf) as usize][(spawn.x & 0xf) as usize];
+                subchunk_range(Dimension::Overworld).start * 16 + i32::from(height)
+            }
+            None => match generator {
+                Some(generator) => generator.surface_height(spawn.x, spawn.z, Dimension::Overworld)?,
+                None => FALLBACK_SPAWN_Y,
+            },
+        };
+
+        tracing::info!("Placed spawn point at Y {y}");
+        self.set_spawn(Vector::from([spawn.x, y, spawn.z]));
+        self.save_settings()
+    }
+
+    /// Replaces the generator that creates columns that have not been generated yet.
+    ///
+    /// Columns that have already been generated are not affected.
+    pub fn set_generator(&self, generator: Arc<dyn Generator>) {
+        *self.generator.write() = Some(generator);
+    }
+
+    /// Creates the generator matching the generator type stored in `level.dat`.
+    ///
+    /// Returns `None` if the level type cannot be generated and no superflat layers have been configured.
+    fn default_generator(&self, states: &BlockStates) -> anyhow::Result<Option<Arc<dyn Generator>>> {
+        let settings = self.settings.lock();
+        let generator = GeneratorType::try_from(settings.generator).unwrap_or(GeneratorType::Infinite);
+        let spawn = Vector::from([settings.spawn_x, settings.spawn_y, settings.spawn_z]);
+        let flat_world_layers = settings.flat_world_layers.clone();
+        drop(settings);
+
+        let preset = match &self.flat_preset {
+            Some(preset) => preset.clone(),
+            None if flat_world_layers.is_empty() => FlatPreset::default(),
+            None => FlatPreset::from_json(&flat_world_layers).context("Invalid FlatWorldLayers in level.dat")?,
+        };
+
+        Ok(match generator {
+            GeneratorType::Flat => Some(Arc::new(FlatGenerator::new(&preset, states)?)),
+            GeneratorType::Void => Some(Arc::new(VoidGenerator::new(spawn, states)?)),
+            // Superflat terrain is only generated in these levels if it was configured explicitly,
+            // since it would otherwise be saved permanently in place of the missing vanilla terrain.
+            GeneratorType::Limited | GeneratorType::Infinite if self.flat_preset.is_some() => {
+                Some(Arc::new(FlatGenerator::new(&preset, states)?))
+            }
+            GeneratorType::Limited | GeneratorType::Infinite => {
+                tracing::warn!("{generator:?} levels cannot be generated, missing terrain will be empty and is not saved");
+                None
+            }
+        })
+    }
+
+    /// Returns the instance that owns this service.
+    fn instance(&self) -> Arc<Instance> {
+        // This will not panic because the instance field is initialised before the first block can be modified.
+        #[allow(clippy::unwrap_used)]
+        self.instance.get().unwrap().upgrade().unwrap()
     }
 
     /// Requests chunks using the specified region iterator.
@@ -82,16 +243,156 @@ impl Service {
         }
     }
 
+    /// Loads the biomes of the chunk column at the given chunk coordinates.
+    pub fn biomes(&self, coordinates: Vector<i32, 2>, dimension: Dimension) -> anyhow::Result<Option<Biomes>> {
+        // Generated columns are only read from the cache until they have been written to disk.
+        if let Some(biomes) = self.cache.biomes(dimension, coordinates.clone()) {
+            return Ok(Some(biomes));
+        }
+
+        self.provider.biomes(coordinates, dimension)
+    }
+
     /// Creates a new [`RegionSink`]. A region sink allows you to save modified subchunks to disk.
     pub fn region_sink(&self) -> RegionSink {
         self.collector.create_sink()
     }
 
+    /// Returns the subchunk at the given subchunk coordinates, loading it into the cache if necessary.
+    ///
+    /// Columns that have never been generated are generated first.
+    /// Subchunks that do not exist are returned as an empty subchunk.
+    pub fn subchunk(&self, coordinates: Vector<i32, 3>, dimension: Dimension) -> anyhow::Result<SubChunk> {
+        self.cache
+            .get_or_load(dimension, RegionIndex::from(coordinates.clone()), || self.load(&coordinates, dimension))
+    }
+
+    /// Returns the block at the given position in the world.
+    pub fn block(&self, position: Vector<i32, 3>, dimension: Dimension) -> anyhow::Result<PaletteEntry> {
+        let (coordinates, local) = Self::split_position(&position);
+        let air = self.air()?;
+
+        let subchunk = self.subchunk(coordinates, dimension)?;
+        Ok(Self::block_in(&subchunk, local).unwrap_or(air))
+    }
+
+    /// Replaces the block at the given position in the world, returning the block that was there before.
+    ///
+    /// The modified subchunk is kept in the cache, which submits it to the [`Collector`] so that the change is saved to disk.
+    pub fn set_block(&self, position: Vector<i32, 3>, dimension: Dimension, block: PaletteEntry) -> anyhow::Result<PaletteEntry> {
+        let (coordinates, local) = Self::split_position(&position);
+        let air = self.air()?;
+
+        let index = RegionIndex::from(coordinates.clone());
+        self.cache.modify(
+            dimension,
+            index,
+            || self.load(&coordinates, dimension),
+            |subchunk| {
+                if subchunk.layers.is_empty() {
+                    subchunk.layers.push(SubStorage::empty());
+                }
+
+                let layer = &mut subchunk.layers[0];
+                if layer.is_empty() {
+                    layer.palette.push(air);
+                }
+
+                layer.set(local, block).context("Block position is out of subchunk bounds")
+            },
+        )
+    }
+
+    /// Reads a subchunk from disk, generating its column if it has never been generated.
+    ///
+    /// Returns an empty subchunk if the subchunk does not exist in a generated column.
+    fn load(&self, coordinates: &Vector<i32, 3>, dimension: Dimension) -> anyhow::Result<SubChunk> {
+        if let Some(subchunk) = self.provider.subchunk(coordinates.clone(), dimension)? {
+            return Ok(subchunk);
+        }
+
+        let column = Vector::from([coordinates.x, coordinates.z]);
+        if self.provider.version(column.clone(), dimension)?.is_none() {
+            if let Some(subchunk) = self.generate(column, dimension, coordinates.y)? {
+                return Ok(subchunk);
+            }
+
+            // The column was generated and saved by another thread in the meantime.
+            if let Some(subchunk) = self.provider.subchunk(coordinates.clone(), dimension)? {
+                return Ok(subchunk);
+            }
+        }
+
+        Ok(SubChunk::empty(coordinates.y as i8))
+    }
+
+    /// Generates a column and inserts it into the cache, which submits it to the [`Collector`].
+    ///
+    /// Returns the subchunk at the given vertical index, or `None` if the column has already been saved
+    /// by another thread, the index is outside of the dimension or the level has no generator.
+    fn generate(&self, column: Vector<i32, 2>, dimension: Dimension, y: i32) -> anyhow::Result<Option<SubChunk>> {
+        // Without a generator, missing subchunks are empty and the column is not marked as generated.
+        let Some(generator) = self.generator.read().clone() else { return Ok(None) };
+
+        let guard = self.generation.lock();
+
+        // Another thread might have generated the column while this one was waiting.
+        if let Some(subchunk) = self.cache.get(dimension, RegionIndex::from(Vector::from([column.x, y, column.y]))) {
+            return Ok(Some(subchunk));
+        }
+        if self.provider.version(column.clone(), dimension)?.is_some() {
+            return Ok(None);
+        }
+
+        let generated = generator.generate(column.clone(), dimension)?;
+
+        let range = subchunk_range(dimension);
+        let mut subchunks = range.clone().map(|y| SubChunk::empty(y as i8)).collect::<Vec<_>>();
+        for subchunk in generated.subchunks {
+            let slot = usize::try_from(i32::from(subchunk.index) - range.start)
+                .ok()
+                .and_then(|i| subchunks.get_mut(i))
+                .with_context(|| format!("Generated subchunk {} is outside of the dimension", subchunk.index))?;
+
+            *slot = subchunk;
+        }
+
+        let requested = usize::try_from(y - range.start).ok().and_then(|i| subchunks.get(i)).cloned();
+        let inserted = self.cache.insert_generated(dimension, column, subchunks, generated.biomes);
+        drop(guard);
+
+        Ok(requested.filter(|_| inserted))
+    }
+
+    /// Returns the block state of air.
+    fn air(&self) -> anyhow::Result<PaletteEntry> {
+        let instance = self.instance();
+        let states = &instance.block_states;
+
+        states.entry(states.air()).cloned().context("Block state of air is not registered")
+    }
+
+    /// Returns the block at the given position inside of the first layer of the subchunk.
+    #[inline]
+    fn block_in(subchunk: &SubChunk, local: Vector<u8, 3>) -> Option<PaletteEntry> {
+        subchunk.layer(0).filter(|layer| !layer.is_empty()).and_then(|layer| layer.get(local)).cloned()
+    }
+
+    /// Splits a block position into the coordinates of the subchunk it is located in
+    /// and the position inside of that subchunk.
+    #[inline]
+    fn split_position(position: &Vector<i32, 3>) -> (Vector<i32, 3>, Vector<u8, 3>) {
+        let coordinates = Vector::from([position.x >> 4, position.y >> 4, position.z >> 4]);
+        let local = Vector::from([(position.x & 0xf) as u8, (position.y & 0xf) as u8, (position.z & 0xf) as u8]);
+
+        (coordinates, local)
+    }
+
     /// Loads a region using a sequential iterator.
     ///
     /// This function is used for smaller regions that do not benefit from
     /// parallel processing.
-    fn request_sequential_region<R: Region>(&self, region: R) -> RegionStream
+    fn request_sequential_region<R: Region>(self: &Arc<Service>, region: R) -> RegionStream
     where
         R::IntoIter: Send,
     {
@@ -101,11 +402,11 @@ impl Service {
 
         let (sender, receiver) = mpsc::channel(len);
 
-        let provider = Arc::clone(&self.provider);
+        let service = Arc::clone(self);
         tokio::task::spawn_blocking(move || {
             // If this returns an error, the receiver has closed so we can stop processing.
             let _: Result<(), SendError<IndexedSubChunk>> = iter.try_for_each(|item| {
-                let indexed = Self::for_each_subchunk(item, dim, &provider);
+                let indexed = service.for_each_subchunk(item, dim);
                 sender.blocking_send(indexed)
             });
         });
@@ -123,17 +424,17 @@ impl Service {
     ///
     /// The parallel iterator is not used for small regions because the overhead is larger
     /// than the performance boost for small regions.
-    fn request_parallel_region<R: Region>(&self, region: R) -> RegionStream {
+    fn request_parallel_region<R: Region>(self: &Arc<Service>, region: R) -> RegionStream {
         let len = region.len();
         let dim = region.dimension();
         let iter = region.into_par_iter();
         let (sender, receiver) = mpsc::channel(len);
 
-        let provider = Arc::clone(&self.provider);
+        let service = Arc::clone(self);
         rayon::spawn(move || {
             // If this returns an error, the receiver has closed so we can stop processing.
             let _: Result<(), SendError<IndexedSubChunk>> = iter.try_for_each(|item| {
-                let indexed = Self::for_each_subchunk(item, dim, &provider);
+                let indexed = service.for_each_subchunk(item, dim);
                 sender.blocking_send(indexed)
             });
         });
@@ -144,12 +445,11 @@ impl Service {
     /// Operation performed on each subchunk. This is put into a separate function because both
     /// the sequential and parallel iterator perform the exact same operations.
     #[inline]
-    fn for_each_subchunk(item: Vector<i32, 3>, dimension: Dimension, provider: &Provider) -> IndexedSubChunk {
-        let subchunk = provider.subchunk([item.x, item.y, item.z], dimension);
+    fn for_each_subchunk(&self, item: Vector<i32, 3>, dimension: Dimension) -> IndexedSubChunk {
+        let subchunk = self.subchunk(item.clone(), dimension);
 
         let subchunk = match subchunk {
-            Ok(Some(chunk)) => chunk,
-            Ok(None) => SubChunk::empty(item.y as i8),
+            Ok(chunk) => chunk,
             Err(e) => {
                 tracing::error!("Failed to load subchunk at {item:?}: {e:#}. Replacing it with an empty one...");
                 SubChunk::empty(item.y as i8)
@@ -158,6 +458,7 @@ impl Service {
 
         IndexedSubChunk {
             index: RegionIndex::from(item),
+            dimension,
             data: subchunk,
         }
     }
@@ -176,12 +477,10 @@ impl Service {
     where
         RuleValue: From<R::Value>, // Ensure that the gamerule has a valid value type.
     {
-        let value = RuleValue::from(value);
-        let old = self.gamerules.insert(TypeId::of::<R>(), value);
+        let old = self.gamerule::<R>();
+        self.gamerules.insert(TypeId::of::<R>(), (R::NAME, RuleValue::from(value)));
 
-        let Some(old) = old else { return R::Value::default() };
-
-        old.into()
+        old
     }
 
     /// Returns the value of the given gamerule.
@@ -198,18 +497,82 @@ impl Service {
     where
         RuleValue: From<R::Value>, // Ensure that the gamerule has a valid value type.
     {
-        let Some(kv) = self.gamerules.get(&TypeId::of::<R>()) else {
-            return R::Value::default();
-        };
+        if let Some(kv) = self.gamerules.get(&TypeId::of::<R>()) {
+            return kv.value().1.into();
+        }
+
+        // Only use the saved value if it has the same type as this gamerule.
+        let default = RuleValue::from(R::Value::default());
+        match self.saved_gamerules.get(R::NAME) {
+            Some(&saved) if mem::discriminant(&saved) == mem::discriminant(&default) => saved.into(),
+            _ => R::Value::default(),
+        }
+    }
+
+    /// Returns the time of day in ticks.
+    pub fn time(&self) -> i64 {
+        self.settings.lock().time
+    }
+
+    /// Sets the time of day in ticks.
+    pub fn set_time(&self, time: i64) {
+        self.settings.lock().time = time;
+    }
+
+    /// Returns the difficulty of the level.
+    pub fn difficulty(&self) -> Difficulty {
+        Difficulty::try_from(self.settings.lock().difficulty).unwrap_or(Difficulty::Normal)
+    }
+
+    /// Sets the difficulty of the level.
+    pub fn set_difficulty(&self, difficulty: Difficulty) {
+        self.settings.lock().difficulty = difficulty as i32;
+    }
 
-        (*kv.value()).into()
+    /// Returns the world spawn point.
+    pub fn spawn(&self) -> Vector<i32, 3> {
+        let settings = self.settings.lock();
+        Vector::from([settings.spawn_x, settings.spawn_y, settings.spawn_z])
+    }
+
+    /// Sets the world spawn point.
+    pub fn set_spawn(&self, spawn: Vector<i32, 3>) {
+        let mut settings = self.settings.lock();
+        settings.spawn_x = spawn.x;
+        settings.spawn_y = spawn.y;
+        settings.spawn_z = spawn.z;
+    }
+
+    /// Writes the level settings and all changed gamerules to `level.dat`.
+    pub fn save_settings(&self) -> anyhow::Result<()> {
+        let mut compound = self.settings.lock().to_compound()?;
+        for entry in &self.gamerules {
+            let (name, value) = *entry.value();
+            let value = match value {
+                RuleValue::Bool(value) => nbt::Value::Byte(i8::from(value)),
+                RuleValue::I32(value) => nbt::Value::Int(value),
+            };
+
+            compound.insert(name.to_owned(), value);
+        }
+
+        let settings = LevelSettings::from_compound(compound)?;
+        self.provider.save_settings(&settings)
     }
 }
 
 impl Joinable for Service {
+    /// Waits for the collector to finish and saves the level settings.
+    ///
+    /// The settings are saved even if the collector failed, in which case both errors are reported.
     async fn join(&self) -> anyhow::Result<()> {
-        self.collector.join().await?;
+        let collected = self.collector.join().await;
+        let saved = self.save_settings().context("Failed to save level settings");
 
-        Ok(())
+        match (collected, saved) {
+            (Ok(()), Ok(())) => Ok(()),
+            (Err(err), Ok(())) | (Ok(()), Err(err)) => Err(err),
+            (Err(collector), Err(settings)) => Err(anyhow::anyhow!("{collector:#}; {settings:#}")),
+        }
     }
 }
@@ -1,28 +1,62 @@
 use std::{
-    collections::HashMap,
+    collections::HashSet,
+    ops::Range,
     sync::{
         atomic::{AtomicI32, AtomicU16, Ordering},
         Arc,
     },
 };
 
-use futures::{future, StreamExt};
 use level::SubChunk;
-use nohash_hasher::BuildNoHashHasher;
-use proto::{
-    bedrock::{HeightmapType, SubChunkEntry, SubChunkResponse, SubChunkResult},
-    types::Dimension,
-};
+use parking_lot::Mutex;
+use proto::types::Dimension;
 use util::Vector;
 
 use super::io::point::PointRegion;
-use super::io::r#box::BoxRegion;
-use super::net::column::ChunkColumn;
-use super::net::heightmap::Heightmap;
+use super::io::radial::RadialRegion;
+use super::io::stream::RegionStream;
 use super::Service;
 
 pub type ChunkOffset = Vector<i8, 3>;
 
+/// Returns the range of subchunk indices that make up a chunk column in the given dimension.
+pub const fn subchunk_range(dimension: Dimension) -> Range<i32> {
+    match dimension {
+        Dimension::Overworld => -4..20,
+        Dimension::Nether => 0..8,
+        Dimension::End => 0..16,
+    }
+}
+
+/// Chunk columns that have to be sent to a viewer after its view changed.
+pub struct ViewUpdate {
+    /// Chunk coordinates of the center of the view.
+    pub center: Vector<i32, 2>,
+    /// Render distance of the viewer in chunks.
+    pub radius: u16,
+    /// Dimension the columns are located in.
+    pub dimension: Dimension,
+    /// Columns that the viewer has not received yet, sorted by distance to the center.
+    pub columns: Vec<Vector<i32, 2>>,
+    /// Stream that loads the subchunks of all columns in `columns`.
+    pub stream: RegionStream,
+}
+
+/// Chunk columns that are in view of a viewer.
+struct ViewState {
+    /// Dimension that the columns are located in.
+    dimension: Dimension,
+    /// Columns that are in view.
+    ///
+    /// These columns are pinned in the chunk cache.
+    pinned: HashSet<Vector<i32, 2>>,
+    /// Columns that have been sent to the viewer.
+    ///
+    /// This is a subset of `pinned`.
+    loaded: HashSet<Vector<i32, 2>>,
+}
+
+/// Keeps track of the chunks that a client can see.
 pub struct Viewer {
     pub service: Arc<Service>,
     radius: AtomicU16,
@@ -30,136 +64,161 @@ pub struct Viewer {
     // The current position of this viewer in chunk coordinates.
     current_x: AtomicI32,
     current_z: AtomicI32,
+
+    state: Mutex<ViewState>,
 }
 
 impl Viewer {
-    pub const fn new(service: Arc<Service>) -> Viewer {
+    pub fn new(service: Arc<Service>) -> Viewer {
         Viewer {
             service,
             radius: AtomicU16::new(0),
             current_x: AtomicI32::new(0),
             current_z: AtomicI32::new(0),
+            state: Mutex::new(ViewState { dimension: Dimension::Overworld, pinned: HashSet::new(), loaded: HashSet::new() }),
         }
     }
 
-    /// Updates the position of this viewer.
-    pub fn update_position(&self, position: Vector<f32, 2>) {
-        // Transform player coordinates to chunk coordinates.
-        let chunk_x = (position.x / 16.0).ceil() as i32;
-        let chunk_z = (position.y / 16.0).ceil() as i32;
-
-        self.current_x.store(chunk_x, Ordering::Relaxed);
-        self.current_z.store(chunk_z, Ordering::Relaxed);
-
-        // Update view if required
-        self.on_view_update();
+    /// The render distance of this viewer in chunks.
+    #[inline]
+    pub fn radius(&self) -> u16 {
+        self.radius.load(Ordering::Relaxed)
     }
 
-    /// Updates the render distance of this viewer
+    /// The chunk coordinates that this viewer is currently located in.
     #[inline]
-    pub fn update_radius(&self, radius: u16) {
-        self.radius.store(radius, Ordering::Relaxed);
-        self.on_view_update();
+    pub fn center(&self) -> Vector<i32, 2> {
+        Vector::from([self.current_x.load(Ordering::Relaxed), self.current_z.load(Ordering::Relaxed)])
     }
 
-    fn create_entry(&self, base: Vector<i32, 3>, offset: ChunkOffset, full_chunk: &ChunkColumn) -> anyhow::Result<SubChunkEntry> {
-        let absolute_y = base.y + offset.y as i32;
-        let subchunk_index = full_chunk.y_to_index(absolute_y as i16);
-
-        let heightmap = Heightmap::new(subchunk_index, full_chunk);
-        let entry = SubChunkEntry {
-            result: SubChunkResult::Success,
-            offset,
-            heightmap_type: heightmap.map_type,
-            heightmap: heightmap.data,
-            blob_hash: todo!(),
-            payload: todo!(),
-        };
+    /// Whether the given chunk column has been sent to this viewer.
+    #[inline]
+    pub fn is_loaded(&self, column: &Vector<i32, 2>, dimension: Dimension) -> bool {
+        let state = self.state.lock();
+        state.dimension == dimension && state.loaded.contains(column)
+    }
 
-        Ok(entry)
+    /// Marks a column as sent to this viewer.
+    ///
+    /// This should only be called after the column has been sent successfully. Columns that left the view
+    /// in the meantime are ignored.
+    pub fn mark_loaded(&self, column: Vector<i32, 2>, dimension: Dimension) {
+        let mut state = self.state.lock();
+        if state.dimension == dimension && state.pinned.contains(&column) {
+            state.loaded.insert(column);
+        }
     }
 
-    pub fn load_offsets(&self, base: Vector<i32, 3>, offsets: &[ChunkOffset], dimension: Dimension) -> anyhow::Result<SubChunkResponse> {
-        // Group all subchunks into chunk columns,
-        // with the map indices being two concatenated 32-bit integers representing X and Z coords.
-        let mut col_map: HashMap<i64, ChunkColumn, BuildNoHashHasher<i64>> = HashMap::with_hasher(std::hash::BuildHasherDefault::default());
-        for offset in offsets {
-            let abs_coord: Vector<i32, 3> = (base.x + offset.x as i32, base.y + offset.y as i32, base.z + offset.z as i32).into();
+    /// Updates the position of this viewer.
+    ///
+    /// This returns a [`ViewUpdate`] if the viewer moved into another chunk.
+    pub fn update_position(&self, position: Vector<f32, 2>, dimension: Dimension) -> Option<ViewUpdate> {
+        // Transform player coordinates to chunk coordinates.
+        let chunk_x = (position.x / 16.0).floor() as i32;
+        let chunk_z = (position.y / 16.0).floor() as i32;
 
-            let xz = (abs_coord.x as i64) | (abs_coord.z as i64) >> 32;
-            let col = col_map.entry(xz).or_insert_with(ChunkColumn::empty);
+        let old_x = self.current_x.swap(chunk_x, Ordering::Relaxed);
+        let old_z = self.current_z.swap(chunk_z, Ordering::Relaxed);
 
-            match self.load(abs_coord.clone(), dimension) {
-                Ok(opt) => {
-                    col.subchunks.push((offset.clone(), opt));
-                }
-                Err(e) => {
-                    tracing::error!("Failed to load subchunk at {abs_coord:?}: {e}");
-                    col.subchunks.push((offset.clone(), None));
-                }
-            }
+        if old_x == chunk_x && old_z == chunk_z && self.state.lock().dimension == dimension {
+            return None;
         }
 
-        // Generate all heightmaps now that the columns are finalised.
-        // TODO: Could maybe benefit from parallelisation depending on the offset count?
-        col_map.values_mut().for_each(ChunkColumn::generate_heightmap);
-
-        let mut entries = Vec::with_capacity(offsets.len());
-        for col in col_map.values() {
-            for (offset, opt) in &col.subchunks {
-                if let Some(sub) = opt {
-                    let subchunk_idx = base.y + offset.y as i32 - col.range.start as i32;
-                    dbg!(subchunk_idx);
-                    let heightmap = Heightmap::new(subchunk_idx as u16, col);
-                    dbg!(&heightmap);
-
-                    let payload = todo!();
-                    entries.push(SubChunkEntry {
-                        offset: offset.clone(),
-                        result: SubChunkResult::Success,
-                        heightmap_type: heightmap.map_type,
-                        heightmap: heightmap.data,
-                        blob_hash: 0,
-                        payload,
-                    });
-                } else {
-                    entries.push(SubChunkEntry {
-                        result: SubChunkResult::AllAir,
-                        offset: offset.clone(),
-                        ..Default::default()
-                    });
-                }
-            }
+        self.on_view_update(dimension)
+    }
+
+    /// Updates the render distance of this viewer.
+    ///
+    /// This returns a [`ViewUpdate`] if the radius changed.
+    #[inline]
+    pub fn update_radius(&self, radius: u16, dimension: Dimension) -> Option<ViewUpdate> {
+        if self.radius.swap(radius, Ordering::Relaxed) == radius {
+            return None;
         }
 
-        todo!()
+        self.on_view_update(dimension)
     }
 
     #[inline]
-    pub fn load(&self, pos: Vector<i32, 3>, dimension: Dimension) -> anyhow::Result<Option<SubChunk>> {
-        self.service.provider.subchunk(pos, dimension)
+    pub fn load(&self, pos: Vector<i32, 3>, dimension: Dimension) -> anyhow::Result<SubChunk> {
+        self.service.subchunk(pos, dimension)
     }
 
-    fn on_view_update(&self) {
-        let x = self.current_x.load(Ordering::Relaxed);
-        let z = self.current_z.load(Ordering::Relaxed);
+    /// Determines which columns have entered and left the view and starts loading the new ones.
+    ///
+    /// Columns that left the view are forgotten so that they will be sent again when they come back into view.
+    /// Columns that are in view but have not been sent yet are included again, because the previous update
+    /// might have been interrupted.
+    fn on_view_update(&self, dimension: Dimension) -> Option<ViewUpdate> {
+        let radius = self.radius();
+        if radius == 0 {
+            // The client has not requested a render distance yet.
+            return None;
+        }
+
+        let center = self.center();
+        let region = RadialRegion::from_center(center.clone(), radius as usize, subchunk_range(dimension), dimension);
+
+        let (columns, stream) = {
+            let mut state = self.state.lock();
+            let state = &mut *state;
+
+            // None of the columns in the old dimension are visible anymore.
+            if state.dimension != dimension {
+                for column in state.pinned.drain() {
+                    self.service.cache.unpin(state.dimension, column);
+                }
+                state.loaded.clear();
+                state.dimension = dimension;
+            }
+
+            let in_view = region.columns().collect::<HashSet<_>>();
+            state.pinned.retain(|column| {
+                let keep = in_view.contains(column);
+                if !keep {
+                    self.service.cache.unpin(dimension, column.clone());
+                }
+                keep
+            });
+            state.loaded.retain(|column| in_view.contains(column));
+            let was_empty = state.loaded.is_empty();
+
+            // The region is already sorted nearest-first.
+            let columns = region.columns().filter(|column| !state.loaded.contains(column)).collect::<Vec<_>>();
+            if columns.is_empty() {
+                return None;
+            }
 
-        // // Request the chunk the player is in
-        // let stream = self.service.region(BoxRegion::from_bounds(
-        //     (x, -4, z), (x, 15, z), Dimension::Overworld
-        // ));
+            // Pin the columns before loading them so they cannot be evicted while they are in view.
+            for column in &columns {
+                if state.pinned.insert(column.clone()) {
+                    self.service.cache.pin(dimension, column.clone());
+                }
+            }
 
-        // tokio::spawn(async move {
-        //     let fut = stream.take(1).for_each(|res| {
-        //         tracing::debug!("{res:?}");
+            let stream = if was_empty {
+                self.service.region(region)
+            } else {
+                let points = columns
+                    .iter()
+                    .flat_map(|column| subchunk_range(dimension).map(move |y| Vector::from([column.x, y, column.y])))
+                    .collect();
 
-        //         let chunk = res.data;
-        //         chunk.serialize_network().unwrap();
+                self.service.region(PointRegion::from_points(points, dimension))
+            };
 
-        //         future::ready(())
-        //     });
+            (columns, stream)
+        };
 
-        //     fut.await;
-        // });
+        Some(ViewUpdate { center, radius, dimension, columns, stream })
+    }
+}
+
+impl Drop for Viewer {
+    fn drop(&mut self) {
+        let state = self.state.get_mut();
+        for column in state.pinned.drain() {
+            self.service.cache.unpin(state.dimension, column);
+        }
     }
 }
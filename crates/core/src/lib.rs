@@ -48,11 +48,16 @@
 
 pub mod command;
 pub mod config;
+pub mod event;
 pub mod forms;
+pub mod handoff;
 pub mod instance;
 pub mod item;
 pub mod level;
+pub mod metrics;
 pub mod net;
+pub mod pack;
+pub mod plugin;
 
 #[cfg(test)]
 mod test;
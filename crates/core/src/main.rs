@@ -1,12 +1,13 @@
 #![allow(dead_code)]
 
-use std::net::SocketAddrV4;
+use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::atomic::{AtomicU16, Ordering};
 
 use anyhow::Context;
 use tokio::runtime;
 
+use mirai::config::ConfigFile;
 use mirai::instance::Instance;
 use util::Joinable;
 
@@ -23,7 +24,8 @@ fn main() -> anyhow::Result<()> {
 
     init_logging().context("Unable to initialise logging")?;
 
-    let builder = Instance::builder().ipv4_addr(SocketAddrV4::from_str("0.0.0.0:19132").unwrap());
+    let (config_path, overrides) = parse_args(std::env::args().skip(1))?;
+    let builder = Instance::builder().config_file(config_path, overrides)?;
 
     runtime.block_on(async move {
         let instance = builder.build().await?;
@@ -36,6 +38,55 @@ fn main() -> anyhow::Result<()> {
     })
 }
 
+const USAGE: &str = "\
+Usage: mirai [OPTIONS]
+
+Options:
+  --config <PATH>            Configuration file to load [default: mirai.toml]
+  --ipv4 <ADDR>              Address of the IPv4 socket
+  --ipv6 <ADDR>              Address of the IPv6 socket
+  --name <NAME>              Name of the server
+  --motd <MOTD>              Message of the day
+  --max-players <COUNT>      Maximum amount of players
+  --render-distance <CHUNKS> Maximum render distance
+  --level <PATH>             Path of the level directory
+  --offline                  Allow players that are not signed into Xbox Live";
+
+/// Parses the command line flags into the configuration file path and the options that override the file.
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> anyhow::Result<(PathBuf, ConfigFile)> {
+    fn value<T: FromStr, I: Iterator<Item = String>>(flag: &str, args: &mut I) -> anyhow::Result<T>
+    where
+        T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        let value = args.next().with_context(|| format!("Missing value for {flag}\n\n{USAGE}"))?;
+        value.parse().with_context(|| format!("Invalid value for {flag}: {value}"))
+    }
+
+    let mut path = PathBuf::from("mirai.toml");
+    let mut overrides = ConfigFile::default();
+
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--config" => path = value(&flag, &mut args)?,
+            "--ipv4" => overrides.ipv4_addr = Some(value(&flag, &mut args)?),
+            "--ipv6" => overrides.ipv6_addr = Some(value(&flag, &mut args)?),
+            "--name" => overrides.name = Some(value(&flag, &mut args)?),
+            "--motd" => overrides.motd = Some(value(&flag, &mut args)?),
+            "--max-players" => overrides.max_players = Some(value(&flag, &mut args)?),
+            "--render-distance" => overrides.max_render_distance = Some(value(&flag, &mut args)?),
+            "--level" => overrides.level.path = Some(value(&flag, &mut args)?),
+            "--offline" => overrides.online_mode = Some(false),
+            "--help" | "-h" => {
+                println!("{USAGE}");
+                std::process::exit(0);
+            }
+            _ => anyhow::bail!("Unknown flag {flag}\n\n{USAGE}"),
+        }
+    }
+
+    Ok((path, overrides))
+}
+
 /// Initialises logging with tokio-console.
 #[cfg(feature = "tokio-console")]
 fn init_logging() -> anyhow::Result<()> {
@@ -0,0 +1,245 @@
+//! Prometheus metrics of the server.
+//!
+//! When a metrics address has been configured, the server exposes all metrics in the OpenMetrics text
+//! format at `/metrics` on that address.
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+use prometheus_client::registry::Registry;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::sync::CancellationToken;
+use util::pool;
+
+use crate::net::Clients;
+
+/// Content type of the OpenMetrics text format.
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Maximum size of an HTTP request sent to the exporter.
+const MAX_REQUEST_SIZE: usize = 4096;
+
+/// How long a client is allowed to take to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+/// Returns the global metrics of the server.
+pub fn metrics() -> &'static Metrics {
+    METRICS.get_or_init(Metrics::new)
+}
+
+/// Labels of the per-packet metrics.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+pub struct PacketLabels {
+    /// ID of the packet.
+    pub id: u32,
+}
+
+/// Statistics about the server that can be exported to Prometheus.
+pub struct Metrics {
+    packets_received: Family<PacketLabels, Counter>,
+    bytes_received: Family<PacketLabels, Counter>,
+    packets_sent: Family<PacketLabels, Counter>,
+    bytes_sent: Family<PacketLabels, Counter>,
+    connected: Gauge,
+    connecting: Gauge,
+    region_load: Histogram,
+    collector_flush: Histogram,
+    pool_requests: Counter,
+    pool_recycles: Counter,
+    pool_allocations: Counter,
+    pool_hit_ratio: Gauge<f64, AtomicU64>,
+    firewall_dropped: Counter,
+    registry: Registry,
+}
+
+impl Metrics {
+    fn new() -> Metrics {
+        let metrics = Metrics {
+            packets_received: Family::default(),
+            bytes_received: Family::default(),
+            packets_sent: Family::default(),
+            bytes_sent: Family::default(),
+            connected: Gauge::default(),
+            connecting: Gauge::default(),
+            // 1 ms up to 16 s.
+            region_load: Histogram::new(exponential_buckets(0.001, 2.0, 15)),
+            collector_flush: Histogram::new(exponential_buckets(0.001, 2.0, 15)),
+            pool_requests: Counter::default(),
+            pool_recycles: Counter::default(),
+            pool_allocations: Counter::default(),
+            pool_hit_ratio: Gauge::default(),
+            firewall_dropped: Counter::default(),
+            registry: Registry::default(),
+        };
+
+        let mut registry = Registry::with_prefix("mirai");
+        registry.register("packets_received", "Game packets received from clients", metrics.packets_received.clone());
+        registry.register("packet_bytes_received", "Size of the game packets received from clients", metrics.bytes_received.clone());
+        registry.register("packets_sent", "Game packets sent to clients", metrics.packets_sent.clone());
+        registry.register("packet_bytes_sent", "Size of the game packets sent to clients", metrics.bytes_sent.clone());
+        registry.register("clients_connected", "Clients that have finished logging in", metrics.connected.clone());
+        registry.register("clients_connecting", "Clients that are in the process of logging in", metrics.connecting.clone());
+        registry.register(
+            "region_load_seconds",
+            "Time it took to load all subchunks of a region from the level",
+            metrics.region_load.clone(),
+        );
+        registry.register(
+            "collector_flush_seconds",
+            "Time it took to write collected subchunks to the level",
+            metrics.collector_flush.clone(),
+        );
+        registry.register("pool_requests", "Buffers requested from the recycle pool", metrics.pool_requests.clone());
+        registry.register("pool_recycles", "Buffers returned to the recycle pool", metrics.pool_recycles.clone());
+        registry.register("pool_allocations", "Buffers that the recycle pool had to allocate", metrics.pool_allocations.clone());
+        registry.register(
+            "pool_hit_ratio",
+            "Fraction of buffer requests that were served by a recycled buffer",
+            metrics.pool_hit_ratio.clone(),
+        );
+
+        registry.register(
+            "firewall_dropped",
+            "Packets dropped because of a ban or rate limit",
+            metrics.firewall_dropped.clone(),
+        );
+
+        raknet::METRICS.register(&mut registry);
+
+        Metrics { registry, ..metrics }
+    }
+
+    /// Records a game packet received from a client.
+    pub fn record_received(&self, id: u32, bytes: usize) {
+        let labels = PacketLabels { id };
+        self.packets_received.get_or_create(&labels).inc();
+        self.bytes_received.get_or_create(&labels).inc_by(bytes as u64);
+    }
+
+    /// Records a game packet sent to a client.
+    pub fn record_sent(&self, id: u32, bytes: usize) {
+        let labels = PacketLabels { id };
+        self.packets_sent.get_or_create(&labels).inc();
+        self.bytes_sent.get_or_create(&labels).inc_by(bytes as u64);
+    }
+
+    /// Records a packet that was dropped by the firewall.
+    pub fn record_firewall_drop(&self) {
+        self.firewall_dropped.inc();
+    }
+
+    /// Records how long it took to load a region.
+    pub fn observe_region_load(&self, duration: Duration) {
+        self.region_load.observe(duration.as_secs_f64());
+    }
+
+    /// Records how long it took to flush the collector.
+    pub fn observe_collector_flush(&self, duration: Duration) {
+        self.collector_flush.observe(duration.as_secs_f64());
+    }
+
+    /// Updates the metrics that are sampled rather than recorded, such as the amount of clients.
+    pub fn sample(&self, clients: &Clients) {
+        self.connected.set(clients.total_connected() as i64);
+        self.connecting.set(clients.total_connecting() as i64);
+
+        // The pool keeps its own totals, so the counters are advanced to them. Taking the maximum keeps them
+        // monotonic when multiple requests are sampled at the same time.
+        let requests = pool::total_requests();
+        let allocations = pool::total_allocations();
+        self.pool_requests.inner().fetch_max(requests as u64, Ordering::Relaxed);
+        self.pool_recycles.inner().fetch_max(pool::total_recycles() as u64, Ordering::Relaxed);
+        self.pool_allocations.inner().fetch_max(allocations as u64, Ordering::Relaxed);
+        if requests != 0 {
+            self.pool_hit_ratio.set(requests.saturating_sub(allocations) as f64 / requests as f64);
+        }
+    }
+
+    /// Encodes all metrics in the OpenMetrics text format.
+    pub fn encode(&self) -> anyhow::Result<String> {
+        let mut output = String::new();
+        prometheus_client::encoding::text::encode(&mut output, &self.registry)?;
+
+        Ok(output)
+    }
+}
+
+/// Serves the metrics over HTTP until the token is cancelled.
+pub async fn serve(addr: SocketAddr, clients: Arc<Clients>, token: CancellationToken) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    tracing::info!("Metrics exporter listening on {addr}");
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, peer) = match accepted {
+                    Ok(accepted) => accepted,
+                    Err(err) => {
+                        tracing::warn!("Failed to accept metrics connection: {err}");
+                        continue
+                    }
+                };
+
+                let clients = Arc::clone(&clients);
+                tokio::spawn(async move {
+                    if let Err(err) = respond(stream, &clients).await {
+                        tracing::debug!("Failed to respond to metrics request from {peer}: {err:#}");
+                    }
+                });
+            },
+            () = token.cancelled() => break
+        }
+    }
+
+    Ok(())
+}
+
+/// Responds to a single HTTP request.
+async fn respond(mut stream: TcpStream, clients: &Clients) -> anyhow::Result<()> {
+    let mut request = Vec::with_capacity(512);
+    let read = async {
+        let mut buf = [0u8; 512];
+        while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+            let n = stream.read(&mut buf).await?;
+            if n == 0 {
+                break
+            }
+
+            request.extend_from_slice(&buf[..n]);
+            if request.len() > MAX_REQUEST_SIZE {
+                anyhow::bail!("Metrics request is too large");
+            }
+        }
+
+        Ok(())
+    };
+
+    tokio::time::timeout(REQUEST_TIMEOUT, read).await.map_err(|_| anyhow::anyhow!("Metrics request timed out"))??;
+
+    let line = request.split(|b| *b == b'\n').next().unwrap_or_default();
+    let mut parts = line.split(|b| *b == b' ');
+    let (method, path) = (parts.next(), parts.next());
+
+    let response = if method == Some(b"GET") && path == Some(b"/metrics") {
+        metrics().sample(clients);
+        let body = metrics().encode()?;
+        format!("HTTP/1.1 200 OK\r\nContent-Type: {CONTENT_TYPE}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}", body.len())
+    } else {
+        String::from("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+    };
+
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+
+    Ok(())
+}
@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use proto::bedrock::{GameMode, ItemInstance, UpdateBlock, UpdateBlockFlags, UseItemAction, WindowId};
+use util::{BlockPosition, Vector};
+
+use crate::event::{BlockBreak, BlockPlace};
+use crate::level::subchunk_range;
+
+use super::BedrockClient;
+
+/// Maximum distance between the eyes of a survival player and the center of a block they interact with.
+const SURVIVAL_REACH: f32 = 8.0;
+/// Maximum distance between the eyes of a creative player and the center of a block they interact with.
+const CREATIVE_REACH: f32 = 13.0;
+
+impl BedrockClient {
+    /// Handles a use item transaction, which is sent when the player breaks or clicks a block.
+    pub(super) async fn handle_use_item(
+        self: &Arc<Self>,
+        action_type: UseItemAction,
+        position: Vector<i32, 3>,
+        face: i32,
+        hotbar_slot: i32,
+        held_item: &ItemInstance<'_>,
+    ) -> anyhow::Result<()> {
+        match action_type {
+            UseItemAction::BreakBlock => self.break_block(position).await,
+            UseItemAction::ClickBlock => self.place_block(position, face, hotbar_slot, held_item).await,
+            UseItemAction::ClickAir => Ok(()),
+        }
+    }
+
+    /// Replaces the block at the given position with air.
+    async fn break_block(self: &Arc<Self>, position: Vector<i32, 3>) -> anyhow::Result<()> {
+        if !self.can_modify(&position)? {
+            return self.resend_block(position);
+        }
+
+        let event = BlockBreak { client: Arc::clone(self), position: position.clone(), cancelled: false };
+        if self.instance().events().dispatch(event).await.cancelled {
+            return self.resend_block(position);
+        }
+
+        let instance = self.instance();
+        let air = instance.block_states.air();
+
+        self.update_block(position, air)
+    }
+
+    /// Places the block that the player is holding against the given face of the clicked block.
+    ///
+    /// In survival mode, the held item is consumed.
+    async fn place_block(self: &Arc<Self>, clicked: Vector<i32, 3>, face: i32, hotbar_slot: i32, held_item: &ItemInstance<'_>) -> anyhow::Result<()> {
+        if held_item.block_runtime_id <= 0 {
+            // The player is not holding a block.
+            return Ok(());
+        }
+
+        let player = self.player()?;
+        let held = player.inventory.lock().hotbar.get(hotbar_slot as usize).cloned();
+        let Some(held) = held.filter(|held| held.network_id == held_item.network_id && held.count > 0) else {
+            tracing::debug!("Client attempted to place a block it does not have");
+            return self.send_inventory();
+        };
+
+        let position = match face {
+            0 => Vector::from([clicked.x, clicked.y - 1, clicked.z]),
+            1 => Vector::from([clicked.x, clicked.y + 1, clicked.z]),
+            2 => Vector::from([clicked.x, clicked.y, clicked.z - 1]),
+            3 => Vector::from([clicked.x, clicked.y, clicked.z + 1]),
+            4 => Vector::from([clicked.x - 1, clicked.y, clicked.z]),
+            5 => Vector::from([clicked.x + 1, clicked.y, clicked.z]),
+            _ => anyhow::bail!("Invalid block face {face}"),
+        };
+
+        if !self.can_modify(&position)? {
+            return self.resend_block(position);
+        }
+
+        // Blocks can only replace air.
+        let instance = self.instance();
+        let current = self.viewer.service.block(position.clone(), player.dimension())?;
+        if instance.block_states.state(&current) != Some(instance.block_states.air()) {
+            return self.resend_block(position);
+        }
+
+        let event = BlockPlace { client: Arc::clone(self), position: position.clone(), block: held.block_runtime_id as u32, cancelled: false };
+        if instance.events().dispatch(event).await.cancelled {
+            self.resend_block(position)?;
+            // The client has already removed the item from its inventory.
+            return self.send_inventory();
+        }
+
+        self.update_block(position, held.block_runtime_id as u32)?;
+
+        if player.gamemode() != GameMode::Creative {
+            let remaining = held.count - 1;
+            let item = if remaining == 0 { ItemInstance::air() } else { ItemInstance { count: remaining, ..held } };
+
+            self.set_item(WindowId::Inventory, hotbar_slot as u32, item)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether the player is allowed to modify the block at the given position.
+    fn can_modify(&self, position: &Vector<i32, 3>) -> anyhow::Result<bool> {
+        let player = self.player()?;
+
+        let reach = match player.gamemode() {
+            GameMode::Creative => CREATIVE_REACH,
+            GameMode::Survival | GameMode::WorldDefault => SURVIVAL_REACH,
+            GameMode::Adventure | GameMode::Spectator | GameMode::SurvivalSpectator | GameMode::CreativeSpectator => return Ok(false),
+        };
+
+        let range = subchunk_range(player.dimension());
+        if !range.contains(&(position.y >> 4)) {
+            return Ok(false);
+        }
+
+        // The position of the player is located at their eyes.
+        let eyes = player.position();
+        let (dx, dy, dz) = (
+            position.x as f32 + 0.5 - eyes.x,
+            position.y as f32 + 0.5 - eyes.y,
+            position.z as f32 + 0.5 - eyes.z,
+        );
+
+        Ok(dx.hypot(dy).hypot(dz) <= reach)
+    }
+
+    /// Changes a block in the world and sends the change to every client that can see it.
+    fn update_block(&self, position: Vector<i32, 3>, runtime_id: u32) -> anyhow::Result<()> {
+        let instance = self.instance();
+        if instance.block_states.entry(runtime_id).is_none() {
+            tracing::warn!("Client attempted to place block with unknown runtime ID {runtime_id}");
+            return self.resend_block(position);
+        }
+
+        instance.set_block(position, self.player()?.dimension(), runtime_id)
+    }
+
+    /// Sends the actual block at the given position to the client, undoing any client-side prediction.
+    fn resend_block(&self, position: Vector<i32, 3>) -> anyhow::Result<()> {
+        let runtime_id = self.instance().block(position.clone(), self.player()?.dimension())?;
+
+        self.send_block_update(UpdateBlock {
+            position: BlockPosition::new(position.x, position.y as u32, position.z),
+            block_runtime_id: runtime_id,
+            flags: UpdateBlockFlags::UpdateNetwork as u32,
+            layer: 0,
+        })
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use proto::bedrock::{CacheBlob, CacheBlobStatus, CacheMissResponse};
+use util::{Deserialize, RVec};
+
+use super::{BedrockClient, CHUNK_SEND_CONFIG};
+
+/// Computes the hash that the client uses to identify a blob.
+#[inline]
+pub fn blob_hash(payload: &[u8]) -> u64 {
+    xxhash_rust::xxh64::xxh64(payload, 0)
+}
+
+/// Maximum amount of bytes in blobs that can wait for a status report from a single client.
+const MAX_PENDING_SIZE: usize = 16 * 1024 * 1024;
+/// Time after which a blob is discarded if the client has not reported its status.
+const BLOB_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A blob that has been sent to a client as a hash and whose status has not been reported yet.
+struct PendingBlob {
+    payload: Arc<[u8]>,
+    /// Amount of packets referring to this blob that the client has not responded to.
+    references: usize,
+    /// Time at which the blob was last sent to the client.
+    sent: Instant,
+}
+
+/// Blobs that are waiting for a status report.
+#[derive(Default)]
+struct PendingBlobs {
+    blobs: HashMap<u64, PendingBlob>,
+    /// Total size of the payloads in bytes.
+    size: usize,
+}
+
+impl PendingBlobs {
+    /// Removes a reference to a pending blob, discarding it once nothing refers to it anymore.
+    fn release(&mut self, hash: u64) {
+        if let Some(blob) = self.blobs.get_mut(&hash) {
+            blob.references -= 1;
+            if blob.references == 0 {
+                self.remove(hash);
+            }
+        }
+    }
+
+    /// Discards a pending blob.
+    fn remove(&mut self, hash: u64) {
+        if let Some(blob) = self.blobs.remove(&hash) {
+            self.size -= blob.payload.len();
+        }
+    }
+
+    /// Discards blobs that have not been reported within `timeout` and then the oldest blobs
+    /// until the total size is within `capacity`.
+    fn evict(&mut self, capacity: usize, timeout: Duration) {
+        let now = Instant::now();
+        let size = &mut self.size;
+        self.blobs.retain(|_, blob| {
+            let keep = now.duration_since(blob.sent) < timeout;
+            if !keep {
+                *size -= blob.payload.len();
+            }
+            keep
+        });
+
+        while self.size > capacity {
+            let Some(oldest) = self.blobs.iter().min_by_key(|(_, blob)| blob.sent).map(|(hash, _)| *hash) else { break };
+            self.remove(oldest);
+        }
+    }
+}
+
+/// Keeps track of the blobs that clients with the blob cache enabled might request.
+///
+/// Instead of sending chunk data directly, the server sends the hashes of the data. The client then
+/// reports which blobs it already has stored and the server only sends the data of the missing blobs.
+///
+/// Clients that never report the status of their blobs could make the store grow indefinitely. Blobs are therefore
+/// discarded after a timeout and when the store exceeds its capacity, in which case the client will not receive them.
+pub struct BlobStore {
+    pending: Mutex<PendingBlobs>,
+    /// Maximum total size of the pending blobs in bytes.
+    capacity: usize,
+    /// Time after which unreported blobs are discarded.
+    timeout: Duration,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl BlobStore {
+    /// Creates a store that keeps at most `capacity` bytes of blobs for at most `timeout`.
+    pub fn with_limits(capacity: usize, timeout: Duration) -> BlobStore {
+        BlobStore {
+            pending: Mutex::new(PendingBlobs::default()),
+            capacity,
+            timeout,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Stores the blobs referred to by a single packet until the client reports their status.
+    ///
+    /// Blobs that occur multiple times in the same packet are only stored once.
+    pub fn insert<I: IntoIterator<Item = (u64, Arc<[u8]>)>>(&self, blobs: I) {
+        let mut pending = self.pending.lock();
+        let mut seen = Vec::new();
+        let now = Instant::now();
+
+        for (hash, payload) in blobs {
+            if seen.contains(&hash) {
+                continue;
+            }
+            seen.push(hash);
+
+            if let Some(blob) = pending.blobs.get_mut(&hash) {
+                blob.references += 1;
+                blob.sent = now;
+            } else {
+                pending.size += payload.len();
+                pending.blobs.insert(hash, PendingBlob { payload, references: 1, sent: now });
+            }
+        }
+
+        pending.evict(self.capacity, self.timeout);
+    }
+
+    /// Processes a status report from the client, returning the blobs that it is missing.
+    ///
+    /// Missing blobs that the server does not know about are skipped.
+    pub fn resolve(&self, status: &CacheBlobStatus) -> Vec<(u64, Arc<[u8]>)> {
+        self.hits.fetch_add(status.hits.len() as u64, Ordering::Relaxed);
+        self.misses.fetch_add(status.misses.len() as u64, Ordering::Relaxed);
+
+        let mut pending = self.pending.lock();
+        for hash in &status.hits {
+            pending.release(*hash);
+        }
+
+        let missing = status
+            .misses
+            .iter()
+            .filter_map(|hash| {
+                let payload = pending.blobs.get(hash).map(|blob| Arc::clone(&blob.payload));
+                pending.release(*hash);
+                payload.map(|payload| (*hash, payload))
+            })
+            .collect();
+        drop(pending);
+
+        missing
+    }
+
+    /// Amount of blobs that the client already had cached.
+    #[inline]
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Amount of blobs that had to be sent to the client.
+    #[inline]
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Amount of blobs that are waiting for a status report.
+    #[inline]
+    pub fn pending(&self) -> usize {
+        self.pending.lock().blobs.len()
+    }
+}
+
+impl Default for BlobStore {
+    fn default() -> BlobStore {
+        BlobStore::with_limits(MAX_PENDING_SIZE, BLOB_TIMEOUT)
+    }
+}
+
+impl BedrockClient {
+    /// Handles a [`CacheBlobStatus`] packet by sending the blobs that the client is missing.
+    pub fn handle_cache_blob_status(&self, packet: RVec) -> anyhow::Result<()> {
+        let status = CacheBlobStatus::deserialize(packet.as_ref())?;
+        let missing = self.blobs.resolve(&status);
+
+        tracing::trace!(
+            "Client blob cache: {} hits, {} misses ({} hits, {} misses in total)",
+            status.hits.len(),
+            status.misses.len(),
+            self.blobs.hits(),
+            self.blobs.misses()
+        );
+
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        let blobs = missing.iter().map(|(hash, payload)| CacheBlob { hash: *hash, payload }).collect::<Vec<_>>();
+        self.send_with_config(CacheMissResponse { blobs: &blobs }, CHUNK_SEND_CONFIG)
+    }
+}
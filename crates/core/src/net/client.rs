@@ -9,28 +9,44 @@ use std::time::{Instant, Duration};
 use anyhow::Context;
 use flate2::Compression;
 use flate2::write::DeflateEncoder;
-use parking_lot::RwLock;
-use raknet::{BroadcastPacket, Frame, FrameBatch, RakNetClient, RakNetCommand, SendConfig, DEFAULT_SEND_CONFIG};
+use parking_lot::{Mutex, RwLock};
+use raknet::{BroadcastPacket, Frame, FrameBatch, PacketEncoder, RakNetClient, RakNetCommand, SendConfig, DEFAULT_SEND_CONFIG};
 use tokio::sync::{broadcast, mpsc};
-use proto::bedrock::{Animate, CacheStatus, ChunkRadiusRequest, ClientToServerHandshake, CommandPermissionLevel, CommandRequest, CompressionAlgorithm, ConnectedPacket, ContainerClose, Disconnect, DisconnectReason, FormResponseData, GameMode, Header, Interact, InventoryTransaction, Login, MobEquipment, MovePlayer, PermissionLevel, PlayerAction, PlayerAuthInput, RequestAbility, RequestNetworkSettings, ResourcePackClientResponse, SetInventoryOptions, SetLocalPlayerAsInitialized, SettingsCommand, Skin, TextMessage, TickSync, UpdateSkin, ViolationWarning, CONNECTED_PACKET_ID};
+use tokio::task::JoinHandle;
+use proto::bedrock::{Animate, CacheBlobStatus, CacheStatus, ChunkRadiusRequest, ClientToServerHandshake, CommandPermissionLevel, CommandRequest, CompressionAlgorithm, ConnectedPacket, ContainerClose, Disconnect, DisconnectReason, FormResponseData, GameMode, Header, Interact, InventoryTransaction, ItemStackRequest, Login, MobEquipment, MovePlayer, PermissionLevel, PlayerAction, PlayerAuthInput, RequestAbility, RequestNetworkSettings, ResourcePackChunkRequest, ResourcePackClientResponse, SetInventoryOptions, SetLocalPlayerAsInitialized, SettingsCommand, Skin, TextMessage, TickSync, UpdateSkin, ViolationWarning, CONNECTED_PACKET_ID};
 use proto::crypto::{Encryptor, BedrockIdentity, BedrockClientInfo};
+use proto::types::{AtomicDimension, Dimension};
 use proto::uuid::Uuid;
 
 use tokio_util::sync::CancellationToken;
 use util::{AtomicFlag, BinaryRead, BinaryWrite, Deserialize, Joinable, RVec, pool, Serialize, Vector};
 
+use crate::event::PacketReceive;
 use crate::forms;
+use crate::handoff::Handoff;
 use crate::instance::Instance;
+use crate::item::Inventory;
 use crate::level::Viewer;
+use crate::metrics;
+
+use super::{BlobStore, MovementState};
 
 const REQUEST_TIMEOUT: Duration = Duration::from_millis(50);
+/// Maximum size of a packet batch after decompression.
+///
+/// This prevents small compressed packets from allocating large amounts of memory.
+const MAX_DECOMPRESSED_SIZE: usize = 8 * 1024 * 1024;
 
 /// Represents a user connected to the server.
 pub struct BedrockClient {
-    pub(super) encryptor: OnceLock<Encryptor>,
+    pub(super) encryptor: OnceLock<Arc<Encryptor>>,
     pub(super) identity: OnceLock<BedrockIdentity>,
     pub(super) client_info: OnceLock<BedrockClientInfo>,
+    /// Handoff token of the trusted peer that transferred this player, if any.
+    pub(super) handoff: OnceLock<Handoff>,
     pub(super) viewer: Viewer,
+    /// Task that is sending the chunks of the last view update.
+    pub(super) view_task: Mutex<Option<JoinHandle<()>>>,
 
     /// Next packet that the server is expecting to receive.
     pub(crate) expected: AtomicU32,
@@ -38,6 +54,8 @@ pub struct BedrockClient {
     pub(crate) should_decompress: AtomicFlag,
     /// Whether the client supports the blob cache.
     pub(crate) supports_cache: AtomicBool,
+    /// Blobs that have been sent to the client as hashes.
+    pub(crate) blobs: BlobStore,
     pub(crate) raknet: Arc<RakNetClient>,
     pub(crate) player: OnceLock<PlayerData>,
 
@@ -47,8 +65,15 @@ pub struct BedrockClient {
 
     pub(crate) broadcast: broadcast::Sender<BroadcastPacket>,
 
+    /// Split screen subclient ID of this player, 0 for the player that owns the connection.
+    pub(crate) subclient_id: u8,
+    /// The client that owns the connection if this is a split screen subclient.
+    pub(super) primary: Option<Weak<BedrockClient>>,
+    /// Split screen players that share the connection of this client.
+    pub(crate) subclients: RwLock<Vec<Arc<BedrockClient>>>,
+
     instance: Weak<Instance>,
-    shutdown_token: CancellationToken
+    pub(super) shutdown_token: CancellationToken
 }
 
 impl BedrockClient {
@@ -65,9 +90,11 @@ impl BedrockClient {
             encryptor: OnceLock::new(),
             identity: OnceLock::new(),
             client_info: OnceLock::new(),
+            handoff: OnceLock::new(),
             expected: AtomicU32::new(RequestNetworkSettings::ID),
             should_decompress: AtomicFlag::new(),
             supports_cache: AtomicBool::new(false),
+            blobs: BlobStore::default(),
             raknet,
             player: OnceLock::new(),
             forms: forms::Subscriber::new(),
@@ -75,7 +102,11 @@ impl BedrockClient {
             broadcast,
             instance,
             shutdown_token: CancellationToken::new(),
-            viewer: Viewer::new(level)
+            viewer: Viewer::new(level),
+            view_task: Mutex::new(None),
+            subclient_id: 0,
+            primary: None,
+            subclients: RwLock::new(Vec::new())
         });
 
         let this = Arc::clone(&client);
@@ -86,6 +117,35 @@ impl BedrockClient {
         client
     }
 
+    /// Creates a split screen player that shares the connection of `primary`.
+    ///
+    /// Subclients do not have their own receiver, their packets are routed to them by the primary client.
+    pub(super) fn new_subclient(primary: &Arc<Self>, subclient_id: u8) -> Arc<Self> {
+        Arc::new(Self {
+            encryptor: OnceLock::new(),
+            identity: OnceLock::new(),
+            client_info: OnceLock::new(),
+            handoff: OnceLock::new(),
+            // The connection has already been set up by the primary client.
+            expected: AtomicU32::new(u32::MAX),
+            should_decompress: AtomicFlag::new(),
+            supports_cache: AtomicBool::new(primary.supports_cache.load(Ordering::Relaxed)),
+            blobs: BlobStore::default(),
+            raknet: Arc::clone(&primary.raknet),
+            player: OnceLock::new(),
+            forms: forms::Subscriber::new(),
+            commands: Arc::clone(&primary.commands),
+            broadcast: primary.broadcast.clone(),
+            instance: Weak::clone(&primary.instance),
+            shutdown_token: CancellationToken::new(),
+            viewer: Viewer::new(Arc::clone(&primary.viewer.service)),
+            view_task: Mutex::new(None),
+            subclient_id,
+            primary: Some(Arc::downgrade(primary)),
+            subclients: RwLock::new(Vec::new())
+        })
+    }
+
     /// The worker that processes incoming packets.
     #[tracing::instrument(
         skip_all,
@@ -113,6 +173,7 @@ impl BedrockClient {
                             }
                         },
                         RakNetCommand::BudgetExhausted => {
+                            self.instance().firewall().strike(&self.raknet.address.ip(), "Exhausted request budget");
                             if let Err(err) = self.kick_with_reason("Exhausted request budget", DisconnectReason::NotAllowed) {
                                 tracing::error!("Failed to kick user, forcing it: {err:#}");
                                 // If kicking does not work, force disconnect them.
@@ -155,12 +216,26 @@ impl BedrockClient {
     }
 
     /// Handles a packet broadcasted by another user.
-    #[allow(clippy::unwrap_in_result)]
+    ///
+    /// The packet is also forwarded to the split screen players sharing this connection.
     fn handle_broadcast(&self, packet: BroadcastPacket) -> anyhow::Result<()> {
-        let should_send = packet.sender.map(|sender| sender != self.raknet.address).unwrap_or(true);
-        if should_send {
+        self.forward_broadcast(&packet)?;
+
+        let subclients = self.subclients.read().clone();
+        for subclient in subclients {
+            subclient.forward_broadcast(&packet)?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends a broadcasted packet to this player unless it is the sender.
+    #[allow(clippy::unwrap_in_result)]
+    fn forward_broadcast(&self, packet: &BroadcastPacket) -> anyhow::Result<()> {
+        let is_sender = packet.sender == Some(self.raknet.address) && packet.sender_subclient == self.subclient_id;
+        if !is_sender {
             let header = Header {
-                id: packet.id, sender_subclient: 0, target_subclient: 0
+                id: packet.id, sender_subclient: 0, target_subclient: self.subclient_id
             };
 
             // Header::size_hint always returns `Some`.
@@ -175,6 +250,7 @@ impl BedrockClient {
             full.write_var_u32(body.len() as u32)?;
             full.write_all(&body)?;
 
+            metrics::metrics().record_sent(packet.id, full.len());
             self.send_serialized(full, DEFAULT_SEND_CONFIG)?;
         }
 
@@ -218,6 +294,15 @@ impl BedrockClient {
 
         tracing::info!("User has been kicked");
 
+        if reason == DisconnectReason::BadPacket {
+            self.instance().firewall().strike(&self.raknet.address.ip(), message);
+        }
+
+        // Kicking a split screen player should not disconnect the other players on the connection.
+        if self.primary.is_some() {
+            return self.leave();
+        }
+
         // Force the session to shut down. Without this, the client could just ignore the disconnect packet.
         self.raknet.active.cancel();
         Ok(())
@@ -237,16 +322,25 @@ impl BedrockClient {
         &self,
         packet: P,
     ) -> anyhow::Result<()> {
-        self.broadcast.send(BroadcastPacket::new(packet, Some(self.raknet.address))?)?;
+        self.broadcast.send(BroadcastPacket {
+            sender_subclient: self.subclient_id,
+            ..BroadcastPacket::new(packet, Some(self.raknet.address))?
+        })?;
         Ok(())
     }
 
     /// Sends a game packet with default settings
     /// (reliable ordered and medium priority)
-    #[allow(clippy::unwrap_in_result, clippy::missing_panics_doc)]
+    #[inline]
     pub fn send<T: ConnectedPacket + Serialize>(&self, packet: T) -> anyhow::Result<()> {
+        self.send_with_config(packet, DEFAULT_SEND_CONFIG)
+    }
+
+    /// Sends a game packet with custom reliability and priority.
+    #[allow(clippy::unwrap_in_result, clippy::missing_panics_doc)]
+    pub fn send_with_config<T: ConnectedPacket + Serialize>(&self, packet: T, config: SendConfig) -> anyhow::Result<()> {
         let header = Header {
-            id: T::ID, sender_subclient: 0, target_subclient: 0
+            id: T::ID, sender_subclient: 0, target_subclient: self.subclient_id
         };
 
         // Header::size_hint always returns a value.
@@ -263,7 +357,8 @@ impl BedrockClient {
         full.write_var_u32(body.len() as u32)?;
         full.write_all(&body)?;
 
-        self.send_serialized(full, DEFAULT_SEND_CONFIG)
+        metrics::metrics().record_sent(T::ID, full.len());
+        self.send_serialized(full, config)
     }
 
     /// Sends a game packet with custom reliability and priority
@@ -271,33 +366,39 @@ impl BedrockClient {
         where
             B: AsRef<[u8]>
     {
+        // Subclients use the compression and encryption of the connection they share.
+        if let Some(primary) = &self.primary {
+            let primary = primary.upgrade().ok_or_else(|| anyhow::anyhow!("Connection of split screen player has been closed"))?;
+            return primary.send_serialized(packet, config);
+        }
+
         let mut out;
         if self.should_decompress.get() {
-            let (algorithm, threshold) = {
+            let (algorithm, threshold, flate_level) = {
                 let instance = self.instance();
                 let compression = instance.config().compression();
-                (compression.algorithm, compression.threshold)
+                (compression.algorithm, compression.threshold, compression.flate_level)
             };
 
             if packet.as_ref().len() > threshold as usize {
                 // Compress packet
-                match algorithm {
+                let compressed_body = match algorithm {
                     CompressionAlgorithm::Snappy => {
-                        unimplemented!("Snappy compression");
+                        snap::raw::Encoder::new().compress_vec(packet.as_ref()).context("Failed to compress packet")?
                     }
                     CompressionAlgorithm::Flate => {
-                        let writer_inner = RVec::alloc_with_capacity(packet.as_ref().len());
-                        let mut writer = DeflateEncoder::new(writer_inner, Compression::best());
+                        let writer_inner = Vec::with_capacity(packet.as_ref().len());
+                        let mut writer = DeflateEncoder::new(writer_inner, Compression::new(flate_level));
 
                         writer.write_all(packet.as_ref())?;
-                        let compressed_body = writer.finish()?;
-
-                        out = RVec::alloc_with_capacity(1 + 1 + compressed_body.len());
-                        out.write_u8(CONNECTED_PACKET_ID)?;
-                        out.write_u8(algorithm as u8)?;
-                        out.write_all(&compressed_body)?;
+                        writer.finish()?
                     }
-                }
+                };
+
+                out = RVec::alloc_with_capacity(1 + 1 + compressed_body.len());
+                out.write_u8(CONNECTED_PACKET_ID)?;
+                out.write_u8(algorithm as u8)?;
+                out.write_all(&compressed_body)?;
             } else {
                 // Also reserve capacity for checksum even if encryption is disabled,
                 // preventing allocations.
@@ -313,17 +414,14 @@ impl BedrockClient {
             out.write_all(packet.as_ref())?;
         };
 
-        let chunk_max_size = self.raknet.mtu as usize
-            - std::mem::size_of::<Frame>()
-            - std::mem::size_of::<FrameBatch>();
-
-        let compound_size = out.len().div_ceil(chunk_max_size) as u64;
-
-        if let Some(encryptor) = self.encryptor.get() {
-            encryptor.encrypt(compound_size, &mut out).context("Failed to encrypt packet")?;
+        // Encrypted packets are encrypted by the RakNet layer when they are actually sent,
+        // because the order in which packets are encrypted must match the order in which they arrive.
+        if self.encryptor.get().is_some() {
+            self.raknet.send_encoded_buffer_with_config(out, config);
+        } else {
+            self.raknet.send_raw_buffer_with_config(out, config);
         }
 
-        self.raknet.send_raw_buffer_with_config(out, config);
         Ok(())
     }
 
@@ -356,14 +454,28 @@ impl BedrockClient {
 
                 match algorithm {
                     CompressionAlgorithm::Flate => {
-                        let mut reader = flate2::read::DeflateDecoder::new(packet.as_slice());
+                        let reader = flate2::read::DeflateDecoder::new(packet.as_slice());
                         let mut decompressed = RVec::alloc_with_capacity(packet.len() * 2);
                         
-                        reader.read_to_end(&mut decompressed)?;
+                        reader.take(MAX_DECOMPRESSED_SIZE as u64 + 1).read_to_end(&mut decompressed)?;
+                        if decompressed.len() > MAX_DECOMPRESSED_SIZE {
+                            anyhow::bail!("Decompressed packet exceeds maximum size of {MAX_DECOMPRESSED_SIZE} bytes");
+                        }
                         self.handle_frame_body(decompressed).await
                     },
                     CompressionAlgorithm::Snappy => {
-                        anyhow::bail!("Snappy compression is not implemented");
+                        let len = snap::raw::decompress_len(&packet)?;
+                        if len > MAX_DECOMPRESSED_SIZE {
+                            anyhow::bail!("Decompressed packet exceeds maximum size of {MAX_DECOMPRESSED_SIZE} bytes");
+                        }
+
+                        let mut decompressed = RVec::alloc_with_capacity(0);
+                        decompressed.resize(len, 0);
+
+                        snap::raw::Decoder::new()
+                            .decompress(&packet, &mut decompressed)
+                            .context("Failed to decompress packet")?;
+                        self.handle_frame_body(decompressed).await
                     }
                 }
             }
@@ -389,10 +501,21 @@ impl BedrockClient {
         let mut reader: &[u8] = packet.as_ref();
         let _length = reader.read_var_u32()?;
         let header = Header::deserialize_from(&mut reader)?;
+        metrics::metrics().record_received(header.id, start_len);
 
         let remaining = reader.remaining();
         packet.drain(0..(start_len - remaining));
-        
+
+        // Packets of split screen players are handled by their own client.
+        if header.sender_subclient != 0 {
+            return self.handle_subclient_packet(header, packet).await;
+        }
+
+        self.handle_packet(header, packet).await
+    }
+
+    /// Handles a single packet that was sent by this player.
+    pub(super) async fn handle_packet(self: &Arc<Self>, header: Header, packet: RVec) -> anyhow::Result<()> {
         let expected = self.expected();
         if expected != u32::MAX && header.id != expected {
             // Server received an unexpected packet.
@@ -406,31 +529,43 @@ impl BedrockClient {
 
         let this = Arc::clone(self);
         let future = async move {
+            let event = PacketReceive { client: Arc::clone(&this), id: header.id, packet, cancelled: false };
+            let event = this.instance().events().dispatch(event).await;
+            if event.cancelled {
+                return Ok(());
+            }
+            let packet = event.packet;
+
             match header.id {
                 SetInventoryOptions::ID => this.handle_inventory_options(packet).context("while handling SetInventoryOptions"),
                 MobEquipment::ID => this.handle_mob_equipment(packet).context("while handling MobEquipment"),
-                InventoryTransaction::ID => this.handle_inventory_transaction(packet).context("while handling InventoryTransaction"),
-                PlayerAuthInput::ID => this.handle_auth_input(packet).context("while handling PlayerAuthInput"),
+                InventoryTransaction::ID => this.handle_inventory_transaction(packet).await.context("while handling InventoryTransaction"),
+                ItemStackRequest::ID => this.handle_item_stack_request(packet).context("while handling ItemStackRequest"),
+                PlayerAuthInput::ID => this.handle_auth_input(packet).await.context("while handling PlayerAuthInput"),
                 RequestNetworkSettings::ID => {
                     this.handle_network_settings_request(packet).context("while handling RequestNetworkSettings")
                 }
                 Login::ID => this.handle_login(packet).await.context("while handling Login"),
                 ClientToServerHandshake::ID => {
-                    this.handle_client_to_server_handshake(packet).context("while handling ClientToServerHandshake")
+                    this.handle_client_to_server_handshake(packet).await.context("while handling ClientToServerHandshake")
                 }
                 CacheStatus::ID => this.handle_cache_status(packet).context("while handling CacheStatus"),
+                CacheBlobStatus::ID => this.handle_cache_blob_status(packet).context("while handling CacheBlobStatus"),
                 ResourcePackClientResponse::ID => {
                     this.handle_resource_client_response(packet).context("while handling ResourcePackClientResponse")
                 }
+                ResourcePackChunkRequest::ID => {
+                    this.handle_resource_pack_chunk_request(packet).context("while handling ResourcePackChunkRequest")
+                }
                 ViolationWarning::ID => this.handle_violation_warning(packet).context("while handling ViolationWarning"),
-                ChunkRadiusRequest::ID => this.handle_chunk_radius_request(packet).context("while handling ChunkRadiusRequest"),
-                Interact::ID => this.handle_interaction(packet).context("while handling Interact"),
-                TextMessage::ID => this.handle_text_message(packet),
+                ChunkRadiusRequest::ID => this.handle_chunk_radius_request(packet).await.context("while handling ChunkRadiusRequest"),
+                Interact::ID => this.handle_interaction(packet).await.context("while handling Interact"),
+                TextMessage::ID => this.handle_text_message(packet).await,
                 SetLocalPlayerAsInitialized::ID => {
-                    this.handle_local_initialized(packet)
+                    this.handle_local_initialized(packet).await
                 }
-                MovePlayer::ID => this.handle_move_player(packet),
-                PlayerAction::ID => this.handle_player_action(packet),
+                MovePlayer::ID => this.handle_move_player(packet).await,
+                PlayerAction::ID => this.handle_player_action(packet).await,
                 RequestAbility::ID => this.handle_ability_request(packet),
                 Animate::ID => this.handle_animation(packet),
                 // Command request does not return a result because it does not fail.
@@ -438,9 +573,9 @@ impl BedrockClient {
                     this.handle_command_request(packet); 
                     Ok(())
                 },
-                UpdateSkin::ID => this.handle_skin_update(packet),
+                UpdateSkin::ID => this.handle_skin_update(packet).await,
                 SettingsCommand::ID => this.handle_settings_command(packet),
-                ContainerClose::ID => this.handle_container_close(packet),
+                ContainerClose::ID => this.handle_container_close(packet).await,
                 FormResponseData::ID => this.handle_form_response(packet),
                 TickSync::ID => this.handle_tick_sync(packet),
                 id => anyhow::bail!("Invalid game packet: {id:#04x}"),
@@ -462,40 +597,73 @@ impl BedrockClient {
         &self.forms
     }
 
-    /// This function panics if the identity was not set.
+    /// Returns the identity of the user.
+    ///
+    /// This function returns an error if the user has not logged in yet.
     #[inline]
     pub fn identity(&self) -> anyhow::Result<&BedrockIdentity> {
         self.identity.get().ok_or_else(|| anyhow::anyhow!("Identity unknown: user has not logged in yet"))
     }
 
-    /// This function panics if the name was not set.
+    /// Returns the client info of the user.
+    ///
+    /// This function returns an error if the user has not logged in yet.
+    #[inline]
+    pub fn client_info(&self) -> anyhow::Result<&BedrockClientInfo> {
+        self.client_info.get().ok_or_else(|| anyhow::anyhow!("Client info unknown: user has not logged in yet"))
+    }
+
+    /// Returns the handoff token of the trusted peer that transferred this player to this server.
+    ///
+    /// This is `None` if the player joined directly.
+    #[inline]
+    pub fn handoff(&self) -> Option<&Handoff> {
+        self.handoff.get()
+    }
+
+    /// Returns the name of the user.
+    ///
+    /// This function returns an error if the user has not logged in yet.
     #[inline]
     pub fn name(&self) -> anyhow::Result<&str> {
         self.identity().map(|id| id.name.as_str())
     }
 
-    /// This function panics if the player data was not set.
+    /// Returns the runtime ID of the player.
+    ///
+    /// This function returns an error if the player has not been created yet.
     #[inline]
     pub fn runtime_id(&self) -> anyhow::Result<u64> {
         Ok(self.player()?.runtime_id)
     }
 
-    /// This function panics if the XUID was not set.
+    /// Returns the XUID of the user.
+    ///
+    /// This function returns an error if the user has not logged in yet.
     #[inline]
     pub fn xuid(&self) -> anyhow::Result<u64> {
-        self.identity().map(|id| id.xuid)
+        let identity = self.identity()?;
+        match self.handoff() {
+            // Unauthenticated players that were handed off use the XUID verified by the trusted peer.
+            Some(handoff) if !identity.authenticated => Ok(handoff.xuid),
+            _ => Ok(identity.xuid),
+        }
     }
 
-    /// This function panics if the UUID was not set.
+    /// Returns the UUID of the user.
+    ///
+    /// This function returns an error if the user has not logged in yet.
     #[inline]
     pub fn uuid(&self) -> anyhow::Result<&Uuid> {
         self.identity().map(|id| &id.uuid)
     }
 
-    /// This function panics if the encryptor was not set.
+    /// Returns the encryptor of the connection.
+    ///
+    /// This function returns an error if the encryption handshake has not been performed yet.
     #[inline]
     pub fn encryptor(&self) -> anyhow::Result<&Encryptor> {
-        self.encryptor.get().ok_or_else(|| anyhow::anyhow!("Encryption handshake has not been performed yet"))
+        self.encryptor.get().map(AsRef::as_ref).ok_or_else(|| anyhow::anyhow!("Encryption handshake has not been performed yet"))
     }
 
     /// Returns the next expected packet for this session.
@@ -535,12 +703,24 @@ impl Joinable for BedrockClient {
 pub struct PlayerData {
     /// Whether the player's inventory is currently open.
     pub is_inventory_open: AtomicBool,
-    /// Position of the player.
-    pub position: Vector<f32, 3>,
+    /// Items in the player's inventory.
+    ///
+    /// This is the authoritative state, client transactions are validated against it.
+    pub inventory: Mutex<Inventory>,
+    /// Whether the player has been spawned for other clients.
+    pub is_spawned: AtomicBool,
+    /// Whether the player is leaving the server, which makes sure that it only quits once.
+    pub is_quitting: AtomicBool,
+    /// Last position of the player that was accepted by the server.
+    pub position: RwLock<Vector<f32, 3>>,
     /// Rotation of the player.
     /// x and y components are general rotation.
     /// z component is head yaw.
-    pub rotation: Vector<f32, 3>,
+    pub rotation: RwLock<Vector<f32, 3>>,
+    /// State used to validate the movement of the player.
+    pub movement: Mutex<MovementState>,
+    /// Dimension that the player is located in.
+    pub dimension: AtomicDimension,
     /// Game mode.
     pub game_mode: GameMode,
     /// General permission level.
@@ -555,16 +735,21 @@ pub struct PlayerData {
 
 impl PlayerData {
     /// Creates a new player data struct.
-    pub fn new(skin: Skin) -> Self {
+    pub fn new(skin: Skin, runtime_id: u64) -> Self {
         Self {
             is_inventory_open: AtomicBool::new(false),
-            position: Vector::from([0.0, 50.0, 0.0]),
-            rotation: Vector::from([0.0; 3]),
+            inventory: Mutex::new(Inventory::new()),
+            is_spawned: AtomicBool::new(false),
+            is_quitting: AtomicBool::new(false),
+            position: RwLock::new(Vector::from([0.0, 50.0, 0.0])),
+            rotation: RwLock::new(Vector::from([0.0; 3])),
+            movement: Mutex::new(MovementState::new()),
+            dimension: AtomicDimension::from(Dimension::Overworld),
             game_mode: GameMode::Creative,
             permission_level: PermissionLevel::Member,
             command_permission_level: CommandPermissionLevel::Owner,
             skin: RwLock::new(skin),
-            runtime_id: 1
+            runtime_id
         }
     }
 
@@ -573,6 +758,21 @@ impl PlayerData {
         self.game_mode
     }
 
+    /// The dimension the player is currently in.
+    pub fn dimension(&self) -> Dimension {
+        self.dimension.load(Ordering::Relaxed)
+    }
+
+    /// The last accepted position of the player.
+    pub fn position(&self) -> Vector<f32, 3> {
+        self.position.read().clone()
+    }
+
+    /// The current rotation of the player.
+    pub fn rotation(&self) -> Vector<f32, 3> {
+        self.rotation.read().clone()
+    }
+
     /// The runtime ID of the player.
     pub const fn runtime_id(&self) -> u64 {
         self.runtime_id
@@ -587,4 +787,21 @@ impl PlayerData {
     pub const fn command_permission_level(&self) -> CommandPermissionLevel {
         self.command_permission_level
     }
-}
\ No newline at end of file
+}
+
+/// Encrypts game packets in the order in which the RakNet layer sends them.
+pub(super) struct PacketEncryptor {
+    pub(super) encryptor: Arc<Encryptor>,
+    pub(super) mtu: u16,
+}
+
+impl PacketEncoder for PacketEncryptor {
+    fn encode(&self, packet: &mut RVec) -> anyhow::Result<()> {
+        let chunk_max_size = self.mtu as usize
+            - std::mem::size_of::<Frame>()
+            - std::mem::size_of::<FrameBatch>();
+
+        let compound_size = packet.len().div_ceil(chunk_max_size) as u64;
+        self.encryptor.encrypt(compound_size, packet).context("Failed to encrypt packet")
+    }
+}
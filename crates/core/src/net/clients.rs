@@ -1,14 +1,17 @@
 use std::net::SocketAddr;
 use std::sync::{Arc, OnceLock, Weak};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 
 use anyhow::Context;
 use dashmap::DashMap;
+use parking_lot::{Mutex, MutexGuard};
 
 use proto::uuid::Uuid;
 use raknet::{BroadcastPacket, RakNetCreateDescription, RakNetClient};
 use proto::bedrock::{ConnectedPacket, Disconnect, DisconnectReason};
-use util::{RVec, Joinable, Serialize};
+use proto::types::Dimension;
+use util::{RVec, Joinable, Serialize, Vector};
 
 use tokio::sync::{broadcast, mpsc};
 use tokio::task::{JoinHandle, JoinSet};
@@ -52,7 +55,11 @@ pub struct Clients {
 
     commands: Arc<crate::command::Service>,
     level: Arc<crate::level::Service>,
-    instance: OnceLock<Weak<Instance>>
+    instance: OnceLock<Weak<Instance>>,
+    /// Runtime ID that will be assigned to the next player.
+    next_runtime_id: AtomicU64,
+    /// Held while a player is spawned or despawned, so that every player sees a consistent list of players.
+    spawning: Mutex<()>
 }
 
 impl Clients {
@@ -70,7 +77,9 @@ impl Clients {
             broadcast, 
             commands, 
             level,
-            instance: OnceLock::new()
+            instance: OnceLock::new(),
+            next_runtime_id: AtomicU64::new(1),
+            spawning: Mutex::new(())
         }
     }   
 
@@ -120,7 +129,14 @@ impl Clients {
 
         tokio::spawn(async move {
             state_clone.active.cancelled().await;
-            connected_map.remove(&state_clone.address);
+            if let Some((_, user)) = connected_map.remove(&state_clone.address) {
+                let subclients = std::mem::take(&mut *user.state.subclients.write());
+                for client in subclients.iter().chain(std::iter::once(&user.state)) {
+                    if let Err(err) = client.despawn().await {
+                        tracing::error!("Failed to despawn disconnected player: {err:#}");
+                    }
+                }
+            }
             connecting_map.remove(&state_clone.address);
         });
 
@@ -143,14 +159,45 @@ impl Clients {
         self.instance.get().unwrap().upgrade().unwrap()
     }
 
+    /// Generates a new unique runtime ID for a player.
+    #[inline]
+    pub(crate) fn next_runtime_id(&self) -> u64 {
+        self.next_runtime_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Returns every connected user, including split screen players that share a connection.
+    pub(crate) fn all(&self) -> Vec<Arc<BedrockClient>> {
+        let mut users = Vec::with_capacity(self.connected_map.len());
+        for entry in self.connected_map.iter() {
+            let user = &entry.value().state;
+            users.push(Arc::clone(user));
+            users.extend(user.subclients.read().iter().cloned());
+        }
+
+        users
+    }
+
+    /// Returns the first user that matches the given predicate.
+    fn find<F>(&self, predicate: F) -> Option<Arc<BedrockClient>>
+    where
+        F: Fn(&BedrockClient) -> bool
+    {
+        self.all().into_iter().find(|user| predicate(user))
+    }
+
     /// Attempts to retrieve the user with the given XUID.
     pub fn by_xuid(&self, xuid: u64) -> Option<Arc<BedrockClient>> {
-        todo!()
+        self.find(|user| user.xuid().is_ok_and(|x| x == xuid))
     }
 
     /// Attempts to retrieve the user with the given UUID.
     pub fn by_uuid(&self, uuid: Uuid) -> Option<Arc<BedrockClient>> {
-        todo!()
+        self.find(|user| user.uuid().is_ok_and(|u| *u == uuid))
+    }
+
+    /// Attempts to retrieve the user with the given runtime ID.
+    pub fn by_runtime_id(&self, runtime_id: u64) -> Option<Arc<BedrockClient>> {
+        self.find(|user| user.runtime_id().is_ok_and(|id| id == runtime_id))
     }
 
     /// Attempts to retrieve the user with the given IP address.
@@ -162,7 +209,29 @@ impl Clients {
 
     /// Attempts to retrieve the user with the given username.
     pub fn by_username<S: AsRef<str>>(&self, username: S) -> Option<Arc<BedrockClient>> {
-        todo!()
+        let username = username.as_ref();
+        self.find(|user| user.name().is_ok_and(|name| name == username))
+    }
+
+    /// Prevents other players from being spawned or despawned until the guard is dropped.
+    pub(crate) fn lock_spawning(&self) -> MutexGuard<'_, ()> {
+        self.spawning.lock()
+    }
+
+    /// Returns all users whose player has been spawned into the world.
+    pub fn spawned(&self) -> Vec<Arc<BedrockClient>> {
+        self.all()
+            .into_iter()
+            .filter(|user| user.player().is_ok_and(|p| p.is_spawned.load(Ordering::Relaxed)))
+            .collect()
+    }
+
+    /// Returns all users that have received the given chunk column.
+    pub fn viewing(&self, column: &Vector<i32, 2>, dimension: Dimension) -> Vec<Arc<BedrockClient>> {
+        self.all()
+            .into_iter()
+            .filter(|user| user.viewer.is_loaded(column, dimension))
+            .collect()
     }
 
     /// Forwards a packet to a user within the map.
@@ -0,0 +1,382 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use parking_lot::{Mutex, RwLock};
+use serde::Deserialize;
+
+use crate::config::FirewallConfig;
+
+/// Strikes older than this are forgotten.
+const STRIKE_WINDOW: Duration = Duration::from_secs(10 * 60);
+/// Buckets that have not been used for this long are removed when the firewall is pruned.
+const BUCKET_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+/// Prefix length of the IPv4 subnets that share a token bucket.
+const IPV4_SUBNET_PREFIX: u8 = 24;
+/// Prefix length of the IPv6 subnets that share a token bucket.
+const IPV6_SUBNET_PREFIX: u8 = 64;
+/// Maximum amount of addresses and subnets that the firewall keeps a token bucket or strikes for.
+///
+/// Sources that are not tracked because the firewall is full are limited by their subnet, or by a bucket shared
+/// by all untracked subnets if the subnets are full as well.
+const MAX_TRACKED_SOURCES: usize = 65_536;
+
+/// A range of IP addresses in CIDR notation, such as `192.168.0.0/16`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// Creates a range from an address and prefix length, clearing the bits outside of the prefix.
+    pub fn new(addr: IpAddr, prefix: u8) -> anyhow::Result<Cidr> {
+        let addr = addr.to_canonical();
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        if prefix > max {
+            anyhow::bail!("Prefix length {prefix} is too long for {addr}");
+        }
+
+        Ok(Cidr { addr: mask(addr, prefix), prefix })
+    }
+
+    /// Creates a range that only contains a single address.
+    pub const fn single(addr: IpAddr) -> Cidr {
+        let addr = addr.to_canonical();
+        let prefix = if addr.is_ipv4() { 32 } else { 128 };
+
+        Cidr { addr, prefix }
+    }
+
+    /// First address of the range.
+    #[inline]
+    pub const fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// Length of the prefix shared by all addresses in the range.
+    #[inline]
+    pub const fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    /// Whether the address lies in this range.
+    pub fn contains(&self, addr: &IpAddr) -> bool {
+        let addr = addr.to_canonical();
+        addr.is_ipv4() == self.addr.is_ipv4() && mask(addr, self.prefix) == self.addr
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Cidr> {
+        match s.split_once('/') {
+            Some((addr, prefix)) => Cidr::new(addr.parse()?, prefix.parse()?),
+            None => Ok(Cidr::single(s.parse()?)),
+        }
+    }
+}
+
+impl TryFrom<String> for Cidr {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> anyhow::Result<Cidr> {
+        value.parse()
+    }
+}
+
+impl From<IpAddr> for Cidr {
+    fn from(value: IpAddr) -> Cidr {
+        Cidr::single(value)
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// Clears all bits of the address after the prefix.
+fn mask(addr: IpAddr, prefix: u8) -> IpAddr {
+    match addr {
+        IpAddr::V4(v4) => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+            IpAddr::V4(Ipv4Addr::from(u32::from(v4) & mask))
+        }
+        IpAddr::V6(v6) => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0);
+            IpAddr::V6(Ipv6Addr::from(u128::from(v6) & mask))
+        }
+    }
+}
+
+/// A banned range of addresses.
+#[derive(Debug, Clone)]
+pub struct Ban {
+    /// Addresses that are banned.
+    pub range: Cidr,
+    /// When the ban is lifted, or `None` if the ban is permanent.
+    pub expires: Option<Instant>,
+    /// Why the range was banned.
+    pub reason: String,
+}
+
+impl Ban {
+    /// Whether the ban has been lifted.
+    pub fn is_expired(&self) -> bool {
+        self.expires.is_some_and(|expires| expires <= Instant::now())
+    }
+}
+
+/// Limits the rate at which a source can send packets.
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+    /// Whether the last packet was rejected because the bucket was empty.
+    limited: bool,
+}
+
+impl TokenBucket {
+    fn full(burst: f64) -> TokenBucket {
+        TokenBucket { tokens: burst, updated: Instant::now(), limited: false }
+    }
+
+    /// Refills the bucket and attempts to take a single token from it.
+    fn take(&mut self, rate: f64, burst: f64) -> Take {
+        let now = Instant::now();
+        self.tokens = rate.mul_add(now.duration_since(self.updated).as_secs_f64(), self.tokens).min(burst);
+        self.updated = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            self.limited = false;
+            Take::Allowed
+        } else if self.limited {
+            Take::Limited
+        } else {
+            self.limited = true;
+            Take::Exhausted
+        }
+    }
+}
+
+/// Outcome of taking a token from a bucket.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Take {
+    /// A token was taken.
+    Allowed,
+    /// The bucket has just run out of tokens.
+    Exhausted,
+    /// The bucket was already empty.
+    Limited,
+}
+
+/// Banned ranges, indexed so that looking up an address does not require visiting every ban.
+#[derive(Debug, Default)]
+struct BanList {
+    ranges: HashMap<Cidr, Ban>,
+    /// Amount of bans per address family (`true` for IPv4) and prefix length.
+    prefixes: BTreeMap<(bool, u8), usize>,
+}
+
+impl BanList {
+    fn insert(&mut self, ban: Ban) {
+        let range = ban.range;
+        if self.ranges.insert(range, ban).is_none() {
+            *self.prefixes.entry((range.addr.is_ipv4(), range.prefix)).or_default() += 1;
+        }
+    }
+
+    fn remove(&mut self, range: &Cidr) -> bool {
+        if self.ranges.remove(range).is_none() {
+            return false;
+        }
+
+        let key = (range.addr.is_ipv4(), range.prefix);
+        if let Some(count) = self.prefixes.get_mut(&key) {
+            *count -= 1;
+            if *count == 0 {
+                self.prefixes.remove(&key);
+            }
+        }
+        true
+    }
+
+    /// Returns the ban of a range that contains the address.
+    ///
+    /// Only the prefix lengths that are actually banned are looked up.
+    fn find(&self, addr: &IpAddr) -> Option<&Ban> {
+        let addr = addr.to_canonical();
+        self.prefixes
+            .keys()
+            .filter(|(ipv4, _)| *ipv4 == addr.is_ipv4())
+            .filter_map(|(_, prefix)| self.ranges.get(&Cidr { addr: mask(addr, *prefix), prefix: *prefix }))
+            .find(|ban| !ban.is_expired())
+    }
+
+    fn retain<F: Fn(&Ban) -> bool>(&mut self, keep: F) {
+        let removed = self.ranges.values().filter(|ban| !keep(ban)).map(|ban| ban.range).collect::<Vec<_>>();
+        for range in removed {
+            self.remove(&range);
+        }
+    }
+}
+
+/// Misbehaviour recorded for an address.
+#[derive(Debug)]
+struct Strikes {
+    count: u32,
+    first: Instant,
+}
+
+/// Filters packets from clients that do not have a session yet.
+///
+/// Every address and every subnet has a token bucket that limits how many unconnected packets, such as pings
+/// and connection requests, it can send. Addresses in a banned range are ignored completely.
+/// Addresses that misbehave repeatedly, including addresses that keep exhausting their token bucket,
+/// are banned temporarily.
+pub struct Firewall {
+    config: FirewallConfig,
+    bans: RwLock<BanList>,
+    addresses: DashMap<IpAddr, TokenBucket>,
+    subnets: DashMap<IpAddr, TokenBucket>,
+    /// Bucket shared by all subnets that are not tracked because the firewall is full.
+    untracked: Mutex<TokenBucket>,
+    strikes: DashMap<IpAddr, Strikes>,
+}
+
+impl Firewall {
+    /// Creates a firewall with the given settings and permanent bans.
+    pub fn new(config: &FirewallConfig) -> Firewall {
+        let mut bans = BanList::default();
+        for range in &config.bans {
+            bans.insert(Ban { range: *range, expires: None, reason: String::from("Banned in configuration") });
+        }
+
+        Firewall {
+            config: config.clone(),
+            bans: RwLock::new(bans),
+            addresses: DashMap::new(),
+            subnets: DashMap::new(),
+            untracked: Mutex::new(TokenBucket::full(config.subnet_burst)),
+            strikes: DashMap::new(),
+        }
+    }
+
+    /// Whether an unconnected packet from the address should be processed.
+    ///
+    /// This takes a token from the buckets of both the address and its subnet. Every time the address runs out
+    /// of tokens, it receives a strike.
+    pub fn allow_unconnected(&self, addr: &IpAddr) -> bool {
+        if self.is_banned(addr) {
+            return false;
+        }
+
+        let addr = addr.to_canonical();
+        let config = &self.config;
+
+        let taken = if self.addresses.len() < MAX_TRACKED_SOURCES || self.addresses.contains_key(&addr) {
+            self.addresses
+                .entry(addr)
+                .or_insert_with(|| TokenBucket::full(config.ip_burst))
+                .take(config.ip_rate, config.ip_burst)
+        } else {
+            Take::Allowed
+        };
+
+        match taken {
+            Take::Allowed => (),
+            Take::Exhausted => {
+                self.strike(&addr, "Exceeded the unconnected packet rate limit");
+                return false;
+            }
+            Take::Limited => return false,
+        }
+
+        // Addresses that are already limited should not use up the tokens of their subnet.
+        let subnet = mask(addr, if addr.is_ipv4() { IPV4_SUBNET_PREFIX } else { IPV6_SUBNET_PREFIX });
+        let taken = if self.subnets.len() < MAX_TRACKED_SOURCES || self.subnets.contains_key(&subnet) {
+            self.subnets
+                .entry(subnet)
+                .or_insert_with(|| TokenBucket::full(config.subnet_burst))
+                .take(config.subnet_rate, config.subnet_burst)
+        } else {
+            self.untracked.lock().take(config.subnet_rate, config.subnet_burst)
+        };
+
+        taken == Take::Allowed
+    }
+
+    /// Whether the address lies in a banned range.
+    pub fn is_banned(&self, addr: &IpAddr) -> bool {
+        self.bans.read().find(addr).is_some()
+    }
+
+    /// Bans a range of addresses, replacing any existing ban of the same range.
+    ///
+    /// The ban is permanent if `duration` is `None`.
+    pub fn ban<S: Into<String>>(&self, range: Cidr, duration: Option<Duration>, reason: S) {
+        let ban = Ban { range, expires: duration.map(|duration| Instant::now() + duration), reason: reason.into() };
+
+        let mut bans = self.bans.write();
+        bans.remove(&range);
+        bans.insert(ban);
+    }
+
+    /// Lifts the ban of a range, returning whether it was banned.
+    ///
+    /// This only removes a ban of exactly this range, it does not unban addresses in other banned ranges.
+    pub fn unban(&self, range: &Cidr) -> bool {
+        self.bans.write().remove(range)
+    }
+
+    /// Returns all bans that have not expired.
+    pub fn bans(&self) -> Vec<Ban> {
+        self.bans.read().ranges.values().filter(|ban| !ban.is_expired()).cloned().collect()
+    }
+
+    /// Records misbehaviour of an address, banning it temporarily if it misbehaves too often.
+    ///
+    /// Returns whether the address has been banned.
+    pub fn strike(&self, addr: &IpAddr, reason: &str) -> bool {
+        let addr = addr.to_canonical();
+        if self.strikes.len() >= MAX_TRACKED_SOURCES && !self.strikes.contains_key(&addr) {
+            return false;
+        }
+
+        let count = {
+            let mut strikes = self.strikes.entry(addr).or_insert_with(|| Strikes { count: 0, first: Instant::now() });
+            if strikes.first.elapsed() > STRIKE_WINDOW {
+                *strikes = Strikes { count: 0, first: Instant::now() };
+            }
+
+            strikes.count += 1;
+            strikes.count
+        };
+
+        if count < self.config.strike_limit {
+            return false;
+        }
+
+        self.strikes.remove(&addr);
+        self.ban(Cidr::single(addr), Some(self.config.ban_duration), reason);
+
+        tracing::warn!("Temporarily banned {addr} for {:?}: {reason}", self.config.ban_duration);
+        true
+    }
+
+    /// Removes expired bans, old strikes and buckets that have not been used for a while.
+    pub fn prune(&self) {
+        self.bans.write().retain(|ban| !ban.is_expired());
+        self.addresses.retain(|_, bucket| bucket.updated.elapsed() < BUCKET_IDLE_TIMEOUT);
+        self.subnets.retain(|_, bucket| bucket.updated.elapsed() < BUCKET_IDLE_TIMEOUT);
+        self.strikes.retain(|_, strikes| strikes.first.elapsed() < STRIKE_WINDOW);
+    }
+}
@@ -1,39 +1,17 @@
-use std::{collections::HashMap, sync::Arc};
+use std::sync::Arc;
 
-use futures::{future, StreamExt};
-use level::{BiomeEncoding, BiomeStorage, Biomes, SubChunk, SubStorage};
-use proto::{
-    bedrock::{
-        Animate, CommandOutput, CommandOutputMessage, CommandOutputType, CommandRequest, DisconnectReason, FormResponseData, HeightmapType,
-        HudElement, HudVisibility, InventoryTransaction, ItemInstance, LevelChunk, MobEquipment, NetworkChunkPublisherUpdate, PlayerAuthInput,
-        RequestAbility, SetHud, SetInventoryOptions, SettingsCommand, SubChunkEntry, SubChunkRequestMode, SubChunkResponse, SubChunkResult, TextData,
-        TextMessage, TickSync, TransactionAction, TransactionSourceType, TransactionType, UpdateSkin, WindowId,
-    },
-    types::Dimension,
+use proto::bedrock::{
+    Animate, CommandOutput, CommandOutputMessage, CommandOutputType, CommandRequest, DisconnectReason, FormResponseData, RequestAbility,
+    SetInventoryOptions, SettingsCommand, TextData, TextMessage, TickSync, UpdateSkin,
 };
 
-use util::{BinaryRead, BinaryWrite, CowSlice, Deserialize, RVec};
+use util::{CowSlice, Deserialize, RVec};
 
-use crate::level::io::r#box::BoxRegion;
-use crate::level::io::stream::IndexedSubChunk;
+use crate::event::{ChatMessage, CommandExecute, PlayerSkinChange};
 
 use super::BedrockClient;
 
 impl BedrockClient {
-    /// Handles a mob equipment packet.
-    pub fn handle_mob_equipment(&self, packet: RVec) -> anyhow::Result<()> {
-        let equipment = MobEquipment::deserialize(packet.as_ref())?;
-
-        // Verify that runtime ID matches player's runtime ID.
-        // Clients only send this packet to modify themselves.
-        if equipment.runtime_id != self.runtime_id()? {
-            // Illegal packet modifications
-            self.kick_with_reason("Illegal packets", DisconnectReason::BadPacket)?;
-        }
-
-        self.broadcast_others(equipment)
-    }
-
     pub fn handle_inventory_options(&self, packet: RVec) -> anyhow::Result<()> {
         let options = SetInventoryOptions::deserialize(packet.as_ref())?;
         tracing::debug!("{options:?}");
@@ -41,56 +19,6 @@ impl BedrockClient {
         Ok(())
     }
 
-    pub fn handle_inventory_transaction(&self, packet: RVec) -> anyhow::Result<()> {
-        let transaction = InventoryTransaction::deserialize(packet.as_ref())?;
-        tracing::debug!("{transaction:?}");
-        // let action = &transaction.actions[0];
-        // let item = &action.new_item;
-
-        // let transaction = InventoryTransaction {
-        //     legacy_request_id: 0,
-        //     legacy_transactions: vec![],
-        //     transaction_type: TransactionType::Normal,
-        //     actions: vec![
-        //         TransactionAction {
-        //             slot: 0,
-        //             source_type: TransactionSourceType::Container {
-        //                 inventory_id: WindowId::Ui
-        //             },
-        //             new_item: ItemInstance::air(),
-        //             old_item: item.clone()
-        //         },
-        //         TransactionAction {
-        //             slot: 2,
-        //             source_type: TransactionSourceType::Container {
-        //                 inventory_id: WindowId::Hotbar
-        //             },
-        //             old_item: ItemInstance::air(),
-        //             new_item: item.clone()
-        //         }
-        //     ]
-        // };
-        // self.send(transaction)?;
-
-        // for action in transaction.actions {
-        //     let instance = self.instance();
-
-        //     let new = instance.item_network_ids.get_name(action.new_item.network_id);
-        //     // let old = instance.item_network_ids.get_name(action.old_item.network_id);
-
-        //     let mut buf = Vec::with_capacity(5);
-        //     buf.write_var_i32(action.new_item.network_id)?;
-
-        //     let mut var = buf.as_slice();
-        //     let var = var.read_var_u32()?;
-        //     println!("{var}")
-
-        //     // println!("Switch from {old:?} to {new:?}");
-        // }
-
-        Ok(())
-    }
-
     /// Handles a [`SettingsCommand`] packet used to adjust a world setting.
     pub fn handle_settings_command(&self, packet: RVec) -> anyhow::Result<()> {
         let request = SettingsCommand::deserialize(packet.as_ref())?;
@@ -120,7 +48,7 @@ impl BedrockClient {
             msg
         )
     )]
-    pub fn handle_text_message(self: &Arc<Self>, packet: RVec) -> anyhow::Result<()> {
+    pub async fn handle_text_message(self: &Arc<Self>, packet: RVec) -> anyhow::Result<()> {
         let request = TextMessage::deserialize(packet.as_ref())?;
         if let TextData::Chat { source, message } = request.data {
             tracing::Span::current().record("msg", message);
@@ -132,9 +60,15 @@ impl BedrockClient {
                 return self.kick_with_reason("Illegal packet modifications detected", DisconnectReason::BadPacket);
             }
 
+            let event = ChatMessage { client: Arc::clone(self), message: message.to_owned(), cancelled: false };
+            let event = self.instance().events().dispatch(event).await;
+            if event.cancelled {
+                return Ok(());
+            }
+
             // We must also return the packet to the client that sent it
             // otherwise their message won't be displayed in their own chat.
-            self.broadcast(request)
+            self.broadcast(TextMessage { data: TextData::Chat { source, message: &event.message }, ..request })
         } else {
             // Only the server is allowed to create text packets that are not of the chat type.
             tracing::warn!("Client sent an illegal message type. Kicking them for forbidden modifications");
@@ -142,21 +76,16 @@ impl BedrockClient {
         }
     }
 
-    /// Handles a [`PlayerAuthInput`] packet. These are sent every tick and are used
-    /// for server authoritative player movement.
-    pub fn handle_auth_input(&self, packet: RVec) -> anyhow::Result<()> {
-        let input = PlayerAuthInput::deserialize(packet.as_ref())?;
-        if input.input_data.0 != 0 {
-            // tracing::debug!("{:?}", input.input_data);
-        }
-        
-        Ok(())
-    }
-
     /// Handles an [`UpdateSkin`] packet.
-    pub fn handle_skin_update(&self, packet: RVec) -> anyhow::Result<()> {
+    pub async fn handle_skin_update(self: &Arc<Self>, packet: RVec) -> anyhow::Result<()> {
         let request = UpdateSkin::deserialize(packet.as_ref())?;
         tracing::debug!("{request:?}");
+
+        let event = PlayerSkinChange { client: Arc::clone(self), cancelled: false };
+        if self.instance().events().dispatch(event).await.cancelled {
+            return Ok(());
+        }
+
         self.broadcast(request)
     }
 
@@ -171,89 +100,6 @@ impl BedrockClient {
     /// Handles an [`Animation`] packet.
     pub fn handle_animation(&self, packet: RVec) -> anyhow::Result<()> {
         let request = Animate::deserialize(packet.as_ref())?;
-
-        let transaction = InventoryTransaction {
-            legacy_request_id: 0,
-            legacy_transactions: vec![],
-            transaction_type: TransactionType::Normal,
-            actions: vec![
-                TransactionAction {
-                    slot: 0,
-                    source_type: TransactionSourceType::Container { inventory_id: WindowId::Creative },
-                    new_item: ItemInstance::air(),
-                    old_item: ItemInstance {
-                        block_runtime_id: 13256,
-                        network_id: 5,
-                        blocking_tick: 0,
-                        can_destroy: vec![],
-                        can_place_on: vec![],
-                        count: 12,
-                        metadata: 0,
-                        nbt: HashMap::new(),
-                        stack_id: None,
-                    },
-                },
-                TransactionAction {
-                    slot: 0,
-                    source_type: TransactionSourceType::Container { inventory_id: WindowId::Ui },
-                    old_item: ItemInstance::air(),
-                    new_item: ItemInstance {
-                        block_runtime_id: 13256,
-                        network_id: 5,
-                        blocking_tick: 0,
-                        can_destroy: vec![],
-                        can_place_on: vec![],
-                        count: 12,
-                        metadata: 0,
-                        nbt: HashMap::new(),
-                        stack_id: None,
-                    },
-                },
-            ],
-        };
-        self.send(transaction)?;
-
-        let transaction = InventoryTransaction {
-            legacy_request_id: 0,
-            legacy_transactions: vec![],
-            transaction_type: TransactionType::Normal,
-            actions: vec![
-                TransactionAction {
-                    slot: 0,
-                    source_type: TransactionSourceType::Container { inventory_id: WindowId::Ui },
-                    new_item: ItemInstance::air(),
-                    old_item: ItemInstance {
-                        block_runtime_id: 13256,
-                        network_id: 5,
-                        blocking_tick: 0,
-                        can_destroy: vec![],
-                        can_place_on: vec![],
-                        count: 12,
-                        metadata: 0,
-                        nbt: HashMap::new(),
-                        stack_id: None,
-                    },
-                },
-                TransactionAction {
-                    slot: 0,
-                    source_type: TransactionSourceType::Container { inventory_id: WindowId::Inventory },
-                    old_item: ItemInstance::air(),
-                    new_item: ItemInstance {
-                        block_runtime_id: 13256,
-                        network_id: 5,
-                        blocking_tick: 0,
-                        can_destroy: vec![],
-                        can_place_on: vec![],
-                        count: 12,
-                        metadata: 0,
-                        nbt: HashMap::new(),
-                        stack_id: None,
-                    },
-                },
-            ],
-        };
-        self.send(transaction)?;
-
         tracing::debug!("{request:?}");
 
         Ok(())
@@ -284,86 +130,6 @@ impl BedrockClient {
         )
     )]
     pub fn handle_command_request(self: Arc<Self>, packet: RVec) {
-        // let this = self.clone();
-        // tokio::spawn(async move {
-        //     let stream = this.viewer.service.region(BoxRegion::from_bounds(
-        //         [0, -4, 0], [0, 15, 0], Dimension::Overworld
-        //     ));
-
-        //     stream.take(1).for_each(|sub| {
-        //         dbg!(sub);
-        //         future::ready(())
-        //     }).await;
-        // });
-
-        // Request the chunk the player is in
-        let stream = self
-            .viewer
-            .service
-            .region(BoxRegion::from_bounds((0, -4, 0), (0, 15, 0), Dimension::Overworld));
-
-        self.send(NetworkChunkPublisherUpdate { position: (0, 0, 0).into(), radius: 12 }).unwrap();
-
-        // let this = self.clone();
-        // tokio::spawn(async move {
-        //     let fut = stream.take(1).for_each(|res| {
-        //         tracing::debug!("{res:?}");
-
-        //         let chunk = res.data;
-
-        //         let mut ser = chunk.serialize_network(&this.instance().block_states).unwrap();
-
-        //         // No biomes
-        //         let indices = Box::new([0u16; 4096]);
-        //         let storage = BiomeStorage {
-        //             indices, palette: vec![0]
-        //         };
-
-        //         let biome = Biomes {
-        //             fragments: vec![BiomeEncoding::Paletted(storage)],
-        //             heightmap: Box::new([[0; 16]; 16])
-        //         };
-
-        //         biome.serialize(&mut ser).unwrap();
-
-        //         // Border block count
-        //         ser.write_u8(0).unwrap();
-
-        //         // let de = SubChunk::deserialize_network(ser).unwrap();
-        //         // tracing::debug!("{de:?}");
-
-        //         this.send(LevelChunk {
-        //             request_mode: SubChunkRequestMode::Limitless,
-        //             coordinates: (0, 0).into(),
-        //             dimension: Dimension::Overworld,
-        //             sub_chunk_count: 24,
-        //             highest_sub_chunk: 16,
-        //             blob_hashes: None,
-        //             raw_payload: ser
-        //         }).unwrap();
-
-        //         // this.send(SubChunkResponse {
-        //         //     cache_enabled: false,
-        //         //     dimension: Dimension::Overworld,
-        //         //     entries: vec![SubChunkEntry {
-        //         //         blob_hash: 0,
-        //         //         payload: ser,
-        //         //         offset: (0, 0, 0).into(),
-        //         //         result: SubChunkResult::Success,
-        //         //         heightmap: Box::new([[0; 16]; 16]),
-        //         //         heightmap_type: HeightmapType::None
-        //         //     }],
-        //         //     position: (0, 0, 0).into()
-        //         // }).unwrap();
-
-        //         future::ready(())
-        //     });
-
-        //     fut.await;
-        // });
-
-        // self.viewer.update_radius(12);
-
         // Command execution could take several ticks, await the result in a separate task
         // to avoid blocking the request handler.
         tokio::spawn(async move {
@@ -376,7 +142,13 @@ impl BedrockClient {
             };
             tracing::Span::current().record("command", request.command);
 
-            let receiver = match self.commands.execute(Arc::clone(&self), request.command.to_owned()).await {
+            let event = CommandExecute { client: Arc::clone(&self), command: request.command.to_owned(), cancelled: false };
+            let event = self.instance().events().dispatch(event).await;
+            if event.cancelled {
+                return;
+            }
+
+            let receiver = match self.commands.execute(Arc::clone(&self), event.command).await {
                 Ok(r) => r,
                 Err(e) => {
                     tracing::error!("{e:#}");
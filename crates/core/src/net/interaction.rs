@@ -1,28 +1,38 @@
 use std::sync::atomic::Ordering;
+use std::sync::Arc;
 
-use proto::bedrock::{ABILITY_FLYING, AbilityData, AbilityLayer, AbilityType, ContainerClose, ContainerOpen, ContainerType, GameMode, Interact, InteractAction, INVENTORY_WINDOW_ID, MovePlayer, PlayerAction, PlayerActionType, UpdateAbilities, ABILITY_FLAG_END};
+use proto::bedrock::{ABILITY_FLYING, AbilityData, AbilityLayer, AbilityType, ContainerClose, ContainerOpen, ContainerType, GameMode, Interact, InteractAction, INVENTORY_WINDOW_ID, PlayerAction, PlayerActionType, UpdateAbilities, ABILITY_FLAG_END};
 use util::{RVec, Deserialize};
 
+use crate::event::{InventoryClose, InventoryOpen, PlayerToggleFlight};
+
 use super::BedrockClient;
 
 impl BedrockClient {
     /// Handles an [`Interact`] packet.
-    pub fn handle_interaction(&self, packet: RVec) -> anyhow::Result<()> {
+    pub async fn handle_interaction(self: &Arc<Self>, packet: RVec) -> anyhow::Result<()> {
         let request = Interact::deserialize(packet.as_ref())?;
       
-        if request.action == InteractAction::OpenInventory && !self.player()?.is_inventory_open.fetch_or(true, Ordering::Relaxed) {
-            self.send(ContainerOpen {
-                window_id: INVENTORY_WINDOW_ID,
-                container_type: ContainerType::Inventory,
-                ..Default::default()
-            })?;
+        if request.action == InteractAction::OpenInventory && !self.player()?.is_inventory_open.load(Ordering::Relaxed) {
+            let event = InventoryOpen { client: Arc::clone(self), cancelled: false };
+            if self.instance().events().dispatch(event).await.cancelled {
+                return Ok(());
+            }
+
+            if !self.player()?.is_inventory_open.fetch_or(true, Ordering::Relaxed) {
+                self.send(ContainerOpen {
+                    window_id: INVENTORY_WINDOW_ID,
+                    container_type: ContainerType::Inventory,
+                    ..Default::default()
+                })?;
+            }
         }
 
         Ok(())
     }
 
     /// Handles a [`ContainerClose`] packet.
-    pub fn handle_container_close(&self, packet: RVec) -> anyhow::Result<()> {
+    pub async fn handle_container_close(self: &Arc<Self>, packet: RVec) -> anyhow::Result<()> {
         let request = ContainerClose::deserialize(packet.as_ref())?;
         if request.window_id == INVENTORY_WINDOW_ID {
             self.player()?.is_inventory_open.store(false, Ordering::Relaxed);
@@ -32,31 +42,33 @@ impl BedrockClient {
                 window_id: INVENTORY_WINDOW_ID,
                 ..Default::default()
             })?;
+
+            self.instance().events().dispatch(InventoryClose { client: Arc::clone(self) }).await;
         }
 
         Ok(())
     }
 
-    /// Handles a [`MovePlayer`] packet.
-    pub fn handle_move_player(&self, packet: RVec) -> anyhow::Result<()> {
-        let _request = MovePlayer::deserialize(packet.as_ref())?;
+    /// Handles a [`PlayerAction`] packet.
+    pub async fn handle_player_action(self: &Arc<Self>, packet: RVec) -> anyhow::Result<()> {
+        let request = PlayerAction::deserialize(packet.as_ref())?;
 
-        Ok(())
-        // self.replicator.move_player(self.xuid(), &request).await?;
+        let flying = match request.action {
+            PlayerActionType::StartFlying => true,
+            PlayerActionType::StopFlying => false,
+            _ => return Ok(())
+        };
 
-        // request.mode = MovementMode::Normal;
+        let event = PlayerToggleFlight { client: Arc::clone(self), flying, cancelled: false };
+        if self.instance().events().dispatch(event).await.cancelled {
+            // The client has already changed its flight state, revert it.
+            return if flying { self.action_stop_flying(request) } else { self.action_start_flying(request) };
+        }
 
-        // self.broadcast(request)
-    }
-    
-    /// Handles a [`PlayerAction`] packet.
-    pub fn handle_player_action(&self, packet: RVec) -> anyhow::Result<()> {
-        let request = PlayerAction::deserialize(packet.as_ref())?;
-        
-        match request.action {
-            PlayerActionType::StartFlying => self.action_start_flying(request),
-            PlayerActionType::StopFlying => self.action_stop_flying(request),
-            _ => Ok(())
+        if flying {
+            self.action_start_flying(request)
+        } else {
+            self.action_stop_flying(request)
         }
     }
 
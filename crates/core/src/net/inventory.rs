@@ -0,0 +1,236 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use proto::bedrock::{
+    DisconnectReason, GameMode, InventoryContent, InventorySlot, InventoryTransaction, ItemInstance, MobEquipment, TransactionAction,
+    TransactionSourceType, TransactionType, WindowId,
+};
+use util::{Deserialize, RVec, Vector};
+
+use crate::item::{same_stack, Inventory};
+
+use super::BedrockClient;
+
+impl BedrockClient {
+    /// Handles an [`InventoryTransaction`] packet.
+    pub async fn handle_inventory_transaction(self: &Arc<Self>, packet: RVec) -> anyhow::Result<()> {
+        let transaction = InventoryTransaction::deserialize(packet.as_ref())?;
+
+        match transaction.transaction_type {
+            TransactionType::Normal => self.apply_transaction(&transaction.actions),
+            TransactionType::Use { action_type, block_position, face, hotbar_slot, held_item, .. } => {
+                let position = Vector::from([block_position.x, block_position.y as i32, block_position.z]);
+                self.handle_use_item(action_type, position, face, hotbar_slot, &held_item).await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Handles a [`MobEquipment`] packet, sent when the player selects another hotbar slot.
+    pub fn handle_mob_equipment(&self, packet: RVec) -> anyhow::Result<()> {
+        let equipment = MobEquipment::deserialize(packet.as_ref())?;
+
+        // Verify that runtime ID matches player's runtime ID.
+        // Clients only send this packet to modify themselves.
+        if equipment.runtime_id != self.runtime_id()? {
+            // Illegal packet modifications
+            return self.kick_with_reason("Illegal packets", DisconnectReason::BadPacket);
+        }
+
+        if equipment.window_id == WindowId::Inventory {
+            let player = self.player()?;
+            let accepted = {
+                let mut inventory = player.inventory.lock();
+                let slot = equipment.hotbar_slot as usize;
+
+                inventory.hotbar.get(slot).is_some_and(|item| item.network_id == equipment.new_item.network_id)
+                    && inventory.set_selected(slot)
+            };
+
+            if !accepted {
+                tracing::debug!("Client selected an item it does not have, resending inventory");
+                return self.send_inventory();
+            }
+        }
+
+        self.broadcast_others(equipment)
+    }
+
+    /// Sends the full inventory of the player to the client.
+    pub fn send_inventory(&self) -> anyhow::Result<()> {
+        let inventory: Inventory = self.player()?.inventory.lock().clone();
+
+        self.send(InventoryContent { window_id: WindowId::Inventory, items: &inventory.window_items() })?;
+        self.send(InventoryContent { window_id: WindowId::Armor, items: inventory.armor.items() })?;
+        self.send(InventoryContent { window_id: WindowId::OffHand, items: inventory.offhand.items() })?;
+        self.send(InventorySlot { window_id: WindowId::Ui, slot: 0, item: inventory.cursor.items()[0].clone() })
+    }
+
+    /// Replaces the item in a slot of the player's inventory and sends the change to the client.
+    ///
+    /// Returns the item that was previously in the slot.
+    pub fn set_item(&self, window_id: WindowId, slot: u32, mut item: ItemInstance<'static>) -> anyhow::Result<ItemInstance<'static>> {
+        let player = self.player()?;
+        let old = {
+            let mut inventory = player.inventory.lock();
+            inventory.assign_stack_id(&mut item);
+            inventory
+                .slot_mut(window_id, slot)
+                .map(|current| std::mem::replace(current, item.clone()))
+                .ok_or_else(|| anyhow::anyhow!("Slot {slot} does not exist in window {window_id:?}"))?
+        };
+
+        self.send(InventorySlot { window_id, slot, item })?;
+        Ok(old)
+    }
+
+    /// Adds an item to the first free slot of the player's inventory.
+    ///
+    /// Returns `false` if the inventory is full.
+    pub fn give_item(&self, item: ItemInstance<'static>) -> anyhow::Result<bool> {
+        let added = {
+            let mut inventory = self.player()?.inventory.lock();
+            inventory
+                .add(item)
+                .and_then(|(window_id, slot)| Some((window_id, slot, inventory.slot(window_id, slot)?.clone())))
+        };
+        let Some((window_id, slot, item)) = added else {
+            return Ok(false);
+        };
+
+        self.send(InventorySlot { window_id, slot, item })?;
+        Ok(true)
+    }
+
+    /// Removes every item from the player's inventory.
+    pub fn clear_inventory(&self) -> anyhow::Result<()> {
+        self.player()?.inventory.lock().clear();
+        self.send_inventory()
+    }
+
+    /// Validates a transaction against the server-side inventory and applies it.
+    ///
+    /// Rejected transactions cause the inventory to be resent, undoing the client's changes.
+    fn apply_transaction(&self, actions: &[TransactionAction]) -> anyhow::Result<()> {
+        let player = self.player()?;
+        let creative = player.gamemode() == GameMode::Creative;
+
+        let accepted = {
+            let mut inventory = player.inventory.lock();
+            let changes = validate_transaction(&inventory, actions, creative);
+            let accepted = changes.is_some();
+
+            for (window_id, slot, mut item) in changes.into_iter().flatten() {
+                inventory.assign_stack_id(&mut item);
+                if let Some(current) = inventory.slot_mut(window_id, slot) {
+                    *current = item;
+                }
+            }
+
+            accepted
+        };
+
+        if !accepted {
+            tracing::debug!("Rejected inventory transaction from {}", self.name().unwrap_or("<unknown>"));
+            return self.send_inventory();
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks whether a transaction is consistent with the current inventory.
+///
+/// Every changed slot must contain the item that the client expects it to contain and no items
+/// can be created out of thin air unless they were taken from the creative inventory.
+/// Every slot can only be changed once, because each action is validated against the current inventory.
+///
+/// Returns the new contents of every changed slot if the transaction is valid.
+pub fn validate_transaction(
+    inventory: &Inventory,
+    actions: &[TransactionAction],
+    creative: bool,
+) -> Option<Vec<(WindowId, u32, ItemInstance<'static>)>> {
+    let mut balance: HashMap<(i32, u32), i64> = HashMap::new();
+    let mut slots = HashSet::new();
+    let mut templates = Vec::new();
+    let mut from_creative = false;
+
+    for action in actions {
+        match action.source_type {
+            TransactionSourceType::Container { inventory_id } => {
+                // Multiple actions on the same slot would each take the same items out of it.
+                if !slots.insert((inventory_id, action.slot)) {
+                    return None;
+                }
+
+                let current = inventory.slot(inventory_id, action.slot)?;
+                if !same_stack(current, &action.old_item) {
+                    return None;
+                }
+
+                if current.network_id != 0 {
+                    templates.push(current);
+                }
+            }
+            TransactionSourceType::Creative => {
+                if !creative {
+                    return None;
+                }
+                from_creative = true;
+            }
+            // Items dropped into the world.
+            TransactionSourceType::WorldInteraction { .. } => (),
+            _ => return None,
+        }
+
+        if action.old_item.network_id != 0 {
+            *balance.entry((action.old_item.network_id, action.old_item.metadata)).or_default() += action.old_item.count as i64;
+        }
+        if action.new_item.network_id != 0 {
+            *balance.entry((action.new_item.network_id, action.new_item.metadata)).or_default() -= action.new_item.count as i64;
+        }
+    }
+
+    if !from_creative && balance.values().any(|count| *count != 0) {
+        return None;
+    }
+
+    let mut changes = Vec::with_capacity(actions.len());
+    for action in actions {
+        let TransactionSourceType::Container { inventory_id } = action.source_type else { continue };
+
+        let new = &action.new_item;
+        let item = if new.network_id == 0 {
+            ItemInstance::air()
+        } else if let Some(template) = templates.iter().find(|t| t.network_id == new.network_id && t.metadata == new.metadata) {
+            // Prefer the server's copy of the item so that the client cannot modify item data.
+            ItemInstance { count: new.count, ..(*template).clone() }
+        } else if from_creative {
+            detach(new)
+        } else {
+            return None;
+        };
+
+        changes.push((inventory_id, action.slot, item));
+    }
+
+    Some(changes)
+}
+
+/// Copies an item sent by the client so that it can be stored in an inventory.
+///
+/// Lists of blocks that the item can be placed on or destroy are not copied.
+fn detach(item: &ItemInstance) -> ItemInstance<'static> {
+    ItemInstance {
+        network_id: item.network_id,
+        count: item.count,
+        metadata: item.metadata,
+        stack_id: item.stack_id,
+        block_runtime_id: item.block_runtime_id,
+        nbt: item.nbt.clone(),
+        can_place_on: vec![],
+        can_destroy: vec![],
+        blocking_tick: item.blocking_tick,
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::BTreeMap;
+
+use proto::bedrock::{
+    ContainerSlotType, GameMode, ItemInstance, ItemStack, ItemStackRequest, ItemStackResponse, StackRequest, StackRequestAction,
+    StackRequestSlotInfo, StackResponse, StackResponseContainer, StackResponseSlot, StackResponseStatus, WindowId,
+};
+use util::{Deserialize, RVec};
+
+use crate::item::Inventory;
+
+use super::BedrockClient;
+
+/// Maximum amount of items that fit in a single slot.
+const MAX_STACK_SIZE: u16 = 64;
+
+impl BedrockClient {
+    /// Handles an [`ItemStackRequest`] packet.
+    pub fn handle_item_stack_request(&self, packet: RVec) -> anyhow::Result<()> {
+        let request = ItemStackRequest::deserialize(packet.as_ref())?;
+
+        let responses = request
+            .requests
+            .iter()
+            .map(|request| self.apply_stack_request(request))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        self.send(ItemStackResponse { responses: &responses })
+    }
+
+    /// Handles an item stack request that was embedded in a [`PlayerAuthInput`](proto::bedrock::PlayerAuthInput) packet.
+    pub(super) fn handle_embedded_stack_request(&self, request: &StackRequest) -> anyhow::Result<()> {
+        let response = self.apply_stack_request(request)?;
+        self.send(ItemStackResponse { responses: &[response] })
+    }
+
+    /// Applies all actions of a request to the player's inventory.
+    ///
+    /// Either every action is applied or the inventory is left untouched and the request is rejected.
+    fn apply_stack_request(&self, request: &StackRequest) -> anyhow::Result<StackResponse<'static>> {
+        let player = self.player()?;
+        let instance = self.instance();
+
+        let creative = player.gamemode() == GameMode::Creative;
+        let changed = StackTransaction::run(&mut player.inventory.lock(), request, creative, &instance.creative_items.stacks);
+
+        let Some(changed) = changed else {
+            tracing::debug!("Rejected item stack request {} from {}", request.request_id, self.name().unwrap_or("<unknown>"));
+            return Ok(StackResponse { status: StackResponseStatus::Error, request_id: request.request_id, containers: Vec::new() });
+        };
+
+        let mut containers: Vec<StackResponseContainer> = Vec::new();
+        for ((container, slot), item) in changed {
+            let slot = StackResponseSlot {
+                slot,
+                hotbar_slot: slot,
+                count: item.count as u8,
+                stack_network_id: item.stack_id.unwrap_or(0),
+                custom_name: "",
+                filtered_custom_name: "",
+                durability_correction: 0,
+            };
+
+            match containers.last_mut() {
+                Some(last) if last.container == container => last.slots.push(slot),
+                _ => containers.push(StackResponseContainer { container, slots: vec![slot] }),
+            }
+        }
+
+        Ok(StackResponse { status: StackResponseStatus::Ok, request_id: request.request_id, containers })
+    }
+}
+
+/// Working copy of an inventory that stack request actions are applied to.
+struct StackTransaction<'a> {
+    inventory: Inventory,
+    /// Item that was created by a crafting action and has not been taken yet.
+    created: ItemInstance<'static>,
+    creative: bool,
+    creative_items: &'a [ItemStack],
+    /// New contents of every slot that was modified.
+    changed: BTreeMap<(ContainerSlotType, u8), ItemInstance<'static>>,
+}
+
+impl<'a> StackTransaction<'a> {
+    /// Applies all actions of a request to a copy of the inventory and replaces the inventory with it if every action succeeded.
+    ///
+    /// Returns the new contents of the changed slots, or `None` if the request was rejected.
+    fn run(
+        inventory: &mut Inventory,
+        request: &StackRequest,
+        creative: bool,
+        creative_items: &'a [ItemStack],
+    ) -> Option<BTreeMap<(ContainerSlotType, u8), ItemInstance<'static>>> {
+        let mut transaction =
+            StackTransaction { inventory: inventory.clone(), created: ItemInstance::air(), creative, creative_items, changed: BTreeMap::new() };
+
+        if !request.actions.iter().all(|action| transaction.apply(action)) {
+            return None;
+        }
+
+        *inventory = transaction.inventory;
+        Some(transaction.changed)
+    }
+
+    /// Applies a single action, returning whether it is valid.
+    fn apply(&mut self, action: &StackRequestAction) -> bool {
+        match action {
+            StackRequestAction::Take { count, source, destination }
+            | StackRequestAction::Place { count, source, destination }
+            | StackRequestAction::PlaceInContainer { count, source, destination }
+            | StackRequestAction::TakeOutContainer { count, source, destination } => self.transfer(*count, source, destination),
+            StackRequestAction::Swap { source, destination } => self.swap(source, destination),
+            // Item entities do not exist yet, so dropped items disappear.
+            StackRequestAction::Drop { count, source, .. } | StackRequestAction::Consume { count, source } => {
+                self.remove(*count, source)
+            }
+            StackRequestAction::Destroy { count, source } => self.creative && self.remove(*count, source),
+            StackRequestAction::CraftCreative { creative_network_id } => self.craft_creative(*creative_network_id),
+            // The results are already known from the previous actions.
+            StackRequestAction::CraftResultsDeprecated { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Moves `count` items from one slot to another, merging them with the destination if it contains the same item.
+    fn transfer(&mut self, count: u8, source: &StackRequestSlotInfo, destination: &StackRequestSlotInfo) -> bool {
+        let count = count as u16;
+        let Some(from) = self.get(source).cloned() else { return false };
+        let Some(to) = self.get(destination).cloned() else { return false };
+
+        if count == 0 || from.network_id == 0 || from.count < count {
+            return false;
+        }
+
+        let moved = if to.network_id == 0 {
+            let mut moved = ItemInstance { count, ..from.clone() };
+            if count != from.count {
+                // The stack was split, the new stack needs its own ID.
+                moved.stack_id = None;
+                self.inventory.assign_stack_id(&mut moved);
+            }
+            moved
+        } else if to.network_id == from.network_id && to.metadata == from.metadata && to.count + count <= MAX_STACK_SIZE {
+            ItemInstance { count: to.count + count, ..to }
+        } else {
+            return false;
+        };
+
+        let remaining = if from.count == count { ItemInstance::air() } else { ItemInstance { count: from.count - count, ..from } };
+
+        self.set(source, remaining) && self.set(destination, moved)
+    }
+
+    /// Swaps the items in two slots.
+    fn swap(&mut self, source: &StackRequestSlotInfo, destination: &StackRequestSlotInfo) -> bool {
+        let Some(from) = self.get(source).cloned() else { return false };
+        let Some(to) = self.get(destination).cloned() else { return false };
+
+        self.set(source, to) && self.set(destination, from)
+    }
+
+    /// Removes `count` items from a slot.
+    fn remove(&mut self, count: u8, source: &StackRequestSlotInfo) -> bool {
+        let count = count as u16;
+        let Some(item) = self.get(source).cloned() else { return false };
+
+        if count == 0 || item.network_id == 0 || item.count < count {
+            return false;
+        }
+
+        let remaining = if item.count == count { ItemInstance::air() } else { ItemInstance { count: item.count - count, ..item } };
+        self.set(source, remaining)
+    }
+
+    /// Puts a full stack of an item from the creative inventory in the created output slot.
+    fn craft_creative(&mut self, network_id: u32) -> bool {
+        if !self.creative {
+            return false;
+        }
+
+        // Creative network IDs start at 1.
+        let Some(stack) = (network_id as usize).checked_sub(1).and_then(|index| self.creative_items.get(index)) else {
+            return false;
+        };
+
+        let mut item = ItemInstance {
+            network_id: stack.item_type.network_id,
+            count: MAX_STACK_SIZE,
+            metadata: stack.item_type.meta,
+            stack_id: None,
+            block_runtime_id: stack.block_runtime_id,
+            nbt: stack.nbt_data.clone(),
+            can_place_on: vec![],
+            can_destroy: vec![],
+            blocking_tick: 0,
+        };
+        self.inventory.assign_stack_id(&mut item);
+        self.created = item;
+
+        true
+    }
+
+    /// Returns the item in a slot.
+    ///
+    /// This returns `None` if the slot does not exist or the client's stack network ID does not match the item.
+    fn get(&self, info: &StackRequestSlotInfo) -> Option<&ItemInstance<'static>> {
+        let container = ContainerSlotType::try_from(info.container_id).ok()?;
+        let item = if container == ContainerSlotType::CreatedOutput {
+            &self.created
+        } else {
+            let (window_id, slot) = window_slot(container, info.slot)?;
+            self.inventory.slot(window_id, slot)?
+        };
+
+        // Negative IDs refer to items that were created by an earlier request that the client has not received a response for yet.
+        if info.stack_network_id > 0 && item.stack_id != Some(info.stack_network_id) {
+            return None;
+        }
+
+        Some(item)
+    }
+
+    /// Replaces the item in a slot, returning whether the slot exists.
+    fn set(&mut self, info: &StackRequestSlotInfo, item: ItemInstance<'static>) -> bool {
+        let Ok(container) = ContainerSlotType::try_from(info.container_id) else { return false };
+        if container == ContainerSlotType::CreatedOutput {
+            self.created = item;
+            return true;
+        }
+
+        let Some((window_id, slot)) = window_slot(container, info.slot) else { return false };
+        let Some(current) = self.inventory.slot_mut(window_id, slot) else { return false };
+
+        *current = item.clone();
+        self.changed.insert((container, info.slot), item);
+
+        true
+    }
+}
+
+/// Maps a container used in item stack requests to a window of the player's inventory.
+///
+/// This returns `None` for containers that are not part of the player's inventory.
+const fn window_slot(container: ContainerSlotType, slot: u8) -> Option<(WindowId, u32)> {
+    let window_id = match container {
+        ContainerSlotType::Hotbar | ContainerSlotType::Inventory | ContainerSlotType::CombinedHotbarAndInventory => WindowId::Inventory,
+        ContainerSlotType::Armor => WindowId::Armor,
+        ContainerSlotType::Offhand => WindowId::OffHand,
+        ContainerSlotType::Cursor => WindowId::Ui,
+        _ => return None,
+    };
+
+    Some((window_id, slot as u32))
+}
@@ -1,23 +1,24 @@
 use level::PaletteEntry;
 use proto::bedrock::{
     BiomeDefinitionList, BroadcastIntent, CacheStatus, ChatRestrictionLevel, ChunkRadiusReply, ChunkRadiusRequest, ClientToServerHandshake,
-    ConnectedPacket, CreativeContent, Difficulty, DisconnectReason, EditorWorldType, ExperimentData, GameMode, GameRule, HeightmapType,
-    InventoryTransaction, ItemInstance, LevelChunk, Login, NetworkChunkPublisherUpdate, NetworkSettings, PermissionLevel, PlayStatus,
-    PlayerMovementSettings, PlayerMovementType, PropertyData, RequestNetworkSettings, ResourcePackClientResponse, ResourcePackStack,
-    ResourcePacksInfo, ServerToClientHandshake, SetLocalPlayerAsInitialized, SpawnBiomeType, StartGame, Status, SubChunkEntry, SubChunkRequestMode,
+    ConnectedPacket, CreativeContent, DisconnectReason, EditorWorldType, ExperimentData, GameMode, GameRule, HeightmapType,
+    InventoryTransaction, ItemInstance, LevelChunk, Login, NetworkSettings, PermissionLevel, PlayStatus,
+    PlayerMovementSettings, PlayerMovementType, PropertyData, RequestNetworkSettings, ResourcePackClientResponse, ResourcePackStatus,
+    ServerToClientHandshake, SetLocalPlayerAsInitialized, SpawnBiomeType, StartGame, Status, SubChunkEntry, SubChunkRequestMode,
     SubChunkResponse, SubChunkResult, TextData, TextMessage, TransactionAction, TransactionSourceType, TransactionType, UpdateBlock,
     UpdateBlockFlags, ViolationWarning, WindowId, WorldGenerator, CLIENT_VERSION_STRING, PROTOCOL_VERSION,
 };
 use proto::crypto::Encryptor;
-use proto::types::Dimension;
 use std::collections::HashMap;
 use std::sync::atomic::Ordering;
+use std::sync::Arc;
 
 use util::{BlockPosition, Deserialize, RVec, Vector};
 
+use crate::event::{ChunkRadiusChange, PlayerJoin, PlayerLogin};
 use crate::net::PlayerData;
 
-use super::BedrockClient;
+use super::{BedrockClient, PacketEncryptor};
 
 impl BedrockClient {
     /// Handles a [`CacheStatus`] packet.
@@ -60,6 +61,7 @@ impl BedrockClient {
     ///
     /// All connected sessions are notified of the new player
     /// and the new player gets a list of all current players.
+    /// See [`spawn`](Self::spawn).
     #[tracing::instrument(
         skip_all,
         name = "BedrockUser::handle_local_initialized",
@@ -67,17 +69,12 @@ impl BedrockClient {
             username = %self.name().unwrap_or("<unknown>")
         )
     )]
-    pub fn handle_local_initialized(&self, packet: RVec) -> anyhow::Result<()> {
+    pub async fn handle_local_initialized(self: &Arc<Self>, packet: RVec) -> anyhow::Result<()> {
         let _request = SetLocalPlayerAsInitialized::deserialize(packet.as_ref())?;
         self.expected.store(u32::MAX, Ordering::SeqCst);
 
         tracing::debug!("Player fully initialised");
 
-        self.send(NetworkChunkPublisherUpdate { position: (0, 0, 0).into(), radius: 12 })?;
-
-        let res = self.viewer.load_offsets((0, 0, 0).into(), &[(0, 0, 0).into()], Dimension::Overworld)?;
-        tracing::debug!("res {res:?}");
-
         // self.send(LevelChunk {
         //     blob_hashes: None,
         //     coordinates: (0, 0).into(),
@@ -95,52 +92,26 @@ impl BedrockClient {
         //     block_runtime_id: 13256
         // })?;
 
-        // Add player to other's player lists
-
         // Tell rest of server that this client has joined...
         {
-            // let identity_data = self.get_identity_data()?;
-            // let _user_data = self.get_user_data()?;
-
-            // self.broadcast_others(PlayerListAdd {
-            //     entries: &[PlayerListAddEntry {
-            //         uuid: identity_data.uuid,
-            //         entity_id: self.player.read().runtime_id as i64,
-            //         username: &identity_data.display_name,
-            //         xuid: identity_data.xuid,
-            //         device_os: user_data.build_platform,
-            //         skin: self.player.read().skin.as_ref().ok_or_else(
-            //             || {
-            //                 error!(
-            //                     NotInitialized,
-            //                     "Skin data has not been initialised"
-            //                 )
-            //             },
-            //         )?,
-            //         host: false,
-            //     }],
-            // })?;
-
-            // let level_chunk = self.level_manager.request_biomes(Vector::from([0, 0]), Dimension::Overworld)?;
-            // dbg!(level_chunk);
+            self.send_inventory()?;
+            self.spawn()?;
 
             tracing::info!("{} has joined the server", self.name()?);
-            self.broadcast(TextMessage {
-                data: TextData::Translation {
-                    parameters: vec![&format!("§e{}", self.name()?)],
-                    message: "multiplayer.player.joined", // message: &format!("§e{} has joined the server.", identity_data.display_name),
-                },
-                needs_translation: true,
-                xuid: 0,
-                platform_chat_id: "",
-            })?;
-            
-            let stack = &self.instance().creative_items.stacks[1];
-            tracing::debug!("stack: {stack:?}");
-        }   
-
-        // ...then tell the client about all the other players.
-        // TODO
+
+            let event = self.instance().events().dispatch(PlayerJoin { client: Arc::clone(self), announce: true }).await;
+            if event.announce {
+                self.broadcast(TextMessage {
+                    data: TextData::Translation {
+                        parameters: vec![&format!("§e{}", self.name()?)],
+                        message: "multiplayer.player.joined", // message: &format!("§e{} has joined the server.", identity_data.display_name),
+                    },
+                    needs_translation: true,
+                    xuid: 0,
+                    platform_chat_id: "",
+                })?;
+            }
+        }
 
         Ok(())
     }
@@ -153,46 +124,67 @@ impl BedrockClient {
             username = %self.name().unwrap_or("<unknown>")
         )
     )]
-    pub fn handle_chunk_radius_request(&self, packet: RVec) -> anyhow::Result<()> {
+    pub async fn handle_chunk_radius_request(self: &Arc<Self>, packet: RVec) -> anyhow::Result<()> {
         let request = ChunkRadiusRequest::deserialize(packet.as_ref())?;
 
         // FIXME: Use render distance configured with builder instead of SERVER_CONFIG global.
-        let allowed_radius = std::cmp::min(self.instance().config().max_render_distance() as i32, request.radius);
+        let max_radius = self.instance().config().max_render_distance() as i32;
+        let event = ChunkRadiusChange {
+            client: Arc::clone(self),
+            requested: request.radius,
+            radius: std::cmp::min(max_radius, request.radius),
+        };
+        let event = self.instance().events().dispatch(event).await;
+
+        let allowed_radius = event.radius.min(max_radius).max(0);
         tracing::debug!("Chunk radius set to {allowed_radius} ({} was requested)", request.radius);
 
         self.send(ChunkRadiusReply { allowed_radius })?;
 
-        self.viewer.update_radius(allowed_radius as u16);
-
-        // self.player().viewer.set_radius(allowed_radius);
-
-        Ok(())
+        self.update_view_radius(allowed_radius as u16)
     }
 
     /// Handles a [`ResourcePackClientResponse`] packet.
     pub fn handle_resource_client_response(&self, packet: RVec) -> anyhow::Result<()> {
         self.expected.store(u32::MAX, Ordering::SeqCst);
 
-        let _request = ResourcePackClientResponse::deserialize(packet.as_ref())?;
-        tracing::debug!("Received resource pack client response");
+        let response = ResourcePackClientResponse::deserialize(packet.as_ref())?;
+        tracing::debug!("Received resource pack client response: {:?}", response.status);
 
-        // TODO: Implement resource packs.
+        match response.status {
+            ResourcePackStatus::SendPacks => self.send_pack_data_info(&response.pack_ids),
+            ResourcePackStatus::HaveAllPacks => self.send_pack_stack(),
+            ResourcePackStatus::Refused if self.instance().config().resource_packs().required => {
+                self.kick_with_reason("You must accept the resource packs to join this server", DisconnectReason::ResourcePackProblem)
+            }
+            ResourcePackStatus::Refused => self.send_pack_stack(),
+            ResourcePackStatus::Completed => self.start_game(),
+            ResourcePackStatus::None => Ok(()),
+        }
+    }
 
+    /// Sends the [`StartGame`] packet and the data that the client requires before it can spawn.
+    pub(super) fn start_game(&self) -> anyhow::Result<()> {
+        let player = self.player()?;
+        let rotation = player.rotation();
+        let instance = self.instance();
+        let level = instance.level();
+        let spawn = level.spawn();
         let start_game = StartGame {
-            entity_id: 1,
-            runtime_id: 1,
-            game_mode: self.player()?.gamemode(),
-            position: Vector::from([0.0, 6.0, 0.0]),
-            rotation: Vector::from([0.0, 0.0]),
+            entity_id: player.runtime_id() as i64,
+            runtime_id: player.runtime_id(),
+            game_mode: player.gamemode(),
+            position: player.position(),
+            rotation: Vector::from([rotation.x, rotation.y]),
             world_seed: 0,
             spawn_biome_type: SpawnBiomeType::Default,
             custom_biome_name: "plains",
-            dimension: Dimension::Overworld,
+            dimension: player.dimension(),
             generator: WorldGenerator::Infinite,
             world_game_mode: GameMode::Survival,
             hardcore: false,
-            difficulty: Difficulty::Normal,
-            world_spawn: BlockPosition::new(0, 60, 0),
+            difficulty: level.difficulty(),
+            world_spawn: BlockPosition::new(spawn.x, spawn.y as u32, spawn.z),
             achievements_disabled: true,
             editor_world_type: EditorWorldType::NotEditor,
             created_in_editor: false,
@@ -206,7 +198,7 @@ impl BedrockClient {
             xbox_broadcast_intent: BroadcastIntent::Public,
             platform_broadcast_intent: BroadcastIntent::Public,
             enable_commands: true,
-            texture_packs_required: true,
+            texture_packs_required: self.instance().config().resource_packs().required,
             // FIXME: Reimplement with new level interface.
             // game_rules: &self.level.get_game_rules(),
             game_rules: &[GameRule::ShowCoordinates(true)],
@@ -237,9 +229,10 @@ impl BedrockClient {
             movement_settings: PlayerMovementSettings {
                 movement_type: PlayerMovementType::ServerAuthoritative,
                 rewind_history_size: 0,
-                server_authoritative_breaking: true,
+                // Block breaking is validated when the client sends an inventory transaction.
+                server_authoritative_breaking: false,
             },
-            time: 0,
+            time: level.time(),
             enchantment_seed: 0,
             // block_properties: &[BlockEntry {
             //     name: "minecraft:bedrock".to_owned(),
@@ -248,7 +241,8 @@ impl BedrockClient {
             block_properties: &[],
             item_properties: &[],
             property_data: PropertyData {},
-            server_authoritative_inventory: false,
+            // Inventory changes are validated using item stack requests.
+            server_authoritative_inventory: true,
             game_version: CLIENT_VERSION_STRING,
             // property_data: nbt::Value::Compound(HashMap::new()),
             server_block_state_checksum: 0,
@@ -279,6 +273,9 @@ impl BedrockClient {
 
     /// Handles a [`ClientToServerHandshake packet`]. After receiving this packet, the server will now
     /// use encryption when communicating with this client.
+    ///
+    /// The client has proven that it owns its identity key at this point, so this is where a handoff
+    /// from a trusted peer is claimed.
     #[tracing::instrument(
         skip_all,
         name = "BedrockUser::handle_client_to_server_handshake",
@@ -286,40 +283,45 @@ impl BedrockClient {
             username = %self.name().unwrap_or("<unknown>")
         )
     )]
-    pub fn handle_client_to_server_handshake(&self, packet: RVec) -> anyhow::Result<()> {
+    pub async fn handle_client_to_server_handshake(self: &Arc<Self>, packet: RVec) -> anyhow::Result<()> {
         self.expected.store(CacheStatus::ID, Ordering::SeqCst);
 
         ClientToServerHandshake::deserialize(packet.as_ref())?;
         tracing::debug!("Encryption handshake successful");
 
-        let response = PlayStatus { status: Status::LoginSuccess };
-        self.send(response)?;
+        // Players transferred by a trusted peer have already been authenticated by that peer.
+        let identity = self.identity()?;
+        let handoff = self.instance().handoffs.take(&identity.name, &identity.public_key);
+        let authenticated = identity.authenticated && identity.xuid != 0;
+
+        if let Some(handoff) = handoff {
+            tracing::debug!("{} was handed off by a trusted peer", identity.name);
+            // Cannot fail because the handshake can only be performed once.
+            let _: Result<(), _> = self.handoff.set(handoff);
+        } else if self.instance().config().online_mode() && !authenticated {
+            // The handoff was claimed by another connection or expired since the client logged in.
+            tracing::warn!("{} attempted to join without being signed into Xbox Live", identity.name);
+            return self.kick_with_reason("You must be signed into Xbox Live to join this server", DisconnectReason::NotAuthenticated);
+        }
 
-        // TODO: Implement resource packs
-        // FIXME: Sometimes these two packets might be sent in different ticks instead of being batched together.
-        // This will result in the client sending a resource pack response which we do not want.
-        let pack_info = ResourcePacksInfo {
-            required: false,
-            scripting_enabled: false,
-            forcing_server_packs: false,
-            has_addons: false,
-            behavior_info: &[],
-            resource_info: &[],
+        let event = PlayerLogin {
+            client: Arc::clone(self),
+            name: self.name()?.to_owned(),
+            xuid: self.xuid()?,
+            kick_message: String::from("You are not allowed to join this server"),
+            cancelled: false,
         };
-        self.send(pack_info)?;
+        let event = self.instance().events().dispatch(event).await;
+        if event.cancelled {
+            tracing::info!("Login of {} was cancelled", event.name);
+            return self.kick_with_reason(&event.kick_message, DisconnectReason::NotAllowed);
+        }
 
-        let pack_stack = ResourcePackStack {
-            forced_to_accept: false,
-            resource_packs: &[],
-            behavior_packs: &[],
-            game_version: CLIENT_VERSION_STRING,
-            experiments: &[],
-            experiments_previously_toggled: false,
-            includes_editor_packs: false,
-        };
-        self.send(pack_stack)?;
+        let response = PlayStatus { status: Status::LoginSuccess };
+        self.send(response)?;
 
-        Ok(())
+        // The pack stack is sent once the client has downloaded all packs.
+        self.send_packs_info()
     }
 
     /// Handles a [`Login`] packet.
@@ -331,7 +333,7 @@ impl BedrockClient {
             address = %self.raknet.address
         )
     )]
-    pub async fn handle_login(&self, packet: RVec) -> anyhow::Result<()> {
+    pub async fn handle_login(self: &Arc<Self>, packet: RVec) -> anyhow::Result<()> {
         self.expected.store(ClientToServerHandshake::ID, Ordering::SeqCst);
 
         let Ok(request) = Login::deserialize(packet.as_ref()) else {
@@ -343,6 +345,17 @@ impl BedrockClient {
 
         tracing::Span::current().record("username", &request.identity.name);
 
+        // Players transferred by a trusted peer have already been authenticated by that peer.
+        // The handoff is only claimed once the client has proven that it owns the identity key
+        // in the encryption handshake.
+        let handed_off = self.instance().handoffs.contains(&request.identity.name, &request.identity.public_key);
+
+        let online_mode = self.instance().config().online_mode();
+        if online_mode && !handed_off && (!request.identity.authenticated || request.identity.xuid == 0) {
+            tracing::warn!("{} attempted to join without being signed into Xbox Live", request.identity.name);
+            return self.kick_with_reason("You must be signed into Xbox Live to join this server", DisconnectReason::NotAuthenticated);
+        }
+
         let Ok((encryptor, jwt)) = Encryptor::new(&request.identity.public_key) else {
             self.kick_with_reason("Encryption failed", DisconnectReason::BadPacket)?;
             anyhow::bail!("Failed to enable encryption");
@@ -362,14 +375,18 @@ impl BedrockClient {
         self.raknet.flush().await?;
 
         self.send(ServerToClientHandshake { jwt: &jwt })?;
-        if self.encryptor.set(encryptor).is_err() {
+
+        let encryptor = Arc::new(encryptor);
+        let encoder = PacketEncryptor { encryptor: Arc::clone(&encryptor), mtu: self.raknet.mtu };
+        if self.raknet.encoder.set(Box::new(encoder)).is_err() || self.encryptor.set(encryptor).is_err() {
             // Client sent a second login packet?
             // Something is wrong, disconnect the client.
             tracing::warn!("Client unexpectedly sent a second login packet");
             return self.kick_with_reason("Unexpected login", DisconnectReason::UnexpectedPacket);
         }
 
-        if self.player.set(PlayerData::new(request.skin)).is_err() {
+        let runtime_id = self.instance().clients().next_runtime_id();
+        if self.player.set(PlayerData::new(request.skin, runtime_id)).is_err() {
             anyhow::bail!("Player data was already set");
         };
 
@@ -7,8 +7,19 @@ use ::util::glob_export;
 
 glob_export!(level);
 glob_export!(client);
+glob_export!(subclient);
+glob_export!(transfer);
 glob_export!(clients);
 glob_export!(login);
+glob_export!(packs);
 glob_export!(interaction);
+glob_export!(movement);
+glob_export!(players);
+glob_export!(view);
+glob_export!(cache);
+glob_export!(blocks);
+glob_export!(inventory);
+glob_export!(item_stack);
 glob_export!(handlers);
 glob_export!(forwardable);
+glob_export!(firewall);
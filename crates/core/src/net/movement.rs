@@ -0,0 +1,296 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use proto::bedrock::{DisconnectReason, MovePlayer, MovementMode, PlayerAuthInput, TeleportCause};
+use util::{Deserialize, RVec, Vector};
+
+use crate::event::PlayerMove;
+
+use super::BedrockClient;
+
+/// Maximum horizontal distance in blocks that a player is allowed to move in a single tick without sprinting.
+/// This is slightly more than the speed of a player flying in creative mode.
+const WALK_DISTANCE: f32 = 0.7;
+/// Maximum horizontal distance in blocks that a sprinting player is allowed to move in a single tick.
+/// This is slightly more than the speed of a player sprint-flying in creative mode.
+const SPRINT_DISTANCE: f32 = 1.25;
+/// Maximum distance in blocks that a player gliding with an elytra is allowed to move in a single tick.
+const GLIDE_DISTANCE: f32 = 4.0;
+/// Maximum distance in blocks that a player is allowed to move upwards in a single tick without gliding.
+/// Jumping players rise by about 0.42 blocks in the first tick.
+const RISE_DISTANCE: f32 = 0.6;
+/// Maximum distance in blocks that a player is allowed to fall in a single tick.
+/// Falling players reach a terminal velocity of about 3.92 blocks per tick.
+const FALL_DISTANCE: f32 = 4.0;
+/// Maximum amount of ticks worth of movement that a player can save up.
+/// This allows movement packets that arrive in bursts, without letting clients skip ticks to move further in one step.
+const MAX_BUDGET: f32 = 20.0;
+/// Duration of a single game tick.
+const TICK_DURATION: Duration = Duration::from_millis(50);
+/// Maximum distance in blocks between the client's position and the position it was moved back to
+/// for the correction to count as acknowledged, unless the client reports that it handled the correction.
+const TELEPORT_TOLERANCE: f32 = 0.1;
+
+/// Server-side state used to validate the movement of a player.
+pub struct MovementState {
+    /// Time at which the last movement was received.
+    last_update: Instant,
+    /// Amount of ticks worth of movement that the player is still allowed to perform.
+    ///
+    /// This is refilled using the time measured by the server, so clients cannot move further by
+    /// reporting a higher tick.
+    budget: f32,
+    /// Whether the player is gliding with an elytra.
+    gliding: bool,
+    /// Position that the player was moved back to, if the client has not acknowledged it yet.
+    teleport_target: Option<Vector<f32, 3>>,
+}
+
+impl MovementState {
+    /// Creates the movement state of a player that has just spawned.
+    pub fn new() -> MovementState {
+        MovementState { last_update: Instant::now(), budget: MAX_BUDGET, gliding: false, teleport_target: None }
+    }
+
+    /// Refills the budget with the time that has passed since the last movement.
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_update).as_secs_f32() / TICK_DURATION.as_secs_f32();
+
+        self.budget = (self.budget + elapsed).min(MAX_BUDGET);
+        self.last_update = now;
+    }
+
+    /// Validates a movement starting at the last accepted position of the player.
+    fn check(&mut self, input: &MovementInput, old_position: &Vector<f32, 3>) -> Verdict {
+        self.refill();
+        if let Some(gliding) = input.gliding {
+            self.gliding = gliding;
+        }
+
+        let (max_horizontal, max_rise, max_fall) = input.limits(self.gliding);
+        let position = &input.position;
+
+        // Inputs sent before the client has processed the correction are based on the old position
+        // and would immediately trigger another correction.
+        if let Some(target) = &self.teleport_target {
+            let (dx, dy, dz) = (position.x - target.x, position.y - target.y, position.z - target.z);
+            let distance = dx.hypot(dy).hypot(dz);
+
+            // The client might already have moved during the tick in which it processed the correction.
+            // That movement is validated below, because the target is the last accepted position.
+            let reached = distance <= TELEPORT_TOLERANCE || (input.handled_teleport && distance <= max_horizontal);
+            if !reached {
+                return Verdict::Ignore;
+            }
+            self.teleport_target = None;
+        }
+
+        let ticks = |dx: f32, dy: f32, dz: f32| {
+            let vertical = if dy > 0.0 { dy / max_rise } else { -dy / max_fall };
+            (dx.hypot(dz) / max_horizontal).max(vertical)
+        };
+
+        // Amount of ticks that the player needs to travel this distance.
+        let required = ticks(position.x - old_position.x, position.y - old_position.y, position.z - old_position.z);
+        // The velocity reported by the client is not allowed to exceed the limits either.
+        let velocity = input.delta.as_ref().map_or(0.0, |delta| ticks(delta.x, delta.y, delta.z));
+
+        if required > self.budget || velocity > 1.0 {
+            return Verdict::Reject;
+        }
+
+        self.budget -= required;
+        Verdict::Accept
+    }
+}
+
+impl Default for MovementState {
+    fn default() -> MovementState {
+        MovementState::new()
+    }
+}
+
+/// Outcome of validating a movement.
+enum Verdict {
+    /// The movement is valid.
+    Accept,
+    /// The movement is too far, the player has to be moved back.
+    Reject,
+    /// The client has not acknowledged the last correction yet.
+    Ignore,
+}
+
+/// Movement input of a single packet.
+struct MovementInput {
+    position: Vector<f32, 3>,
+    rotation: Vector<f32, 3>,
+    /// Velocity reported by the client.
+    delta: Option<Vector<f32, 3>>,
+    on_ground: bool,
+    sprinting: bool,
+    /// Whether the client started (`Some(true)`) or stopped (`Some(false)`) gliding.
+    gliding: Option<bool>,
+    tick: u64,
+    /// Whether the client reports to have processed the last position correction in this tick.
+    handled_teleport: bool,
+}
+
+impl MovementInput {
+    /// Returns the maximum horizontal and vertical distances that the player is allowed to move in a single tick,
+    /// where the vertical distance is split into the upward and downward distance.
+    const fn limits(&self, gliding: bool) -> (f32, f32, f32) {
+        if gliding {
+            (GLIDE_DISTANCE, GLIDE_DISTANCE, GLIDE_DISTANCE)
+        } else if self.sprinting {
+            (SPRINT_DISTANCE, RISE_DISTANCE, FALL_DISTANCE)
+        } else {
+            (WALK_DISTANCE, RISE_DISTANCE, FALL_DISTANCE)
+        }
+    }
+}
+
+impl BedrockClient {
+    /// Handles a [`PlayerAuthInput`] packet. These are sent every tick and are used
+    /// for server authoritative player movement.
+    pub async fn handle_auth_input(self: &Arc<Self>, packet: RVec) -> anyhow::Result<()> {
+        let input = PlayerAuthInput::deserialize(packet.as_ref())?;
+        if let Some(request) = &input.item_stack {
+            self.handle_embedded_stack_request(request)?;
+        }
+
+        let flags = input.input_data;
+        let gliding = if flags.start_gliding() {
+            Some(true)
+        } else if flags.stop_gliding() {
+            Some(false)
+        } else {
+            None
+        };
+
+        // A vertical collision is reported both when landing and when hitting a ceiling.
+        // Only the former happens while the player is not moving upwards.
+        let on_ground = flags.vertical_collision() && input.delta.y <= 0.0;
+
+        self.process_movement(MovementInput {
+            position: input.position,
+            rotation: Vector::from([input.pitch, input.yaw, input.head_yaw]),
+            delta: Some(input.delta),
+            on_ground,
+            sprinting: flags.sprinting(),
+            gliding,
+            tick: input.tick,
+            handled_teleport: flags.handled_teleport(),
+        })
+        .await
+    }
+
+    /// Handles a [`MovePlayer`] packet.
+    ///
+    /// The server uses server authoritative movement so clients should not normally send this.
+    /// It is still validated in the same way as [`PlayerAuthInput`] in case they do.
+    pub async fn handle_move_player(self: &Arc<Self>, packet: RVec) -> anyhow::Result<()> {
+        let request = MovePlayer::deserialize(packet.as_ref())?;
+        if request.runtime_id != self.runtime_id()? {
+            tracing::warn!("Client attempted to move another entity. Kicking them for forbidden modifications");
+            return self.kick_with_reason("Illegal packet modifications detected", DisconnectReason::BadPacket);
+        }
+
+        // This packet does not contain any input flags, so the stricter walking limits are used.
+        self.process_movement(MovementInput {
+            position: request.translation,
+            rotation: Vector::from([request.pitch, request.yaw, request.head_yaw]),
+            delta: None,
+            on_ground: request.on_ground,
+            sprinting: false,
+            gliding: None,
+            tick: request.tick,
+            handled_teleport: false,
+        })
+        .await
+    }
+
+    /// Validates a movement of the player and either accepts it or moves the player back to their last
+    /// accepted position.
+    ///
+    /// Accepted movements are broadcast to all other players.
+    async fn process_movement(self: &Arc<Self>, input: MovementInput) -> anyhow::Result<()> {
+        let player = self.player()?;
+
+        let mut components = input.position.as_ref().iter().chain(input.rotation.as_ref()).chain(input.delta.iter().flat_map(AsRef::as_ref));
+        if !components.all(|c| c.is_finite()) {
+            tracing::warn!("Client sent non-finite movement. Kicking them for forbidden modifications");
+            return self.kick_with_reason("Illegal packet modifications detected", DisconnectReason::BadPacket);
+        }
+
+        let old_position = player.position();
+        let verdict = player.movement.lock().check(&input, &old_position);
+
+        let MovementInput { position, rotation, on_ground, tick, .. } = input;
+        match verdict {
+            Verdict::Accept => (),
+            Verdict::Ignore => return Ok(()),
+            Verdict::Reject => {
+                tracing::debug!("{} moved too far to {position:?}, correcting position", self.name().unwrap_or("<unknown>"));
+                return self.correct_position(old_position, rotation, tick);
+            }
+        }
+
+        let old_rotation = player.rotation();
+        if position == old_position && rotation == old_rotation {
+            return Ok(());
+        }
+
+        let event = PlayerMove {
+            client: Arc::clone(self),
+            from: old_position.clone(),
+            to: position.clone(),
+            rotation: rotation.clone(),
+            cancelled: false,
+        };
+        if self.instance().events().dispatch(event).await.cancelled {
+            return self.correct_position(old_position, old_rotation, tick);
+        }
+
+        *player.position.write() = position.clone();
+        *player.rotation.write() = rotation.clone();
+
+        self.update_view_position(Vector::from([position.x, position.z]))?;
+
+        self.broadcast_others(MovePlayer {
+            runtime_id: player.runtime_id(),
+            translation: position,
+            pitch: rotation.x,
+            yaw: rotation.y,
+            head_yaw: rotation.z,
+            mode: MovementMode::Normal,
+            on_ground,
+            ridden_runtime_id: 0,
+            teleport_cause: TeleportCause::Unknown,
+            teleport_source_type: 0,
+            tick,
+        })
+    }
+
+    /// Moves the player back to the given position.
+    ///
+    /// Movement sent by the client is ignored until it has moved to the given position.
+    fn correct_position(&self, position: Vector<f32, 3>, rotation: Vector<f32, 3>, tick: u64) -> anyhow::Result<()> {
+        let player = self.player()?;
+        player.movement.lock().teleport_target = Some(position.clone());
+
+        self.send(MovePlayer {
+            runtime_id: player.runtime_id(),
+            translation: position,
+            pitch: rotation.x,
+            yaw: rotation.y,
+            head_yaw: rotation.z,
+            mode: MovementMode::Reset,
+            on_ground: false,
+            ridden_runtime_id: 0,
+            teleport_cause: TeleportCause::Unknown,
+            teleport_source_type: 0,
+            tick,
+        })
+    }
+}
@@ -0,0 +1,106 @@
+use proto::bedrock::{
+    DisconnectReason, ResourcePack, ResourcePackChunkData, ResourcePackChunkRequest, ResourcePackDataInfo, ResourcePackStack,
+    ResourcePackStackEntry, ResourcePackType, ResourcePacksInfo, CLIENT_VERSION_STRING,
+};
+use util::{Deserialize, RVec};
+
+use crate::pack::PACK_CHUNK_SIZE;
+
+use super::BedrockClient;
+
+impl BedrockClient {
+    /// Handles a [`ResourcePackChunkRequest`] packet by sending the requested chunk.
+    pub fn handle_resource_pack_chunk_request(&self, packet: RVec) -> anyhow::Result<()> {
+        let request = ResourcePackChunkRequest::deserialize(packet.as_ref())?;
+
+        let instance = self.instance();
+        let Some(pack) = instance.resource_packs.get(request.pack_id) else {
+            tracing::warn!("Client requested unknown resource pack {}", request.pack_id);
+            return self.kick_with_reason("Requested unknown resource pack", DisconnectReason::ResourcePackProblem);
+        };
+
+        let Some(data) = pack.chunk(request.chunk_index) else {
+            tracing::warn!("Client requested nonexistent chunk {} of resource pack {}", request.chunk_index, pack.id);
+            return self.kick_with_reason("Requested invalid resource pack chunk", DisconnectReason::ResourcePackProblem);
+        };
+
+        self.send(ResourcePackChunkData {
+            pack_id: &pack.id,
+            chunk_index: request.chunk_index,
+            offset: request.chunk_index as u64 * PACK_CHUNK_SIZE as u64,
+            data,
+        })
+    }
+
+    /// Advertises the resource packs of the server.
+    pub(super) fn send_packs_info(&self) -> anyhow::Result<()> {
+        let instance = self.instance();
+        let resource_info = instance
+            .resource_packs
+            .iter()
+            .map(|pack| ResourcePack {
+                uuid: pack.uuid.clone(),
+                version: pack.version.clone(),
+                size: pack.size(),
+                content_key: String::new(),
+                subpack_name: String::new(),
+                content_identity: String::new(),
+                has_scripts: false,
+                addon_pack: false,
+                rtx_enabled: false,
+            })
+            .collect::<Vec<_>>();
+
+        self.send(ResourcePacksInfo {
+            required: instance.config().resource_packs().required,
+            scripting_enabled: false,
+            forcing_server_packs: false,
+            has_addons: false,
+            behavior_info: &[],
+            resource_info: &resource_info,
+        })
+    }
+
+    /// Sends the order in which the client should apply the resource packs.
+    pub(super) fn send_pack_stack(&self) -> anyhow::Result<()> {
+        let instance = self.instance();
+        let resource_packs = instance
+            .resource_packs
+            .iter()
+            .map(|pack| ResourcePackStackEntry { pack_id: &pack.uuid, pack_version: &pack.version, subpack_name: "" })
+            .collect::<Vec<_>>();
+
+        self.send(ResourcePackStack {
+            forced_to_accept: instance.config().resource_packs().required,
+            resource_packs: &resource_packs,
+            behavior_packs: &[],
+            game_version: CLIENT_VERSION_STRING,
+            experiments: &[],
+            experiments_previously_toggled: false,
+            includes_editor_packs: false,
+        })
+    }
+
+    /// Tells the client how the requested packs are split into chunks, after which it will start requesting them.
+    pub(super) fn send_pack_data_info(&self, pack_ids: &[&str]) -> anyhow::Result<()> {
+        let instance = self.instance();
+        for id in pack_ids {
+            let Some(pack) = instance.resource_packs.get(id) else {
+                tracing::warn!("Client requested unknown resource pack {id}");
+                return self.kick_with_reason("Requested unknown resource pack", DisconnectReason::ResourcePackProblem);
+            };
+
+            self.send(ResourcePackDataInfo {
+                pack_id: &pack.id,
+                chunk_size: PACK_CHUNK_SIZE as u32,
+                chunk_count: pack.chunk_count(),
+                size: pack.size(),
+                hash: &pack.hash,
+                premium: false,
+                pack_type: ResourcePackType::Resources,
+            })?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+
+use proto::bedrock::{
+    AbilityData, AbilityLayer, AbilityType, AddPlayer, PlayerListAdd, PlayerListAddEntry, PlayerListRemove, RemoveEntity, Skin,
+    TextData, TextMessage, ABILITY_FLAG_END,
+};
+use util::Vector;
+
+use crate::event::PlayerQuit;
+
+use super::BedrockClient;
+
+impl BedrockClient {
+    /// Spawns this player for all other players and spawns all other players for this client.
+    ///
+    /// Every player is also added to each other's player list, including their own.
+    pub(crate) fn spawn(self: &Arc<Self>) -> anyhow::Result<()> {
+        let player = self.player()?;
+        let instance = self.instance();
+        let clients = instance.clients();
+
+        // The player is added to the list before the snapshot is taken, so that players spawning at the same time
+        // either appear in each other's snapshot or receive each other's spawn broadcast.
+        let guard = clients.lock_spawning();
+        player.is_spawned.store(true, Ordering::Relaxed);
+        let others = clients.spawned().into_iter().filter(|other| !Arc::ptr_eq(other, self)).collect::<Vec<_>>();
+
+        {
+            let skin = player.skin.read();
+            self.broadcast(PlayerListAdd { entries: &[self.player_list_entry(&skin)?] })?;
+        }
+
+        if !others.is_empty() {
+            // Hold on to the skin locks instead of cloning the skins, they can be quite large.
+            let skins = others
+                .iter()
+                .map(|other| Ok(other.player()?.skin.read()))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            let entries = others
+                .iter()
+                .zip(&skins)
+                .map(|(other, skin)| other.player_list_entry(skin))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            self.send(PlayerListAdd { entries: &entries })?;
+
+            for other in &others {
+                self.send(other.add_player_packet()?)?;
+            }
+        }
+
+        self.broadcast_others(self.add_player_packet()?)?;
+        drop(guard);
+
+        Ok(())
+    }
+
+    /// Removes this player from the world and the player lists of all other players.
+    ///
+    /// The [`PlayerQuit`] event is dispatched before the player is removed, so that listeners can still
+    /// interact with the player. This does nothing if the player was never spawned or has already left.
+    pub(crate) async fn despawn(self: &Arc<Self>) -> anyhow::Result<()> {
+        let Ok(player) = self.player() else {
+            return Ok(())
+        };
+
+        if !player.is_spawned.load(Ordering::Relaxed) || player.is_quitting.swap(true, Ordering::Relaxed) {
+            return Ok(())
+        }
+
+        let event = self.instance().events().dispatch(PlayerQuit { client: Arc::clone(self), announce: true }).await;
+
+        let instance = self.instance();
+        let guard = instance.clients().lock_spawning();
+        if !player.is_spawned.swap(false, Ordering::Relaxed) {
+            return Ok(())
+        }
+
+        self.broadcast_others(RemoveEntity { entity_id: player.runtime_id() as i64 })?;
+        self.broadcast_others(PlayerListRemove { entries: &[*self.uuid()?] })?;
+        drop(guard);
+
+        tracing::info!("{} has left the server", self.name()?);
+
+        if event.announce {
+            self.broadcast_others(TextMessage {
+                data: TextData::Translation {
+                    parameters: vec![&format!("§e{}", self.name()?)],
+                    message: "multiplayer.player.left",
+                },
+                needs_translation: true,
+                xuid: 0,
+                platform_chat_id: "",
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Creates the player list entry of this player.
+    fn player_list_entry<'a>(&'a self, skin: &'a Skin) -> anyhow::Result<PlayerListAddEntry<'a>> {
+        let identity = self.identity()?;
+
+        Ok(PlayerListAddEntry {
+            uuid: identity.uuid,
+            entity_id: self.runtime_id()? as i64,
+            username: &identity.name,
+            xuid: self.xuid()?,
+            device_os: self.client_info()?.build_platform,
+            skin,
+            host: false,
+        })
+    }
+
+    /// Creates the packet that spawns this player for other clients.
+    fn add_player_packet(&self) -> anyhow::Result<AddPlayer<'_>> {
+        let identity = self.identity()?;
+        let client_info = self.client_info()?;
+        let player = self.player()?;
+
+        Ok(AddPlayer {
+            uuid: identity.uuid,
+            username: &identity.name,
+            runtime_id: player.runtime_id(),
+            position: player.position(),
+            velocity: Vector::from([0.0; 3]),
+            rotation: player.rotation(),
+            game_mode: player.gamemode(),
+            held_item: player.inventory.lock().held().clone(),
+            ability_data: AbilityData {
+                unique_id: player.runtime_id(),
+                permission_level: player.permission_level(),
+                command_permission_level: player.command_permission_level(),
+                layers: vec![AbilityLayer {
+                    ability_type: AbilityType::Base,
+                    abilities: ABILITY_FLAG_END - 1,
+                    values: 0,
+                    fly_speed: 0.05,
+                    walk_speed: 0.1,
+                }],
+            },
+            links: &[],
+            device_id: &client_info.device_id,
+            device_os: client_info.build_platform,
+        })
+    }
+}
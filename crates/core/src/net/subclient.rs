@@ -0,0 +1,157 @@
+use std::sync::{Arc, Weak};
+
+use proto::bedrock::{ConnectedPacket, Disconnect, DisconnectReason, Header, PlayStatus, Status, SubClientLogin};
+use util::{Deserialize, RVec};
+
+use crate::event::PlayerLogin;
+
+use super::{BedrockClient, PlayerData};
+
+/// Maximum amount of split screen players that can share a connection with the primary player.
+const MAX_SUBCLIENTS: usize = 3;
+
+impl BedrockClient {
+    /// Returns the split screen player with the given subclient ID that shares this client's connection.
+    pub fn subclient(&self, subclient_id: u8) -> Option<Arc<BedrockClient>> {
+        self.subclients.read().iter().find(|subclient| subclient.subclient_id == subclient_id).cloned()
+    }
+
+    /// Whether this client is a split screen player that shares the connection of another client.
+    #[inline]
+    pub const fn is_subclient(&self) -> bool {
+        self.primary.is_some()
+    }
+
+    /// Routes a packet sent by a split screen player to the client of that player.
+    pub(super) async fn handle_subclient_packet(self: &Arc<Self>, header: Header, packet: RVec) -> anyhow::Result<()> {
+        if header.id == SubClientLogin::ID {
+            return self.handle_subclient_login(header.sender_subclient, packet).await;
+        }
+
+        let Some(subclient) = self.subclient(header.sender_subclient) else {
+            anyhow::bail!("Received packet {:#04x} from unknown subclient {}", header.id, header.sender_subclient);
+        };
+
+        // Sent when a split screen player leaves, the connection itself stays open.
+        if header.id == Disconnect::ID {
+            return subclient.leave();
+        }
+
+        subclient.handle_packet(header, packet).await
+    }
+
+    /// Handles a [`SubClientLogin`] packet by creating a new player that shares this client's connection.
+    ///
+    /// Split screen players go through the same events as the primary player. [`PlayerLogin`] is dispatched here
+    /// and [`PlayerJoin`](crate::event::PlayerJoin) once the player has sent its `SetLocalPlayerAsInitialized`
+    /// packet.
+    #[tracing::instrument(
+        skip_all,
+        name = "BedrockUser::handle_subclient_login",
+        fields(
+            address = %self.raknet.address,
+            subclient = subclient_id
+        )
+    )]
+    async fn handle_subclient_login(self: &Arc<Self>, subclient_id: u8, packet: RVec) -> anyhow::Result<()> {
+        if !self.initialized() {
+            anyhow::bail!("Split screen player attempted to join before the connection was initialised");
+        }
+
+        let request = SubClientLogin::deserialize(packet.as_ref())?.request;
+        tracing::Span::current().record("username", &request.identity.name);
+
+        // Kicking a player under an ID that is already in use would remove the existing player instead.
+        if self.subclient(subclient_id).is_some() {
+            tracing::warn!("Received login for split screen player {subclient_id}, which has already logged in");
+            return Ok(());
+        }
+
+        let subclient = BedrockClient::new_subclient(self, subclient_id);
+        let online_mode = self.instance().config().online_mode();
+        if online_mode && (!request.identity.authenticated || request.identity.xuid == 0) {
+            tracing::warn!("{} attempted to join without being signed into Xbox Live", request.identity.name);
+            return subclient.kick_with_reason("You must be signed into Xbox Live to join this server", DisconnectReason::NotAuthenticated);
+        }
+
+        // The slot is reserved before the login event is dispatched, so that plugins never see a login that is
+        // rejected afterwards. Kicking the player releases the slot again.
+        {
+            let mut subclients = self.subclients.write();
+            if subclients.iter().any(|other| other.subclient_id == subclient_id) {
+                drop(subclients);
+                tracing::warn!("Received login for split screen player {subclient_id}, which has already logged in");
+                return Ok(());
+            }
+
+            if subclients.len() >= MAX_SUBCLIENTS {
+                drop(subclients);
+                return subclient.kick_with_reason("Too many split screen players", DisconnectReason::ServerFull);
+            }
+
+            subclients.push(Arc::clone(&subclient));
+        }
+
+        let runtime_id = self.instance().clients().next_runtime_id();
+        let name = request.identity.name.clone();
+        if subclient.identity.set(request.identity).is_err()
+            || subclient.client_info.set(request.client_info).is_err()
+            || subclient.player.set(PlayerData::new(request.skin, runtime_id)).is_err()
+        {
+            subclient.leave()?;
+            anyhow::bail!("Subclient was already initialised");
+        }
+
+        let xuid = match subclient.xuid() {
+            Ok(xuid) => xuid,
+            Err(err) => {
+                subclient.leave()?;
+                return Err(err);
+            }
+        };
+
+        let event = PlayerLogin {
+            client: Arc::clone(&subclient),
+            name: name.clone(),
+            xuid,
+            kick_message: String::from("You are not allowed to join this server"),
+            cancelled: false,
+        };
+        let event = self.instance().events().dispatch(event).await;
+        if event.cancelled {
+            tracing::info!("Login of split screen player {name} was cancelled");
+            return subclient.kick_with_reason(&event.kick_message, DisconnectReason::NotAllowed);
+        }
+
+        tracing::info!("{name} joined as split screen player of {}", self.name()?);
+
+        subclient.send(PlayStatus { status: Status::LoginSuccess })?;
+        subclient.start_game()
+    }
+
+    /// Removes a split screen player from its connection and from the world.
+    pub(super) fn leave(&self) -> anyhow::Result<()> {
+        self.shutdown_token.cancel();
+
+        let Some(primary) = self.primary.as_ref().and_then(Weak::upgrade) else {
+            // The connection has closed, which already despawns all of its players.
+            return Ok(());
+        };
+
+        let mut subclients = primary.subclients.write();
+        let index = subclients.iter().position(|subclient| subclient.subclient_id == self.subclient_id);
+        let removed = index.map(|index| subclients.remove(index));
+        drop(subclients);
+
+        // Split screen players can be kicked outside of an asynchronous context.
+        if let Some(subclient) = removed {
+            tokio::spawn(async move {
+                if let Err(err) = subclient.despawn().await {
+                    tracing::error!("Failed to despawn split screen player: {err:#}");
+                }
+            });
+        }
+
+        Ok(())
+    }
+}
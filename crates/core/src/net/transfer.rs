@@ -0,0 +1,42 @@
+use std::net::SocketAddr;
+
+use proto::bedrock::Transfer;
+
+use crate::handoff::Handoff;
+
+use super::BedrockClient;
+
+impl BedrockClient {
+    /// Transfers the player to another server.
+    ///
+    /// See [`transfer_with_data`](Self::transfer_with_data).
+    #[inline]
+    pub async fn transfer_to(&self, addr: SocketAddr) -> anyhow::Result<()> {
+        self.transfer_with_data(addr, Vec::new()).await
+    }
+
+    /// Transfers the player to another server and hands the given data over to it.
+    ///
+    /// If the server is a trusted peer and a handoff secret has been configured, a signed handoff token
+    /// is sent to the server before the client is told to connect. The server can then skip the
+    /// authentication checks and read the data using [`handoff`](Self::handoff).
+    /// For any other server, the data is discarded.
+    ///
+    /// The player is only transferred once the server has accepted the token. This function returns an
+    /// error without transferring the player if the token could not be delivered.
+    pub async fn transfer_with_data(&self, addr: SocketAddr, data: Vec<(String, String)>) -> anyhow::Result<()> {
+        let instance = self.instance();
+        let network = instance.config().network();
+
+        if network.secret.is_some() && network.is_trusted(&addr) {
+            let identity = self.identity()?;
+            let handoff = Handoff::new(self.xuid()?, identity.name.clone(), identity.public_key.clone(), data);
+            instance.send_handoff(addr, &handoff).await?;
+        } else if !data.is_empty() {
+            tracing::warn!("Discarding handoff data of {}: {addr} is not a trusted peer", self.name()?);
+        }
+
+        tracing::info!("Transferring {} to {addr}", self.name()?);
+        self.send(Transfer { addr: &addr.ip().to_string(), port: addr.port() })
+    }
+}
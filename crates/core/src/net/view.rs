@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use futures::StreamExt;
+use level::{BlockStates, SubChunk};
+use proto::bedrock::{LevelChunk, NetworkChunkPublisherUpdate, SubChunkRequestMode, UpdateBlock};
+use proto::types::Dimension;
+use raknet::{Reliability, SendConfig, SendPriority};
+use util::{BinaryWrite, RVec, Vector};
+
+use crate::level::net::{serialize_biomes_network, NetworkChunkExt};
+use crate::level::{subchunk_range, ViewUpdate};
+
+use super::{blob_hash, BedrockClient};
+
+/// Send configuration of chunk data.
+///
+/// Chunk data is large and not urgent, so it is sent with low priority to prevent it from delaying
+/// movement, combat and chat packets.
+///
+/// Packets that modify chunk data, such as block updates, must be sent with this configuration as well.
+/// Frames are only sent in order within a single priority queue, so an update sent with a higher priority
+/// could reach the client before the column that it applies to and be overwritten by it.
+pub(super) const CHUNK_SEND_CONFIG: SendConfig = SendConfig {
+    reliability: Reliability::ReliableOrdered,
+    priority: SendPriority::Low,
+};
+
+impl BedrockClient {
+    /// Updates the position of this client's viewer and sends any chunks that came into view.
+    pub(crate) fn update_view_position(self: &Arc<Self>, position: Vector<f32, 2>) -> anyhow::Result<()> {
+        let dimension = self.player()?.dimension();
+        match self.viewer.update_position(position, dimension) {
+            Some(update) => self.send_view(update),
+            None => Ok(()),
+        }
+    }
+
+    /// Updates the render distance of this client's viewer and sends any chunks that came into view.
+    pub(crate) fn update_view_radius(self: &Arc<Self>, radius: u16) -> anyhow::Result<()> {
+        let dimension = self.player()?.dimension();
+        match self.viewer.update_radius(radius, dimension) {
+            Some(update) => self.send_view(update),
+            None => Ok(()),
+        }
+    }
+
+    /// Sends all chunk columns in the view update to the client, nearest column first.
+    ///
+    /// Columns are loaded in the background and sent as soon as all columns closer to the viewer
+    /// have been sent. Every client has at most one of these tasks, the task of the previous update is
+    /// cancelled because its remaining columns are included in the new update.
+    fn send_view(self: &Arc<Self>, update: ViewUpdate) -> anyhow::Result<()> {
+        let ViewUpdate { center, radius, dimension, columns, mut stream } = update;
+
+        self.send(NetworkChunkPublisherUpdate {
+            position: Vector::from([center.x * 16, 0, center.y * 16]),
+            radius: radius as u32 * 16,
+        })?;
+
+        let this = Arc::clone(self);
+        let task = tokio::spawn(async move {
+            let height = subchunk_range(dimension).len();
+            let mut pending: HashMap<Vector<i32, 2>, Vec<(i32, SubChunk)>> = HashMap::new();
+            let mut ready: HashMap<Vector<i32, 2>, LevelChunk> = HashMap::new();
+            let mut next = 0;
+
+            while let Some(indexed) = stream.next().await {
+                if this.raknet.active.is_cancelled() {
+                    return;
+                }
+
+                let coord: Vector<i32, 3> = indexed.index.into();
+                let column = Vector::from([coord.x, coord.z]);
+
+                let subchunks = pending.entry(column.clone()).or_default();
+                subchunks.push((coord.y, indexed.data));
+                if subchunks.len() < height {
+                    continue;
+                }
+
+                // Column is complete.
+                if let Some(subchunks) = pending.remove(&column) {
+                    match this.serialize_column(column.clone(), dimension, subchunks) {
+                        Ok(packet) => {
+                            ready.insert(column, packet);
+                        }
+                        Err(err) => tracing::error!("Failed to serialize chunk column {column:?}: {err:#}"),
+                    }
+                }
+
+                while let Some(packet) = columns.get(next).and_then(|column| ready.remove(column)) {
+                    this.send_column(packet);
+                    next += 1;
+                }
+            }
+
+            // Send any remaining columns that were left behind by a column that failed to load.
+            for column in columns.iter().skip(next) {
+                if let Some(packet) = ready.remove(column) {
+                    this.send_column(packet);
+                }
+            }
+        });
+
+        let previous = self.view_task.lock().replace(task);
+        if let Some(previous) = previous {
+            previous.abort();
+        }
+
+        Ok(())
+    }
+
+    /// Sends a block update to the client.
+    ///
+    /// Block updates share the queue of the chunk data, so they cannot overtake the column that they apply to.
+    pub(crate) fn send_block_update(&self, packet: UpdateBlock) -> anyhow::Result<()> {
+        self.send_with_config(packet, CHUNK_SEND_CONFIG)
+    }
+
+    /// Sends a chunk column and marks it as loaded if it was sent successfully.
+    ///
+    /// Columns that failed to send are sent again on the next view update.
+    fn send_column(&self, packet: LevelChunk) {
+        let (column, dimension) = (packet.coordinates.clone(), packet.dimension);
+        match self.send_with_config(packet, CHUNK_SEND_CONFIG) {
+            Ok(()) => self.viewer.mark_loaded(column, dimension),
+            Err(err) => tracing::error!("Failed to send chunk column: {err:#}"),
+        }
+    }
+
+    /// Serialises a complete chunk column into a [`LevelChunk`] packet.
+    fn serialize_column(&self, column: Vector<i32, 2>, dimension: Dimension, mut subchunks: Vec<(i32, SubChunk)>) -> anyhow::Result<LevelChunk> {
+        subchunks.sort_unstable_by_key(|(y, _)| *y);
+
+        let instance = self.instance();
+        let states: &BlockStates = &instance.block_states;
+
+        let biomes = self.viewer.service.biomes(column.clone(), dimension)?;
+
+        let mut payload = RVec::alloc();
+        let blob_hashes = if self.supports_cache.load(Ordering::Relaxed) {
+            // Every sub chunk and the biomes are sent as separate blobs, only their hashes are part of the packet.
+            let mut blobs = Vec::with_capacity(subchunks.len() + 1);
+            for (_, subchunk) in &subchunks {
+                let mut blob = Vec::new();
+                subchunk.serialize_network_in(states, &mut blob)?;
+                blobs.push(blob);
+            }
+
+            let mut blob = Vec::new();
+            serialize_biomes_network(biomes.as_ref(), subchunks.len(), &mut blob)?;
+            blobs.push(blob);
+
+            let blobs = blobs.into_iter().map(|blob| (blob_hash(&blob), Arc::<[u8]>::from(blob))).collect::<Vec<_>>();
+            let hashes = blobs.iter().map(|(hash, _)| *hash).collect();
+            self.blobs.insert(blobs);
+
+            Some(hashes)
+        } else {
+            for (_, subchunk) in &subchunks {
+                subchunk.serialize_network_in(states, &mut payload)?;
+            }
+            serialize_biomes_network(biomes.as_ref(), subchunks.len(), &mut payload)?;
+
+            None
+        };
+
+        // No border blocks.
+        payload.write_u8(0)?;
+
+        Ok(LevelChunk {
+            coordinates: column,
+            dimension,
+            request_mode: SubChunkRequestMode::Legacy,
+            highest_sub_chunk: 0,
+            sub_chunk_count: subchunks.len() as u32,
+            blob_hashes,
+            raw_payload: payload,
+        })
+    }
+}
@@ -0,0 +1,156 @@
+//! Resource packs that are sent to clients when they join.
+
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use anyhow::Context;
+use sha2::{Digest, Sha256};
+
+/// Maximum size of a single chunk of pack data sent to the client.
+pub const PACK_CHUNK_SIZE: usize = 128 * 1024;
+
+/// A resource pack archive that has been loaded into memory.
+pub struct ResourcePack {
+    /// UUID of the pack from the header of its manifest.
+    pub uuid: String,
+    /// Version of the pack from the header of its manifest, formatted as `major.minor.patch`.
+    pub version: String,
+    /// Identifier used by the client to request the pack, formatted as `uuid_version`.
+    pub id: String,
+    /// SHA-256 hash of the archive.
+    pub hash: [u8; 32],
+    /// Contents of the archive.
+    data: Vec<u8>,
+}
+
+impl ResourcePack {
+    /// Reads a pack from the contents of a `.mcpack` or `.zip` archive.
+    pub fn from_archive(data: Vec<u8>) -> anyhow::Result<ResourcePack> {
+        let mut archive = zip::ZipArchive::new(Cursor::new(data.as_slice())).context("Pack is not a valid zip archive")?;
+
+        // Packs are often zipped together with their containing directory, use the manifest closest to the root.
+        let manifest_name = archive
+            .file_names()
+            .filter(|name| *name == "manifest.json" || name.ends_with("/manifest.json"))
+            .min_by_key(|name| name.len())
+            .map(String::from)
+            .ok_or_else(|| anyhow::anyhow!("Pack does not contain a manifest.json"))?;
+
+        let mut manifest = String::new();
+        archive.by_name(&manifest_name)?.read_to_string(&mut manifest)?;
+
+        let (uuid, version) = parse_manifest(&manifest)?;
+        let id = format!("{uuid}_{version}");
+        let hash = Sha256::digest(&data).into();
+
+        Ok(ResourcePack { uuid, version, id, hash, data })
+    }
+
+    /// Size of the archive in bytes.
+    #[inline]
+    pub fn size(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    /// Amount of chunks that the archive is split into when it is sent to a client.
+    #[inline]
+    pub fn chunk_count(&self) -> u32 {
+        self.data.len().div_ceil(PACK_CHUNK_SIZE) as u32
+    }
+
+    /// Returns the chunk with the given index, or `None` if it does not exist.
+    pub fn chunk(&self, index: u32) -> Option<&[u8]> {
+        let start = (index as usize).checked_mul(PACK_CHUNK_SIZE)?;
+        let end = usize::min(start + PACK_CHUNK_SIZE, self.data.len());
+
+        self.data.get(start..end).filter(|chunk| !chunk.is_empty())
+    }
+}
+
+/// Collection of all resource packs that the server uses.
+#[derive(Default)]
+pub struct ResourcePacks {
+    packs: Vec<ResourcePack>,
+}
+
+impl ResourcePacks {
+    /// Loads every `.mcpack` and `.zip` archive in the given directory.
+    ///
+    /// Archives that are not valid packs are skipped with a warning.
+    pub fn load<P: AsRef<Path>>(directory: P) -> anyhow::Result<ResourcePacks> {
+        let directory = directory.as_ref();
+        let entries = std::fs::read_dir(directory).with_context(|| format!("Unable to open resource pack directory {}", directory.display()))?;
+
+        let mut packs: Vec<ResourcePack> = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            let is_archive = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("mcpack") || ext.eq_ignore_ascii_case("zip"));
+
+            if !is_archive {
+                continue;
+            }
+
+            let pack = match std::fs::read(&path).map_err(anyhow::Error::from).and_then(ResourcePack::from_archive) {
+                Ok(pack) => pack,
+                Err(err) => {
+                    tracing::warn!("Skipping resource pack {}: {err:#}", path.display());
+                    continue;
+                }
+            };
+
+            if packs.iter().any(|other| other.uuid == pack.uuid) {
+                tracing::warn!("Skipping resource pack {}: another pack with UUID {} has already been loaded", path.display(), pack.uuid);
+                continue;
+            }
+
+            tracing::info!("Loaded resource pack {} (version {})", pack.uuid, pack.version);
+            packs.push(pack);
+        }
+
+        Ok(ResourcePacks { packs })
+    }
+
+    /// Returns the pack with the given `uuid_version` identifier.
+    ///
+    /// Clients sometimes only send the UUID, so this also matches on the UUID alone.
+    pub fn get(&self, id: &str) -> Option<&ResourcePack> {
+        self.packs.iter().find(|pack| pack.id == id || pack.uuid == id)
+    }
+
+    /// Returns an iterator over all loaded packs.
+    #[inline]
+    pub fn iter(&self) -> std::slice::Iter<'_, ResourcePack> {
+        self.packs.iter()
+    }
+
+    /// Whether no packs have been loaded.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.packs.is_empty()
+    }
+}
+
+/// Reads the UUID and version from the header of a pack manifest.
+fn parse_manifest(manifest: &str) -> anyhow::Result<(String, String)> {
+    let manifest: serde_json::Value = serde_json::from_str(manifest).context("Pack manifest is not valid JSON")?;
+    let header = manifest.get("header").ok_or_else(|| anyhow::anyhow!("Pack manifest has no header"))?;
+
+    let uuid = header
+        .get("uuid")
+        .and_then(|uuid| uuid.as_str())
+        .ok_or_else(|| anyhow::anyhow!("Pack manifest header has no UUID"))?
+        .to_owned();
+
+    let version = header
+        .get("version")
+        .and_then(|version| version.as_array())
+        .filter(|version| version.len() == 3)
+        .and_then(|version| version.iter().map(|part| part.as_u64().map(|part| part.to_string())).collect::<Option<Vec<_>>>())
+        .ok_or_else(|| anyhow::anyhow!("Pack manifest header has no valid version"))?
+        .join(".");
+
+    Ok((uuid, version))
+}
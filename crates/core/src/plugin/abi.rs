@@ -0,0 +1,238 @@
+use std::sync::Arc;
+
+use serde_json::{json, Map, Value};
+
+use crate::event::{BlockBreak, BlockPlace, ChatMessage, CommandExecute, Event, EventPriority, PlayerJoin, PlayerMove, PlayerQuit};
+use crate::net::BedrockClient;
+
+/// Version of the plugin interface implemented by this server.
+///
+/// Plugins built against a different version are refused.
+pub const API_VERSION: i32 = 1;
+
+/// Returned by host functions when the operation failed.
+pub const ERR_FAILED: i32 = -1;
+/// Returned by host functions when the plugin has not declared the required capability.
+pub const ERR_PERMISSION: i32 = -2;
+/// Returned by host functions when an argument is invalid.
+pub const ERR_INVALID: i32 = -3;
+
+/// Returned by `mirai_event` to cancel the event.
+pub const EVENT_CANCEL: i32 = 1;
+
+/// Events that plugins can subscribe to.
+///
+/// Every event is passed to the plugin as a JSON object containing the runtime ID of the player in `player`
+/// and their name in `name`, followed by the fields listed below. Fields marked as modifiable can be
+/// changed with `event_update`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    /// [`PlayerJoin`], with a modifiable `announce` boolean.
+    Join,
+    /// [`PlayerQuit`], with a modifiable `announce` boolean.
+    Quit,
+    /// [`ChatMessage`], with a modifiable `message` string.
+    Chat,
+    /// [`CommandExecute`], with a modifiable `command` string.
+    Command,
+    /// [`BlockBreak`], with the `position` of the block.
+    BlockBreak,
+    /// [`BlockPlace`], with the `position` and runtime ID of the `block`.
+    BlockPlace,
+    /// [`PlayerMove`], with the `from` and `to` positions.
+    Move,
+}
+
+impl TryFrom<i32> for EventKind {
+    type Error = anyhow::Error;
+
+    fn try_from(value: i32) -> anyhow::Result<Self> {
+        Ok(match value {
+            0 => Self::Join,
+            1 => Self::Quit,
+            2 => Self::Chat,
+            3 => Self::Command,
+            4 => Self::BlockBreak,
+            5 => Self::BlockPlace,
+            6 => Self::Move,
+            _ => anyhow::bail!("Invalid event kind {value}"),
+        })
+    }
+}
+
+impl From<EventKind> for i32 {
+    fn from(kind: EventKind) -> i32 {
+        kind as i32
+    }
+}
+
+/// Converts the priority passed to `subscribe`.
+pub(super) const fn event_priority(value: i32) -> Option<EventPriority> {
+    Some(match value {
+        0 => EventPriority::Lowest,
+        1 => EventPriority::Low,
+        2 => EventPriority::Normal,
+        3 => EventPriority::High,
+        4 => EventPriority::Highest,
+        5 => EventPriority::Monitor,
+        _ => return None,
+    })
+}
+
+/// An event that can be passed to plugins.
+pub(super) trait PluginEvent: Event {
+    /// Kind that plugins subscribe to.
+    const KIND: EventKind;
+
+    /// Player that caused the event.
+    fn client(&self) -> &Arc<BedrockClient>;
+    /// Adds the fields specific to this event.
+    fn fields(&self, fields: &mut Map<String, Value>);
+    /// Applies the modifications made by a plugin.
+    fn update(&mut self, _update: &Map<String, Value>) {}
+    /// Cancels the event, if it can be cancelled.
+    fn cancel(&mut self) {}
+
+    /// Serializes the event into the JSON object passed to plugins.
+    fn to_json(&self) -> Value {
+        let client = self.client();
+
+        let mut fields = Map::new();
+        fields.insert("player".into(), json!(client.runtime_id().unwrap_or(0)));
+        fields.insert("name".into(), json!(client.name().unwrap_or("")));
+        fields.insert("cancelled".into(), json!(self.is_cancelled()));
+        self.fields(&mut fields);
+
+        Value::Object(fields)
+    }
+}
+
+impl PluginEvent for PlayerJoin {
+    const KIND: EventKind = EventKind::Join;
+
+    fn client(&self) -> &Arc<BedrockClient> {
+        &self.client
+    }
+
+    fn fields(&self, fields: &mut Map<String, Value>) {
+        fields.insert("announce".into(), json!(self.announce));
+    }
+
+    fn update(&mut self, update: &Map<String, Value>) {
+        if let Some(announce) = update.get("announce").and_then(Value::as_bool) {
+            self.announce = announce;
+        }
+    }
+}
+
+impl PluginEvent for PlayerQuit {
+    const KIND: EventKind = EventKind::Quit;
+
+    fn client(&self) -> &Arc<BedrockClient> {
+        &self.client
+    }
+
+    fn fields(&self, fields: &mut Map<String, Value>) {
+        fields.insert("announce".into(), json!(self.announce));
+    }
+
+    fn update(&mut self, update: &Map<String, Value>) {
+        if let Some(announce) = update.get("announce").and_then(Value::as_bool) {
+            self.announce = announce;
+        }
+    }
+}
+
+impl PluginEvent for ChatMessage {
+    const KIND: EventKind = EventKind::Chat;
+
+    fn client(&self) -> &Arc<BedrockClient> {
+        &self.client
+    }
+
+    fn fields(&self, fields: &mut Map<String, Value>) {
+        fields.insert("message".into(), json!(self.message));
+    }
+
+    fn update(&mut self, update: &Map<String, Value>) {
+        if let Some(message) = update.get("message").and_then(Value::as_str) {
+            message.clone_into(&mut self.message);
+        }
+    }
+
+    fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+}
+
+impl PluginEvent for CommandExecute {
+    const KIND: EventKind = EventKind::Command;
+
+    fn client(&self) -> &Arc<BedrockClient> {
+        &self.client
+    }
+
+    fn fields(&self, fields: &mut Map<String, Value>) {
+        fields.insert("command".into(), json!(self.command));
+    }
+
+    fn update(&mut self, update: &Map<String, Value>) {
+        if let Some(command) = update.get("command").and_then(Value::as_str) {
+            command.clone_into(&mut self.command);
+        }
+    }
+
+    fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+}
+
+impl PluginEvent for BlockBreak {
+    const KIND: EventKind = EventKind::BlockBreak;
+
+    fn client(&self) -> &Arc<BedrockClient> {
+        &self.client
+    }
+
+    fn fields(&self, fields: &mut Map<String, Value>) {
+        fields.insert("position".into(), json!(self.position.as_ref()));
+    }
+
+    fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+}
+
+impl PluginEvent for BlockPlace {
+    const KIND: EventKind = EventKind::BlockPlace;
+
+    fn client(&self) -> &Arc<BedrockClient> {
+        &self.client
+    }
+
+    fn fields(&self, fields: &mut Map<String, Value>) {
+        fields.insert("position".into(), json!(self.position.as_ref()));
+        fields.insert("block".into(), json!(self.block));
+    }
+
+    fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+}
+
+impl PluginEvent for PlayerMove {
+    const KIND: EventKind = EventKind::Move;
+
+    fn client(&self) -> &Arc<BedrockClient> {
+        &self.client
+    }
+
+    fn fields(&self, fields: &mut Map<String, Value>) {
+        fields.insert("from".into(), json!(self.from.as_ref()));
+        fields.insert("to".into(), json!(self.to.as_ref()));
+    }
+
+    fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+}
@@ -0,0 +1,349 @@
+use std::sync::{Arc, Weak};
+
+use proto::bedrock::{TextData, TextMessage};
+use proto::types::Dimension;
+use serde::Deserialize;
+use serde_json::Value;
+use util::Vector;
+use wasmi::{Caller, Engine, Extern, Linker, StoreLimits, StoreLimitsBuilder};
+
+use crate::forms::response::Body;
+use crate::forms::{Button, Menu, Modal, Response};
+use crate::instance::Instance;
+use crate::net::BedrockClient;
+
+use super::{event_priority, Capability, EventKind, Plugin, PluginManifest, ERR_FAILED, ERR_INVALID, ERR_PERMISSION};
+
+/// Name of the module that host functions are imported from.
+const HOST_MODULE: &str = "mirai";
+
+/// A command that was registered during initialisation.
+pub(super) struct PendingCommand {
+    pub name: String,
+    pub description: String,
+}
+
+/// State that is accessible to host functions.
+pub(super) struct HostState {
+    pub name: String,
+    pub capabilities: Vec<Capability>,
+    pub instance: Weak<Instance>,
+    pub plugin: Weak<Plugin>,
+    /// Commands and listeners can only be registered while this is set.
+    pub initializing: bool,
+    pub commands: Vec<PendingCommand>,
+    pub subscriptions: Vec<(EventKind, i32)>,
+    /// Output of the command that is being executed.
+    pub output: Option<String>,
+    /// Modifications to the event that is being handled.
+    pub update: Option<Value>,
+    /// Limits the resources that the plugin can allocate.
+    pub limits: StoreLimits,
+}
+
+impl HostState {
+    /// Creates the state of a plugin whose memory can grow up to `memory` bytes.
+    pub fn new(manifest: &PluginManifest, instance: Weak<Instance>, memory: usize) -> HostState {
+        HostState {
+            name: manifest.name.clone(),
+            capabilities: manifest.capabilities.clone(),
+            instance,
+            plugin: Weak::new(),
+            initializing: true,
+            commands: Vec::new(),
+            subscriptions: Vec::new(),
+            output: None,
+            update: None,
+            limits: StoreLimitsBuilder::new().memory_size(memory).instances(1).build(),
+        }
+    }
+
+    /// Whether the plugin has declared the capability, logging a warning if it has not.
+    fn allowed(&self, capability: Capability) -> bool {
+        let allowed = self.capabilities.contains(&capability);
+        if !allowed {
+            tracing::warn!("Plugin {} attempted to use {capability:?} without declaring the capability", self.name);
+        }
+
+        allowed
+    }
+
+    /// Looks up a connected player by runtime ID.
+    fn player(&self, runtime_id: i64) -> Option<Arc<BedrockClient>> {
+        let runtime_id = u64::try_from(runtime_id).ok()?;
+        self.instance.upgrade()?.clients().by_runtime_id(runtime_id)
+    }
+}
+
+/// A form that a plugin can send.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+enum PluginForm {
+    Modal {
+        title: Option<String>,
+        body: Option<String>,
+        confirm: Option<String>,
+        cancel: Option<String>,
+    },
+    Menu {
+        title: Option<String>,
+        body: Option<String>,
+        #[serde(default)]
+        buttons: Vec<String>,
+    },
+}
+
+/// Reads a string from the memory of the plugin.
+fn read_string(caller: &Caller<'_, HostState>, ptr: i32, len: i32) -> Option<String> {
+    let memory = caller.get_export("memory").and_then(Extern::into_memory)?;
+    let start = usize::try_from(ptr).ok()?;
+    let end = start.checked_add(usize::try_from(len).ok()?)?;
+
+    let bytes = memory.data(caller).get(start..end)?;
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+/// Converts the dimension passed to level functions.
+fn dimension(value: i32) -> Option<Dimension> {
+    Dimension::try_from(u32::try_from(value).ok()?).ok()
+}
+
+/// Creates a linker containing all host functions.
+pub(super) fn linker(engine: &Engine) -> anyhow::Result<Linker<HostState>> {
+    let mut linker = Linker::new(engine);
+    linker
+        .func_wrap(HOST_MODULE, "log", log)?
+        .func_wrap(HOST_MODULE, "register_command", register_command)?
+        .func_wrap(HOST_MODULE, "subscribe", subscribe)?
+        .func_wrap(HOST_MODULE, "output", output)?
+        .func_wrap(HOST_MODULE, "event_update", event_update)?
+        .func_wrap(HOST_MODULE, "send_message", send_message)?
+        .func_wrap(HOST_MODULE, "send_form", send_form)?
+        .func_wrap(HOST_MODULE, "get_block", get_block)?
+        .func_wrap(HOST_MODULE, "set_block", set_block)?;
+
+    Ok(linker)
+}
+
+/// Logs a message on behalf of the plugin.
+fn log(caller: Caller<'_, HostState>, level: i32, ptr: i32, len: i32) {
+    let Some(message) = read_string(&caller, ptr, len) else { return };
+    let name = &caller.data().name;
+
+    match level {
+        0 => tracing::error!("[{name}] {message}"),
+        1 => tracing::warn!("[{name}] {message}"),
+        2 => tracing::info!("[{name}] {message}"),
+        3 => tracing::debug!("[{name}] {message}"),
+        _ => tracing::trace!("[{name}] {message}"),
+    }
+}
+
+/// Registers a command, which is added to the command service after initialisation.
+fn register_command(mut caller: Caller<'_, HostState>, name_ptr: i32, name_len: i32, desc_ptr: i32, desc_len: i32) -> i32 {
+    if !caller.data().allowed(Capability::Commands) {
+        return ERR_PERMISSION;
+    }
+    if !caller.data().initializing {
+        return ERR_FAILED;
+    }
+
+    let (Some(name), Some(description)) = (read_string(&caller, name_ptr, name_len), read_string(&caller, desc_ptr, desc_len)) else {
+        return ERR_INVALID;
+    };
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return ERR_INVALID;
+    }
+
+    let commands = &mut caller.data_mut().commands;
+    commands.push(PendingCommand { name, description });
+    i32::try_from(commands.len() - 1).unwrap_or(ERR_FAILED)
+}
+
+/// Subscribes to an event, the listener is registered after initialisation.
+fn subscribe(mut caller: Caller<'_, HostState>, kind: i32, priority: i32) -> i32 {
+    if !caller.data().allowed(Capability::Events) {
+        return ERR_PERMISSION;
+    }
+    if !caller.data().initializing {
+        return ERR_FAILED;
+    }
+
+    let (Ok(kind), Some(_)) = (EventKind::try_from(kind), event_priority(priority)) else {
+        return ERR_INVALID;
+    };
+
+    let subscriptions = &mut caller.data_mut().subscriptions;
+    subscriptions.push((kind, priority));
+    i32::try_from(subscriptions.len() - 1).unwrap_or(ERR_FAILED)
+}
+
+/// Sets the output of the command that is being executed.
+fn output(mut caller: Caller<'_, HostState>, ptr: i32, len: i32) -> i32 {
+    let Some(output) = read_string(&caller, ptr, len) else {
+        return ERR_INVALID;
+    };
+
+    caller.data_mut().output = Some(output);
+    0
+}
+
+/// Records modifications to the event that is being handled.
+fn event_update(mut caller: Caller<'_, HostState>, ptr: i32, len: i32) -> i32 {
+    let Some(update) = read_string(&caller, ptr, len).and_then(|json| serde_json::from_str::<Value>(&json).ok()) else {
+        return ERR_INVALID;
+    };
+    if !update.is_object() {
+        return ERR_INVALID;
+    }
+
+    caller.data_mut().update = Some(update);
+    0
+}
+
+/// Sends a chat message to a player.
+fn send_message(caller: Caller<'_, HostState>, player: i64, ptr: i32, len: i32) -> i32 {
+    if !caller.data().allowed(Capability::Messages) {
+        return ERR_PERMISSION;
+    }
+
+    let Some(message) = read_string(&caller, ptr, len) else {
+        return ERR_INVALID;
+    };
+    let Some(client) = caller.data().player(player) else {
+        return ERR_INVALID;
+    };
+
+    let result = client.send(TextMessage {
+        data: TextData::Raw { message: &message },
+        needs_translation: false,
+        xuid: 0,
+        platform_chat_id: "",
+    });
+
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            tracing::error!("Failed to send plugin message: {err:#}");
+            ERR_FAILED
+        }
+    }
+}
+
+/// Sends a form to a player and passes the response to `mirai_form_response`.
+fn send_form(caller: Caller<'_, HostState>, player: i64, request: i32, ptr: i32, len: i32) -> i32 {
+    let state = caller.data();
+    if !state.allowed(Capability::Forms) {
+        return ERR_PERMISSION;
+    }
+
+    let Some(form) = read_string(&caller, ptr, len).and_then(|json| serde_json::from_str::<PluginForm>(&json).ok()) else {
+        return ERR_INVALID;
+    };
+    let (Some(client), Some(plugin)) = (state.player(player), state.plugin.upgrade()) else {
+        return ERR_INVALID;
+    };
+
+    let receiver = match &form {
+        PluginForm::Modal { title, body, confirm, cancel } => {
+            let mut modal = Modal::new();
+            if let Some(title) = title {
+                modal = modal.title(title.as_str());
+            }
+            if let Some(body) = body {
+                modal = modal.body(body.as_str());
+            }
+            if let Some(confirm) = confirm {
+                modal = modal.confirm(confirm.as_str());
+            }
+            if let Some(cancel) = cancel {
+                modal = modal.cancel(cancel.as_str());
+            }
+
+            client.forms().subscribe(&client, modal)
+        }
+        PluginForm::Menu { title, body, buttons } => {
+            let mut menu = Menu::new();
+            if let Some(title) = title {
+                menu = menu.title(title.as_str());
+            }
+            if let Some(body) = body {
+                menu = menu.body(body.as_str());
+            }
+            for button in buttons {
+                menu = menu.button(Button::new().body(button.as_str()));
+            }
+
+            client.forms().subscribe(&client, menu)
+        }
+    };
+
+    let receiver = match receiver {
+        Ok(receiver) => receiver,
+        Err(err) => {
+            tracing::error!("Failed to send plugin form: {err:#}");
+            return ERR_FAILED;
+        }
+    };
+
+    tokio::spawn(async move {
+        let value = match receiver.await {
+            Ok(Response::Body(Body::Modal(response))) => i32::from(response.confirmed()),
+            Ok(Response::Body(Body::Menu(response))) => i32::try_from(response.pressed()).unwrap_or(-1),
+            _ => -1,
+        };
+
+        let result = {
+            let plugin = Arc::clone(&plugin);
+            tokio::task::spawn_blocking(move || plugin.form_response(request, value)).await
+        };
+        if let Err(err) = result.map_err(anyhow::Error::from).and_then(|result| result) {
+            tracing::error!("Plugin {} failed to handle form response: {err:#}", plugin.name());
+        }
+    });
+
+    0
+}
+
+/// Returns the runtime ID of a block in the level.
+fn get_block(caller: Caller<'_, HostState>, dim: i32, x: i32, y: i32, z: i32) -> i64 {
+    let state = caller.data();
+    if !state.allowed(Capability::LevelRead) {
+        return i64::from(ERR_PERMISSION);
+    }
+
+    let (Some(dimension), Some(instance)) = (dimension(dim), state.instance.upgrade()) else {
+        return i64::from(ERR_INVALID);
+    };
+
+    match instance.block(Vector::from([x, y, z]), dimension) {
+        Ok(block) => i64::from(block),
+        Err(err) => {
+            tracing::error!("Failed to read block for plugin {}: {err:#}", state.name);
+            i64::from(ERR_FAILED)
+        }
+    }
+}
+
+/// Changes a block in the level.
+fn set_block(caller: Caller<'_, HostState>, dim: i32, x: i32, y: i32, z: i32, block: i64) -> i32 {
+    let state = caller.data();
+    if !state.allowed(Capability::LevelWrite) {
+        return ERR_PERMISSION;
+    }
+
+    let (Some(dimension), Ok(block), Some(instance)) = (dimension(dim), u32::try_from(block), state.instance.upgrade()) else {
+        return ERR_INVALID;
+    };
+    if instance.block_states.entry(block).is_none() {
+        return ERR_INVALID;
+    }
+
+    match instance.set_block(Vector::from([x, y, z]), dimension, block) {
+        Ok(()) => 0,
+        Err(err) => {
+            tracing::error!("Failed to change block for plugin {}: {err:#}", state.name);
+            ERR_FAILED
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use std::path::Path;
+
+use anyhow::Context;
+use serde::Deserialize;
+
+/// Name of the manifest file in a plugin directory.
+pub const MANIFEST_FILE: &str = "plugin.toml";
+
+/// Parts of the server that a plugin can access.
+///
+/// Host functions that require a capability that the plugin has not declared fail with
+/// [`ERR_PERMISSION`](super::ERR_PERMISSION).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Capability {
+    /// Register commands.
+    Commands,
+    /// Subscribe to server events and modify or cancel them.
+    Events,
+    /// Send chat messages to players.
+    Messages,
+    /// Send forms to players.
+    Forms,
+    /// Read blocks from the level.
+    LevelRead,
+    /// Change blocks in the level.
+    LevelWrite,
+}
+
+/// Contents of the `plugin.toml` file of a plugin.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PluginManifest {
+    /// Name of the plugin.
+    pub name: String,
+    /// Version of the plugin.
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Path of the WebAssembly module, relative to the plugin directory.
+    #[serde(default = "default_module")]
+    pub module: String,
+    /// Parts of the server that the plugin can access.
+    #[serde(default)]
+    pub capabilities: Vec<Capability>,
+}
+
+fn default_module() -> String {
+    String::from("plugin.wasm")
+}
+
+impl PluginManifest {
+    /// Reads the manifest of the plugin in the given directory.
+    pub fn load(dir: &Path) -> anyhow::Result<PluginManifest> {
+        let path = dir.join(MANIFEST_FILE);
+        let contents = std::fs::read_to_string(&path).with_context(|| format!("Unable to read plugin manifest {}", path.display()))?;
+
+        Self::parse(&contents).with_context(|| format!("Invalid plugin manifest {}", path.display()))
+    }
+
+    /// Parses the contents of a manifest.
+    pub fn parse(contents: &str) -> anyhow::Result<PluginManifest> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    /// Whether the plugin has declared the capability.
+    pub fn has(&self, capability: Capability) -> bool {
+        self.capabilities.contains(&capability)
+    }
+}
@@ -0,0 +1,66 @@
+//! Host for plugins compiled to WebAssembly.
+//!
+//! Plugins are loaded from subdirectories of the plugin directory (`plugins/` by default). Every subdirectory
+//! contains a [`plugin.toml`](MANIFEST_FILE) manifest and the WebAssembly module that it refers to.
+//! The manifest declares which [capabilities](Capability) the plugin needs, host functions that require
+//! an undeclared capability fail with [`ERR_PERMISSION`].
+//!
+//! Plugins run in an interpreter on the blocking thread pool. Every call into a plugin is limited to the amount
+//! of fuel set in the configuration and the memory of a plugin cannot grow beyond the configured size, so a
+//! misbehaving plugin cannot freeze the server or exhaust its memory.
+//!
+//! # ABI
+//!
+//! This describes version [`API_VERSION`] of the interface. Strings and JSON documents are passed as a pointer
+//! into the memory of the plugin and a length in bytes. Strings are UTF-8 encoded.
+//!
+//! The module must export:
+//!
+//! * `memory` - The linear memory of the module.
+//! * `mirai_api_version() -> i32` - Returns the version of the interface that the plugin was built against.
+//! * `mirai_alloc(len: i32) -> i32` - Allocates `len` bytes that the host writes arguments into.
+//!   The plugin is responsible for freeing them once the call that received them returns.
+//!
+//! The module can optionally export:
+//!
+//! * `mirai_init() -> i32` - Called once after loading. Commands and event listeners can only be registered
+//!   during this call. Returning anything other than 0 aborts loading the plugin.
+//! * `mirai_command(handler: i32, player: i64, args: i32, args_len: i32) -> i32` - Called when a player
+//!   executes a command registered by the plugin. `handler` is the value returned by `register_command`
+//!   and `args` is everything after the command name. Returning 0 indicates success.
+//! * `mirai_event(listener: i32, kind: i32, event: i32, event_len: i32) -> i32` - Called when an event that
+//!   the plugin subscribed to is dispatched. The event is a JSON object, see [`EventKind`] for its contents.
+//!   Returning [`EVENT_CANCEL`] cancels the event.
+//! * `mirai_form_response(request: i32, value: i32)` - Called when a player responds to a form sent with
+//!   `send_form`. `value` is -1 if the form was closed, 1 or 0 for a modal and the index of the pressed
+//!   button for a menu.
+//!
+//! The host provides the following functions in the `mirai` import module. Functions that return an `i32`
+//! return a negative error code on failure.
+//!
+//! * `log(level: i32, msg: i32, msg_len: i32)` - Logs a message. Levels range from 0 (error) to 4 (trace).
+//! * `register_command(name: i32, name_len: i32, desc: i32, desc_len: i32) -> i32` - Registers a command
+//!   and returns the handler ID passed to `mirai_command`. Requires [`Capability::Commands`].
+//! * `subscribe(kind: i32, priority: i32) -> i32` - Subscribes to an [`EventKind`] with a priority ranging
+//!   from 0 (lowest) to 5 (monitor) and returns the listener ID passed to `mirai_event`.
+//!   Requires [`Capability::Events`].
+//! * `output(msg: i32, msg_len: i32) -> i32` - Sets the message shown to the player after a command.
+//! * `event_update(json: i32, json_len: i32) -> i32` - Modifies the event that is being handled.
+//!   The JSON object contains the fields to change.
+//! * `send_message(player: i64, msg: i32, msg_len: i32) -> i32` - Sends a chat message to a player.
+//!   Requires [`Capability::Messages`].
+//! * `send_form(player: i64, request: i32, json: i32, json_len: i32) -> i32` - Sends a form to a player.
+//!   The form is either `{"type": "modal", "title", "body", "confirm", "cancel"}` or
+//!   `{"type": "menu", "title", "body", "buttons": [...]}`. Requires [`Capability::Forms`].
+//! * `get_block(dimension: i32, x: i32, y: i32, z: i32) -> i64` - Returns the runtime ID of a block.
+//!   Requires [`Capability::LevelRead`].
+//! * `set_block(dimension: i32, x: i32, y: i32, z: i32, block: i64) -> i32` - Changes a block.
+//!   Requires [`Capability::LevelWrite`].
+
+use ::util::glob_export;
+
+mod host;
+
+glob_export!(abi);
+glob_export!(manifest);
+glob_export!(plugin);
@@ -0,0 +1,362 @@
+use std::path::Path;
+use std::sync::{Arc, Weak};
+
+use anyhow::Context as _;
+use parking_lot::{Mutex, RwLock};
+use proto::bedrock::{Command, CommandDataType, CommandOverload, CommandParameter, CommandPermissionLevel};
+use serde_json::Value;
+use wasmi::{Config, Engine, Memory, Module, Store, TypedFunc, WasmParams, WasmResults};
+
+use crate::command::{CommandHandler, Context, HandlerOutput, HandlerResult};
+use crate::event::{BlockBreak, BlockPlace, ChatMessage, CommandExecute, EventBus, PlayerJoin, PlayerMove, PlayerQuit};
+use crate::instance::Instance;
+
+use super::host::{self, HostState};
+use super::{event_priority, EventKind, PluginEvent, PluginManifest, API_VERSION, EVENT_CANCEL, MANIFEST_FILE};
+
+/// The instantiated module of a plugin.
+pub struct Runtime {
+    store: Store<HostState>,
+    exports: wasmi::Instance,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    command: Option<TypedFunc<(i32, i64, i32, i32), i32>>,
+    event: Option<TypedFunc<(i32, i32, i32, i32), i32>>,
+    form_response: Option<TypedFunc<(i32, i32), ()>>,
+}
+
+impl Runtime {
+    /// Instantiates a plugin module and checks the version of the interface that it was built against.
+    ///
+    /// The memory of the plugin is limited to `memory` bytes. Its initialisation function is not called.
+    pub fn new(engine: &Engine, manifest: &PluginManifest, wasm: &[u8], instance: Weak<Instance>, fuel: u64, memory: usize) -> anyhow::Result<Runtime> {
+        let module = Module::new(engine, wasm).context("Invalid WebAssembly module")?;
+
+        let mut store = Store::new(engine, HostState::new(manifest, instance, memory));
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(fuel).map_err(wasmi::Error::from)?;
+
+        let exports = host::linker(engine)?.instantiate(&mut store, &module)?.start(&mut store)?;
+        let memory = exports.get_memory(&store, "memory").context("Plugin does not export its memory")?;
+        let alloc = exports.get_typed_func::<i32, i32>(&store, "mirai_alloc").context("Plugin does not export mirai_alloc")?;
+
+        let version = exports
+            .get_typed_func::<(), i32>(&store, "mirai_api_version")
+            .context("Plugin does not export mirai_api_version")?
+            .call(&mut store, ())?;
+
+        if version != API_VERSION {
+            anyhow::bail!("Plugin was built for API version {version}, but the server implements version {API_VERSION}");
+        }
+
+        let command = exports.get_func(&store, "mirai_command").map(|func| func.typed(&store)).transpose()?;
+        let event = exports.get_func(&store, "mirai_event").map(|func| func.typed(&store)).transpose()?;
+        let form_response = exports.get_func(&store, "mirai_form_response").map(|func| func.typed(&store)).transpose()?;
+
+        Ok(Runtime { store, exports, memory, alloc, command, event, form_response })
+    }
+
+    /// Calls a function exported by the plugin, returning `None` if it does not export the function.
+    pub fn call<P: WasmParams, R: WasmResults>(&mut self, fuel: u64, name: &str, params: P) -> anyhow::Result<Option<R>> {
+        let Some(func) = self.exports.get_func(&self.store, name) else {
+            return Ok(None);
+        };
+
+        self.refuel(fuel)?;
+        Ok(Some(func.typed::<P, R>(&self.store)?.call(&mut self.store, params)?))
+    }
+
+    /// Resets the fuel before calling into the plugin.
+    fn refuel(&mut self, fuel: u64) -> anyhow::Result<()> {
+        self.store.set_fuel(fuel).map_err(wasmi::Error::from)?;
+        Ok(())
+    }
+
+    /// Copies data into memory allocated by the plugin.
+    fn write(&mut self, data: &[u8]) -> anyhow::Result<(i32, i32)> {
+        let len = i32::try_from(data.len())?;
+        let ptr = self.alloc.call(&mut self.store, len)?;
+
+        self.memory.write(&mut self.store, usize::try_from(ptr)?, data).map_err(wasmi::Error::from)?;
+        Ok((ptr, len))
+    }
+}
+
+/// A loaded WebAssembly plugin.
+pub struct Plugin {
+    manifest: PluginManifest,
+    /// Fuel available to every call into the plugin.
+    fuel: u64,
+    runtime: Mutex<Runtime>,
+}
+
+impl Plugin {
+    /// Instantiates a plugin module and calls its initialisation function.
+    fn new(engine: &Engine, manifest: PluginManifest, wasm: &[u8], instance: &Arc<Instance>) -> anyhow::Result<Arc<Plugin>> {
+        let config = instance.config().plugins();
+        let fuel = config.fuel;
+        let mut runtime = Runtime::new(engine, &manifest, wasm, Arc::downgrade(instance), fuel, config.memory * 1024 * 1024)?;
+
+        if let Some(code) = runtime.call::<(), i32>(fuel, "mirai_init", ())? {
+            if code != 0 {
+                anyhow::bail!("Plugin initialisation failed with code {code}");
+            }
+        }
+
+        let state = runtime.store.data_mut();
+        state.initializing = false;
+        let commands = std::mem::take(&mut state.commands);
+        let subscriptions = std::mem::take(&mut state.subscriptions);
+
+        let plugin = Arc::new_cyclic(|weak| {
+            runtime.store.data_mut().plugin = Weak::clone(weak);
+            Plugin { manifest, fuel, runtime: Mutex::new(runtime) }
+        });
+
+        for (handler, command) in (0..).zip(commands) {
+            let structure = Command {
+                name: command.name,
+                description: command.description,
+                permission_level: CommandPermissionLevel::Normal,
+                aliases: Vec::new(),
+                overloads: vec![CommandOverload {
+                    parameters: vec![CommandParameter {
+                        name: "args".to_owned(),
+                        data_type: CommandDataType::RawText,
+                        optional: true,
+                        options: 0,
+                        command_enum: None,
+                        suffix: String::new(),
+                    }],
+                }],
+            };
+
+            instance.commands().register_handler(Arc::new(PluginCommand { plugin: Arc::clone(&plugin), handler, structure }))?;
+        }
+
+        for (listener, (kind, priority)) in (0..).zip(subscriptions) {
+            plugin.subscribe(instance.events(), listener, kind, priority);
+        }
+
+        Ok(plugin)
+    }
+
+    /// Returns the manifest of the plugin.
+    #[inline]
+    pub const fn manifest(&self) -> &PluginManifest {
+        &self.manifest
+    }
+
+    /// Returns the name of the plugin.
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.manifest.name
+    }
+
+    /// Registers a listener that passes events of the given kind to the plugin.
+    ///
+    /// The plugin is called on the blocking thread pool, so that it does not hold up other tasks.
+    fn subscribe(self: &Arc<Self>, events: &EventBus, listener: i32, kind: EventKind, priority: i32) {
+        fn listen<E: PluginEvent>(plugin: &Arc<Plugin>, events: &EventBus, listener: i32, priority: i32) {
+            let plugin = Arc::clone(plugin);
+            events.listen_async(event_priority(priority).unwrap_or_default(), move |mut event: E| {
+                let plugin = Arc::clone(&plugin);
+                async move {
+                    let json = event.to_json();
+                    let result = {
+                        let plugin = Arc::clone(&plugin);
+                        tokio::task::spawn_blocking(move || plugin.event(listener, E::KIND, &json)).await
+                    };
+
+                    match result.map_err(anyhow::Error::from).and_then(|result| result) {
+                        Ok((code, update)) => {
+                            if let Some(update) = update.as_ref().and_then(Value::as_object) {
+                                event.update(update);
+                            }
+                            if code == EVENT_CANCEL {
+                                event.cancel();
+                            }
+                        }
+                        Err(err) => tracing::error!("Plugin {} failed to handle event: {err:#}", plugin.name()),
+                    }
+
+                    event
+                }
+            });
+        }
+
+        match kind {
+            EventKind::Join => listen::<PlayerJoin>(self, events, listener, priority),
+            EventKind::Quit => listen::<PlayerQuit>(self, events, listener, priority),
+            EventKind::Chat => listen::<ChatMessage>(self, events, listener, priority),
+            EventKind::Command => listen::<CommandExecute>(self, events, listener, priority),
+            EventKind::BlockBreak => listen::<BlockBreak>(self, events, listener, priority),
+            EventKind::BlockPlace => listen::<BlockPlace>(self, events, listener, priority),
+            EventKind::Move => listen::<PlayerMove>(self, events, listener, priority),
+        }
+    }
+
+    /// Calls the command handler of the plugin, returning its status code and output.
+    ///
+    /// This blocks until the plugin has handled the command. Command handlers run on the blocking thread pool.
+    fn command(&self, handler: i32, caller: u64, args: &str) -> anyhow::Result<(i32, Option<String>)> {
+        let mut runtime = self.runtime.lock();
+        let Some(command) = runtime.command else {
+            anyhow::bail!("Plugin registered a command but does not export mirai_command");
+        };
+
+        runtime.refuel(self.fuel)?;
+        let (ptr, len) = runtime.write(args.as_bytes())?;
+        runtime.store.data_mut().output = None;
+
+        let code = command.call(&mut runtime.store, (handler, caller as i64, ptr, len))?;
+        let output = runtime.store.data_mut().output.take();
+        drop(runtime);
+
+        Ok((code, output))
+    }
+
+    /// Passes an event to the plugin, returning its status code and modifications to the event.
+    ///
+    /// This blocks until the plugin has handled the event.
+    fn event(&self, listener: i32, kind: EventKind, event: &Value) -> anyhow::Result<(i32, Option<Value>)> {
+        let json = serde_json::to_vec(event)?;
+
+        let mut runtime = self.runtime.lock();
+        let Some(handler) = runtime.event else {
+            anyhow::bail!("Plugin subscribed to an event but does not export mirai_event");
+        };
+
+        runtime.refuel(self.fuel)?;
+        let (ptr, len) = runtime.write(&json)?;
+        runtime.store.data_mut().update = None;
+
+        let code = handler.call(&mut runtime.store, (listener, i32::from(kind), ptr, len))?;
+        let update = runtime.store.data_mut().update.take();
+        drop(runtime);
+
+        Ok((code, update))
+    }
+
+    /// Passes the response to a form to the plugin.
+    ///
+    /// This blocks until the plugin has handled the response.
+    pub(super) fn form_response(&self, request: i32, value: i32) -> anyhow::Result<()> {
+        let mut runtime = self.runtime.lock();
+        let Some(handler) = runtime.form_response else {
+            anyhow::bail!("Plugin sent a form but does not export mirai_form_response");
+        };
+
+        runtime.refuel(self.fuel)?;
+        handler.call(&mut runtime.store, (request, value))?;
+        drop(runtime);
+
+        Ok(())
+    }
+}
+
+/// A command registered by a plugin.
+struct PluginCommand {
+    plugin: Arc<Plugin>,
+    handler: i32,
+    structure: Command,
+}
+
+impl CommandHandler for PluginCommand {
+    fn call(&self, input: &str, ctx: &Context) -> HandlerResult {
+        let args = input.split_once(' ').map_or("", |(_, args)| args.trim());
+        let caller = ctx.caller.runtime_id().map_err(|_| HandlerOutput::new().message("Only players can execute this command"))?;
+
+        match self.plugin.command(self.handler, caller, args) {
+            Ok((0, output)) => HandlerOutput::new().message(output.unwrap_or_default()).success(),
+            Ok((_, output)) => HandlerOutput::new().message(output.unwrap_or_else(|| String::from("Command failed"))).error(),
+            Err(err) => {
+                tracing::error!("Plugin {} failed to execute /{}: {err:#}", self.plugin.name(), self.structure.name);
+                HandlerOutput::new().message("An internal error occurred while executing the command").error()
+            }
+        }
+    }
+
+    fn structure(&self) -> &Command {
+        &self.structure
+    }
+}
+
+/// Keeps track of all loaded plugins.
+pub struct Plugins {
+    engine: Engine,
+    loaded: RwLock<Vec<Arc<Plugin>>>,
+}
+
+impl Plugins {
+    /// Creates an empty plugin list.
+    pub fn new() -> Plugins {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+
+        Plugins { engine: Engine::new(&config), loaded: RwLock::new(Vec::new()) }
+    }
+
+    /// Loads every plugin in the plugin directory of the instance.
+    ///
+    /// Plugins that fail to load are skipped. Nothing is loaded if the directory does not exist.
+    pub(crate) fn load_all(&self, instance: &Arc<Instance>) -> anyhow::Result<()> {
+        let path = Path::new(&instance.config().plugins().path);
+        if !path.is_dir() {
+            tracing::debug!("Plugin directory {} does not exist", path.display());
+            return Ok(());
+        }
+
+        let entries = std::fs::read_dir(path).with_context(|| format!("Unable to read plugin directory {}", path.display()))?;
+        for entry in entries {
+            let dir = entry?.path();
+            if !dir.join(MANIFEST_FILE).is_file() {
+                continue;
+            }
+
+            if let Err(err) = self.load(instance, &dir) {
+                tracing::error!("Failed to load plugin {}: {err:#}", dir.display());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Loads the plugin in the given directory.
+    pub fn load(&self, instance: &Arc<Instance>, dir: &Path) -> anyhow::Result<Arc<Plugin>> {
+        let manifest = PluginManifest::load(dir)?;
+        let path = dir.join(&manifest.module);
+        let wasm = std::fs::read(&path).with_context(|| format!("Unable to read plugin module {}", path.display()))?;
+
+        self.load_module(instance, manifest, &wasm)
+    }
+
+    /// Loads a plugin from its manifest and the contents of its WebAssembly module.
+    pub fn load_module(&self, instance: &Arc<Instance>, manifest: PluginManifest, wasm: &[u8]) -> anyhow::Result<Arc<Plugin>> {
+        if self.get(&manifest.name).is_some() {
+            anyhow::bail!("A plugin named {} has already been loaded", manifest.name);
+        }
+
+        let plugin = Plugin::new(&self.engine, manifest, wasm, instance)?;
+        self.loaded.write().push(Arc::clone(&plugin));
+
+        tracing::info!("Loaded plugin {} v{}", plugin.name(), plugin.manifest.version.as_deref().unwrap_or("?"));
+        Ok(plugin)
+    }
+
+    /// Returns the plugin with the given name.
+    pub fn get(&self, name: &str) -> Option<Arc<Plugin>> {
+        self.loaded.read().iter().find(|plugin| plugin.name() == name).cloned()
+    }
+
+    /// Returns all loaded plugins.
+    pub fn all(&self) -> Vec<Arc<Plugin>> {
+        self.loaded.read().clone()
+    }
+}
+
+impl Default for Plugins {
+    fn default() -> Self {
+        Self::new()
+    }
+}
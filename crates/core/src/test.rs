@@ -2,6 +2,12 @@ use util::Deserialize;
 use util::Serialize;
 
 use proto::bedrock::Header;
+use proto::types::Dimension;
+use util::Vector;
+
+use crate::level::io::radial::RadialRegion;
+use crate::level::io::region::Region;
+use crate::level::io::stream::RegionIndex;
 
 #[test]
 fn biome_nbt() {
@@ -23,3 +29,524 @@ fn header() {
 
     assert_eq!(Header::deserialize(buffer.as_ref()).unwrap(), header);
 }
+
+#[test]
+fn region_index() {
+    for coord in [[0, 0, 0], [-1, -4, -1], [12345, 19, -54321], [-(1 << 28), -32, (1 << 28) - 1]] {
+        let coord = Vector::from(coord);
+        let index = RegionIndex::from(coord.clone());
+
+        assert_eq!(Vector::<i32, 3>::from(index), coord);
+    }
+}
+
+#[test]
+fn radial_region() {
+    let region = RadialRegion::from_center((-3, 5), 4, -4..20, Dimension::Overworld);
+    let columns = region.columns().collect::<Vec<_>>();
+
+    assert_eq!(columns.len(), 49, "Gauss circle count for radius 4 is 49");
+    assert_eq!(region.len(), columns.len() * 24);
+    assert_eq!(columns[0], Vector::from([-3, 5]), "nearest column should be the center");
+
+    let mut last = 0;
+    for (i, coord) in region.clone().into_iter().enumerate() {
+        let (dx, dz) = (coord.x + 3, coord.z - 5);
+        let distance = dx * dx + dz * dz;
+
+        assert!(distance <= 16, "column outside of radius");
+        assert!(distance >= last, "columns are not sorted nearest-first");
+        assert_eq!(region.as_index(&coord), Some(i));
+
+        last = distance;
+    }
+}
+
+#[test]
+fn substorage_set() {
+    use level::{PaletteEntry, SubStorage};
+    use std::collections::HashMap;
+
+    let block = |name: &str| PaletteEntry { name: name.to_owned(), version: None, states: HashMap::new() };
+
+    let mut layer = SubStorage::empty();
+    assert!(layer.set([0, 0, 0], block("minecraft:stone")).is_none());
+
+    layer.palette.push(block("minecraft:air"));
+    assert_eq!(layer.set([1, 2, 3], block("minecraft:stone")), Some(block("minecraft:air")));
+    assert_eq!(layer.get([1, 2, 3]), Some(&block("minecraft:stone")));
+    assert_eq!(layer.get([0, 0, 0]), Some(&block("minecraft:air")));
+    assert_eq!(layer.palette().len(), 2);
+
+    // The only stone block is replaced, so its palette slot is reused.
+    assert_eq!(layer.set([1, 2, 3], block("minecraft:dirt")), Some(block("minecraft:stone")));
+    assert_eq!(layer.palette().len(), 2);
+    assert!(layer.set([16, 0, 0], block("minecraft:dirt")).is_none());
+}
+
+#[test]
+fn inventory_slots() {
+    use crate::item::{Inventory, HOTBAR_SIZE, MAIN_SIZE};
+    use proto::bedrock::{ItemInstance, WindowId};
+
+    let item = ItemInstance { network_id: 5, count: 12, ..ItemInstance::air() };
+    let mut inventory = Inventory::new();
+
+    for i in 0..HOTBAR_SIZE + MAIN_SIZE {
+        assert_eq!(inventory.add(item.clone()), Some((WindowId::Inventory, i as u32)));
+    }
+    assert_eq!(inventory.add(item.clone()), None);
+
+    assert_eq!(inventory.slot(WindowId::Inventory, 3), inventory.hotbar.get(3));
+    assert_eq!(inventory.slot(WindowId::Inventory, 9), inventory.main.get(0));
+    assert!(inventory.slot(WindowId::Inventory, 36).is_none());
+    assert!(inventory.slot(WindowId::Creative, 0).is_none());
+
+    assert!(inventory.set_selected(4));
+    assert!(!inventory.set_selected(HOTBAR_SIZE));
+    assert_eq!(inventory.held(), &ItemInstance { stack_id: Some(5), ..item });
+}
+
+#[test]
+fn inventory_stack_ids() {
+    use crate::item::Inventory;
+    use proto::bedrock::{ItemInstance, WindowId};
+
+    let mut inventory = Inventory::new();
+
+    let mut air = ItemInstance::air();
+    inventory.assign_stack_id(&mut air);
+    assert_eq!(air.stack_id, None);
+
+    let mut item = ItemInstance { network_id: 5, count: 1, ..ItemInstance::air() };
+    inventory.assign_stack_id(&mut item);
+    assert_eq!(item.stack_id, Some(1));
+
+    // Existing IDs are kept.
+    inventory.assign_stack_id(&mut item);
+    assert_eq!(item.stack_id, Some(1));
+
+    // Added items always receive a new ID.
+    inventory.add(item);
+    assert_eq!(inventory.slot(WindowId::Inventory, 0).and_then(|item| item.stack_id), Some(2));
+}
+
+#[test]
+fn inventory_transaction_slots() {
+    use crate::item::Inventory;
+    use crate::net::validate_transaction;
+    use proto::bedrock::{ItemInstance, TransactionAction, TransactionSourceType, WindowId};
+
+    let item = ItemInstance { network_id: 5, count: 12, ..ItemInstance::air() };
+    let mut inventory = Inventory::new();
+    inventory.add(item.clone());
+    let item = inventory.slot(WindowId::Inventory, 0).cloned().unwrap();
+
+    let source_type = TransactionSourceType::Container { inventory_id: WindowId::Inventory };
+    let take = TransactionAction { source_type, slot: 0, old_item: item.clone(), new_item: ItemInstance::air() };
+    let put = |count| TransactionAction { source_type, slot: 1, old_item: ItemInstance::air(), new_item: ItemInstance { count, ..item.clone() } };
+
+    let changes = validate_transaction(&inventory, &[take.clone(), put(12)], false).unwrap();
+    assert_eq!(changes.len(), 2);
+
+    // Taking the same stack twice would duplicate it.
+    assert!(validate_transaction(&inventory, &[take.clone(), take, put(24)], false).is_none());
+}
+
+#[test]
+fn resource_pack_archive() {
+    use crate::pack::{ResourcePack, PACK_CHUNK_SIZE};
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    let manifest = r#"{
+        "format_version": 2,
+        "header": { "name": "Test", "uuid": "0b5e3c7a-6f0e-4a57-9a1d-3a0f2f0d6c11", "version": [1, 2, 3] },
+        "modules": [{ "type": "resources", "uuid": "7d3f1f0e-0c5b-4b7e-8a57-2f4d0c6e9b22", "version": [1, 2, 3] }]
+    }"#;
+
+    let mut archive = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    archive.start_file("pack/manifest.json", SimpleFileOptions::default()).unwrap();
+    archive.write_all(manifest.as_bytes()).unwrap();
+    archive.start_file("pack/padding.bin", SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored)).unwrap();
+    archive.write_all(&vec![0; PACK_CHUNK_SIZE]).unwrap();
+    let data = archive.finish().unwrap().into_inner();
+
+    let pack = ResourcePack::from_archive(data.clone()).unwrap();
+    assert_eq!(pack.uuid, "0b5e3c7a-6f0e-4a57-9a1d-3a0f2f0d6c11");
+    assert_eq!(pack.version, "1.2.3");
+    assert_eq!(pack.id, "0b5e3c7a-6f0e-4a57-9a1d-3a0f2f0d6c11_1.2.3");
+    assert_eq!(pack.size(), data.len() as u64);
+
+    assert_eq!(pack.chunk_count(), 2);
+    assert_eq!(pack.chunk(0).map(<[u8]>::len), Some(PACK_CHUNK_SIZE));
+    assert_eq!(pack.chunk(1).map(<[u8]>::len), Some(data.len() - PACK_CHUNK_SIZE));
+    assert!(pack.chunk(2).is_none());
+
+    assert!(ResourcePack::from_archive(vec![1, 2, 3]).is_err());
+}
+
+#[test]
+fn config_file_overrides() {
+    use crate::config::{CompressionName, ConfigFile, GameModeName};
+
+    let file = ConfigFile::parse(
+        r#"
+        ipv4_addr = "0.0.0.0:19132"
+        name = "Test server"
+        max_players = 20
+
+        [compression]
+        algorithm = "snappy"
+
+        [level]
+        path = "resources/level"
+        generator = "flat"
+
+        [discovery]
+        lan_broadcast = true
+        game_mode = "creative"
+    "#,
+    )
+    .unwrap();
+
+    let overrides = ConfigFile { max_players: Some(5), ..Default::default() };
+    let merged = file.merge(overrides);
+
+    assert_eq!(merged.ipv4_addr, Some("0.0.0.0:19132".parse().unwrap()));
+    assert_eq!(merged.name.as_deref(), Some("Test server"));
+    assert_eq!(merged.max_players, Some(5));
+    assert_eq!(merged.compression.algorithm, Some(CompressionName::Snappy));
+    assert_eq!(merged.level.path.as_deref(), Some("resources/level"));
+    assert_eq!(merged.level.generator, Some(level::settings::GeneratorType::Flat));
+    assert!(merged.max_render_distance.is_none());
+    assert_eq!(merged.discovery.lan_broadcast, Some(true));
+    assert_eq!(merged.discovery.game_mode, Some(GameModeName::Creative));
+
+    assert!(ConfigFile::parse("unknown_option = 1").is_err());
+}
+
+#[test]
+fn blob_store_misses() {
+    use crate::net::{blob_hash, BlobStore};
+    use proto::bedrock::CacheBlobStatus;
+    use std::sync::Arc;
+
+    let air: Arc<[u8]> = Arc::from(&[0u8; 4][..]);
+    let stone: Arc<[u8]> = Arc::from(&[1u8; 4][..]);
+    let (air_hash, stone_hash) = (blob_hash(&air), blob_hash(&stone));
+
+    let store = BlobStore::default();
+    // Duplicate blobs in a single packet are only stored once.
+    store.insert([(air_hash, Arc::clone(&air)), (air_hash, Arc::clone(&air)), (stone_hash, Arc::clone(&stone))]);
+    store.insert([(air_hash, Arc::clone(&air))]);
+    assert_eq!(store.pending(), 2);
+
+    let missing = store.resolve(&CacheBlobStatus { misses: vec![stone_hash, 42], hits: vec![air_hash] });
+    assert_eq!(missing.len(), 1);
+    assert_eq!(missing[0].0, stone_hash);
+    assert_eq!(&*missing[0].1, &*stone);
+    assert_eq!((store.hits(), store.misses()), (1, 2));
+
+    // The second packet still refers to the air blob.
+    assert_eq!(store.pending(), 1);
+    let missing = store.resolve(&CacheBlobStatus { misses: vec![air_hash], hits: vec![] });
+    assert_eq!(&*missing[0].1, &*air);
+    assert_eq!(store.pending(), 0);
+}
+
+#[test]
+fn blob_store_limits() {
+    use crate::net::BlobStore;
+    use proto::bedrock::CacheBlobStatus;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let blob = |byte: u8| (u64::from(byte), Arc::<[u8]>::from(&[byte; 4][..]));
+
+    // The oldest blobs are discarded once the capacity is exceeded.
+    let store = BlobStore::with_limits(8, Duration::from_secs(60));
+    for byte in 1..=3 {
+        store.insert([blob(byte)]);
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(store.pending(), 2);
+    let missing = store.resolve(&CacheBlobStatus { misses: vec![1, 2, 3], hits: vec![] });
+    assert_eq!(missing.iter().map(|(hash, _)| *hash).collect::<Vec<_>>(), [2, 3]);
+
+    // Blobs that were not reported in time are discarded.
+    let store = BlobStore::with_limits(1024, Duration::ZERO);
+    store.insert([blob(1)]);
+    store.insert([blob(2)]);
+    assert_eq!(store.pending(), 0);
+}
+
+#[test]
+fn handoff_token() {
+    use crate::handoff::{Handoff, Handoffs};
+
+    let handoff = Handoff::new(2_535_400_000_000_001, "Steve".to_owned(), "MHYwEAYHKoZI".to_owned(), vec![("game".to_owned(), "bedwars".to_owned())]);
+    let mut token = handoff.sign(b"secret").unwrap();
+
+    let verified = Handoff::verify(&token, b"secret").unwrap();
+    assert_eq!(verified, handoff);
+    assert_eq!(verified.get("game"), Some("bedwars"));
+
+    assert!(Handoff::verify(&token, b"other secret").is_err());
+    token[3] ^= 1;
+    assert!(Handoff::verify(&token, b"secret").is_err());
+
+    let expired = Handoff { issued_at: 0, ..handoff.clone() };
+    assert!(Handoff::verify(&expired.sign(b"secret").unwrap(), b"secret").is_err());
+
+    let handoffs = Handoffs::default();
+    handoffs.insert(handoff.clone()).unwrap();
+    assert!(handoffs.contains("Steve", "MHYwEAYHKoZI"));
+    assert!(handoffs.take("Steve", "another key").is_none());
+    assert!(handoffs.take("Steve", "MHYwEAYHKoZI").is_some());
+    assert!(handoffs.take("Steve", "MHYwEAYHKoZI").is_none());
+
+    // Tokens can only be used once.
+    assert!(handoffs.insert(handoff).is_err());
+    assert!(!handoffs.contains("Steve", "MHYwEAYHKoZI"));
+
+    for i in 0..2000 {
+        handoffs.insert(Handoff::new(i, format!("Player{i}"), String::new(), Vec::new())).unwrap();
+    }
+    assert_eq!(handoffs.len(), 1024);
+    assert!(!handoffs.contains("Player0", ""));
+    assert!(handoffs.contains("Player1999", ""));
+}
+
+#[test]
+fn metrics_encoding() {
+    let metrics = crate::metrics::metrics();
+    metrics.record_received(0x01, 512);
+    metrics.record_sent(0x02, 64);
+
+    let encoded = metrics.encode().unwrap();
+    assert!(encoded.contains(r#"mirai_packets_received_total{id="1"} 1"#));
+    assert!(encoded.contains(r#"mirai_packet_bytes_sent_total{id="2"} 64"#));
+    assert!(encoded.contains("mirai_raknet_round_trip_time_seconds"));
+    assert!(encoded.ends_with("# EOF\n"));
+}
+
+#[test]
+fn firewall_bans() {
+    use crate::config::FirewallConfig;
+    use crate::net::{Cidr, Firewall};
+    use std::net::IpAddr;
+
+    let range: Cidr = "192.168.1.77/16".parse().unwrap();
+    assert_eq!(range.to_string(), "192.168.0.0/16");
+    assert!(range.contains(&"192.168.250.1".parse().unwrap()));
+    assert!(range.contains(&"::ffff:192.168.3.4".parse().unwrap()));
+    assert!(!range.contains(&"192.169.0.1".parse().unwrap()));
+    assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+
+    let config = FirewallConfig { ip_rate: 0.0, ip_burst: 2.0, strike_limit: 2, ..Default::default() };
+    let firewall = Firewall::new(&config);
+    let addr: IpAddr = "10.0.0.1".parse().unwrap();
+
+    // The burst is used up and the bucket does not refill.
+    assert!(firewall.allow_unconnected(&addr));
+    assert!(firewall.allow_unconnected(&addr));
+    assert!(!firewall.allow_unconnected(&addr));
+
+    // Running out of tokens counts as a single strike, no matter how many packets are rejected.
+    assert!(!firewall.allow_unconnected(&addr));
+    assert!(!firewall.is_banned(&addr));
+    assert!(firewall.strike(&addr, "Bad packet"));
+    assert!(firewall.is_banned(&addr));
+    assert!(firewall.unban(&Cidr::single(addr)));
+    assert!(!firewall.is_banned(&addr));
+
+    firewall.ban(range, None, "Test");
+    firewall.ban("2001:db8::/32".parse().unwrap(), None, "Test");
+    assert!(firewall.is_banned(&"192.168.0.5".parse().unwrap()));
+    assert!(firewall.is_banned(&"2001:db8:1::1".parse().unwrap()));
+    assert!(!firewall.is_banned(&"2001:db9::1".parse().unwrap()));
+    assert_eq!(firewall.bans().len(), 2);
+}
+
+#[tokio::test]
+async fn event_dispatch() {
+    use crate::event::{Event, EventBus, EventPriority};
+
+    struct Chat {
+        message: String,
+        cancelled: bool,
+    }
+
+    impl Event for Chat {
+        fn is_cancelled(&self) -> bool {
+            self.cancelled
+        }
+    }
+
+    let bus = EventBus::new();
+    assert!(!bus.has_listeners::<Chat>());
+
+    bus.listen(EventPriority::High, |event: &mut Chat| event.message.push('c'));
+    bus.listen(EventPriority::Low, |event: &mut Chat| event.message.push('a'));
+    bus.listen_async(EventPriority::Normal, |mut event: Chat| async move {
+        event.message.push('b');
+        event
+    });
+    let limit = bus.listen(EventPriority::Highest, |event: &mut Chat| event.cancelled = event.message.len() > 3);
+
+    let event = bus.dispatch(Chat { message: String::new(), cancelled: false }).await;
+    assert_eq!(event.message, "abc");
+    assert!(!event.is_cancelled());
+
+    let event = bus.dispatch(Chat { message: String::from("x"), cancelled: false }).await;
+    assert!(event.is_cancelled());
+
+    assert!(bus.unlisten::<Chat>(limit));
+    assert!(!bus.unlisten::<Chat>(limit));
+    let event = bus.dispatch(Chat { message: String::from("x"), cancelled: false }).await;
+    assert!(!event.is_cancelled());
+}
+
+#[test]
+fn plugin_manifest() {
+    use crate::plugin::{Capability, EventKind, PluginManifest};
+
+    let manifest = PluginManifest::parse(
+        r#"
+        name = "greeter"
+        version = "1.0.0"
+        capabilities = ["commands", "level-read"]
+    "#,
+    )
+    .unwrap();
+
+    assert_eq!(manifest.name, "greeter");
+    assert_eq!(manifest.module, "plugin.wasm");
+    assert!(manifest.has(Capability::Commands));
+    assert!(manifest.has(Capability::LevelRead));
+    assert!(!manifest.has(Capability::LevelWrite));
+
+    assert!(PluginManifest::parse("name = \"x\"\ncapabilities = [\"filesystem\"]").is_err());
+    assert!(PluginManifest::parse("version = \"1.0.0\"").is_err());
+
+    assert_eq!(EventKind::try_from(2).unwrap(), EventKind::Chat);
+    assert_eq!(i32::from(EventKind::Move), 6);
+    assert!(EventKind::try_from(7).is_err());
+}
+
+#[test]
+fn plugin_host_functions() {
+    use crate::plugin::{PluginManifest, Runtime, ERR_INVALID, ERR_PERMISSION};
+    use std::sync::Weak;
+
+    // Hand-assembled from the following module:
+    //
+    // (module
+    //   (import "mirai" "register_command" (func $register (param i32 i32 i32 i32) (result i32)))
+    //   (import "mirai" "send_message" (func $message (param i64 i32 i32) (result i32)))
+    //   (import "mirai" "get_block" (func $block (param i32 i32 i32 i32) (result i64)))
+    //   (memory (export "memory") 1 4)
+    //   (data (i32.const 0) "test")
+    //   (func (export "mirai_api_version") (result i32) i32.const 1)
+    //   (func (export "mirai_alloc") (param i32) (result i32) i32.const 1024)
+    //   (func (export "grow") (param i32) (result i32) local.get 0 memory.grow)
+    //   (func (export "register") (result i32) (call $register (i32.const 0) (i32.const 4) (i32.const 0) (i32.const 4)))
+    //   (func (export "message") (result i32) (call $message (i64.const 1) (i32.const 0) (i32.const 4)))
+    //   (func (export "block") (result i64) (call $block (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0))))
+    const WASM: &[u8] = &[
+0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x25, 0x06, 0x60, 0x04, 0x7f, 0x7f, 0x7f,
+        0x7f, 0x01, 0x7f, 0x60, 0x03, 0x7e, 0x7f, 0x7f, 0x01, 0x7f, 0x60, 0x04, 0x7f, 0x7f, 0x7f, 0x7f,
+        0x01, 0x7e, 0x60, 0x00, 0x01, 0x7f, 0x60, 0x01, 0x7f, 0x01, 0x7f, 0x60, 0x00, 0x01, 0x7e, 0x02,
+        0x41, 0x03, 0x05, 0x6d, 0x69, 0x72, 0x61, 0x69, 0x10, 0x72, 0x65, 0x67, 0x69, 0x73, 0x74, 0x65,
+        0x72, 0x5f, 0x63, 0x6f, 0x6d, 0x6d, 0x61, 0x6e, 0x64, 0x00, 0x00, 0x05, 0x6d, 0x69, 0x72, 0x61,
+        0x69, 0x0c, 0x73, 0x65, 0x6e, 0x64, 0x5f, 0x6d, 0x65, 0x73, 0x73, 0x61, 0x67, 0x65, 0x00, 0x01,
+        0x05, 0x6d, 0x69, 0x72, 0x61, 0x69, 0x09, 0x67, 0x65, 0x74, 0x5f, 0x62, 0x6c, 0x6f, 0x63, 0x6b,
+        0x00, 0x02, 0x03, 0x07, 0x06, 0x03, 0x04, 0x04, 0x03, 0x03, 0x05, 0x05, 0x04, 0x01, 0x01, 0x01,
+        0x04, 0x07, 0x50, 0x07, 0x06, 0x6d, 0x65, 0x6d, 0x6f, 0x72, 0x79, 0x02, 0x00, 0x11, 0x6d, 0x69,
+        0x72, 0x61, 0x69, 0x5f, 0x61, 0x70, 0x69, 0x5f, 0x76, 0x65, 0x72, 0x73, 0x69, 0x6f, 0x6e, 0x00,
+        0x03, 0x0b, 0x6d, 0x69, 0x72, 0x61, 0x69, 0x5f, 0x61, 0x6c, 0x6c, 0x6f, 0x63, 0x00, 0x04, 0x04,
+        0x67, 0x72, 0x6f, 0x77, 0x00, 0x05, 0x08, 0x72, 0x65, 0x67, 0x69, 0x73, 0x74, 0x65, 0x72, 0x00,
+        0x06, 0x07, 0x6d, 0x65, 0x73, 0x73, 0x61, 0x67, 0x65, 0x00, 0x07, 0x05, 0x62, 0x6c, 0x6f, 0x63,
+        0x6b, 0x00, 0x08, 0x0a, 0x38, 0x06, 0x04, 0x00, 0x41, 0x01, 0x0b, 0x05, 0x00, 0x41, 0x80, 0x08,
+        0x0b, 0x06, 0x00, 0x20, 0x00, 0x40, 0x00, 0x0b, 0x0c, 0x00, 0x41, 0x00, 0x41, 0x04, 0x41, 0x00,
+        0x41, 0x04, 0x10, 0x00, 0x0b, 0x0a, 0x00, 0x42, 0x01, 0x41, 0x00, 0x41, 0x04, 0x10, 0x01, 0x0b,
+        0x0c, 0x00, 0x41, 0x00, 0x41, 0x00, 0x41, 0x00, 0x41, 0x00, 0x10, 0x02, 0x0b, 0x0b, 0x0a, 0x01,
+        0x00, 0x41, 0x00, 0x0b, 0x04, 0x74, 0x65, 0x73, 0x74,
+    ];
+    const PAGE: usize = 64 * 1024;
+
+    let mut config = wasmi::Config::default();
+    config.consume_fuel(true);
+    let engine = wasmi::Engine::new(&config);
+    let runtime = |capabilities: &str, memory: usize| {
+        let manifest = PluginManifest::parse(&format!("name = \"test\"\ncapabilities = [{capabilities}]")).unwrap();
+        Runtime::new(&engine, &manifest, WASM, Weak::new(), 10_000, memory)
+    };
+
+    // Host functions refuse to work without the capability.
+    let mut denied = runtime("", PAGE).unwrap();
+    assert_eq!(denied.call::<(), i32>(10_000, "register", ()).unwrap(), Some(ERR_PERMISSION));
+    assert_eq!(denied.call::<(), i32>(10_000, "message", ()).unwrap(), Some(ERR_PERMISSION));
+    assert_eq!(denied.call::<(), i64>(10_000, "block", ()).unwrap(), Some(i64::from(ERR_PERMISSION)));
+    assert_eq!(denied.call::<(), i32>(10_000, "missing", ()).unwrap(), None);
+
+    let mut allowed = runtime(r#""commands", "messages", "level-read""#, 2 * PAGE).unwrap();
+    assert_eq!(allowed.call::<(), i32>(10_000, "register", ()).unwrap(), Some(0));
+    // There is no such player and no server.
+    assert_eq!(allowed.call::<(), i32>(10_000, "message", ()).unwrap(), Some(ERR_INVALID));
+    assert_eq!(allowed.call::<(), i64>(10_000, "block", ()).unwrap(), Some(i64::from(ERR_INVALID)));
+
+    // Memory cannot grow beyond the limit.
+    assert_eq!(allowed.call::<i32, i32>(10_000, "grow", 1).unwrap(), Some(1));
+    assert_eq!(allowed.call::<i32, i32>(10_000, "grow", 1).unwrap(), Some(-1));
+    assert_eq!(denied.call::<i32, i32>(10_000, "grow", 1).unwrap(), Some(-1));
+    assert!(runtime("", PAGE / 2).is_err());
+}
+
+#[test]
+fn flat_generator() {
+    use crate::level::generator::{FlatGenerator, FlatPreset, Generator, VoidGenerator};
+    use level::{BlockStates, SubChunk};
+    use proto::types::Dimension;
+    use util::Vector;
+
+    let preset: FlatPreset = "minecraft:bedrock, 2*dirt,minecraft:grass;4".parse().unwrap();
+    assert_eq!(preset.layers, [("minecraft:bedrock".to_owned(), 1), ("minecraft:dirt".to_owned(), 2), ("minecraft:grass".to_owned(), 1)]);
+    assert_eq!(preset.biome, 4);
+    assert!("x*minecraft:dirt".parse::<FlatPreset>().is_err());
+
+    let json = r#"{"biome_id":1,"block_layers":[{"block_name":"minecraft:bedrock","count":1},{"block_name":"minecraft:dirt","count":2},{"block_name":"minecraft:grass","count":1}],"encoding_version":6,"structure_options":null,"world_version":"version.post_1_18"}"#;
+    assert_eq!(FlatPreset::from_json(json).unwrap(), FlatPreset::default());
+
+    let states = BlockStates::new().unwrap();
+    let name = |subchunk: &SubChunk, position: [u8; 3]| subchunk.layers[0].get(position).unwrap().name.clone();
+
+    let column = FlatGenerator::new(&preset, &states).unwrap().generate(Vector::from([3, -7]), Dimension::Overworld).unwrap();
+    assert_eq!(column.subchunks.len(), 1);
+    assert_eq!(column.subchunks[0].index, -4);
+    assert_eq!(name(&column.subchunks[0], [0, 0, 0]), "minecraft:bedrock");
+    assert_eq!(name(&column.subchunks[0], [5, 2, 9]), "minecraft:dirt");
+    assert_eq!(name(&column.subchunks[0], [15, 3, 15]), "minecraft:grass");
+    assert_eq!(name(&column.subchunks[0], [0, 4, 0]), "minecraft:air");
+    assert_eq!(column.biomes.heightmap[0][0], 4);
+    assert_eq!(column.biomes.fragments.len(), 24);
+
+    // Unset spawn points are placed directly above the top layer.
+    let flat = FlatGenerator::new(&preset, &states).unwrap();
+    assert_eq!(flat.surface_height(-5, 20, Dimension::Overworld).unwrap(), -60);
+
+    // The platform is centered on the spawn point and spans multiple columns.
+    let void = VoidGenerator::new(Vector::from([0, 64, 0]), &states).unwrap();
+    let column = void.generate(Vector::from([-1, 0]), Dimension::Overworld).unwrap();
+    assert_eq!(column.subchunks.len(), 1);
+    assert_eq!(column.subchunks[0].index, 3);
+    assert_eq!(name(&column.subchunks[0], [14, 15, 2]), "minecraft:stone");
+    assert_eq!(name(&column.subchunks[0], [13, 15, 2]), "minecraft:air");
+    assert_eq!(name(&column.subchunks[0], [14, 15, 3]), "minecraft:air");
+    assert!(void.generate(Vector::from([1, 0]), Dimension::Overworld).unwrap().subchunks.is_empty());
+    assert!(void.generate(Vector::from([0, 0]), Dimension::Nether).unwrap().subchunks.is_empty());
+
+    let void = VoidGenerator::new(Vector::from([0, level::provider::UNSET_SPAWN_Y, 0]), &states).unwrap();
+    assert_eq!(void.surface_height(0, 0, Dimension::Overworld).unwrap(), 64);
+}
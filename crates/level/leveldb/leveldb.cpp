@@ -47,12 +47,15 @@ struct Database
     }
 };
 
-LevelResult db_open(const char *path)
+LevelResult db_open(const char *path, bool create)
 {
     LevelResult result{};
 
     std::unique_ptr<Database> database = std::make_unique<Database>();
 
+    // A new database must not overwrite an existing one.
+    database->options.create_if_missing = create;
+    database->options.error_if_exists = create;
     database->options.filter_policy = leveldb::NewBloomFilterPolicy(10);
     database->options.block_cache = leveldb::NewLRUCache(40 * 1024 * 1024);
     database->options.info_log = new NoOpLogger();
@@ -271,7 +274,7 @@ void batch_clear(void* batchPtr) {
 }
 
 void batch_destroy(void* batchPtr) {
-    auto batch = reinterpret_cast<void*>(batchPtr);
+    auto batch = reinterpret_cast<leveldb::WriteBatch*>(batchPtr);
     delete batch;
 }
 
@@ -280,9 +283,11 @@ LevelResult batch_execute(void* dbPtr, void* batchPtr) {
     auto batch = reinterpret_cast<leveldb::WriteBatch*>(batchPtr);    
 
     // Use synchronous write with batches.
-    db->write_options.sync = true;
+    // The options are copied so that concurrent writes using the shared options are not affected.
+    leveldb::WriteOptions options = db->write_options;
+    options.sync = true;
 
-    leveldb::Status status = db->database->Write(db->write_options, batch);
+    leveldb::Status status = db->database->Write(options, batch);
 
     LevelResult result;
     result.status = translate_status(status);
@@ -300,7 +305,5 @@ LevelResult batch_execute(void* dbPtr, void* batchPtr) {
         memcpy(result.data, src, src_size);
     }
 
-    db->write_options.sync = false;
-
     return result;
 }
\ No newline at end of file
@@ -25,8 +25,8 @@ struct SizedData {
     void* data;
 };
 
-// Open a LevelDB database.
-struct LevelResult db_open(const char *path);
+// Open a LevelDB database, or create a new one if `create` is set.
+struct LevelResult db_open(const char *path, bool create);
 
 // Close a LevelDB database.
 // This also frees the pointers, it must no longer be used.
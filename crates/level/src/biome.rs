@@ -9,7 +9,7 @@ const HEIGHTMAP_SIZE: usize = 512;
 ///
 /// This biome format is just like the sub chunk format.
 /// Every block is an index into the palette, which is a list of biome IDs.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BiomeStorage {
     /// Indices into the biome palette.
     pub indices: Box<[u16; 4096]>,
@@ -32,7 +32,7 @@ impl BiomeStorage {
 }
 
 /// Represents the three different biome formats.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum BiomeEncoding {
     /// This sub chunk inherits all data from the previous sub chunk.
     Inherit,
@@ -45,7 +45,7 @@ pub enum BiomeEncoding {
 /// Describes the biomes contained in a single full size chunk.
 ///
 /// The biome consists of a heightmap and a biome fragment for each sub chunk.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Biomes {
     /// Highest blocks in the chunk.
     pub heightmap: Box<[[u16; 16]; 16]>,
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+use proto::types::Dimension;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use util::Vector;
+
+use crate::{provider::Provider, Biomes, DataKey, KeyType, SubChunk};
+
+/// Chunk format version written for generated columns.
+const CHUNK_VERSION: u8 = 40;
+/// Finalized state written for generated columns, indicating that the column is fully generated.
+const FINALIZED_STATE: i32 = 2;
+
+/// Subchunks and columns waiting to be written to the database.
+///
+/// Only the most recent version of every subchunk and column is kept,
+/// so repeated modifications are coalesced into a single write.
+#[derive(Debug, Clone, Default)]
+pub struct ChangeSet {
+    /// Subchunks indexed by dimension and subchunk coordinates.
+    subchunks: HashMap<(Dimension, Vector<i32, 3>), SubChunk>,
+    /// Biomes of generated columns, indexed by dimension and chunk coordinates.
+    columns: HashMap<(Dimension, Vector<i32, 2>), Biomes>,
+}
+
+impl ChangeSet {
+    /// Adds a subchunk, replacing any older version of the same subchunk.
+    pub fn insert_subchunk(&mut self, dimension: Dimension, coordinates: Vector<i32, 3>, subchunk: SubChunk) {
+        self.subchunks.insert((dimension, coordinates), subchunk);
+    }
+
+    /// Adds the biomes of a generated column, replacing any older version of the same column.
+    ///
+    /// The column is marked as generated in the same batch that writes it.
+    pub fn insert_column(&mut self, dimension: Dimension, coordinates: Vector<i32, 2>, biomes: Biomes) {
+        self.columns.insert((dimension, coordinates), biomes);
+    }
+
+    /// Number of subchunks in this set.
+    #[inline]
+    pub fn subchunk_count(&self) -> usize {
+        self.subchunks.len()
+    }
+
+    /// Number of columns in this set.
+    #[inline]
+    pub fn column_count(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// Whether there is nothing to write.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.subchunks.is_empty() && self.columns.is_empty()
+    }
+}
+
+impl Provider {
+    /// Serializes the changes and writes them to the database in a single batch.
+    ///
+    /// Either all changes are written or none of them are. The set itself is left untouched,
+    /// so it can be retried after a failure.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if any of the changes could not be serialized or if the batch could not be written.
+    pub fn commit(&self, changes: &ChangeSet) -> anyhow::Result<()> {
+        let serialized = changes
+            .subchunks
+            .par_iter()
+            .map(|(&(dimension, ref coordinates), subchunk)| Ok((dimension, coordinates, subchunk.serialize_disk()?)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mut batch = Provider::batch();
+        for (dimension, coordinates, data) in serialized {
+            let key = DataKey {
+                coordinates: Vector::from([coordinates.x, coordinates.z]),
+                dimension,
+                data: KeyType::SubChunk { index: coordinates.y as i8 },
+            };
+
+            batch.put_key(key, data)?;
+        }
+
+        for (&(dimension, ref coordinates), biomes) in &changes.columns {
+            let key = |data| DataKey { coordinates: coordinates.clone(), dimension, data };
+
+            let mut serialized = Vec::new();
+            biomes.serialize(&mut serialized)?;
+
+            batch.put_key(key(KeyType::Biome3d), serialized)?;
+            batch.put_key(key(KeyType::ChunkVersion), [CHUNK_VERSION])?;
+            batch.put_key(key(KeyType::FinalizedState), FINALIZED_STATE.to_le_bytes())?;
+        }
+
+        self.execute(&batch)
+    }
+}
@@ -177,12 +177,29 @@ impl Database {
     where
         P: AsRef<str>,
     {
-        let ffi_path = CString::new(path.as_ref())?;
+        Self::open_with(path.as_ref(), false)
+    }
+
+    /// Creates a new empty database at the specified path.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if a database already exists at the path or if it could not be created.
+    pub fn create<P>(path: P) -> anyhow::Result<Self>
+    where
+        P: AsRef<str>,
+    {
+        Self::open_with(path.as_ref(), true)
+    }
+
+    /// Opens the database at the specified path, creating it if `create` is set.
+    fn open_with(path: &str, create: bool) -> anyhow::Result<Self> {
+        let ffi_path = CString::new(path)?;
 
         // SAFETY: This function is guaranteed to not return exceptions.
         // It also does not modify the argument and returns a valid struct.
         unsafe {
-            let result = ffi::db_open(ffi_path.as_ptr());
+            let result = ffi::db_open(ffi_path.as_ptr(), create);
             if result.status == LoadStatus::Success {
                 if result.data.is_null() {
                     tracing::error!("Received database was a null pointer despite the result being marked successful");
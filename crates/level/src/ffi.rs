@@ -34,8 +34,8 @@ pub struct LevelResult {
 }
 
 extern "C" {
-    /// Open a LevelDB database.
-    pub fn db_open(path: *const c_char) -> LevelResult;
+    /// Open a LevelDB database, or create a new one if `create` is set.
+    pub fn db_open(path: *const c_char, create: bool) -> LevelResult;
     /// Close a LevelDB database.
     /// This also frees the pointers, it must no longer be used.
     pub fn db_close(database: *mut c_void);
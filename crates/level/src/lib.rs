@@ -165,9 +165,9 @@ mod test;
 
 mod batch;
 mod biome;
+mod changes;
 mod ffi;
 mod key;
-mod settings;
 mod states;
 mod subchunk;
 
@@ -175,9 +175,12 @@ mod subchunk;
 pub mod database;
 /// Implements serialization and deserialization for important types.
 pub mod provider;
+/// Settings of a level, stored in the `level.dat` file.
+pub mod settings;
 
 pub use batch::*;
 pub use biome::*;
+pub use changes::*;
 pub use key::*;
 pub use states::*;
 pub use subchunk::*;
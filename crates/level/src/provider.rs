@@ -2,14 +2,49 @@
 
 use crate::biome::Biomes;
 use crate::database::Database;
-use crate::settings::LevelSettings;
+use crate::settings::{GeneratorType, LevelSettings};
 use crate::{DataKey, KeyType, SubChunk, WriteBatch};
 use anyhow::anyhow;
 use proto::types::Dimension;
+use std::fs::File;
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use util::BinaryRead;
+use std::time::{SystemTime, UNIX_EPOCH};
+use util::{BinaryRead, BinaryWrite};
 use util::Vector;
 
+/// Spawn Y coordinate indicating that the spawn point should be placed on the surface.
+///
+/// This is the value the vanilla game uses for new worlds.
+pub const UNSET_SPAWN_Y: i32 = i16::MAX as i32;
+
+/// Options used to create a new world with [`Provider::create`].
+#[derive(Debug, Clone)]
+pub struct CreateOptions {
+    /// Name of the world.
+    pub name: String,
+    /// Seed used for world generation.
+    pub seed: i64,
+    /// Coordinates of the world spawn point.
+    ///
+    /// If this is `None`, the spawn point is stored as [`UNSET_SPAWN_Y`] above the origin,
+    /// so it can be placed on the surface once the terrain is known.
+    pub spawn: Option<Vector<i32, 3>>,
+    /// Generator that creates new chunks.
+    pub generator: GeneratorType,
+}
+
+impl Default for CreateOptions {
+    fn default() -> Self {
+        Self {
+            name: "Mirai".to_owned(),
+            seed: 0,
+            spawn: None,
+            generator: GeneratorType::Infinite,
+        }
+    }
+}
+
 /// Provides world data.
 ///
 /// This is a wrapper around a database that also deserialises and serialises data.
@@ -35,6 +70,74 @@ impl Provider {
         Ok(Self { database, path: path.as_ref().to_owned() })
     }
 
+    /// Creates a new empty world at the specified path.
+    ///
+    /// This creates an empty database and a `level.dat` file containing the default settings
+    /// combined with the given options.
+    ///
+    /// The world is created in a temporary directory next to the path and moved into place once it is complete,
+    /// so a crash cannot leave behind a database without a `level.dat` file.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if a world or any other non-empty directory already exists at the given path
+    /// or if the world could not be created.
+    pub fn create<P>(path: P, options: CreateOptions) -> anyhow::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        if path.join("level.dat").exists() {
+            anyhow::bail!("A world already exists at {}", path.display());
+        }
+        if path.exists() && path.read_dir()?.next().is_some() {
+            anyhow::bail!("{} is not empty and does not contain a world", path.display());
+        }
+
+        let name = path.file_name().ok_or_else(|| anyhow!("Invalid level path"))?;
+        let mut temporary = path.to_owned();
+        temporary.set_file_name(format!("{}.creating", name.to_string_lossy()));
+
+        // Remove the remains of a creation that was interrupted.
+        if temporary.exists() {
+            std::fs::remove_dir_all(&temporary)?;
+        }
+        std::fs::create_dir_all(&temporary)?;
+
+        let database = Database::create(temporary.join("db").to_str().ok_or_else(|| anyhow!("Invalid level path"))?)?;
+        let provider = Self { database, path: temporary.clone() };
+
+        let spawn = options.spawn.unwrap_or_else(|| Vector::from([0, UNSET_SPAWN_Y, 0]));
+        let last_played = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs() as i64);
+        let settings = LevelSettings {
+            level_name: options.name,
+            random_seed: options.seed,
+            spawn_x: spawn.x,
+            spawn_y: spawn.y,
+            spawn_z: spawn.z,
+            generator: options.generator as i32,
+            last_played,
+            ..LevelSettings::default()
+        };
+
+        provider.save_settings(&settings)?;
+        std::fs::write(temporary.join("levelname.txt"), &settings.level_name)?;
+        drop(provider);
+
+        if path.exists() {
+            std::fs::remove_dir(path)?;
+        }
+        std::fs::rename(&temporary, path)?;
+
+        // Make sure the rename has been written before the world is used.
+        #[cfg(unix)]
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            File::open(parent)?.sync_all()?;
+        }
+
+        Self::open(path)
+    }
+
     /// Gets the world settings, encoded in the `level.dat` file.
     ///
     /// # Errors
@@ -54,8 +157,44 @@ impl Provider {
             anyhow::bail!("Invalid `level.dat` file: header specified length of {file_size} bytes, but found {remaining}");
         }
 
-        let (settings, _) = nbt::from_le_bytes(&mut reader)?;
-        Ok(settings)
+        LevelSettings::from_nbt(reader)
+    }
+
+    /// Writes the world settings to the `level.dat` file.
+    ///
+    /// The previous file is kept as `level.dat_old`, like the vanilla game does.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the settings could not be encoded or the file could not be written.
+    #[tracing::instrument(skip_all, name = "Provider::save_settings")]
+    pub fn save_settings(&self, settings: &LevelSettings) -> anyhow::Result<()> {
+        let data = settings.to_nbt()?;
+
+        let mut raw = Vec::with_capacity(8 + data.len());
+        raw.write_u32_le(settings.storage_version as u32)?;
+        raw.write_u32_le(data.len() as u32)?;
+        raw.extend_from_slice(&data);
+
+        // Write to a temporary file first so that a crash cannot leave behind a truncated file.
+        let path = self.path.join("level.dat");
+        let temporary = self.path.join("level.dat_new");
+
+        let mut file = File::create(&temporary)?;
+        file.write_all(&raw)?;
+        file.sync_all()?;
+        drop(file);
+
+        if path.exists() {
+            std::fs::copy(&path, self.path.join("level.dat_old"))?;
+        }
+        std::fs::rename(temporary, path)?;
+
+        // The rename is only durable once the directory entry has been written as well.
+        #[cfg(unix)]
+        File::open(&self.path)?.sync_all()?;
+
+        Ok(())
     }
 
     /// Load the version of the specified chunk.
@@ -148,6 +287,17 @@ impl Provider {
         }
     }
 
+    /// Atomically applies all operations in the batch.
+    ///
+    /// Either every operation in the batch is written to the database or none of them are.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the batch could not be written.
+    pub fn execute(&self, batch: &WriteBatch) -> anyhow::Result<()> {
+        self.database.execute(batch)
+    }
+
     /// Create a new write batch that can optionally be used in write operations.
     #[inline]
     pub fn batch() -> WriteBatch {
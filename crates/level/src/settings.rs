@@ -1,4 +1,11 @@
-#[derive(serde::Deserialize, Debug, PartialEq)]
+#![allow(missing_docs)] // Fields mirror the keys of the `level.dat` file.
+
+use std::collections::HashMap;
+
+use util::RVec;
+
+/// Default abilities of players.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
 pub struct Abilities {
     #[serde(rename = "attackmobs")]
     pub attack_mobs: bool,
@@ -24,22 +31,22 @@ pub struct Abilities {
     pub walk_speed: f32,
 }
 
-#[derive(serde::Deserialize, Debug, PartialEq, Eq)]
-#[serde(deny_unknown_fields)]
+/// Experimental features used by the level.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct Experiments {
     pub experiments_ever_used: bool,
     pub saved_with_toggled_experiments: bool,
 }
 
-#[derive(serde::Deserialize, Debug, PartialEq, Eq)]
-#[serde(deny_unknown_fields)]
+/// World policies.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct Policies {
     // Not sure what is supposed to be in here
 }
 
-#[derive(serde::Deserialize, Debug, PartialEq)]
+/// Contents of the `level.dat` file.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
 #[serde(rename_all = "camelCase")]
-#[serde(deny_unknown_fields)]
 pub struct LevelSettings {
     pub editor_world_type: i32,
     #[serde(rename = "isCreatedInEditor")]
@@ -217,4 +224,247 @@ pub struct LevelSettings {
     pub prid: String,
     #[serde(rename = "world_policies")]
     pub world_policies: Policies,
+    /// Fields that are not represented by this struct.
+    ///
+    /// These are kept so that they are not lost when the settings are written back to disk.
+    #[serde(skip)]
+    pub unknown: HashMap<String, nbt::Value>,
+}
+
+/// World generator stored in the `Generator` field of the settings.
+#[derive(serde::Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[repr(i32)]
+pub enum GeneratorType {
+    /// The old generator that creates worlds of limited size.
+    Limited = 0,
+    /// The default generator.
+    Infinite = 1,
+    /// Flat world made out of layers.
+    Flat = 2,
+    /// Empty world.
+    Void = 5,
+}
+
+impl TryFrom<i32> for GeneratorType {
+    type Error = anyhow::Error;
+
+    fn try_from(value: i32) -> anyhow::Result<Self> {
+        Ok(match value {
+            0 => Self::Limited,
+            1 => Self::Infinite,
+            2 => Self::Flat,
+            5 => Self::Void,
+            _ => anyhow::bail!("Invalid generator type {value}"),
+        })
+    }
+}
+
+impl Default for Abilities {
+    fn default() -> Self {
+        Self {
+            attack_mobs: true,
+            attack_players: true,
+            build: true,
+            doors_and_switches: true,
+            flying: false,
+            instant_build: false,
+            invulnerable: false,
+            lightning: false,
+            mayfly: false,
+            mine: true,
+            op: false,
+            open_containers: true,
+            teleport: false,
+            fly_speed: 0.05,
+            walk_speed: 0.1,
+        }
+    }
+}
+
+impl Default for LevelSettings {
+    /// Settings of a new survival world, using the default gamerule values.
+    #[allow(clippy::too_many_lines)]
+    fn default() -> Self {
+        Self {
+            editor_world_type: 0,
+            created_in_editor: false,
+            exported_from_editor: false,
+            random_seed_allowed: false,
+            sleeping_percentage: 100,
+            recipes_unlock: true,
+            cheats_enabled: false,
+            lightning_level: 0.0,
+            lightning_time: 0,
+            rain_level: 0.0,
+            rain_time: 0,
+            difficulty: 2,
+            game_mode: 0,
+            generator: GeneratorType::Infinite as i32,
+            limited_world_origin_x: 0,
+            limited_world_origin_y: i32::from(i16::MAX),
+            limited_world_origin_z: 0,
+            limited_world_depth: 16,
+            limited_world_width: 16,
+            minimum_compatible_client_version: [1, 21, 0, 0, 0],
+            nether_scale: 8,
+            network_version: proto::bedrock::PROTOCOL_VERSION as i32,
+            platform: 2,
+            platform_broadcast_intent: 2,
+            random_seed: 0,
+            spawn_v1_villagers: false,
+            spawn_x: 0,
+            spawn_y: i32::from(i16::MAX),
+            spawn_z: 0,
+            storage_version: 10,
+            time: 0,
+            world_version: 1,
+            xbox_broadcast_intent: 1,
+            current_tick: 0,
+            experiments: Experiments { experiments_ever_used: false, saved_with_toggled_experiments: false },
+            abilities: Abilities::default(),
+            edu_offer: 0,
+            education_features_enabled: false,
+            last_opened_with_version: [1, 21, 0, 0, 0],
+            bonus_chest_enabled: false,
+            bonus_chest_spawned: false,
+            command_block_output: true,
+            center_maps_to_origin: false,
+            command_blocks_enabled: true,
+            commands_enabled: true,
+            confirmed_platform_locked_content: false,
+            daylight_cycle: 0,
+            daylight_lock: true,
+            limited_crafting: false,
+            entity_drops: true,
+            fire_tick: true,
+            immediate_respawn: false,
+            insomnia: true,
+            mob_loot: true,
+            mob_spawning: true,
+            tile_drops: true,
+            weather_cycle: true,
+            drowning_damage: true,
+            fall_damage: true,
+            fire_damage: true,
+            freeze_damage: true,
+            keep_inventory: false,
+            max_command_chain_length: 65_536,
+            mob_griefing: true,
+            natural_regeneration: true,
+            function_command_limit: 10_000,
+            pvp: true,
+            random_tick_speed: 1,
+            respawn_blocks_explode: true,
+            send_command_feedback: true,
+            show_border_effect: true,
+            show_coordinates: true,
+            show_death_messages: true,
+            show_tags: true,
+            spawn_radius: 10,
+            tnt_explodes: true,
+            force_game_mode: false,
+            has_been_loaded_in_creative: false,
+            has_locked_behavior_pack: false,
+            has_locked_resource_pack: false,
+            immutable_world: false,
+            is_from_locked_template: false,
+            is_from_world_template: false,
+            is_single_use_world: false,
+            is_world_template_option_locked: false,
+            requires_copied_pack_removal_check: false,
+            texture_packs_required: false,
+            lan_broadcast: true,
+            lan_broadcast_intent: 1,
+            multiplayer_game: true,
+            multiplayer_game_intent: 1,
+            last_played: 0,
+            base_game_version: "*".to_owned(),
+            biome_override: String::new(),
+            flat_world_layers: String::new(),
+            inventory_version: "1.21.0".to_owned(),
+            level_name: "Mirai".to_owned(),
+            use_msa_gamertags_only: false,
+            world_start_count: 0,
+            start_with_map_enabled: false,
+            spawn_mobs: true,
+            server_chunk_tick_range: 12,
+            permissions_level: 0,
+            player_permissions_level: 1,
+            prid: String::new(),
+            world_policies: Policies {},
+            unknown: HashMap::new(),
+        }
+    }
+}
+
+impl LevelSettings {
+    /// Decodes the settings from a little endian NBT compound.
+    ///
+    /// Fields that are not known are stored in [`unknown`](Self::unknown).
+    pub fn from_nbt(mut data: &[u8]) -> anyhow::Result<LevelSettings> {
+        let (compound, _) = nbt::from_le_bytes(&mut data)?;
+        Self::from_compound(compound)
+    }
+
+    /// Encodes the settings into a little endian NBT compound, including all unknown fields.
+    pub fn to_nbt(&self) -> anyhow::Result<RVec> {
+        nbt::to_le_bytes(&self.to_compound()?)
+    }
+
+    /// Converts a compound into settings.
+    ///
+    /// Fields that are not known are stored in [`unknown`](Self::unknown).
+    pub fn from_compound(compound: HashMap<String, nbt::Value>) -> anyhow::Result<LevelSettings> {
+        let encoded = nbt::to_le_bytes(&compound)?;
+        let (mut settings, _): (LevelSettings, _) = nbt::from_le_bytes(&mut encoded.as_ref())?;
+
+        let known = settings.known_fields()?;
+        settings.unknown = unknown_fields(compound, &known);
+
+        Ok(settings)
+    }
+
+    /// Converts the settings into a compound, including all unknown fields.
+    pub fn to_compound(&self) -> anyhow::Result<HashMap<String, nbt::Value>> {
+        let mut compound = self.unknown.clone();
+        merge_fields(&mut compound, self.known_fields()?);
+
+        Ok(compound)
+    }
+
+    /// Returns the fields that are represented by this struct.
+    fn known_fields(&self) -> anyhow::Result<HashMap<String, nbt::Value>> {
+        let encoded = nbt::to_le_bytes(self)?;
+        let (known, _) = nbt::from_le_bytes(&mut encoded.as_ref())?;
+
+        Ok(known)
+    }
+}
+
+/// Removes all fields from `compound` that are present in `known`, descending into nested compounds.
+fn unknown_fields(compound: HashMap<String, nbt::Value>, known: &HashMap<String, nbt::Value>) -> HashMap<String, nbt::Value> {
+    compound
+        .into_iter()
+        .filter_map(|(key, value)| match (value, known.get(&key)) {
+            (value, None) => Some((key, value)),
+            (nbt::Value::Compound(nested), Some(nbt::Value::Compound(known))) => {
+                let nested = unknown_fields(nested, known);
+                (!nested.is_empty()).then_some((key, nbt::Value::Compound(nested)))
+            }
+            (_, Some(_)) => None,
+        })
+        .collect()
+}
+
+/// Inserts all fields of `known` into `compound`, merging nested compounds.
+fn merge_fields(compound: &mut HashMap<String, nbt::Value>, known: HashMap<String, nbt::Value>) {
+    for (key, value) in known {
+        match (compound.get_mut(&key), value) {
+            (Some(nbt::Value::Compound(nested)), nbt::Value::Compound(known)) => merge_fields(nested, known),
+            (_, value) => {
+                compound.insert(key, value);
+            }
+        }
+    }
 }
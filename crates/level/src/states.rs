@@ -154,7 +154,7 @@
 //     }
 // }
 
-use std::{collections::HashMap, sync::atomic::Ordering};
+use std::{cmp::Reverse, collections::HashMap, sync::atomic::Ordering};
 
 use nohash_hasher::{BuildNoHashHasher, IntMap};
 use proto::bedrock::{ItemStack, ItemType, SHIELD_ID};
@@ -300,11 +300,83 @@ impl ItemNetworkIds {
 
 const BLOCK_STATES_RAW: &[u8] = include_bytes!("../include/block_states.nbt");
 
+/// Vanilla default values of string block properties.
+///
+/// The block state data does not mark default states, and its values are not ordered.
+/// Properties that are not listed here have no preferred value.
+const DEFAULT_PROPERTIES: &[(&str, &str)] = &[
+    ("pillar_axis", "y"),
+    ("minecraft:cardinal_direction", "south"),
+    ("minecraft:block_face", "down"),
+    ("minecraft:facing_direction", "down"),
+    ("minecraft:vertical_half", "bottom"),
+    ("wood_type", "oak"),
+    ("old_log_type", "oak"),
+    ("new_log_type", "acacia"),
+    ("old_leaf_type", "oak"),
+    ("new_leaf_type", "acacia"),
+    ("sapling_type", "oak"),
+    ("stone_type", "stone"),
+    ("dirt_type", "normal"),
+    ("sand_type", "normal"),
+    ("color", "white"),
+    ("sponge_type", "dry"),
+    ("chisel_type", "default"),
+    ("sand_stone_type", "default"),
+    ("stone_brick_type", "default"),
+    ("prismarine_block_type", "default"),
+    ("monster_egg_stone_type", "stone"),
+    ("wall_block_type", "cobblestone"),
+    ("stone_slab_type", "smooth_stone"),
+    ("stone_slab_type_2", "red_sandstone"),
+    ("stone_slab_type_3", "end_stone_brick"),
+    ("stone_slab_type_4", "mossy_stone_brick"),
+    ("flower_type", "poppy"),
+    ("double_plant_type", "sunflower"),
+    ("tall_grass_type", "default"),
+    ("sea_grass_type", "default"),
+    ("coral_color", "blue"),
+    ("torch_facing_direction", "unknown"),
+    ("lever_direction", "down_east_west"),
+    ("portal_axis", "unknown"),
+    ("orientation", "down_east"),
+    ("attachment", "standing"),
+    ("damage", "undamaged"),
+    ("cauldron_liquid", "water"),
+    ("turtle_egg_count", "one_egg"),
+    ("cracked_state", "no_cracks"),
+    ("dripstone_thickness", "tip"),
+    ("big_dripleaf_tilt", "none"),
+    ("bamboo_leaf_size", "no_leaves"),
+    ("bamboo_stalk_thickness", "thin"),
+    ("wall_connection_type_east", "none"),
+    ("wall_connection_type_north", "none"),
+    ("wall_connection_type_south", "none"),
+    ("wall_connection_type_west", "none"),
+];
+
+/// Counts the properties of a block state that are set to their vanilla default value.
+fn default_properties(state: &PaletteEntry) -> usize {
+    state
+        .states
+        .iter()
+        .filter(|(property, value)| match value {
+            nbt::Value::Byte(value) => *value == 0,
+            nbt::Value::Short(value) => *value == 0,
+            nbt::Value::Int(value) => *value == 0,
+            nbt::Value::String(value) => DEFAULT_PROPERTIES.contains(&(property.as_str(), value.as_str())),
+            _ => false,
+        })
+        .count()
+}
+
 /// Maps block states to runtime IDs.
 #[derive(Debug, Default)]
 pub struct BlockStates {
     /// Converts state hashes to runtime IDs.
     runtime_hashes: HashMap<u64, u32, BuildNoHashHasher<u64>>,
+    /// Converts runtime IDs back to block states.
+    runtime_states: IntMap<u32, PaletteEntry>,
     air_id: u32,
 }
 
@@ -322,6 +394,7 @@ impl BlockStates {
 
         let mut states = Self {
             runtime_hashes: HashMap::with_capacity_and_hasher(STATE_COUNT, BuildNoHashHasher::default()),
+            runtime_states: IntMap::with_capacity_and_hasher(STATE_COUNT, BuildNoHashHasher::default()),
             air_id: 0,
         };
 
@@ -352,10 +425,28 @@ impl BlockStates {
         self.runtime_hashes.get(&hash).copied()
     }
 
+    /// Converts a runtime ID back into its block state.
+    pub fn entry(&self, runtime_id: u32) -> Option<&PaletteEntry> {
+        self.runtime_states.get(&runtime_id)
+    }
+
     pub const fn air(&self) -> u32 {
         self.air_id
     }
 
+    /// Returns the default state of the block with the given name.
+    ///
+    /// This is the state with the most properties set to their vanilla default value. Numeric and boolean properties
+    /// default to zero and string properties use the values in [`DEFAULT_PROPERTIES`].
+    /// If multiple states match equally well, the one with the lowest runtime ID is returned.
+    pub fn default_state(&self, name: &str) -> Option<&PaletteEntry> {
+        self.runtime_states
+            .iter()
+            .filter(|(_, state)| state.name == name)
+            .max_by_key(|(&id, state)| (default_properties(state), Reverse(id)))
+            .map(|(_, state)| state)
+    }
+
     pub fn register(&mut self, state: PaletteEntry) -> anyhow::Result<()> {
         // tracing::debug!("register {state:?}");
 
@@ -368,6 +459,7 @@ impl BlockStates {
         }
 
         self.runtime_hashes.insert(hash, new_id as u32);
+        self.runtime_states.insert(new_id as u32, state);
 
         Ok(())
     }
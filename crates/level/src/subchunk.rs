@@ -66,7 +66,7 @@ mod block_version {
 }
 
 /// Definition of block in the sub chunk block palette.
-#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 #[serde(rename = "")]
 pub struct PaletteEntry {
     /// Name of the block.
@@ -107,7 +107,7 @@ impl PaletteEntry {
 /// This is prefixed with a 32-bit little endian integer specifying the size of the palette.
 /// The rest of the palette then consists of `n` concatenated NBT compounds.
 #[doc(alias = "storage record")]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct SubStorage {
     /// List of indices into the palette.
     ///
@@ -144,6 +144,43 @@ impl SubStorage {
         Some(&self.palette[index])
     }
 
+    /// Replaces the block at the given position, returning the block that was there before.
+    ///
+    /// The entry is added to the palette if it does not exist yet. If the old block is not referenced
+    /// anywhere else in the layer, its palette slot is reused instead.
+    ///
+    /// This returns `None` if the position is out of bounds or the layer has no palette.
+    pub fn set<V>(&mut self, pos: V, entry: PaletteEntry) -> Option<PaletteEntry>
+    where
+        V: Into<Vector<u8, 3>>,
+    {
+        let pos = pos.into();
+        if pos.x >= 16 || pos.y >= 16 || pos.z >= 16 || self.palette.is_empty() {
+            return None;
+        }
+
+        let offset = to_offset(pos);
+        let old_index = self.indices[offset];
+        if self.palette[old_index as usize] == entry {
+            return Some(entry);
+        }
+
+        if let Some(index) = self.palette.iter().position(|e| *e == entry) {
+            self.indices[offset] = index as u16;
+            return Some(self.palette[old_index as usize].clone());
+        }
+
+        let shared = self.indices.iter().enumerate().any(|(i, index)| i != offset && *index == old_index);
+        if shared {
+            self.indices[offset] = self.palette.len() as u16;
+            self.palette.push(entry);
+
+            Some(self.palette[old_index as usize].clone())
+        } else {
+            Some(std::mem::replace(&mut self.palette[old_index as usize], entry))
+        }
+    }
+
     // FIXME: Using this method will modify every block with the same index
     // instead of only the block at the specified position.
     // pub fn get_mut(&mut self, pos: Vector<u8, 3>) -> Option<&mut PaletteEntry> {
@@ -309,7 +346,7 @@ pub fn from_offset(offset: usize) -> Vector<u8, 3> {
 /// A Minecraft sub chunk.
 ///
 /// Every world contains
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct SubChunk {
     /// Version of the sub chunk.
     ///
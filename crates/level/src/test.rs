@@ -1,9 +1,13 @@
-use std::sync::Mutex;
+use std::{collections::HashMap, path::PathBuf, sync::Mutex};
 
 use proto::types::Dimension;
 use util::Vector;
 
-use crate::{database::Database, provider::Provider, SubChunk};
+use crate::{
+    database::Database,
+    provider::{CreateOptions, Provider},
+    BiomeEncoding, Biomes, BlockStates, ChangeSet, PaletteEntry, SubChunk, SubChunkVersion, SubStorage,
+};
 
 // digp [x] [z] [?dimension]
 // contains two int32
@@ -157,3 +161,116 @@ fn subchunks() {
 //
 //     assert_eq!(entry, de);
 // }
+
+#[test]
+fn level_settings_roundtrip() {
+    let raw = std::fs::read("../../resources/level/level.dat").unwrap();
+    let mut settings = crate::settings::LevelSettings::from_nbt(&raw[8..]).unwrap();
+    settings.unknown.insert("customField".to_owned(), nbt::Value::Int(42));
+
+    let encoded = settings.to_nbt().unwrap();
+    let decoded = crate::settings::LevelSettings::from_nbt(encoded.as_ref()).unwrap();
+    assert_eq!(settings, decoded, "Settings changed after being written");
+}
+
+/// Creates a new empty world in the temporary directory.
+fn temp_world(name: &str) -> (PathBuf, Provider) {
+    let path = std::env::temp_dir().join(format!("mirai-{name}-{}", std::process::id()));
+    let _: Result<(), _> = std::fs::remove_dir_all(&path);
+
+    let provider = Provider::create(&path, CreateOptions::default()).unwrap();
+    (path, provider)
+}
+
+/// Creates a subchunk completely filled with a single block.
+///
+/// Blocks without a version cannot be serialized, which is used to make commits fail.
+fn filled(index: i8, name: &str, version: Option<[u8; 4]>) -> SubChunk {
+    let entry = PaletteEntry { name: name.to_owned(), version, states: HashMap::new() };
+    SubChunk {
+        version: SubChunkVersion::Limitless,
+        index,
+        layers: vec![SubStorage { indices: Box::new([0; 4096]), palette: vec![entry] }],
+    }
+}
+
+#[test]
+fn change_set_coalescing() {
+    let _lock = LOCK.lock().unwrap();
+    let (path, provider) = temp_world("coalescing");
+
+    let mut changes = ChangeSet::default();
+    changes.insert_subchunk(Dimension::Overworld, Vector::from([0, 2, 0]), filled(2, "minecraft:stone", Some([1, 21, 0, 3])));
+    changes.insert_subchunk(Dimension::Overworld, Vector::from([0, 2, 0]), filled(2, "minecraft:dirt", Some([1, 21, 0, 3])));
+    changes.insert_subchunk(Dimension::Nether, Vector::from([0, 2, 0]), filled(2, "minecraft:netherrack", Some([1, 21, 0, 3])));
+    assert_eq!(changes.subchunk_count(), 2, "Older version of the subchunk was not replaced");
+
+    provider.commit(&changes).unwrap();
+
+    let subchunk = provider.subchunk([0, 2, 0], Dimension::Overworld).unwrap().unwrap();
+    assert_eq!(subchunk.layers[0].palette[0].name, "minecraft:dirt", "Most recent version was not written");
+
+    let subchunk = provider.subchunk([0, 2, 0], Dimension::Nether).unwrap().unwrap();
+    assert_eq!(subchunk.layers[0].palette[0].name, "minecraft:netherrack");
+
+    drop(provider);
+    std::fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn change_set_atomicity() {
+    let _lock = LOCK.lock().unwrap();
+    let (path, provider) = temp_world("atomicity");
+
+    let biomes = Biomes { heightmap: Box::new([[0; 16]; 16]), fragments: vec![BiomeEncoding::Single(1)] };
+
+    let mut changes = ChangeSet::default();
+    changes.insert_subchunk(Dimension::Overworld, Vector::from([1, 0, 1]), filled(0, "minecraft:stone", Some([1, 21, 0, 3])));
+    changes.insert_subchunk(Dimension::Overworld, Vector::from([1, 1, 1]), filled(1, "minecraft:stone", None));
+    changes.insert_column(Dimension::Overworld, Vector::from([1, 1]), biomes);
+
+    // One subchunk cannot be serialized, so nothing in the set may be written.
+    provider.commit(&changes).unwrap_err();
+    assert!(provider.subchunk([1, 0, 1], Dimension::Overworld).unwrap().is_none(), "Failed commit was partially written");
+    assert!(provider.version([1, 1], Dimension::Overworld).unwrap().is_none(), "Failed commit marked the column as generated");
+
+    // The set is untouched after a failure, so it can be fixed and retried.
+    assert_eq!(changes.subchunk_count(), 2);
+    assert_eq!(changes.column_count(), 1);
+
+    changes.insert_subchunk(Dimension::Overworld, Vector::from([1, 1, 1]), filled(1, "minecraft:stone", Some([1, 21, 0, 3])));
+    provider.commit(&changes).unwrap();
+    assert!(provider.subchunk([1, 0, 1], Dimension::Overworld).unwrap().is_some());
+    assert!(provider.subchunk([1, 1, 1], Dimension::Overworld).unwrap().is_some());
+    assert_eq!(provider.version([1, 1], Dimension::Overworld).unwrap(), Some(40));
+
+    drop(provider);
+    std::fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn default_block_states() {
+    let slab = |half: &str, wood: &str| PaletteEntry {
+        name: "minecraft:wooden_slab".to_owned(),
+        version: None,
+        states: HashMap::from([
+            ("minecraft:vertical_half".to_owned(), nbt::Value::String(half.to_owned())),
+            ("wood_type".to_owned(), nbt::Value::String(wood.to_owned())),
+        ]),
+    };
+
+    // The default state is found by its properties, not by registration order.
+    let mut states = BlockStates::default();
+    states.register(slab("top", "spruce")).unwrap();
+    states.register(slab("top", "oak")).unwrap();
+    states.register(slab("bottom", "oak")).unwrap();
+    states.register(slab("bottom", "spruce")).unwrap();
+    assert_eq!(states.default_state("minecraft:wooden_slab"), Some(&slab("bottom", "oak")));
+    assert!(states.default_state("minecraft:stone").is_none());
+
+    let states = BlockStates::new().unwrap();
+    let property = |name: &str, property: &str| states.default_state(name).unwrap().states[property].clone();
+    assert_eq!(property("minecraft:oak_log", "pillar_axis"), nbt::Value::String("y".to_owned()));
+    assert_eq!(property("minecraft:blue_candle", "candles"), nbt::Value::Int(0));
+    assert_eq!(property("minecraft:cobblestone_wall", "wall_connection_type_east"), nbt::Value::String("none".to_owned()));
+}
@@ -3,7 +3,7 @@ use uuid::Uuid;
 use util::{Serialize, Vector};
 use util::{BinaryWrite};
 
-use crate::bedrock::{AbilityData, DeviceOS};
+use crate::bedrock::{AbilityData, DeviceOS, ItemInstance};
 use crate::bedrock::{ConnectedPacket, GameMode};
 
 
@@ -65,7 +65,7 @@ pub struct AddPlayer<'a> {
     /// Game mode of the player.
     pub game_mode: GameMode,
     /// Item held by the player.
-    // pub held_item: ItemStack,
+    pub held_item: ItemInstance<'a>,
     // pub metadata: HashMap<u32, nbt::Value>,
     // pub properties: EntityProperties,
     /// Abilities of the player. See [`AbilityData`].
@@ -91,7 +91,7 @@ impl Serialize for AddPlayer<'_> {
         writer.write_vecf(&self.position)?;
         writer.write_vecf(&self.velocity)?;
         writer.write_vecf(&self.rotation)?;
-        // self.held_item.serialize(buffer)?;
+        self.held_item.serialize_into(writer)?;
         writer.write_var_i32(self.game_mode as i32)?;
         // buffer.put_metadata(&self.metadata);
         writer.write_var_u32(0)?; // TODO: Entity metadata.
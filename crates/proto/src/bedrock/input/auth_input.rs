@@ -2,7 +2,22 @@ use macros::variant_count;
 
 use util::{Deserialize, BinaryRead, Vector, BlockPosition};
 
-use crate::bedrock::{ConnectedPacket, PlayerActionType, PlayerAction};
+use crate::bedrock::{ConnectedPacket, ItemInstance, PlayerActionType, TransactionAction, UseItemAction};
+
+/// Maximum amount of entries in any list of an item stack request.
+///
+/// The counts are controlled by the client, so larger lists are rejected instead of allocated.
+pub const MAX_STACK_REQUEST_ENTRIES: u32 = 256;
+
+/// Reads the length of a list in an item stack request, rejecting lengths above [`MAX_STACK_REQUEST_ENTRIES`].
+pub fn read_stack_request_count<'a, R: BinaryRead<'a>>(reader: &mut R, list: &str) -> anyhow::Result<u32> {
+    let count = reader.read_var_u32()?;
+    if count > MAX_STACK_REQUEST_ENTRIES {
+        anyhow::bail!("Too many {list} in item stack request ({count} > {MAX_STACK_REQUEST_ENTRIES})");
+    }
+
+    Ok(count)
+}
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(u64)]
@@ -50,7 +65,15 @@ pub enum InputDataFlag {
     StopCrawling = 1 << 40,
     StartFlying = 1 << 41,
     StopFlying = 1 << 42,
-    AcknowledgeServerData = 1 << 43
+    AcknowledgeServerData = 1 << 43,
+    InClientPredictedVehicle = 1 << 44,
+    PaddlingLeft = 1 << 45,
+    PaddlingRight = 1 << 46,
+    BlockBreakingDelayEnabled = 1 << 47,
+    HorizontalCollision = 1 << 48,
+    VerticalCollision = 1 << 49,
+    DownLeft = 1 << 50,
+    DownRight = 1 << 51
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -130,30 +153,7 @@ impl TryFrom<i32> for InteractionModel {
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-#[repr(u32)]
-pub enum InventoryActionSource {
-    Container = 0,
-    World = 2,
-    Creative = 3,
-    Todo = 99999
-}
-
-impl TryFrom<u32> for InventoryActionSource {
-    type Error = anyhow::Error;
-
-    fn try_from(v: u32) -> anyhow::Result<Self> {
-        Ok(match v {
-            0 => Self::Container,
-            2 => Self::World,
-            3 => Self::Creative,
-            99999 => Self::Todo,
-            _ => anyhow::bail!("Invalid inventory action source")
-        })
-    }
-}
-
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 #[repr(i32)]
 pub enum WindowId {
     DropContents = -100,
@@ -258,35 +258,6 @@ impl TryFrom<i32> for WindowId {
     }
 }
 
-#[derive(Debug)]
-pub struct InventoryAction {
-    pub source_type: InventoryActionSource,
-    pub window: Option<WindowId>,
-    pub source_flags: u32,
-    pub inventory_slot: u32
-}
-
-impl<'a> Deserialize<'a> for InventoryAction {
-    #[allow(clippy::collection_is_never_read)]
-    fn deserialize_from<R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<Self> {
-        let source_type = InventoryActionSource::try_from(reader.read_u32_le()?)?;
-        
-        let mut _window = None;
-        let mut _source_flags = 0;
-
-        if source_type == InventoryActionSource::Container || source_type == InventoryActionSource::Todo {
-            _window = Some(WindowId::try_from(reader.read_i8()? as i32)?);
-        } else if source_type == InventoryActionSource::World {
-            _source_flags = reader.read_var_u32()?;
-        }
-
-        let _inventory_slot = reader.read_var_u32()?;
-
-        // https://github.com/Sandertv/gophertunnel/blob/36e5147307884b745b7d28d546c07ab03d4afb36/minecraft/protocol/inventory.go#L52
-        todo!("Item instance reading");
-    }
-}
-
 #[derive(Debug)]
 pub struct LegacySetItemSlot<'a> {
     pub container: u8,
@@ -305,22 +276,23 @@ impl<'a> Deserialize<'a> for LegacySetItemSlot<'a> {
     }
 }
 
+/// Item transaction that is embedded in a [`PlayerAuthInput`] packet.
 #[derive(Debug)]
 pub struct TransactionData<'a> {
     pub legacy_request_id: i32,
     pub legacy_slots: Vec<LegacySetItemSlot<'a>>,
-    pub actions: Vec<InventoryAction>,
-    pub action_type: PlayerActionType,
+    pub actions: Vec<TransactionAction<'a>>,
+    pub action_type: UseItemAction,
     pub block_position: BlockPosition,
     pub block_face: i32,
     pub hotbar_slot: i32,
+    pub held_item: ItemInstance<'a>,
     pub position: Vector<f32, 3>,
     pub clicked_position: Vector<f32, 3>,
     pub block_runtime_id: u32
 }
 
 impl<'a> Deserialize<'a> for TransactionData<'a> {
-    #[allow(clippy::collection_is_never_read)]
     fn deserialize_from<R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<Self> {
         let legacy_request_id = reader.read_var_i32()?;
         let mut legacy_slots = Vec::new();
@@ -338,10 +310,59 @@ impl<'a> Deserialize<'a> for TransactionData<'a> {
         let mut actions = Vec::with_capacity(action_count as usize);
 
         for _ in 0..action_count {
-            actions.push(InventoryAction::deserialize_from(reader)?);
+            actions.push(TransactionAction::deserialize_from(reader)?);
         }
 
-        todo!()
+        Ok(Self {
+            legacy_request_id,
+            legacy_slots,
+            actions,
+            action_type: UseItemAction::try_from(reader.read_var_u32()?)?,
+            block_position: reader.read_block_pos()?,
+            block_face: reader.read_var_i32()?,
+            hotbar_slot: reader.read_var_i32()?,
+            held_item: ItemInstance::deserialize_from(reader)?,
+            position: reader.read_vecf()?,
+            clicked_position: reader.read_vecf()?,
+            block_runtime_id: reader.read_var_u32()?
+        })
+    }
+}
+
+/// Block action that is embedded in a [`PlayerAuthInput`] packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockAction {
+    /// Action that was performed.
+    pub action: PlayerActionType,
+    /// Position of the block the action was performed on.
+    ///
+    /// This is only set for actions that are related to breaking blocks.
+    pub position: BlockPosition,
+    /// Face of the block the action was performed on.
+    pub face: i32
+}
+
+impl<'a> Deserialize<'a> for BlockAction {
+    fn deserialize_from<R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<Self> {
+        let action = PlayerActionType::try_from(reader.read_var_i32()?)?;
+
+        let (position, face) = match action {
+            PlayerActionType::StartBreak |
+            PlayerActionType::AbortBreak |
+            PlayerActionType::CrackBreak |
+            PlayerActionType::PredictBreak |
+            PlayerActionType::ContinueBreak => {
+                // Unlike most block positions, the Y coordinate is a signed integer here.
+                let x = reader.read_var_i32()?;
+                let y = reader.read_var_i32()?;
+                let z = reader.read_var_i32()?;
+
+                (BlockPosition::new(x, y as u32, z), reader.read_var_i32()?)
+            },
+            _ => (BlockPosition::new(0, 0, 0), 0)
+        };
+
+        Ok(Self { action, position, face })
     }
 }
 
@@ -615,12 +636,114 @@ pub enum StackRequestAction<'a> {
     CraftLoomRecipe {
         /// Pattern to craft with the loom.
         pattern: &'a str
+    },
+    /// Deprecated action that is still sent for crafting that has not been implemented yet.
+    CraftNonImplemented,
+    /// Deprecated action that is sent after every crafting action, containing the expected results.
+    CraftResultsDeprecated {
+        /// Items that the client expects to be created.
+        result_items: Vec<ItemInstance<'a>>,
+        /// How many times the item was crafted.
+        times_crafted: u8
     }
-}   
+}
 
 impl<'a> Deserialize<'a> for StackRequestAction<'a> {
-    fn deserialize_from<R: BinaryRead<'a>>(_reader: &mut R) -> anyhow::Result<Self> {
-        todo!()
+    fn deserialize_from<R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<Self> {
+        let action_type = reader.read_u8()?;
+
+        Ok(match action_type {
+            0 => Self::Take {
+                count: reader.read_u8()?,
+                source: StackRequestSlotInfo::deserialize_from(reader)?,
+                destination: StackRequestSlotInfo::deserialize_from(reader)?
+            },
+            1 => Self::Place {
+                count: reader.read_u8()?,
+                source: StackRequestSlotInfo::deserialize_from(reader)?,
+                destination: StackRequestSlotInfo::deserialize_from(reader)?
+            },
+            2 => Self::Swap {
+                source: StackRequestSlotInfo::deserialize_from(reader)?,
+                destination: StackRequestSlotInfo::deserialize_from(reader)?
+            },
+            3 => Self::Drop {
+                count: reader.read_u8()?,
+                source: StackRequestSlotInfo::deserialize_from(reader)?,
+                randomly: reader.read_bool()?
+            },
+            4 => Self::Destroy {
+                count: reader.read_u8()?,
+                source: StackRequestSlotInfo::deserialize_from(reader)?
+            },
+            5 => Self::Consume {
+                count: reader.read_u8()?,
+                source: StackRequestSlotInfo::deserialize_from(reader)?
+            },
+            6 => Self::Create {
+                results_slot: reader.read_u8()?
+            },
+            7 => Self::PlaceInContainer {
+                count: reader.read_u8()?,
+                source: StackRequestSlotInfo::deserialize_from(reader)?,
+                destination: StackRequestSlotInfo::deserialize_from(reader)?
+            },
+            8 => Self::TakeOutContainer {
+                count: reader.read_u8()?,
+                source: StackRequestSlotInfo::deserialize_from(reader)?,
+                destination: StackRequestSlotInfo::deserialize_from(reader)?
+            },
+            9 => Self::LabTableCombine,
+            10 => Self::BeaconPayment {
+                primary_effect: reader.read_var_i32()?,
+                secondary_effect: reader.read_var_i32()?
+            },
+            11 => Self::MineBlock {
+                hotbar_slot: reader.read_var_i32()?,
+                predicted_durability: reader.read_var_i32()?,
+                stack_network_id: reader.read_var_i32()?
+            },
+            12 => Self::CraftRecipe {
+                recipe_network_id: reader.read_var_u32()?
+            },
+            13 => {
+                let recipe_network_id = reader.read_var_u32()?;
+                let times_crafted = reader.read_u8()?;
+
+                let ingredient_count = reader.read_u8()?;
+                let mut ingredients = Vec::with_capacity(ingredient_count as usize);
+                for _ in 0..ingredient_count {
+                    ingredients.push(ItemDescriptorCount::deserialize_from(reader)?);
+                }
+
+                Self::AutoCraftRecipe { recipe_network_id, times_crafted, ingredients }
+            },
+            14 => Self::CraftCreative {
+                creative_network_id: reader.read_var_u32()?
+            },
+            15 => Self::CraftRecipeOptional {
+                recipe_network_id: reader.read_var_u32()?,
+                filter_string_index: reader.read_i32_le()?
+            },
+            16 => Self::CraftGrindstoneRecipe {
+                recipe_network_id: reader.read_var_u32()?,
+                cost: reader.read_var_i32()?
+            },
+            17 => Self::CraftLoomRecipe {
+                pattern: reader.read_str()?
+            },
+            18 => Self::CraftNonImplemented,
+            19 => {
+                let result_count = read_stack_request_count(reader, "crafting results")?;
+                let mut result_items = Vec::with_capacity(result_count as usize);
+                for _ in 0..result_count {
+                    result_items.push(ItemInstance::deserialize_without_stack_id(reader)?);
+                }
+
+                Self::CraftResultsDeprecated { result_items, times_crafted: reader.read_u8()? }
+            },
+            _ => anyhow::bail!("Invalid item stack request action type {action_type}")
+        })
     }
 }
 
@@ -641,13 +764,13 @@ impl<'a> Deserialize<'a> for StackRequest<'a> {
     fn deserialize_from<R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<Self> {
         let request_id = reader.read_var_i32()?;
 
-        let actions_count = reader.read_var_u32()?;
+        let actions_count = read_stack_request_count(reader, "actions")?;
         let mut actions = Vec::with_capacity(actions_count as usize);
         for _ in 0..actions_count {
             actions.push(StackRequestAction::deserialize_from(reader)?);
         }
 
-        let filter_count = reader.read_var_u32()?;
+        let filter_count = read_stack_request_count(reader, "filters")?;
         let mut filters = Vec::with_capacity(filter_count as usize);
         for _ in 0..filter_count {
             filters.push(reader.read_str()?);
@@ -722,7 +845,15 @@ impl InputData {
         StopCrawling ,
         StartFlying ,
         AcknowledgeServerData,
-        StopFlying
+        StopFlying,
+        InClientPredictedVehicle,
+        PaddlingLeft,
+        PaddlingRight,
+        BlockBreakingDelayEnabled,
+        HorizontalCollision,
+        VerticalCollision,
+        DownLeft,
+        DownRight
     );
 }
 
@@ -760,7 +891,7 @@ pub struct PlayerAuthInput<'a> {
     /// Item stack requests that were performed in the last tick.
     pub item_stack: Option<StackRequest<'a>>,
     /// Block actions that were performed in the last tick.
-    pub block_actions: Option<Vec<PlayerAction>>
+    pub block_actions: Option<Vec<BlockAction>>
 }
 
 impl ConnectedPacket for PlayerAuthInput<'_> {
@@ -789,8 +920,11 @@ impl<'a> Deserialize<'a> for PlayerAuthInput<'a> {
         let delta = reader.read_vecf()?;
 
         let item_transaction = input_data.perform_item_transaction().then(|| TransactionData::deserialize_from(reader)).transpose()?;
-        let item_stack = input_data.perform_item_stack_request().then(|| todo!());
-        let block_actions = input_data.perform_block_actions().then(|| todo!());
+        let item_stack = input_data.perform_item_stack_request().then(|| StackRequest::deserialize_from(reader)).transpose()?;
+        let block_actions = input_data.perform_block_actions().then(|| {
+            let count = reader.read_var_i32()?;
+            (0..count).map(|_| BlockAction::deserialize_from(reader)).collect::<anyhow::Result<Vec<_>>>()
+        }).transpose()?;
         let analogue_moved = reader.read_vecf()?;
         
         Ok(Self {
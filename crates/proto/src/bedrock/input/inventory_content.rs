@@ -0,0 +1,30 @@
+use util::{BinaryWrite, Serialize};
+
+use crate::bedrock::ConnectedPacket;
+
+use super::{ItemInstance, WindowId};
+
+/// Replaces the entire contents of a container window.
+#[derive(Debug, Clone)]
+pub struct InventoryContent<'a> {
+    /// Window that is updated.
+    pub window_id: WindowId,
+    /// Items in the window, indexed by slot.
+    pub items: &'a [ItemInstance<'a>],
+}
+
+impl ConnectedPacket for InventoryContent<'_> {
+    const ID: u32 = 0x31;
+}
+
+impl Serialize for InventoryContent<'_> {
+    fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
+        writer.write_var_u32(Into::<i32>::into(self.window_id) as u32)?;
+        writer.write_var_u32(self.items.len() as u32)?;
+        for item in self.items {
+            item.serialize_into(writer)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,28 @@
+use util::{BinaryWrite, Serialize};
+
+use crate::bedrock::ConnectedPacket;
+
+use super::{ItemInstance, WindowId};
+
+/// Replaces a single slot in a container window.
+#[derive(Debug, Clone)]
+pub struct InventorySlot<'a> {
+    /// Window that contains the slot.
+    pub window_id: WindowId,
+    /// Index of the slot in the window.
+    pub slot: u32,
+    /// New item in the slot.
+    pub item: ItemInstance<'a>,
+}
+
+impl ConnectedPacket for InventorySlot<'_> {
+    const ID: u32 = 0x32;
+}
+
+impl Serialize for InventorySlot<'_> {
+    fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
+        writer.write_var_u32(Into::<i32>::into(self.window_id) as u32)?;
+        writer.write_var_u32(self.slot)?;
+        self.item.serialize_into(writer)
+    }
+}
@@ -293,8 +293,8 @@ impl<'a> TransactionType<'a> {
             2 => Self::Use {
                 action_type: UseItemAction::try_from(reader.read_var_u32()?)?,
                 block_position: reader.read_block_pos()?,
-                face: reader.read_i32_le()?,
-                hotbar_slot: reader.read_i32_le()?,
+                face: reader.read_var_i32()?,
+                hotbar_slot: reader.read_var_i32()?,
                 held_item: ItemInstance::deserialize_from(reader)?,
                 player_position: reader.read_vecf()?,
                 click_position: reader.read_vecf()?,
@@ -303,14 +303,14 @@ impl<'a> TransactionType<'a> {
             3 => Self::UseOnEntity {
                 entity_runtime_id: reader.read_var_u64()?,
                 action_type: UseOnEntityAction::try_from(reader.read_var_u32()?)?,
-                hotbar_slot: reader.read_i32_le()?,
+                hotbar_slot: reader.read_var_i32()?,
                 held_item: ItemInstance::deserialize_from(reader)?,
                 player_position: reader.read_vecf()?,
                 click_position: reader.read_vecf()?
             },
             4 => Self::Release {
                 action_type: ReleaseAction::try_from(reader.read_var_u32()?)?,
-                hotbar_slot: reader.read_i32_le()?,
+                hotbar_slot: reader.read_var_i32()?,
                 held_item: ItemInstance::deserialize_from(reader)?,
                 head_position: reader.read_vecf()?
             },
@@ -336,8 +336,8 @@ impl<'a> Serialize for TransactionType<'a> {
             } => {
                 writer.write_var_u32(Into::<u32>::into(*action_type))?;
                 writer.write_block_pos(block_position)?;
-                writer.write_i32_le(*face)?;
-                writer.write_i32_le(*hotbar_slot)?;
+                writer.write_var_i32(*face)?;
+                writer.write_var_i32(*hotbar_slot)?;
                 held_item.serialize_into(writer)?;
                 writer.write_vecf(player_position)?;
                 writer.write_vecf(click_position)?;
@@ -353,7 +353,7 @@ impl<'a> Serialize for TransactionType<'a> {
             } => {
                 writer.write_var_u64(*entity_runtime_id)?;
                 writer.write_var_u32(Into::<u32>::into(*action_type))?;
-                writer.write_i32_le(*hotbar_slot)?;
+                writer.write_var_i32(*hotbar_slot)?;
                 held_item.serialize_into(writer)?;
                 writer.write_vecf(player_position)?;
                 writer.write_vecf(click_position)
@@ -365,7 +365,7 @@ impl<'a> Serialize for TransactionType<'a> {
                 head_position
             } => {
                 writer.write_var_u32(Into::<u32>::into(*action_type))?;
-                writer.write_i32_le(*hotbar_slot)?;
+                writer.write_var_i32(*hotbar_slot)?;
                 held_item.serialize_into(writer)?;
                 writer.write_vecf(head_position)
             }
@@ -458,6 +458,19 @@ impl<'a> Serialize for ItemInstance<'a> {
 
 impl<'a> Deserialize<'a> for ItemInstance<'a> {
     fn deserialize_from<R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<Self> {
+        Self::deserialize_item(reader, true)
+    }
+}
+
+impl<'a> ItemInstance<'a> {
+    /// Deserializes an item that is not prefixed with an optional stack network ID.
+    ///
+    /// This encoding is used by items that are not located in an inventory, such as crafting results.
+    pub fn deserialize_without_stack_id<R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<Self> {
+        Self::deserialize_item(reader, false)
+    }
+
+    fn deserialize_item<R: BinaryRead<'a>>(reader: &mut R, with_stack_id: bool) -> anyhow::Result<Self> {
         let network_id = reader.read_var_i32()?;
         // tracing::debug!("Network ID: {network_id}");
         if network_id == 0 {
@@ -476,7 +489,7 @@ impl<'a> Deserialize<'a> for ItemInstance<'a> {
         let metadata = reader.read_var_u32()?;
         // tracing::debug!("Metadata: {metadata}");
 
-        let has_stack_id = reader.read_bool()?;
+        let has_stack_id = with_stack_id && reader.read_bool()?;
         let stack_id = has_stack_id.then(|| reader.read_var_i32()).transpose()?;
         // tracing::debug!("Stack ID: {stack_id:?}");
 
@@ -0,0 +1,229 @@
+use macros::variant_count;
+use util::{BinaryRead, BinaryWrite, Deserialize, Serialize};
+
+use crate::bedrock::ConnectedPacket;
+
+use super::{read_stack_request_count, StackRequest};
+
+/// Containers that are referenced by item stack requests and responses.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(u8)]
+#[variant_count]
+pub enum ContainerSlotType {
+    AnvilInput,
+    AnvilMaterial,
+    AnvilResultPreview,
+    SmithingTableInput,
+    SmithingTableMaterial,
+    SmithingTableResultPreview,
+    Armor,
+    LevelEntity,
+    BeaconPayment,
+    BrewingInput,
+    BrewingResult,
+    BrewingFuel,
+    CombinedHotbarAndInventory,
+    CraftingInput,
+    CraftingOutputPreview,
+    RecipeConstruction,
+    RecipeNature,
+    RecipeItems,
+    RecipeSearch,
+    RecipeSearchBar,
+    RecipeEquipment,
+    RecipeBook,
+    EnchantingInput,
+    EnchantingMaterial,
+    FurnaceFuel,
+    FurnaceIngredient,
+    FurnaceResult,
+    HorseEquip,
+    Hotbar,
+    Inventory,
+    ShulkerBox,
+    TradeIngredient1,
+    TradeIngredient2,
+    TradeResultPreview,
+    Offhand,
+    CompoundCreatorInput,
+    CompoundCreatorOutputPreview,
+    ElementConstructorOutputPreview,
+    MaterialReducerInput,
+    MaterialReducerOutput,
+    LabTableInput,
+    LoomInput,
+    LoomDye,
+    LoomMaterial,
+    LoomResultPreview,
+    BlastFurnaceIngredient,
+    SmokerIngredient,
+    Trade2Ingredient1,
+    Trade2Ingredient2,
+    Trade2ResultPreview,
+    GrindstoneInput,
+    GrindstoneAdditional,
+    GrindstoneResultPreview,
+    StonecutterInput,
+    StonecutterResultPreview,
+    CartographyInput,
+    CartographyAdditional,
+    CartographyResultPreview,
+    Barrel,
+    /// Item that the player is moving around in an open container.
+    Cursor,
+    /// Output of a crafting action, such as an item taken from the creative inventory.
+    CreatedOutput,
+    SmithingTableTemplate,
+    CrafterLevelEntity
+}
+
+impl TryFrom<u8> for ContainerSlotType {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u8) -> anyhow::Result<ContainerSlotType> {
+        if (value as usize) < ContainerSlotType::variant_count() {
+            // SAFETY: This is safe because the discriminant is in range and
+            // the representations are the same. Additionally, none of the enum members
+            // have a manually assigned value (this is ensured by the `variant_count` macro).
+            Ok(unsafe { std::mem::transmute::<u8, ContainerSlotType>(value) })
+        } else {
+            anyhow::bail!("Container slot type out of range ({value} >= {})", Self::variant_count())
+        }
+    }
+}
+
+/// Sent by the client to move items around in its inventory.
+///
+/// This is only used when server authoritative inventories are enabled.
+#[derive(Debug)]
+pub struct ItemStackRequest<'a> {
+    /// Requests that should be processed in order.
+    pub requests: Vec<StackRequest<'a>>
+}
+
+impl ConnectedPacket for ItemStackRequest<'_> {
+    const ID: u32 = 0x93;
+}
+
+impl<'a> Deserialize<'a> for ItemStackRequest<'a> {
+    fn deserialize_from<R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<Self> {
+        let count = read_stack_request_count(reader, "requests")?;
+        let mut requests = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            requests.push(StackRequest::deserialize_from(reader)?);
+        }
+
+        Ok(Self { requests })
+    }
+}
+
+/// Whether an item stack request was accepted.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum StackResponseStatus {
+    /// The request was applied.
+    Ok,
+    /// The request was rejected and the client should undo its changes.
+    Error
+}
+
+/// New state of a slot after a request was applied.
+#[derive(Debug, Clone)]
+pub struct StackResponseSlot<'a> {
+    /// Slot within the container.
+    pub slot: u8,
+    /// Same as `slot`, kept for backwards compatibility.
+    pub hotbar_slot: u8,
+    /// Amount of items in the slot.
+    pub count: u8,
+    /// Stack network ID of the item in the slot.
+    pub stack_network_id: i32,
+    /// Custom name of the item.
+    pub custom_name: &'a str,
+    /// Custom name of the item after text filtering.
+    pub filtered_custom_name: &'a str,
+    /// Durability of the item that the client should use instead of its own prediction.
+    pub durability_correction: i32
+}
+
+impl Serialize for StackResponseSlot<'_> {
+    fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
+        writer.write_u8(self.slot)?;
+        writer.write_u8(self.hotbar_slot)?;
+        writer.write_u8(self.count)?;
+        writer.write_var_i32(self.stack_network_id)?;
+        writer.write_str(self.custom_name)?;
+        writer.write_str(self.filtered_custom_name)?;
+        writer.write_var_i32(self.durability_correction)
+    }
+}
+
+/// Changed slots within a single container.
+#[derive(Debug, Clone)]
+pub struct StackResponseContainer<'a> {
+    /// The container that was changed.
+    pub container: ContainerSlotType,
+    /// Slots that were changed.
+    pub slots: Vec<StackResponseSlot<'a>>
+}
+
+impl Serialize for StackResponseContainer<'_> {
+    fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
+        writer.write_u8(self.container as u8)?;
+        writer.write_var_u32(self.slots.len() as u32)?;
+        for slot in &self.slots {
+            slot.serialize_into(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Response to a single [`StackRequest`].
+#[derive(Debug, Clone)]
+pub struct StackResponse<'a> {
+    /// Whether the request was accepted.
+    pub status: StackResponseStatus,
+    /// ID of the request this is a response to.
+    pub request_id: i32,
+    /// Containers that were changed by the request. This is only sent when the request was accepted.
+    pub containers: Vec<StackResponseContainer<'a>>
+}
+
+impl Serialize for StackResponse<'_> {
+    fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
+        writer.write_u8(self.status as u8)?;
+        writer.write_var_i32(self.request_id)?;
+
+        if self.status == StackResponseStatus::Ok {
+            writer.write_var_u32(self.containers.len() as u32)?;
+            for container in &self.containers {
+                container.serialize_into(writer)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Sent in response to an [`ItemStackRequest`].
+#[derive(Debug, Clone)]
+pub struct ItemStackResponse<'a> {
+    /// Responses to every request in the [`ItemStackRequest`].
+    pub responses: &'a [StackResponse<'a>]
+}
+
+impl ConnectedPacket for ItemStackResponse<'_> {
+    const ID: u32 = 0x94;
+}
+
+impl Serialize for ItemStackResponse<'_> {
+    fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
+        writer.write_var_u32(self.responses.len() as u32)?;
+        for response in self.responses {
+            response.serialize_into(writer)?;
+        }
+
+        Ok(())
+    }
+}
@@ -3,4 +3,7 @@ use util::glob_export;
 glob_export!(auth_input);
 glob_export!(move_player);
 glob_export!(inventory_transaction);
-glob_export!(mob_equipment);
\ No newline at end of file
+glob_export!(mob_equipment);
+glob_export!(inventory_content);
+glob_export!(inventory_slot);
+glob_export!(item_stack);
@@ -27,6 +27,9 @@ pub struct LevelChunk {
     pub sub_chunk_count: u32,
     /// List of hashes used to cache the chunks.
     /// This should be set to None if the client does not support the blob cache.
+    ///
+    /// When this is set, it contains the hash of every sub chunk followed by the hash of the biomes,
+    /// and the payload only contains the data that is not cached.
     pub blob_hashes: Option<Vec<u64>>,
     /// Raw chunk data.
     pub raw_payload: RVec,
@@ -57,7 +60,7 @@ impl Serialize for LevelChunk {
         if let Some(hashes) = &self.blob_hashes {
             writer.write_var_u32(hashes.len() as u32)?;
             for hash in hashes {
-                writer.write_u64_be(*hash)?;
+                writer.write_u64_le(*hash)?;
             }
         }
 
@@ -49,9 +49,19 @@ impl Default for SubChunkEntry {
 }
 
 impl SubChunkEntry {
+    /// Serializes the entry for a client that uses the blob cache.
+    ///
+    /// The sub chunk itself is sent as a blob, so the payload only contains data that is not cached.
     #[inline]
-    fn serialize_cached<W: BinaryWrite>(&self, _writer: &mut W) -> anyhow::Result<()> {
-        todo!();
+    fn serialize_cached<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
+        writer.write_vecb(&self.offset)?;
+        writer.write_u8(self.result as u8)?;
+        if self.result != SubChunkResult::AllAir {
+            writer.write_var_u32(self.payload.len() as u32)?;
+            writer.write_all(&self.payload)?;
+        }
+        self.serialize_heightmap(writer)?;
+        writer.write_u64_le(self.blob_hash)
     }
 
     #[inline]
@@ -60,6 +70,11 @@ impl SubChunkEntry {
         writer.write_u8(self.result as u8)?;
         writer.write_var_u32(self.payload.len() as u32)?;
         writer.write_all(&self.payload)?;
+        self.serialize_heightmap(writer)
+    }
+
+    #[inline]
+    fn serialize_heightmap<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
         writer.write_u8(self.heightmap_type as u8)?;
         if self.heightmap_type == HeightmapType::WithData {
             let slice: &[i8; 256] = self.heightmap.as_ref().unwrap();
@@ -66,15 +66,25 @@ impl<'a> Deserialize<'a> for Login {
         let _version = reader.read_u32_be()?; 
         reader.read_var_u32()?;
 
+        Self::deserialize_connection_request(reader)
+    }
+}
+
+impl Login {
+    /// Reads the identity chain and client data that make up a connection request.
+    ///
+    /// This is shared with [`SubClientLogin`](crate::bedrock::SubClientLogin).
+    pub(crate) fn deserialize_connection_request<'a, R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<Self> {
         let identity_data = crypto::parse_identity_data(reader)?;
         let data = crypto::parse_user_data(reader, &identity_data.public_key)?;
 
         Ok(Self {
             identity: BedrockIdentity {
                 uuid: identity_data.client_data.uuid,
-                xuid: identity_data.client_data.xuid.parse()?,
+                xuid: if identity_data.client_data.xuid.is_empty() { 0 } else { identity_data.client_data.xuid.parse()? },
                 name: identity_data.client_data.display_name,
                 public_key: identity_data.public_key,
+                authenticated: identity_data.authenticated,
             },
             client_info: data.data,
             skin: data.skin,
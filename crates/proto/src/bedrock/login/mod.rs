@@ -9,8 +9,12 @@ glob_export!(login);
 glob_export!(network_settings);
 glob_export!(play_status);
 glob_export!(request_network_settings);
+glob_export!(resource_pack_chunk_data);
+glob_export!(resource_pack_chunk_request);
 glob_export!(resource_pack_client_response);
+glob_export!(resource_pack_data_info);
 glob_export!(resource_pack_stack);
 glob_export!(resource_packs_info);
 glob_export!(server_to_client_handshake);
 glob_export!(start_game);
+glob_export!(sub_client_login);
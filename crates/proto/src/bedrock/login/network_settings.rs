@@ -17,8 +17,6 @@ pub enum CompressionAlgorithm {
     Flate,
     /// The Snappy compression algorithm.
     /// Available since Minecraft 1.19.30.
-    /// 
-    /// WARNING: This option is currently not support by the server.
     Snappy,
 }
 
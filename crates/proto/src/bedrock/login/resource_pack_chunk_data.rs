@@ -0,0 +1,37 @@
+use util::{BinaryWrite, Serialize};
+
+use crate::bedrock::ConnectedPacket;
+
+/// Contains a single chunk of a pack archive.
+/// Sent in response to a [`ResourcePackChunkRequest`](crate::bedrock::ResourcePackChunkRequest).
+#[derive(Debug, Clone)]
+pub struct ResourcePackChunkData<'a> {
+    /// UUID and version of the pack, formatted as `uuid_version`.
+    pub pack_id: &'a str,
+    /// Index of this chunk.
+    pub chunk_index: u32,
+    /// Offset of this chunk in the pack archive in bytes.
+    pub offset: u64,
+    /// Contents of the chunk.
+    pub data: &'a [u8],
+}
+
+impl ConnectedPacket for ResourcePackChunkData<'_> {
+    const ID: u32 = 0x53;
+
+    fn serialized_size(&self) -> usize {
+        self.pack_id.len() + 5 + 4 + 8 + self.data.len() + 5
+    }
+}
+
+impl Serialize for ResourcePackChunkData<'_> {
+    fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
+        writer.write_str(self.pack_id)?;
+        writer.write_u32_le(self.chunk_index)?;
+        writer.write_u64_le(self.offset)?;
+        writer.write_var_u32(self.data.len() as u32)?;
+        writer.write_all(self.data)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,67 @@
+use util::{BinaryWrite, Serialize};
+
+use crate::bedrock::ConnectedPacket;
+
+/// Type of a pack in [`ResourcePackDataInfo`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ResourcePackType {
+    /// Invalid pack type.
+    Invalid,
+    /// An addon containing both resources and behaviour.
+    Addon,
+    /// Cached pack.
+    Cached,
+    /// Copy protected pack.
+    CopyProtected,
+    /// A behaviour pack.
+    Behavior,
+    /// A persona piece.
+    PersonaPiece,
+    /// A resource pack.
+    Resources,
+    /// A skin pack.
+    Skins,
+    /// A world template.
+    WorldTemplate,
+}
+
+/// Sent in response to a [`ResourcePackClientResponse`](crate::bedrock::ResourcePackClientResponse) with the
+/// [`SendPacks`](crate::bedrock::ResourcePackStatus::SendPacks) status.
+///
+/// This tells the client how a pack will be split into chunks so that it can request them with
+/// [`ResourcePackChunkRequest`](crate::bedrock::ResourcePackChunkRequest).
+#[derive(Debug, Clone)]
+pub struct ResourcePackDataInfo<'a> {
+    /// UUID and version of the pack, formatted as `uuid_version`.
+    pub pack_id: &'a str,
+    /// Maximum size of a single chunk in bytes.
+    pub chunk_size: u32,
+    /// Amount of chunks that the pack is split into.
+    pub chunk_count: u32,
+    /// Total size of the pack archive in bytes.
+    pub size: u64,
+    /// SHA-256 hash of the pack archive.
+    pub hash: &'a [u8],
+    /// Whether this is a marketplace pack.
+    pub premium: bool,
+    /// Type of the pack.
+    pub pack_type: ResourcePackType,
+}
+
+impl ConnectedPacket for ResourcePackDataInfo<'_> {
+    const ID: u32 = 0x52;
+}
+
+impl Serialize for ResourcePackDataInfo<'_> {
+    fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
+        writer.write_str(self.pack_id)?;
+        writer.write_u32_le(self.chunk_size)?;
+        writer.write_u32_le(self.chunk_count)?;
+        writer.write_u64_le(self.size)?;
+        writer.write_var_u32(self.hash.len() as u32)?;
+        writer.write_all(self.hash)?;
+        writer.write_bool(self.premium)?;
+        writer.write_u8(self.pack_type as u8)
+    }
+}
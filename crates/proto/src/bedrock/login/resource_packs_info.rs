@@ -25,11 +25,13 @@ pub struct BehaviorPack {
     /// Whether the pack contains script.
     /// If it does, the pack will only be downloaded if the client supports scripting.
     pub has_scripts: bool,
+    /// Whether the pack is part of an addon.
+    pub addon_pack: bool,
 }
 
 impl BehaviorPack {
     fn serialized_size(&self) -> usize {
-        8 + 1 +
+        8 + 1 + 1 +
             self.uuid.var_len() +
             self.version.var_len() +
             self.content_key.var_len() +
@@ -59,13 +61,15 @@ pub struct ResourcePack {
     /// Whether the pack contains scripts.
     /// If it does, the pack will only be downloaded if the client supports scripting.
     pub has_scripts: bool,
+    /// Whether the pack is part of an addon.
+    pub addon_pack: bool,
     /// Whether the pack uses raytracing.
     pub rtx_enabled: bool,
 }
 
 impl ResourcePack {
     fn serialized_size(&self) -> usize {
-        8 + 1 + 1 +
+        8 + 1 + 1 + 1 +
             self.uuid.var_len() +
             self.version.var_len() +
             self.content_key.var_len() +
@@ -117,26 +121,28 @@ impl<'a> Serialize for ResourcePacksInfo<'a> {
         writer.write_bool(self.scripting_enabled)?;
         writer.write_bool(self.forcing_server_packs)?;
 
-        writer.write_u16_be(self.behavior_info.len() as u16)?;
+        writer.write_u16_le(self.behavior_info.len() as u16)?;
         for pack in self.behavior_info {
             writer.write_str(&pack.uuid)?;
             writer.write_str(&pack.version)?;
-            writer.write_u64_be(pack.size)?;
+            writer.write_u64_le(pack.size)?;
             writer.write_str(&pack.content_key)?;
             writer.write_str(&pack.subpack_name)?;
             writer.write_str(&pack.content_identity)?;
             writer.write_bool(pack.has_scripts)?;
+            writer.write_bool(pack.addon_pack)?;
         }
 
-        writer.write_u16_be(self.resource_info.len() as u16)?;
+        writer.write_u16_le(self.resource_info.len() as u16)?;
         for pack in self.resource_info {
             writer.write_str(&pack.uuid)?;
             writer.write_str(&pack.version)?;
-            writer.write_u64_be(pack.size)?;
+            writer.write_u64_le(pack.size)?;
             writer.write_str(&pack.content_key)?;
             writer.write_str(&pack.subpack_name)?;
             writer.write_str(&pack.content_identity)?;
             writer.write_bool(pack.has_scripts)?;
+            writer.write_bool(pack.addon_pack)?;
             writer.write_bool(pack.rtx_enabled)?;
         }
 
@@ -0,0 +1,27 @@
+use util::{BinaryRead, Deserialize};
+
+use crate::bedrock::{ConnectedPacket, Login};
+
+/// Sent by a split screen player that joins using the connection of another player.
+///
+/// The header of this packet contains the subclient ID that the new player will use.
+/// Unlike [`Login`], this does not start a new encryption handshake, because the
+/// subclient shares the encryption of the connection.
+#[derive(Debug)]
+pub struct SubClientLogin {
+    /// The connection request of the new player. This has the same format as a [`Login`] packet.
+    pub request: Login,
+}
+
+impl ConnectedPacket for SubClientLogin {
+    const ID: u32 = 0x5e;
+}
+
+impl<'a> Deserialize<'a> for SubClientLogin {
+    fn deserialize_from<R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<Self> {
+        // Length of the connection request.
+        reader.read_var_u32()?;
+
+        Ok(Self { request: Login::deserialize_connection_request(reader)? })
+    }
+}
@@ -37,6 +37,7 @@ glob_export!(mob_effect);
 glob_export!(network_chunk_publisher_update);
 glob_export!(play_sound);
 glob_export!(player_list);
+glob_export!(remove_entity);
 glob_export!(request_ability);
 glob_export!(respawn);
 glob_export!(set_hud);
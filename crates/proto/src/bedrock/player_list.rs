@@ -56,6 +56,7 @@ impl<'a> Serialize for PlayerListAdd<'a> {
             entry.skin.serialize_into(writer)?;
             writer.write_bool(false)?; // Player is not a teacher.
             writer.write_bool(entry.host)?;
+            writer.write_bool(false)?; // Player is not a sub-client.
         }
 
         for entry in self.entries {
@@ -0,0 +1,24 @@
+use util::BinaryWrite;
+use util::Serialize;
+
+use crate::bedrock::ConnectedPacket;
+
+/// Removes an entity from the client's world.
+///
+/// This is also used to despawn players, which should afterwards be removed from the player list
+/// with a [`PlayerListRemove`](crate::bedrock::PlayerListRemove) packet.
+#[derive(Debug, Clone)]
+pub struct RemoveEntity {
+    /// Unique ID of the entity to remove.
+    pub entity_id: i64,
+}
+
+impl ConnectedPacket for RemoveEntity {
+    const ID: u32 = 0x0e;
+}
+
+impl Serialize for RemoveEntity {
+    fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
+        writer.write_var_i64(self.entity_id)
+    }
+}
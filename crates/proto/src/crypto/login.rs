@@ -2,6 +2,7 @@
 use base64::Engine;
 use jsonwebtoken::{Algorithm, DecodingKey, Validation};
 use p384::pkcs8::spki;
+use serde::de::DeserializeOwned;
 use uuid::Uuid;
 
 use util::{BinaryRead};
@@ -21,6 +22,8 @@ const BASE64_ENGINE: base64::engine::GeneralPurpose = base64::engine::general_pu
 #[derive(Debug, Clone)]
 pub struct BedrockIdentity {
     /// Xbox account ID.
+    ///
+    /// This is 0 if the client is not signed into Xbox Live.
     pub xuid: u64,
     /// UUID unique for this player.
     pub uuid: Uuid,
@@ -28,6 +31,8 @@ pub struct BedrockIdentity {
     pub name: String,
     /// Public key used for token verification and encryption.
     pub public_key: String,
+    /// Whether the identity was signed by Mojang, proving that the client is signed into Xbox Live.
+    pub authenticated: bool,
 }
 
 /// Used to extract data from the user data token.
@@ -76,7 +81,9 @@ struct KeyTokenPayload {
 #[derive(serde::Deserialize, Debug)]
 pub struct RawIdentityData {
     /// The Xbox user ID of the client. This is what uniquely identifies a user and is used in several packets.
-    #[serde(rename = "XUID")]
+    ///
+    /// This is empty if the client is not signed into Xbox Live.
+    #[serde(rename = "XUID", default)]
     pub xuid: String,
     /// The display name of the user. This is their Xbox gamertag.
     #[serde(rename = "displayName")]
@@ -98,6 +105,10 @@ pub struct IdentityTokenPayload {
     /// Contains the user's public key. This is used for encryption.
     #[serde(rename = "identityPublicKey")]
     pub public_key: String,
+    /// Whether the identity chain was signed by Mojang.
+    /// This is not part of the token and is set after the chain has been verified.
+    #[serde(skip)]
+    pub authenticated: bool,
 }
 
 /// Data structure that splits the user data token into separate [`Skin`] and
@@ -112,6 +123,20 @@ pub struct UserDataTokenPayload {
     pub skin: Skin,
 }
 
+/// Decodes a Base64 encoded DER public key so that it can be used to verify tokens.
+fn decoding_key(key: &str) -> anyhow::Result<DecodingKey> {
+    let bytes = BASE64_ENGINE.decode(key)?;
+    let public_key = match spki::SubjectPublicKeyInfoRef::try_from(bytes.as_ref()) {
+        Ok(p) => p,
+        Err(e) => {
+            tracing::error!("A public key received during login is invalid");
+            anyhow::bail!("Invalid client public key: {e}")
+        }
+    };
+
+    Ok(DecodingKey::from_ec_der(public_key.subject_public_key.raw_bytes()))
+}
+
 /// First token in the chain holds the client's self-signed public key in the X5U.
 /// It is extracted from the header of the token and used to verify its signature.
 /// The payload of the token contains a new key which is used to verify the next token.
@@ -119,7 +144,7 @@ pub struct UserDataTokenPayload {
     skip_all,
     name = "crypto::parse_initial_token"
 )]
-fn parse_initial_token(token: &str) -> anyhow::Result<String> {
+fn parse_initial_token<T: DeserializeOwned>(token: &str) -> anyhow::Result<T> {
     // Decode JWT header to get X5U.
     let header = match jsonwebtoken::decode_header(token) {
         Ok(header) => header,
@@ -133,23 +158,13 @@ fn parse_initial_token(token: &str) -> anyhow::Result<String> {
         tracing::error!("Missing X.509 certificate in initial JWT");
         anyhow::bail!("Missing X.509 certificate in initial JWT");
     };
-    let bytes = BASE64_ENGINE.decode(base64_x5u)?;
-
-    // Public key that can be used to verify the token.
-    let public_key = match spki::SubjectPublicKeyInfoRef::try_from(bytes.as_ref()) {
-        Ok(p) => p,
-        Err(e) => {
-            tracing::error!("The first public key received during login is invalid");
-            anyhow::bail!("Invalid client public key: {e}")
-        }
-    };
 
-    let decoding_key = DecodingKey::from_ec_der(public_key.subject_public_key.raw_bytes());
+    let decoding_key = decoding_key(&base64_x5u)?;
     let mut validation = Validation::new(Algorithm::ES384);
     validation.validate_exp = true;
     validation.validate_nbf = true;
 
-    let payload = match jsonwebtoken::decode::<KeyTokenPayload>(token, &decoding_key, &validation) {
+    let payload = match jsonwebtoken::decode::<T>(token, &decoding_key, &validation) {
         Ok(payload) => payload,
         Err(err) => {
             tracing::error!("Unable to decode initial JWT | {err:#}");
@@ -157,76 +172,67 @@ fn parse_initial_token(token: &str) -> anyhow::Result<String> {
         }
     };
 
-    Ok(payload.claims.public_key)
+    Ok(payload.claims)
 }
 
-/// The second token in the chain can be verified using Mojang's public key
-/// (or the identityPublicKey from the previous token).
-/// This token contains another identityPublicKey which is the public key for the third token.
+/// Every token after the first one is verified using the identityPublicKey from the previous token.
+///
+/// Tokens that follow Mojang's public key in the chain must have been issued by Mojang.
 #[tracing::instrument(
     skip_all,
-    name = "crypto::parse_mojang_token"
+    name = "crypto::parse_chained_token"
 )]
-fn parse_mojang_token(token: &str, key: &str) -> anyhow::Result<String> {
-    let bytes = BASE64_ENGINE.decode(key)?;
-    let public_key = match spki::SubjectPublicKeyInfoRef::try_from(bytes.as_ref()) {
-        Ok(p) => p,
-        Err(e) => {
-            tracing::error!("The second public key received during login is invalid");
-            anyhow::bail!("Invalid client public key: {e}")
-        }
-    };
-
-    let decoding_key = DecodingKey::from_ec_der(public_key.subject_public_key.raw_bytes());
+fn parse_chained_token<T: DeserializeOwned>(token: &str, key: &str, issued_by_mojang: bool) -> anyhow::Result<T> {
+    let decoding_key = decoding_key(key)?;
     let mut validation = Validation::new(Algorithm::ES384);
-    validation.set_issuer(&["Mojang"]);
+    if issued_by_mojang {
+        validation.set_issuer(&["Mojang"]);
+    }
     validation.validate_nbf = true;
     validation.validate_exp = true;
 
-    let payload = match jsonwebtoken::decode::<KeyTokenPayload>(token, &decoding_key, &validation) {
+    let payload = match jsonwebtoken::decode::<T>(token, &decoding_key, &validation) {
         Ok(payload) => payload,
         Err(err) => {
-            tracing::error!("Unable to decode second JWT | {err:#}");
-            anyhow::bail!("Unable to decode second JWT | {err:#}")
+            tracing::error!("Unable to decode chained JWT | {err:#}");
+            anyhow::bail!("Unable to decode chained JWT | {err:#}")
         }
     };
 
-    Ok(payload.claims.public_key)
+    Ok(payload.claims)
 }
 
-/// The third token contains the client's actual public key and extra data.
-/// The extraData field contains the XUID, client identity (UUID) and the display name.
+/// Verifies the signatures of an identity token chain and decodes the identity data in its last token.
 ///
-/// Just like the second one, this token can be verified using the identityPublicKey from the last token.
-#[tracing::instrument(
-    skip_all,
-    name = "crypto::parse_identity_token"
-)]
-fn parse_identity_token(token: &str, key: &str) -> anyhow::Result<IdentityTokenPayload> {
-    let bytes = BASE64_ENGINE.decode(key)?;
-    let public_key = match spki::SubjectPublicKeyInfoRef::try_from(bytes.as_ref()) {
-        Ok(p) => p,
-        Err(e) => {
-            tracing::error!("The third public key received during login is invalid");
-            anyhow::bail!("Invalid client public key: {e}")
-        }
+/// Clients that are signed into Xbox Live send three tokens: a self-signed token that points to Mojang's public key,
+/// a token signed by Mojang and the identity token. Clients that are not signed in only send a single self-signed token.
+///
+/// The returned identity is marked as [`authenticated`](IdentityTokenPayload::authenticated) if the chain
+/// passed through `trusted_key`, which is normally [`MOJANG_PUBLIC_KEY`].
+pub fn verify_identity_chain<S: AsRef<str>>(chain: &[S], trusted_key: &str) -> anyhow::Result<IdentityTokenPayload> {
+    let Some((first, rest)) = chain.split_first().filter(|_| chain.len() <= 3) else {
+        tracing::error!("Received invalid amount of tokens. Got {}, expected 1 to 3", chain.len());
+        anyhow::bail!("Received invalid amount of tokens. Got {}, expected 1 to 3", chain.len())
     };
 
-    let decoding_key = DecodingKey::from_ec_der(public_key.subject_public_key.raw_bytes());
-    let mut validation = Validation::new(Algorithm::ES384);
-    validation.set_issuer(&["Mojang"]);
-    validation.validate_nbf = true;
-    validation.validate_exp = true;
-
-    let payload = match jsonwebtoken::decode::<IdentityTokenPayload>(token, &decoding_key, &validation) {
-        Ok(payload) => payload,
-        Err(err) => {
-            tracing::error!("Unable to decode identity JWT | {err:#}");
-            anyhow::bail!("Unable to decode identity JWT | {err:#}")
-        }
+    let Some((last, middle)) = rest.split_last() else {
+        // Client is not signed into Xbox, the only token is self-signed.
+        return parse_initial_token(first.as_ref());
     };
 
-    Ok(payload.claims)
+    let mut key = parse_initial_token::<KeyTokenPayload>(first.as_ref())?.public_key;
+    let mut authenticated = false;
+
+    for token in middle {
+        authenticated |= key == trusted_key;
+        key = parse_chained_token::<KeyTokenPayload>(token.as_ref(), &key, authenticated)?.public_key;
+    }
+
+    authenticated |= key == trusted_key;
+    let mut identity = parse_chained_token::<IdentityTokenPayload>(last.as_ref(), &key, authenticated)?;
+    identity.authenticated = authenticated;
+
+    Ok(identity)
 }
 
 /// Verifies and decodes the user data token.
@@ -264,36 +270,17 @@ fn parse_user_data_token(token: &str, key: &str) -> anyhow::Result<UserDataToken
 /// Parses the identification data contained in the first token chain.
 ///
 /// This contains such as the XUID, display name and public key.
+/// Whether the client is signed into Xbox Live is not enforced here, check
+/// [`authenticated`](IdentityTokenPayload::authenticated) instead.
 pub fn parse_identity_data<'a, R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<IdentityTokenPayload> {
     let token_length = reader.read_u32_le()?;
     let token_chain = reader.take_n(token_length as usize)?;
 
     let tokens = serde_json::from_slice::<TokenChain>(token_chain)?;
-    let identity_data = match tokens.chain.len() {
-        1 => {
-            // Client is not signed into Xbox.
-            tracing::warn!("User is not authenticated with Microsoft services");
-            anyhow::bail!("User must be authenticated with Microsoft services");
-        }
-        3 => {
-            // Verify the first token and decode the public key for the next token.
-            // This public key must be equal to Mojang's public key to verify that the second
-            // token was signed by Mojang.
-
-            let mut key = parse_initial_token(&tokens.chain[0])?;
-            if !key.eq(MOJANG_PUBLIC_KEY) {
-                tracing::error!("Attempt to login using a token that was not created by Mojang");
-                anyhow::bail!("Identity token was not signed by Mojang");
-            }
-
-            key = parse_mojang_token(&tokens.chain[1], &key)?;
-            parse_identity_token(&tokens.chain[2], &key)?
-        }
-        len => {
-            tracing::error!("Received invalid amount of tokens. Got {len}, expected 3");
-            anyhow::bail!("Received invalid amount of tokens. Got {len}, expected 3")
-        }
-    };
+    let identity_data = verify_identity_chain(&tokens.chain, MOJANG_PUBLIC_KEY)?;
+    if !identity_data.authenticated {
+        tracing::debug!("User is not authenticated with Microsoft services");
+    }
 
     Ok(identity_data)
 }
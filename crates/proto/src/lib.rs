@@ -51,3 +51,6 @@ pub mod types;
 
 pub use base64;
 pub use uuid;
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,205 @@
+use base64::Engine;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use p384::ecdsa::SigningKey;
+use p384::pkcs8::{EncodePrivateKey, EncodePublicKey};
+use rand::rngs::OsRng;
+use serde_json::json;
+
+use crate::crypto::verify_identity_chain;
+
+const BASE64_ENGINE: base64::engine::general_purpose::GeneralPurpose = base64::engine::general_purpose::STANDARD_NO_PAD;
+
+/// Generates a key pair, returning the private key and the Base64 encoded DER public key.
+fn key_pair() -> (SigningKey, String) {
+    let private_key = SigningKey::random(&mut OsRng);
+    let public_key = BASE64_ENGINE.encode(private_key.verifying_key().to_public_key_der().unwrap());
+
+    (private_key, public_key)
+}
+
+/// Signs a token with the given key, putting the key's public key in the X5U header.
+fn sign(key: &(SigningKey, String), claims: serde_json::Value) -> String {
+    let mut header = Header::new(Algorithm::ES384);
+    header.x5u = Some(key.1.clone());
+
+    let encoding_key = EncodingKey::from_ec_der(&key.0.to_pkcs8_der().unwrap().to_bytes());
+    jsonwebtoken::encode(&header, &claims, &encoding_key).unwrap()
+}
+
+/// Builds a chain of tokens in the same way as the Xbox Live authentication service does.
+fn xbox_chain(client: &(SigningKey, String), mojang: &(SigningKey, String), identity: &(SigningKey, String)) -> Vec<String> {
+    let exp = jsonwebtoken::get_current_timestamp() + 3600;
+    vec![
+        sign(client, json!({ "identityPublicKey": mojang.1, "exp": exp })),
+        sign(mojang, json!({ "identityPublicKey": identity.1, "iss": "Mojang", "exp": exp })),
+        sign(
+            identity,
+            json!({
+                "identityPublicKey": client.1,
+                "iss": "Mojang",
+                "exp": exp,
+                "extraData": { "XUID": "2535400000000000", "displayName": "Steve", "identity": "6ba7b810-9dad-11d1-80b4-00c04fd430c8" }
+            }),
+        ),
+    ]
+}
+
+#[test]
+fn identity_chain_signed_by_trusted_key() {
+    let (client, mojang, identity) = (key_pair(), key_pair(), key_pair());
+
+    let data = verify_identity_chain(&xbox_chain(&client, &mojang, &identity), &mojang.1).unwrap();
+    assert!(data.authenticated, "chain passing through the trusted key should be authenticated");
+    assert_eq!(data.client_data.xuid, "2535400000000000");
+    assert_eq!(data.client_data.display_name, "Steve");
+    assert_eq!(data.public_key, client.1);
+}
+
+#[test]
+fn identity_chain_signed_by_untrusted_key() {
+    let (client, impostor, identity) = (key_pair(), key_pair(), key_pair());
+    let (_, trusted) = key_pair();
+
+    let data = verify_identity_chain(&xbox_chain(&client, &impostor, &identity), &trusted).unwrap();
+    assert!(!data.authenticated, "chain not passing through the trusted key should not be authenticated");
+
+    // Claiming to be signed by the trusted key without having its private key must fail verification.
+    let mut forged = xbox_chain(&client, &impostor, &identity);
+    let exp = jsonwebtoken::get_current_timestamp() + 3600;
+    forged[0] = sign(&client, json!({ "identityPublicKey": trusted, "exp": exp }));
+    assert!(verify_identity_chain(&forged, &trusted).is_err(), "forged chain should be rejected");
+}
+
+#[test]
+fn identity_chain_self_signed() {
+    let client = key_pair();
+    let (_, trusted) = key_pair();
+
+    let token = sign(
+        &client,
+        json!({
+            "identityPublicKey": client.1,
+            "exp": jsonwebtoken::get_current_timestamp() + 3600,
+            "extraData": { "displayName": "Alex", "identity": "6ba7b810-9dad-11d1-80b4-00c04fd430c8" }
+        }),
+    );
+
+    let data = verify_identity_chain(&[token], &trusted).unwrap();
+    assert!(!data.authenticated, "self-signed chain should not be authenticated");
+    assert!(data.client_data.xuid.is_empty(), "self-signed chain should not have an XUID");
+
+    assert!(verify_identity_chain::<&str>(&[], &trusted).is_err(), "empty chain should be rejected");
+}
+
+/// Encodes a [`PlayerAuthInput`](crate::bedrock::PlayerAuthInput) packet with the given flags.
+/// `body` writes the data that depends on the flags.
+fn auth_input<F>(flags: u64, body: F) -> Vec<u8>
+where
+    F: FnOnce(&mut Vec<u8>) -> anyhow::Result<()>,
+{
+    use util::{BinaryWrite, Vector};
+
+    let mut buffer = Vec::new();
+    buffer.write_f32_le(10.0).unwrap();
+    buffer.write_f32_le(20.0).unwrap();
+    buffer.write_vecf(&Vector::from([1.0, 2.0, 3.0])).unwrap();
+    buffer.write_vecf(&Vector::from([0.0, 1.0])).unwrap();
+    buffer.write_f32_le(30.0).unwrap();
+    buffer.write_var_u64(flags).unwrap();
+    buffer.write_var_u32(1).unwrap(); // Input mode: mouse
+    buffer.write_var_u32(0).unwrap(); // Play mode: normal
+    buffer.write_var_i32(1).unwrap(); // Interaction model: crosshair
+    buffer.write_var_u64(42).unwrap();
+    buffer.write_vecf(&Vector::from([0.0, -0.1, 0.2])).unwrap();
+    body(&mut buffer).unwrap();
+    buffer.write_vecf(&Vector::from([0.0, 0.0])).unwrap();
+
+    buffer
+}
+
+#[test]
+fn auth_input_item_transaction() {
+    use util::{BinaryWrite, BlockPosition, Deserialize, Serialize, Vector};
+    use crate::bedrock::{InputDataFlag, ItemInstance, PlayerAuthInput, TransactionAction, TransactionSourceType, UseItemAction, WindowId};
+
+    let action = TransactionAction {
+        source_type: TransactionSourceType::Container { inventory_id: WindowId::Inventory },
+        slot: 3,
+        old_item: ItemInstance::air(),
+        new_item: ItemInstance { network_id: 5, count: 2, ..ItemInstance::air() },
+    };
+
+    let packet = auth_input(InputDataFlag::PerformItemTransaction as u64, |buffer| {
+        buffer.write_var_i32(0)?; // Legacy request ID
+        buffer.write_var_u32(1)?;
+        action.serialize_into(buffer)?;
+        buffer.write_var_u32(1)?; // Click air
+        buffer.write_block_pos(&BlockPosition::new(1, 2, 3))?;
+        buffer.write_var_i32(4)?;
+        buffer.write_var_i32(5)?;
+        ItemInstance::air().serialize_into(buffer)?;
+        buffer.write_vecf(&Vector::from([1.0, 2.0, 3.0]))?;
+        buffer.write_vecf(&Vector::from([0.5, 0.5, 0.5]))?;
+        buffer.write_var_u32(7)
+    });
+
+    let input = PlayerAuthInput::deserialize(packet.as_slice()).unwrap();
+    assert_eq!(input.tick, 42);
+
+    let transaction = input.item_transaction.unwrap();
+    assert_eq!(transaction.actions.len(), 1);
+    assert_eq!(transaction.actions[0].slot, 3);
+    assert_eq!(transaction.actions[0].new_item, action.new_item);
+    assert_eq!(transaction.action_type, UseItemAction::ClickAir);
+    assert_eq!(transaction.block_position, BlockPosition::new(1, 2, 3));
+    assert_eq!((transaction.block_face, transaction.hotbar_slot, transaction.block_runtime_id), (4, 5, 7));
+
+    // Truncated packets must be rejected instead of panicking.
+    assert!(PlayerAuthInput::deserialize(&packet[..packet.len() - 12]).is_err());
+}
+
+#[test]
+fn auth_input_item_stack_request() {
+    use util::{BinaryWrite, Deserialize};
+    use crate::bedrock::{FilterCause, InputDataFlag, PlayerAuthInput};
+
+    let packet = auth_input(InputDataFlag::PerformItemStackRequest as u64, |buffer| {
+        buffer.write_var_i32(-3)?;
+        buffer.write_var_u32(0)?; // Actions
+        buffer.write_var_u32(1)?;
+        buffer.write_str("filtered")?;
+        buffer.write_i32_le(FilterCause::SignText as i32)
+    });
+
+    let input = PlayerAuthInput::deserialize(packet.as_slice()).unwrap();
+    let request = input.item_stack.unwrap();
+    assert_eq!(request.request_id, -3);
+    assert_eq!(request.filters, ["filtered"]);
+    assert_eq!(request.filter_cause, FilterCause::SignText);
+}
+
+#[test]
+fn auth_input_block_actions() {
+    use util::{BinaryWrite, BlockPosition, Deserialize};
+    use crate::bedrock::{BlockAction, InputDataFlag, PlayerActionType, PlayerAuthInput};
+
+    let packet = auth_input(InputDataFlag::PerformBlockActions as u64, |buffer| {
+        buffer.write_var_i32(2)?;
+        buffer.write_var_i32(PlayerActionType::StartBreak as i32)?;
+        buffer.write_var_i32(1)?;
+        buffer.write_var_i32(-5)?;
+        buffer.write_var_i32(3)?;
+        buffer.write_var_i32(1)?;
+        // Jumping does not include a position.
+        buffer.write_var_i32(PlayerActionType::Jump as i32)
+    });
+
+    let input = PlayerAuthInput::deserialize(packet.as_slice()).unwrap();
+    assert_eq!(
+        input.block_actions.unwrap(),
+        [
+            BlockAction { action: PlayerActionType::StartBreak, position: BlockPosition::new(1, -5i32 as u32, 3), face: 1 },
+            BlockAction { action: PlayerActionType::Jump, position: BlockPosition::new(0, 0, 0), face: 0 },
+        ]
+    );
+}
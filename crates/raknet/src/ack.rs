@@ -1,8 +1,10 @@
+use std::sync::atomic::Ordering;
+
 use util::{Deserialize, BinaryRead, Serialize};
 
 use proto::raknet::{Ack, Nak};
 
-use crate::RakNetClient;
+use crate::{FrameBatch, RakNetClient, METRICS};
 
 impl RakNetClient {
     /// Processes an acknowledgement received from the client.
@@ -14,7 +16,8 @@ impl RakNetClient {
         #[cfg(trace_raknet)]
         tracing::debug!("{ack:?}");
 
-        self.recovery.acknowledge(&ack.records);
+        let acknowledged = self.recovery.acknowledge(&ack.records);
+        self.congestion.on_ack(acknowledged.count, acknowledged.rtt);
 
         Ok(())
     }
@@ -29,9 +32,36 @@ impl RakNetClient {
         tracing::warn!("Received nak for {nak:?}");
 
         let frame_batches = self.recovery.recover(&nak.records);
+        // NAKs for batches that have already been acknowledged or resent do not indicate new losses.
+        if !frame_batches.is_empty() {
+            self.congestion.on_loss();
+        }
+
+        self.resend(frame_batches).await
+    }
+
+    /// Resends the batches that were not acknowledged before the retransmission timeout expired.
+    pub async fn resend_expired(&self) -> anyhow::Result<()> {
+        let expired = self.recovery.expired(self.congestion.rto());
+        if expired.is_empty() {
+            return Ok(());
+        }
 
+        tracing::debug!("{} frame batches timed out, resending them", expired.len());
+
+        METRICS.timeouts.inc_by(expired.len() as u64);
+        self.congestion.on_timeout();
+
+        self.resend(expired).await
+    }
+
+    /// Sends frame batches again under new sequence numbers.
+    ///
+    /// The batches are put back into the recovery queue in case they are lost again.
+    async fn resend(&self, frame_batches: Vec<FrameBatch>) -> anyhow::Result<()> {
         let mut serialized = Vec::new();
-        for frame_batch in frame_batches {
+        for mut frame_batch in frame_batches {
+            frame_batch.sequence_number = self.batch_number.fetch_add(1, Ordering::SeqCst);
             frame_batch.serialize_into(&mut serialized)?;
 
             self
@@ -41,6 +71,8 @@ impl RakNetClient {
                 )
                 .await?;
 
+            METRICS.resends.inc();
+            self.recovery.insert_resent(frame_batch);
             serialized.clear();
         }
 
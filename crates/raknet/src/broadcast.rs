@@ -21,6 +21,11 @@ pub struct BroadcastPacket {
     /// If it matches, the packet will not be sent.
     /// This can be used to broadcast raknet to every client other than self.
     pub sender: Option<SocketAddr>,
+    /// Split screen subclient of the sender.
+    ///
+    /// Subclients share the address of their connection, so this is used together with `sender`
+    /// to identify the sending player.
+    pub sender_subclient: u8,
     /// The ID of the packet.
     pub id: u32,
     /// Content of the packet.
@@ -39,6 +44,7 @@ impl BroadcastPacket {
     ) -> anyhow::Result<Self> {
         Ok(Self {
             sender,
+            sender_subclient: 0,
             id: T::ID,
             content: Arc::from(packet.serialize()?),
         })
@@ -1,4 +1,4 @@
-use std::{net::SocketAddr, sync::{Arc, atomic::{AtomicU16, AtomicU32, AtomicU64}}, time::Instant, mem::MaybeUninit};
+use std::{net::SocketAddr, sync::{Arc, OnceLock, atomic::{AtomicU16, AtomicU32, AtomicU64, AtomicUsize}}, time::Instant, mem::MaybeUninit};
 
 use parking_lot::{Mutex, RwLock};
 use proto::raknet::DisconnectNotification;
@@ -6,7 +6,7 @@ use tokio::{net::UdpSocket, sync::{broadcast, mpsc, Semaphore}};
 use tokio_util::sync::CancellationToken;
 use util::{RVec, Joinable};
 
-use crate::{BroadcastPacket, Compounds, OrderChannel, Recovery, Reliability, SendConfig, SendPriority, SendQueues, BUDGET_SIZE};
+use crate::{BroadcastPacket, Compounds, CongestionControl, OrderChannel, PacketEncoder, Recovery, Reliability, SendConfig, SendPriority, SendQueues, BUDGET_SIZE, DEFAULT_TICK_BUDGET};
 
 const ORDER_CHANNEL_COUNT: usize = 5;
 const OUTPUT_CHANNEL_SIZE: usize = 5;
@@ -66,6 +66,13 @@ pub struct RakNetClient {
     pub batch_number: AtomicU32,
     /// Packets pending submission to the client.
     pub send: SendQueues,
+    /// Maximum amount of bytes sent to the client per tick.
+    pub tick_budget: AtomicUsize,
+    /// Encodes game packets right before they are sent.
+    pub encoder: OnceLock<Box<dyn PacketEncoder>>,
+    /// Makes sure that packets are encoded and sent by one flush at a time,
+    /// so that they arrive in the order they were encoded in.
+    pub flush_lock: tokio::sync::Mutex<()>,
     /// Pending acknowledgements.
     /// Wrapped in a mutex since reading this will also clear it.
     pub acknowledged: Mutex<Vec<u32>>,
@@ -79,6 +86,8 @@ pub struct RakNetClient {
     pub compounds: Compounds,
     /// Stores packets for recovery in case of packet loss.
     pub recovery: Recovery,
+    /// Round trip time estimation and congestion window, which limits the amount of batches in flight.
+    pub congestion: CongestionControl,
     /// Current sequence index, this is increased for every sequenced packet sent.
     pub sequence_index: AtomicU32,
     /// Multiple channels that ensure packets are received in the right order.
@@ -127,8 +136,12 @@ impl RakNetClient {
             tick: AtomicU64::new(0),
             batch_number: AtomicU32::new(0),
             send: SendQueues::new(),
+            tick_budget: AtomicUsize::new(DEFAULT_TICK_BUDGET),
+            encoder: OnceLock::new(),
+            flush_lock: tokio::sync::Mutex::new(()),
             acknowledged: Mutex::new(Vec::with_capacity(5)),
             recovery: Recovery::new(),
+            congestion: CongestionControl::new(),
             mtu: info.mtu,
             acknowledge_index: AtomicU32::new(0),
             compound_id: AtomicU16::new(0),
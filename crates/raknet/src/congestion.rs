@@ -0,0 +1,154 @@
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+/// Amount of datagrams that can be in flight when a connection is created.
+pub const INITIAL_WINDOW: f64 = 16.0;
+/// The congestion window never shrinks below this amount of datagrams.
+pub const MIN_WINDOW: f64 = 2.0;
+/// The congestion window never grows beyond this amount of datagrams.
+pub const MAX_WINDOW: f64 = 1024.0;
+
+/// Retransmission timeout used before any round trip time has been measured.
+pub const INITIAL_RTO: Duration = Duration::from_secs(1);
+/// Lower bound of the retransmission timeout.
+pub const MIN_RTO: Duration = Duration::from_millis(100);
+/// Upper bound of the retransmission timeout.
+///
+/// This is lower than the session timeout so that a batch is resent at least once before a client is
+/// considered unresponsive.
+pub const MAX_RTO: Duration = Duration::from_secs(2);
+
+/// Granularity of the clock, used as a lower bound for the variance term of the timeout.
+const CLOCK_GRANULARITY: Duration = Duration::from_millis(10);
+
+/// Round trip time estimation and congestion window of a single connection.
+///
+/// The round trip time and retransmission timeout are estimated as described in RFC 6298. The congestion
+/// window starts in slow start, growing by one datagram per acknowledged datagram until the slow start
+/// threshold is reached, after which it grows by one datagram per round trip. Loss reported by a NAK halves
+/// the window, while a retransmission timeout collapses it to the minimum and restarts slow start.
+#[derive(Debug)]
+pub struct CongestionControl {
+    state: Mutex<CongestionState>,
+}
+
+#[derive(Debug)]
+struct CongestionState {
+    /// Smoothed round trip time.
+    srtt: Option<Duration>,
+    /// Round trip time variation.
+    rttvar: Duration,
+    /// Current retransmission timeout.
+    rto: Duration,
+    /// Maximum amount of datagrams in flight.
+    window: f64,
+    /// Window size at which slow start ends.
+    threshold: f64,
+    /// Last time the window was decreased, used to react only once to losses within the same round trip.
+    last_decrease: Option<Instant>,
+}
+
+impl CongestionControl {
+    /// Creates the congestion state of a new connection.
+    pub const fn new() -> CongestionControl {
+        CongestionControl {
+            state: Mutex::new(CongestionState {
+                srtt: None,
+                rttvar: Duration::ZERO,
+                rto: INITIAL_RTO,
+                window: INITIAL_WINDOW,
+                threshold: MAX_WINDOW,
+                last_decrease: None,
+            }),
+        }
+    }
+
+    /// Processes datagrams that have been acknowledged by the client.
+    ///
+    /// `rtt` is the round trip time of one of the datagrams, if it could be measured. Datagrams that have
+    /// been resent should not be used to measure the round trip time since it is unknown which copy was
+    /// acknowledged.
+    pub fn on_ack(&self, count: usize, rtt: Option<Duration>) {
+        let mut state = self.state.lock();
+
+        if let Some(rtt) = rtt {
+            state.sample(rtt);
+        }
+
+        for _ in 0..count {
+            if state.window < state.threshold {
+                state.window += 1.0;
+            } else {
+                state.window += 1.0 / state.window;
+            }
+        }
+        state.window = state.window.min(MAX_WINDOW);
+    }
+
+    /// Processes datagrams that the client reported as lost.
+    pub fn on_loss(&self) {
+        let mut state = self.state.lock();
+
+        // Multiple NAKs sent within the same round trip are usually caused by the same congestion.
+        let round_trip = state.srtt.unwrap_or(INITIAL_RTO);
+        if state.last_decrease.is_some_and(|last| last.elapsed() < round_trip) {
+            return;
+        }
+
+        state.threshold = (state.window / 2.0).max(MIN_WINDOW);
+        state.window = state.threshold;
+        state.last_decrease = Some(Instant::now());
+    }
+
+    /// Processes datagrams that were not acknowledged before the retransmission timeout expired.
+    pub fn on_timeout(&self) {
+        let mut state = self.state.lock();
+
+        state.threshold = (state.window / 2.0).max(MIN_WINDOW);
+        state.window = MIN_WINDOW;
+        state.rto = (state.rto * 2).min(MAX_RTO);
+        state.last_decrease = Some(Instant::now());
+    }
+
+    /// Maximum amount of datagrams that can be in flight.
+    pub fn window(&self) -> usize {
+        self.state.lock().window as usize
+    }
+
+    /// Current retransmission timeout.
+    pub fn rto(&self) -> Duration {
+        self.state.lock().rto
+    }
+
+    /// Smoothed round trip time, if it has been measured yet.
+    pub fn rtt(&self) -> Option<Duration> {
+        self.state.lock().srtt
+    }
+}
+
+impl Default for CongestionControl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CongestionState {
+    /// Updates the round trip time estimate and retransmission timeout with a new measurement.
+    fn sample(&mut self, rtt: Duration) {
+        match self.srtt {
+            None => {
+                self.srtt = Some(rtt);
+                self.rttvar = rtt / 2;
+            }
+            Some(srtt) => {
+                let deviation = if srtt > rtt { srtt - rtt } else { rtt - srtt };
+                self.rttvar = (self.rttvar * 3 + deviation) / 4;
+                self.srtt = Some((srtt * 7 + rtt) / 8);
+            }
+        }
+
+        let srtt = self.srtt.unwrap_or(rtt);
+        self.rto = (srtt + (self.rttvar * 4).max(CLOCK_GRANULARITY)).clamp(MIN_RTO, MAX_RTO);
+    }
+}
@@ -1,20 +1,12 @@
 use std::{
-    sync::{Arc, atomic::{AtomicU64, Ordering}},
+    sync::{Arc, atomic::Ordering},
     time::{Duration, Instant},
 };
 
-use prometheus_client::metrics::counter::Counter;
 use tokio::sync::{mpsc, TryAcquireError};
 use util::RVec;
 
-use crate::{RakNetCommand, RakNetClient};
-
-use lazy_static::lazy_static;
-
-lazy_static! {
-    #[doc(hidden)]
-    pub static ref TOTAL_PACKETS_METRIC: Counter::<u64, AtomicU64> = Counter::default();
-}
+use crate::{RakNetCommand, RakNetClient, METRICS};
 
 /// Limit to the amount of packets a client is allowed to send per second.
 pub const BUDGET_SIZE: usize = 50;
@@ -80,7 +72,7 @@ impl RakNetClient {
                     if let Err(err) = self.handle_raw_packet(packet).await {
                         tracing::error!("{err:?}");
                     }
-                    TOTAL_PACKETS_METRIC.inc();
+                    METRICS.packets_received.inc();
                 }
             }
 
@@ -112,6 +104,7 @@ impl RakNetClient {
             self.active.cancel();
         }
 
+        self.resend_expired().await?;
         self.flush().await?;
         Ok(())
     }
@@ -47,8 +47,10 @@ use util::glob_export;
 glob_export!(ack);
 glob_export!(broadcast);
 glob_export!(compound);
+glob_export!(congestion);
 glob_export!(frame);
 glob_export!(login);
+glob_export!(metrics);
 glob_export!(order);
 glob_export!(receive);
 glob_export!(recovery);
@@ -57,3 +59,6 @@ glob_export!(send_queue);
 glob_export!(send);
 glob_export!(client);
 glob_export!(job);
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,54 @@
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+use prometheus_client::registry::Registry;
+
+use lazy_static::lazy_static;
+
+lazy_static! {
+    /// Metrics shared by all RakNet clients.
+    pub static ref METRICS: RakNetMetrics = RakNetMetrics::new();
+}
+
+/// Statistics about the RakNet layer that can be exported to Prometheus.
+pub struct RakNetMetrics {
+    /// Amount of raw packets received from clients.
+    pub packets_received: Counter,
+    /// Amount of frame batch sequence numbers that clients acknowledged.
+    pub acks: Counter,
+    /// Amount of frame batch sequence numbers that clients reported as lost.
+    pub naks: Counter,
+    /// Amount of frame batches that were sent again.
+    pub resends: Counter,
+    /// Amount of frame batches that were not acknowledged before the retransmission timeout expired.
+    pub timeouts: Counter,
+    /// Time between sending a frame batch and receiving its acknowledgement, in seconds.
+    pub round_trip_time: Histogram,
+}
+
+impl RakNetMetrics {
+    fn new() -> RakNetMetrics {
+        RakNetMetrics {
+            packets_received: Counter::default(),
+            acks: Counter::default(),
+            naks: Counter::default(),
+            resends: Counter::default(),
+            timeouts: Counter::default(),
+            // 2.5 ms up to 1.28 s.
+            round_trip_time: Histogram::new(exponential_buckets(0.0025, 2.0, 10)),
+        }
+    }
+
+    /// Adds the metrics to a registry.
+    pub fn register(&self, registry: &mut Registry) {
+        registry.register("raknet_packets_received", "Raw packets received from clients", self.packets_received.clone());
+        registry.register("raknet_acks", "Frame batches acknowledged by clients", self.acks.clone());
+        registry.register("raknet_naks", "Frame batches reported as lost by clients", self.naks.clone());
+        registry.register("raknet_resends", "Frame batches sent again after being lost", self.resends.clone());
+        registry.register("raknet_timeouts", "Frame batches that were not acknowledged in time", self.timeouts.clone());
+        registry.register(
+            "raknet_round_trip_time_seconds",
+            "Time between sending a frame batch and receiving its acknowledgement",
+            self.round_trip_time.clone(),
+        );
+    }
+}
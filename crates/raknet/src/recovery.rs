@@ -1,7 +1,28 @@
+use std::time::{Duration, Instant};
+
 use dashmap::DashMap;
 use proto::raknet::AckEntry;
 
-use crate::FrameBatch;
+use crate::{FrameBatch, METRICS};
+
+/// A frame batch that has been sent and not been acknowledged yet.
+#[derive(Debug)]
+struct SentBatch {
+    batch: FrameBatch,
+    /// When the batch was last sent.
+    sent: Instant,
+    /// Whether this batch has been sent more than once.
+    resent: bool,
+}
+
+/// Summary of the batches removed by an acknowledgement.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Acknowledged {
+    /// Amount of batches that were removed from the queue.
+    pub count: usize,
+    /// Round trip time of the most recently sent batch that was only sent once.
+    pub rtt: Option<Duration>,
+}
 
 /// Holds previously sent raknet to be able to recover them when packet loss occurs.
 ///
@@ -10,7 +31,7 @@ use crate::FrameBatch;
 /// If a NAK is received, the specified raknet can be recovered from the queue.
 #[derive(Default, Debug)]
 pub struct Recovery {
-    frames: DashMap<u32, FrameBatch>,
+    frames: DashMap<u32, SentBatch>,
 }
 
 impl Recovery {
@@ -24,25 +45,62 @@ impl Recovery {
     /// The frame batch will stay in the queue until it is acknowledged.
     #[inline]
     pub fn insert(&self, batch: FrameBatch) {
-        self.frames.insert(batch.sequence_number, batch);
+        self.frames.insert(batch.sequence_number, SentBatch { batch, sent: Instant::now(), resent: false });
+    }
+
+    /// Inserts a frame batch that has been sent again under a new sequence number.
+    ///
+    /// Acknowledgements of resent batches are not used to measure the round trip time,
+    /// since it is unknown which of the copies was acknowledged.
+    #[inline]
+    pub fn insert_resent(&self, batch: FrameBatch) {
+        self.frames.insert(batch.sequence_number, SentBatch { batch, sent: Instant::now(), resent: true });
+    }
+
+    /// Amount of batches that have been sent but not acknowledged yet.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Whether all sent batches have been acknowledged.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
     }
 
     /// Removes the specified raknet from the recovery queue.
     ///
     /// This method should be called when an ACK is received.
-    pub fn acknowledge(&self, records: &[AckEntry]) {
+    pub fn acknowledge(&self, records: &[AckEntry]) -> Acknowledged {
+        let mut acknowledged = Acknowledged::default();
         for record in records {
             match record {
-                AckEntry::Single(id) => {
-                    self.frames.remove(id);
-                }
+                AckEntry::Single(id) => self.remove_acknowledged(*id, &mut acknowledged),
                 AckEntry::Range(range) => {
                     for id in range.clone() {
-                        self.frames.remove(&id);
+                        self.remove_acknowledged(id, &mut acknowledged);
                     }
                 }
             }
         }
+
+        acknowledged
+    }
+
+    /// Removes an acknowledged batch and records its round trip time.
+    fn remove_acknowledged(&self, id: u32, acknowledged: &mut Acknowledged) {
+        let Some((_, sent)) = self.frames.remove(&id) else {
+            return;
+        };
+
+        METRICS.acks.inc();
+        acknowledged.count += 1;
+        if !sent.resent {
+            let rtt = sent.sent.elapsed();
+            METRICS.round_trip_time.observe(rtt.as_secs_f64());
+            acknowledged.rtt = Some(acknowledged.rtt.map_or(rtt, |other| other.min(rtt)));
+        }
     }
 
     /// Recovers the specified raknet from the recovery queue.
@@ -57,15 +115,17 @@ impl Recovery {
         for record in records {
             match record {
                 AckEntry::Single(id) => {
-                    if let Some(frame) = self.frames.remove(id) {
-                        recovered.push(frame.1);
+                    METRICS.naks.inc();
+                    if let Some((_, sent)) = self.frames.remove(id) {
+                        recovered.push(sent.batch);
                     }
                 }
                 AckEntry::Range(range) => {
+                    METRICS.naks.inc_by(range.len() as u64);
                     recovered.reserve(range.len());
                     for id in range.clone() {
-                        if let Some(frame) = self.frames.remove(&id) {
-                            recovered.push(frame.1);
+                        if let Some((_, sent)) = self.frames.remove(&id) {
+                            recovered.push(sent.batch);
                         }
                     }
                 }
@@ -78,4 +138,19 @@ impl Recovery {
 
         recovered
     }
+
+    /// Removes the batches that have not been acknowledged within the given timeout.
+    ///
+    /// The returned batches are ordered by their sequence number.
+    pub fn expired(&self, timeout: Duration) -> Vec<FrameBatch> {
+        let mut ids = self
+            .frames
+            .iter()
+            .filter(|entry| entry.sent.elapsed() > timeout)
+            .map(|entry| *entry.key())
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+
+        ids.into_iter().filter_map(|id| self.frames.remove(&id).map(|(_, sent)| sent.batch)).collect()
+    }
 }
@@ -5,7 +5,22 @@ use proto::raknet::{Ack, AckEntry};
 
 use util::{RVec, Serialize};
 
-use crate::{SendPriority, RakNetClient, Reliability, Frame, FrameBatch};
+use crate::{SendPriority, RakNetClient, Reliability, Frame, FrameBatch, QueuedFrame};
+
+/// Transforms game packets right before they are sent.
+///
+/// Packets are sent in order of priority rather than in the order they were queued in.
+/// Transformations that depend on the order of packets, such as encryption with a stream cipher,
+/// must therefore be performed by an encoder rather than before the packet is queued.
+pub trait PacketEncoder: Send + Sync {
+    /// Encodes a single packet in place.
+    fn encode(&self, packet: &mut RVec) -> anyhow::Result<()>;
+}
+
+/// Default amount of bytes that can be sent to a client per tick.
+///
+/// At 20 ticks per second, this limits a client to about 1.25 MiB/s.
+pub const DEFAULT_TICK_BUDGET: usize = 64 * 1024;
 
 /// Specifies the reliability and priority of a packet.
 pub struct SendConfig {
@@ -44,27 +59,36 @@ impl RakNetClient {
         );
     }
 
+    /// Sends a game packet that will be passed through the client's [`PacketEncoder`] right before it is sent.
+    ///
+    /// Use this for packets whose encoding depends on the order in which they are sent, such as encrypted packets.
+    /// Since packets are scheduled by priority, they can be sent in a different order than they were queued in.
+    pub fn send_encoded_buffer_with_config<B>(
+        &self,
+        buffer: B,
+        config: SendConfig,
+    ) where B: Into<RVec> {
+        let buffer = buffer.into();
+        self.send.insert_encoded(
+            config.priority,
+            Frame::new(config.reliability, buffer),
+        );
+    }
+
     /// Flushes the send queue.
+    ///
+    /// The amount of data sent is limited by both the client's byte budget per tick and the congestion window.
+    /// Frames that do not fit stay queued until the next tick.
     pub async fn flush(&self) -> anyhow::Result<()> {
         let tick = self.tick.load(Ordering::SeqCst);
 
-        if let Some(frames) = self.send.flush(SendPriority::High) {
-            self.send_raw_frames(frames).await?;
-        }
+        let budget = self.send_budget();
+        if budget > 0 {
+            let _guard = self.flush_lock.lock().await;
 
-        if tick % 2 == 0 {
-            // Also flush broadcast raknet.
-            if let Some(frames) =
-                self.send.flush(SendPriority::Medium)
-            {
-                self.send_raw_frames(frames).await?;
-            }
-        }
-
-        if tick % 4 == 0 {
-            if let Some(frames) =
-                self.send.flush(SendPriority::Low)
-            {
+            let frames = self.send.schedule(budget);
+            if !frames.is_empty() {
+                let frames = self.encode_frames(frames)?;
                 self.send_raw_frames(frames).await?;
             }
         }
@@ -77,24 +101,49 @@ impl RakNetClient {
         Ok(())
     }
 
+    /// Amount of bytes that can be sent in the current tick.
+    fn send_budget(&self) -> usize {
+        let in_flight = self.recovery.len();
+        let window = self.congestion.window().saturating_sub(in_flight) * self.mtu as usize;
+
+        window.min(self.tick_budget.load(Ordering::Relaxed))
+    }
+
     /// Flushes both the frames and acknowledgements.
+    ///
+    /// This ignores the byte budget and congestion window, it is used to send the final packets of a connection.
     pub async fn flush_all(&self) -> anyhow::Result<()> {
-        if let Some(frames) = self.send.flush(SendPriority::High) {
-            self.send_raw_frames(frames).await?;
-        }
-
-        if let Some(frames) = self.send.flush(SendPriority::Medium)
         {
-            self.send_raw_frames(frames).await?;
-        }
+            let _guard = self.flush_lock.lock().await;
 
-        if let Some(frames) = self.send.flush(SendPriority::Low) {
-            self.send_raw_frames(frames).await?;
+            let frames = self.send.drain();
+            if !frames.is_empty() {
+                let frames = self.encode_frames(frames)?;
+                self.send_raw_frames(frames).await?;
+            }
         }
 
         self.flush_acknowledgements().await
     }
 
+    /// Runs the encoder over the frames that require it, in the order they will be sent in.
+    fn encode_frames(&self, frames: Vec<QueuedFrame>) -> anyhow::Result<Vec<Frame>> {
+        frames
+            .into_iter()
+            .map(|mut queued| {
+                if queued.encode {
+                    let Some(encoder) = self.encoder.get() else {
+                        anyhow::bail!("Packet requires encoding but no encoder has been set");
+                    };
+
+                    encoder.encode(&mut queued.frame.body)?;
+                }
+
+                Ok(queued.frame)
+            })
+            .collect()
+    }
+
     /// Flushes all of the pending acknowledgements.
     pub async fn flush_acknowledgements(&self) -> anyhow::Result<()> {
         let mut confirmed = {
@@ -143,41 +192,17 @@ impl RakNetClient {
 
     /// Send a list of frames. 
     ///
-    /// These frames are not guaranteed to be sent in the same frame batch.
-    /// If the batch would be bigger than the MTU, the list will be split into multiple batches.
-    /// Similarly if a frame is larger than the MTU, it will be split and sent in multiple packets.
+    /// Frames are packed into as few frame batches as possible, each at most as large as the MTU.
+    /// Frames that are larger than the MTU are split and sent as a compound of fragments.
+    /// The frames are sent in the order they are given in.
     ///
     /// ## Warning 
     ///
     /// In case the passed frames are already fragmented, there should at maximum one compound
     /// in the entire list.
     #[async_recursion]
-    async fn send_raw_frames(&self, mut frames: Vec<Frame>) -> anyhow::Result<()> {
+    async fn send_raw_frames(&self, frames: Vec<Frame>) -> anyhow::Result<()> {
         let mut serialized = Vec::new();
-
-        // Process fragments first to prevent sequence number duplication.
-        let mut index = 0;
-        while index < frames.len() {
-            let frame_size = frames[index].body.len() + std::mem::size_of::<Frame>();
-
-            if frame_size > self.mtu as usize {
-                let large_frame = frames.swap_remove(index);
-                let compound = self.split_frame(&large_frame);
-
-                // Only one compound is passed to the function, so this is fine.
-                self.send_raw_frames(compound).await?;
-            } else {
-                index += 1;
-            }
-        }
-        
-        debug_assert!(
-            !frames
-                .iter()
-                .any(|f| f.body.len() > self.mtu as usize - std::mem::size_of::<Frame>()),
-            "Frames were not split properly"
-        );
-
         let mut batch = FrameBatch {
             sequence_number: 0,
             frames: vec![],
@@ -191,6 +216,21 @@ impl RakNetClient {
         for mut frame in frames {
             let frame_size = frame.body.len() + std::mem::size_of::<Frame>();
 
+            if frame_size > self.mtu as usize {
+                // Send the frames before this one first to preserve the order.
+                if !batch.is_empty() {
+                    let full = std::mem::replace(&mut batch, FrameBatch { sequence_number: 0, frames: vec![] });
+                    self.send_batch(full, has_reliable_packet, &mut serialized).await?;
+                    has_reliable_packet = false;
+                }
+
+                let compound = self.split_frame(&frame);
+
+                // Only one compound is passed to the function, so this is fine.
+                self.send_raw_frames(compound).await?;
+                continue;
+            }
+
             if frame.reliability.is_ordered() && !frame.is_compound {
                 let order_index = self.order[frame.order_channel as usize]
                     .alloc_index();
@@ -216,65 +256,49 @@ impl RakNetClient {
             if frame.reliability.is_reliable() {
                 frame.reliable_index =
                     self.acknowledge_index.fetch_add(1, Ordering::SeqCst);
-
-                has_reliable_packet = true;
             }
 
             #[allow(clippy::unwrap_used)]
-            if batch.size_hint().unwrap() + frame_size <= self.mtu as usize {
-                batch.frames.push(frame);
-            } else if !batch.is_empty() {
-                serialized.clear();
-
-                batch.sequence_number = self.batch_number.fetch_add(1, Ordering::SeqCst);
-                batch.serialize_into(&mut serialized)?;
-
-                // TODO: Add IPv6 support
-                self.socket
-                    .send_to(serialized.as_ref(), self.address)
-                    .await?;
-
-                if has_reliable_packet {
-                    self.recovery.insert(batch);
-                }
-
+            if batch.size_hint().unwrap() + frame_size > self.mtu as usize && !batch.is_empty() {
+                let full = std::mem::replace(&mut batch, FrameBatch { sequence_number: 0, frames: vec![] });
+                self.send_batch(full, has_reliable_packet, &mut serialized).await?;
                 has_reliable_packet = false;
-                batch = FrameBatch {
-                    sequence_number: self
-                        .batch_number
-                        .fetch_add(0, Ordering::SeqCst),
-
-                    frames: vec![frame],
-                };
             }
+
+            has_reliable_packet |= frame.reliability.is_reliable();
+            batch.frames.push(frame);
         }
 
         // Send remaining packets not sent by loop
         if !batch.is_empty() {
-            serialized.clear();
+            self.send_batch(batch, has_reliable_packet, &mut serialized).await?;
+        }
 
-            batch.sequence_number = self.batch_number.fetch_add(1, Ordering::SeqCst);
-            batch.serialize_into(&mut serialized)?;
+        Ok(())
+    }
 
-            if has_reliable_packet {
-                self.recovery.insert(batch);
-            }
+    /// Assigns a sequence number to a batch and sends it.
+    ///
+    /// Batches containing reliable frames are stored for recovery.
+    async fn send_batch(&self, mut batch: FrameBatch, has_reliable_packet: bool, serialized: &mut Vec<u8>) -> anyhow::Result<()> {
+        serialized.clear();
+
+        batch.sequence_number = self.batch_number.fetch_add(1, Ordering::SeqCst);
+        batch.serialize_into(serialized)?;
 
-            // TODO: Add IPv6 support
-            self.socket
-                .send_to(serialized.as_ref(), self.address)
-                .await?;
+        // TODO: Add IPv6 support
+        self.socket
+            .send_to(serialized.as_ref(), self.address)
+            .await?;
+
+        if has_reliable_packet {
+            self.recovery.insert(batch);
         }
-        // } else {
-        //     self.batch_number.fetch_sub(1, Ordering::SeqCst);
-        // }
 
         Ok(())
     }
 
     fn split_frame(&self, frame: &Frame) -> Vec<Frame> {
-        println!("{}", frame.body.len());
-
         let chunk_max_size = self.mtu as usize
             - std::mem::size_of::<Frame>()
             - std::mem::size_of::<FrameBatch>();
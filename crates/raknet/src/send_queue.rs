@@ -7,30 +7,74 @@ use crate::Frame;
 
 /// Priority of the packet.
 /// This affects when they're sent.
+///
+/// Every tick, the queues are visited in turns until the client's byte budget for that tick has been used up.
+/// In each turn a queue can send up to a fixed amount of frames, so that lower priority frames are delayed
+/// when bandwidth is scarce but are never starved completely.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum SendPriority {
-    /// High priority is flushed every session tick.
+    /// High priority sends up to four frames per turn.
     High,
-    /// Medium priority is flushed every two session ticks.
+    /// Medium priority sends up to two frames per turn.
     Medium,
-    /// Low priority is flushed every four session ticks.
+    /// Low priority sends one frame per turn.
     Low,
 }
 
-/// Contains three LIFO deques that each have a different priority assigned to them.
+impl SendPriority {
+    /// All priorities in the order in which they take turns.
+    const ALL: [SendPriority; 3] = [SendPriority::High, SendPriority::Medium, SendPriority::Low];
+
+    /// Maximum amount of frames that a queue can send in a single turn.
+    pub const fn weight(self) -> usize {
+        match self {
+            SendPriority::High => 4,
+            SendPriority::Medium => 2,
+            SendPriority::Low => 1,
+        }
+    }
+}
+
+/// A frame waiting in a send queue.
+#[derive(Debug)]
+pub struct QueuedFrame {
+    /// The frame itself.
+    pub frame: Frame,
+    /// Whether the frame body must be passed through the client's [`PacketEncoder`](crate::PacketEncoder)
+    /// before it is sent.
+    pub encode: bool,
+}
+
+/// Position of the scheduler, which is kept in between ticks.
+#[derive(Debug)]
+struct Turn {
+    /// Index into [`SendPriority::ALL`] of the queue whose turn it is.
+    current: usize,
+    /// Amount of frames that the current queue can still send in this turn.
+    remaining: usize,
+}
+
+impl Default for Turn {
+    fn default() -> Self {
+        Turn { current: 0, remaining: SendPriority::High.weight() }
+    }
+}
+
+/// Contains three FIFO deques that each have a different priority assigned to them.
 /// The priority of a given frame determines which queue it enters and how the
 /// server prioritizes sending it.
 #[derive(Default, Debug)]
 pub struct SendQueues {
     /// Queue for high priority frames.
-    /// Flushed every session tick.
-    high_priority: Mutex<VecDeque<Frame>>,
+    high_priority: Mutex<VecDeque<QueuedFrame>>,
     /// Queue for medium priority frames.
-    /// Flushed every 2 session ticks.
-    medium_priority: Mutex<VecDeque<Frame>>,
+    medium_priority: Mutex<VecDeque<QueuedFrame>>,
     /// Queue for low priority frames.
-    /// Flushed every 4 session ticks.
-    low_priority: Mutex<VecDeque<Frame>>,
+    low_priority: Mutex<VecDeque<QueuedFrame>>,
+    /// Queue whose turn it is.
+    ///
+    /// When the budget of a tick runs out, the next tick continues where the previous one stopped.
+    turn: Mutex<Turn>,
     /// It is faster to update a boolean on each read/write and check that,
     /// than to lock each of the three queues to check if they are empty.
     is_empty: AtomicBool,
@@ -62,64 +106,91 @@ impl SendQueues {
 
     /// Inserts a new packet into the send queue.
     pub fn insert_raw(&self, priority: SendPriority, frame: Frame) {
+        self.insert(priority, QueuedFrame { frame, encode: false });
+    }
+
+    /// Inserts a new packet into the send queue that will be encoded right before it is sent.
+    pub fn insert_encoded(&self, priority: SendPriority, frame: Frame) {
+        self.insert(priority, QueuedFrame { frame, encode: true });
+    }
+
+    fn insert(&self, priority: SendPriority, frame: QueuedFrame) {
+        self.queue(priority).lock().push_back(frame);
         self.is_empty.store(false, Ordering::SeqCst);
+    }
 
-        match priority {
-            SendPriority::High => {
-                let mut lock = self.high_priority.lock();
-                lock.push_back(frame);
-            }
-            SendPriority::Medium => {
-                let mut lock = self.medium_priority.lock();
-                lock.push_back(frame);
+    /// Takes frames from the queues until their combined size would exceed `budget` bytes.
+    ///
+    /// The queues take turns according to their [`weight`](SendPriority::weight). If the next frame does not fit
+    /// in the budget, scheduling stops and the next call starts with that frame. The first frame is always taken,
+    /// even if it exceeds the budget by itself, so that large frames cannot get stuck.
+    pub fn schedule(&self, budget: usize) -> Vec<QueuedFrame> {
+        let mut turn = self.turn.lock();
+        let mut scheduled = Vec::new();
+        let mut size = 0;
+
+        // Amount of consecutive turns in which the queue was empty.
+        let mut idle = 0;
+        'turns: while idle < SendPriority::ALL.len() {
+            let priority = SendPriority::ALL[turn.current];
+
+            let mut queue = self.queue(priority).lock();
+            if queue.is_empty() {
+                idle += 1;
+            } else {
+                idle = 0;
             }
-            SendPriority::Low => {
-                let mut lock = self.low_priority.lock();
-                lock.push_back(frame);
+
+            while turn.remaining > 0 {
+                let Some(queued) = queue.pop_front() else {
+                    break;
+                };
+
+                let frame_size = queued.frame.body.len();
+                if size + frame_size > budget && !scheduled.is_empty() {
+                    queue.push_front(queued);
+                    break 'turns;
+                }
+
+                size += frame_size;
+                turn.remaining -= 1;
+                scheduled.push(queued);
             }
+            drop(queue);
+
+            turn.current = (turn.current + 1) % SendPriority::ALL.len();
+            turn.remaining = SendPriority::ALL[turn.current].weight();
         }
+        drop(turn);
+
+        self.update_empty();
+        scheduled
     }
 
-    /// Flushes the specified queue.
-    pub fn flush(&self, priority: SendPriority) -> Option<Vec<Frame>> {
-        // FIXME: This function can potentially return a reference instead of moving the frames
-        // to reduce allocations.
+    /// Takes all frames from the queues, ignoring priorities.
+    pub fn drain(&self) -> Vec<QueuedFrame> {
+        let mut drained = Vec::new();
+        for priority in SendPriority::ALL {
+            drained.extend(self.queue(priority).lock().drain(..));
+        }
+
+        self.update_empty();
+        drained
+    }
 
-        // self.is_empty.store(true, Ordering::SeqCst);
+    /// Returns the queue of the given priority.
+    const fn queue(&self, priority: SendPriority) -> &Mutex<VecDeque<QueuedFrame>> {
         match priority {
-            SendPriority::High => {
-                let is_empty = self.low_priority.lock().is_empty() && self.medium_priority.lock().is_empty();
-                self.is_empty.store(is_empty, Ordering::SeqCst);
-
-                let mut lock = self.high_priority.lock();
-                if lock.is_empty() {
-                    None
-                } else {
-                    Some(lock.drain(0..).collect::<Vec<_>>())
-                }
-            }
-            SendPriority::Medium => {
-                let is_empty = self.low_priority.lock().is_empty() && self.medium_priority.lock().is_empty();
-                self.is_empty.store(is_empty, Ordering::SeqCst);
-
-                let mut lock = self.medium_priority.lock();
-                if lock.is_empty() {
-                    None
-                } else {
-                    Some(lock.drain(0..).collect::<Vec<_>>())
-                }
-            }
-            SendPriority::Low => {
-                let is_empty = self.low_priority.lock().is_empty() && self.medium_priority.lock().is_empty();
-                self.is_empty.store(is_empty, Ordering::SeqCst);
-
-                let mut lock = self.low_priority.lock();
-                if lock.is_empty() {
-                    None
-                } else {
-                    Some(lock.drain(0..).collect::<Vec<_>>())
-                }
-            }
+            SendPriority::High => &self.high_priority,
+            SendPriority::Medium => &self.medium_priority,
+            SendPriority::Low => &self.low_priority,
         }
     }
-}
\ No newline at end of file
+
+    fn update_empty(&self) {
+        let is_empty = self.high_priority.lock().is_empty()
+            && self.medium_priority.lock().is_empty()
+            && self.low_priority.lock().is_empty();
+        self.is_empty.store(is_empty, Ordering::SeqCst);
+    }
+}
@@ -1,20 +1,63 @@
+use std::time::Duration;
+
+use util::RVec;
+
+use crate::{CongestionControl, Frame, OrderChannel, QueuedFrame, Reliability, SendPriority, SendQueues, INITIAL_WINDOW, MIN_WINDOW};
+
 #[test]
 fn order_channel() {
     let mut channel = OrderChannel::new();
 
     let mut test_frame = Frame::default();
     test_frame.order_index = 0;
-    assert!(channel.insert(test_frame).is_some());
+    assert!(channel.insert(test_frame).unwrap().is_some());
 
     let mut test_frame = Frame::default();
     test_frame.order_index = 2;
-    assert!(channel.insert(test_frame).is_none());
+    assert!(channel.insert(test_frame).unwrap().is_none());
 
     let mut test_frame = Frame::default();
     test_frame.order_index = 1;
-    let output = channel.insert(test_frame).unwrap();
+    let output = channel.insert(test_frame).unwrap().unwrap();
 
     assert_eq!(output.len(), 2);
     assert_eq!(output[0].order_index, 1);
     assert_eq!(output[1].order_index, 2);
-}
\ No newline at end of file
+}
+
+#[test]
+fn congestion_window() {
+    let congestion = CongestionControl::new();
+    congestion.on_ack(4, Some(Duration::from_millis(50)));
+    assert_eq!(congestion.window(), INITIAL_WINDOW as usize + 4);
+    assert_eq!(congestion.rtt(), Some(Duration::from_millis(50)));
+    assert_eq!(congestion.rto(), Duration::from_millis(150));
+
+    // Losses within the same round trip only shrink the window once.
+    congestion.on_loss();
+    congestion.on_loss();
+    assert_eq!(congestion.window(), 10);
+
+    congestion.on_timeout();
+    assert_eq!(congestion.window(), MIN_WINDOW as usize);
+    assert_eq!(congestion.rto(), Duration::from_millis(300));
+}
+
+#[test]
+fn send_scheduling() {
+    let queues = SendQueues::new();
+    for _ in 0..6 {
+        queues.insert_raw(SendPriority::High, Frame::new(Reliability::Reliable, RVec::alloc_from_slice(&[b'h'; 10])));
+    }
+    for _ in 0..2 {
+        queues.insert_raw(SendPriority::Low, Frame::new(Reliability::Reliable, RVec::alloc_from_slice(&[b'l'; 100])));
+    }
+
+    let order = |frames: Vec<QueuedFrame>| frames.iter().map(|queued| queued.frame.body[0]).collect::<Vec<_>>();
+
+    // Low priority frames get a turn after four high priority frames and the budget stops the tick.
+    assert_eq!(order(queues.schedule(150)), b"hhhhlh");
+    // The next tick continues where the previous one stopped.
+    assert_eq!(order(queues.schedule(usize::MAX)), b"hl");
+    assert!(queues.is_empty());
+}